@@ -1,16 +1,14 @@
 #![no_main]
 
 use libfuzzer_sys::fuzz_target;
-use mdbook_i18n_helpers::{extract_events, group_events, Group};
+use mdbook_i18n_helpers::{extract_events, group_events};
 use pretty_assertions::assert_eq;
 
 fuzz_target!(|text: String| {
     let events = extract_events(&text, None);
     let flattened_groups = group_events(&events)
         .into_iter()
-        .flat_map(|group| match group {
-            Group::Translate(events) | Group::Skip(events) => events,
-        })
+        .flat_map(|group| group.events())
         .cloned()
         .collect::<Vec<_>>();
 
@@ -0,0 +1,66 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mdbook_i18n_helpers::alignment::{migrate, SentenceLanguages};
+use mdbook_i18n_helpers::{extract_messages_with_comments, MarkdownConfig};
+use polib::catalog::Catalog;
+use polib::message::Message;
+use polib::metadata::CatalogMetadata;
+use std::collections::HashMap;
+
+fuzz_target!(|input: (String, String, String)| {
+    // An old chapter, its translation, and the new chapter.
+    let (old, translation, new) = input;
+    let config = MarkdownConfig::default();
+    let old_messages = extract_messages_with_comments(&old, &config);
+    let translated_messages = extract_messages_with_comments(&translation, &config);
+
+    let mut catalog = Catalog::new(CatalogMetadata::new());
+    for (message, translated) in old_messages.iter().zip(&translated_messages) {
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_source(format!("src/foo.md:{}", message.lineno))
+                .with_msgid(message.msgid.clone())
+                .with_msgstr(translated.msgid.clone())
+                .done(),
+        );
+    }
+    // A plural message must not be merged into a singular message with
+    // the same msgid.
+    let plural = old_messages.first().map(|message| message.msgid.clone());
+    if let Some(msgid) = &plural {
+        catalog.append_or_update(
+            Message::build_plural()
+                .with_msgid(msgid.clone())
+                .with_msgid_plural(format!("{msgid}s"))
+                .with_msgstr_plural(vec![String::new(), String::new()])
+                .done(),
+        );
+    }
+
+    let files = HashMap::from([(String::from("src/foo.md"), new.clone())]);
+    let languages = SentenceLanguages {
+        source: String::from("en"),
+        target: String::from("de"),
+    };
+    let (migrated, summary) = migrate(catalog, &files, &config, Some(&languages));
+
+    let new_messages = extract_messages_with_comments(&new, &config);
+    if !old_messages.is_empty() && !translated_messages.is_empty() {
+        for message in &new_messages {
+            assert!(
+                migrated.find_message(None, &message.msgid, None).is_some(),
+                "{:?} is missing",
+                message.msgid
+            );
+        }
+    }
+    if let Some(msgid) = &plural {
+        let msgid_plural = format!("{msgid}s");
+        assert!(migrated
+            .find_message(None, msgid, Some(&msgid_plural))
+            .is_some());
+    }
+    assert!(summary.unchanged + summary.migrated <= new_messages.len());
+    assert!(summary.low_confidence <= summary.migrated);
+});
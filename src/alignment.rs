@@ -0,0 +1,958 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Align the messages of an old PO file with newly extracted messages.
+//!
+//! When the Markdown is split into messages differently, such as after
+//! an upgrade, the translations in an existing PO file no longer
+//! match. The old messages are aligned with the new messages by the
+//! line numbers in their source references: an old message belongs to
+//! the new message which starts at or before its line. Translations
+//! which are carried over this way are marked fuzzy, unless the old
+//! message was only formatted differently. Each of them is scored with
+//! a [`Confidence`], and a low score is explained in a comment on the
+//! message. See [`migrate`].
+//!
+//! This is the engine of the `mdbook-i18n-helpers migrate` command.
+//! Other tools can use it to recover translations as well.
+
+use crate::sentences::{sentence_ranges, split_sentences};
+use crate::{extract_messages_with_comments, extract_messages_with_config, MarkdownConfig};
+use polib::catalog::Catalog;
+use polib::message::{CatalogMessageMutView, Message, MessageFlags, MessageMutView, MessageView};
+use polib::metadata::CatalogMetadata;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Extract the messages from `text`.
+///
+/// A message which only differs from `text` by surrounding whitespace
+/// is returned as `text`: messages next to inline HTML keep their
+/// whitespace when they are extracted, but lose it when extracted
+/// again on their own. Likewise, text from raw HTML blocks is kept as
+/// it is when `config.extract_html` is set.
+pub fn extract(text: &str, config: &MarkdownConfig) -> Vec<String> {
+    let messages = extract_messages_with_config(text, config);
+    if let [(_, msgid)] = messages.as_slice() {
+        if msgid == text.trim() {
+            return vec![String::from(text)];
+        }
+    }
+    if config.extract_html {
+        let html = format!("<div>\n{text}\n</div>\n");
+        if let [(_, msgid)] = extract_messages_with_config(&html, config).as_slice() {
+            if msgid == text {
+                return vec![String::from(text)];
+            }
+        }
+    }
+    messages.into_iter().map(|(_, msgid)| msgid).collect()
+}
+
+/// Add `message` to `catalog`.
+///
+/// If the message is already there, the source references are merged
+/// and the existing translation is kept, unless it's empty. Messages
+/// are matched by their context and plural form as well, so a plural
+/// message is never merged into a singular one.
+pub fn add_message(catalog: &mut Catalog, message: Message) {
+    let msgctxt = Some(message.msgctxt()).filter(|msgctxt| !msgctxt.is_empty());
+    let msgid_plural = message.msgid_plural().ok();
+    let Some(mut existing) = catalog.find_message_mut(msgctxt, message.msgid(), msgid_plural)
+    else {
+        catalog.append_or_update(message);
+        return;
+    };
+    for line in message.source().lines() {
+        if !existing.source().lines().any(|existing| existing == line) {
+            let source = existing.source_mut();
+            if !source.is_empty() {
+                source.push('\n');
+            }
+            source.push_str(line);
+        }
+    }
+    if !existing.is_translated() && message.is_translated() {
+        *existing.flags_mut() = message.flags().clone();
+        if let (Ok(msgstr), Ok(_)) = (message.msgstr(), existing.msgstr()) {
+            existing
+                .set_msgstr(String::from(msgstr))
+                .expect("message is singular");
+        } else if let (Ok(msgstr_plural), Ok(existing_plural)) =
+            (message.msgstr_plural(), existing.msgstr_plural_mut())
+        {
+            existing_plural.clone_from(msgstr_plural);
+        }
+    }
+}
+
+/// The languages of the messages and of their translations, used to
+/// split them into sentences.
+#[derive(Debug)]
+pub struct SentenceLanguages {
+    /// The language of the messages, such as `en`.
+    pub source: String,
+    /// The language of the translations, such as `da`.
+    pub target: String,
+}
+
+/// Why a translation is dropped when no messages are extracted from
+/// its chapter any more.
+pub const NO_NEW_MESSAGES: &str = "its chapter has no messages any more";
+
+/// Why a translation is dropped when the new message it is aligned
+/// with is unchanged.
+pub const UNCHANGED_MESSAGE: &str = "the new message at its line kept its own translation";
+
+/// A translation which could not be carried over to a new message.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DroppedTranslation {
+    /// The source reference of the old message, such as
+    /// `src/foo.md:12`.
+    pub source: String,
+    /// The old message.
+    pub msgid: String,
+    /// Why the translation was dropped, such as [`NO_NEW_MESSAGES`].
+    pub reason: &'static str,
+}
+
+/// Statistics from migrating a catalog.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    /// Translations of messages which did not change.
+    pub unchanged: usize,
+    /// Translations which were carried over to a new message.
+    pub migrated: usize,
+    /// Migrated translations with a confidence below
+    /// [`LOW_CONFIDENCE`].
+    pub low_confidence: usize,
+    /// Translations which could not be aligned with a new message.
+    pub dropped: Vec<DroppedTranslation>,
+}
+
+impl Summary {
+    /// Count the dropped translations by chapter and reason.
+    pub fn dropped_by_chapter(&self) -> BTreeMap<(&str, &'static str), usize> {
+        let mut counts = BTreeMap::new();
+        for dropped in &self.dropped {
+            let path =
+                parse_source(&dropped.source).map_or(dropped.source.as_str(), |(path, _)| path);
+            *counts.entry((path, dropped.reason)).or_default() += 1;
+        }
+        counts
+    }
+}
+
+/// A translated message from the old catalog.
+#[derive(Debug)]
+pub struct OldMessage {
+    /// The line of the message in its chapter, starting from 1.
+    pub lineno: usize,
+    pub msgid: String,
+    pub msgstr: String,
+}
+
+/// Split a `path:lineno` source reference.
+pub fn parse_source(source: &str) -> Option<(&str, usize)> {
+    let (path, lineno) = source.rsplit_once(':')?;
+    Some((path, lineno.parse().ok()?))
+}
+
+/// Group the `old` messages by the `new` message they belong to.
+///
+/// An old message belongs to the last new message which starts at or
+/// before its line. Old messages before the first new message belong
+/// to the first new message, so their translations are not lost. They
+/// are only left out if there are no new messages.
+pub fn align<'a>(new: &[(usize, String)], old: &'a [OldMessage]) -> Vec<Vec<&'a OldMessage>> {
+    let mut groups = vec![Vec::new(); new.len()];
+    if new.is_empty() {
+        return groups;
+    }
+    for message in old {
+        let idx = new.partition_point(|(lineno, _)| *lineno <= message.lineno);
+        groups[idx.saturating_sub(1)].push(message);
+    }
+    groups
+}
+
+/// Translations carried over with a lower [`Confidence::score`] are
+/// explained in a comment.
+pub const LOW_CONFIDENCE: f64 = 0.5;
+
+/// How much to trust a translation which is carried over from a group
+/// of old messages to a new message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Confidence {
+    /// The length of the shorter of the new message and the old
+    /// messages, divided by the length of the longer one.
+    pub length_ratio: f64,
+    /// The number of old messages beyond the first, which were
+    /// combined into the new message.
+    pub combined: usize,
+    /// The number of old messages which start before the new message.
+    pub earlier: usize,
+    /// The length ratio, reduced by a quarter for each combined or
+    /// earlier old message. Between 0 and 1.
+    pub score: f64,
+}
+
+impl Confidence {
+    /// Score the alignment of the `group` of old messages with the new
+    /// message `msgid` at `lineno`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::alignment::{Confidence, OldMessage};
+    ///
+    /// let old = OldMessage {
+    ///     lineno: 3,
+    ///     msgid: String::from("Hello"),
+    ///     msgstr: String::from("Hej"),
+    /// };
+    /// let confidence = Confidence::new(3, "Hello world", &[&old]);
+    /// assert_eq!(confidence.score, 5.0 / 11.0);
+    /// assert!(confidence.is_low());
+    /// ```
+    pub fn new(lineno: usize, msgid: &str, group: &[&OldMessage]) -> Confidence {
+        let new_len = msgid.chars().count();
+        let old_len = group
+            .iter()
+            .map(|message| message.msgid.chars().count())
+            .sum::<usize>()
+            + group.len().saturating_sub(1);
+        let length_ratio = match new_len.max(old_len) {
+            0 => 1.0,
+            longest => new_len.min(old_len) as f64 / longest as f64,
+        };
+        let combined = group.len().saturating_sub(1);
+        let earlier = group
+            .iter()
+            .filter(|message| message.lineno < lineno)
+            .count();
+        let score = length_ratio * 0.75f64.powi((combined + earlier) as i32);
+        Confidence {
+            length_ratio,
+            combined,
+            earlier,
+            score,
+        }
+    }
+
+    /// Check if the score is below [`LOW_CONFIDENCE`].
+    pub fn is_low(&self) -> bool {
+        self.score < LOW_CONFIDENCE
+    }
+
+    /// Explain the score in a comment for the translator.
+    pub fn comment(&self) -> String {
+        let mut reasons = Vec::new();
+        if self.length_ratio < 0.75 {
+            reasons.push(format!(
+                "the lengths of the old and new text differ ({:.0}%)",
+                self.length_ratio * 100.0
+            ));
+        }
+        if self.combined > 0 {
+            reasons.push(format!("{} old messages were combined", self.combined + 1));
+        }
+        if self.earlier > 0 {
+            reasons.push(format!(
+                "{} old messages started before this one",
+                self.earlier
+            ));
+        }
+        format!(
+            "Migrated with low confidence ({:.2}): {}.",
+            self.score,
+            reasons.join(", ")
+        )
+    }
+}
+
+/// Find the translation of `msgid` from the old messages in `group`.
+///
+/// Returns the translation and whether it should be fuzzy.
+pub fn aligned_translation(
+    msgid: &str,
+    group: &[&OldMessage],
+    config: &MarkdownConfig,
+) -> Option<(String, bool)> {
+    match group {
+        [] => None,
+        [message] => {
+            // The old message may only be formatted differently, in
+            // which case its translation can be used as is.
+            let formatted = extract(&message.msgid, config) == [msgid];
+            match extract(&message.msgstr, config).as_slice() {
+                [msgstr] if formatted => Some((msgstr.clone(), false)),
+                _ => Some((message.msgstr.clone(), true)),
+            }
+        }
+        messages => {
+            let msgstrs = messages
+                .iter()
+                .map(|message| message.msgstr.as_str())
+                .collect::<Vec<_>>();
+            Some((msgstrs.join("\n\n"), true))
+        }
+    }
+}
+
+/// Split the translation of `old` over the `msgids` of the new
+/// messages which its text now belongs to.
+///
+/// Returns a translation for each of the `msgids` and whether they
+/// should be fuzzy, or `None` if the sentences of the `msgids` don't
+/// add up to the sentences of `old`.
+pub fn sentence_translations(
+    old: &OldMessage,
+    msgids: &[&str],
+    languages: &SentenceLanguages,
+) -> Option<(Vec<String>, bool)> {
+    let old_sentences = split_sentences(&old.msgid, &languages.source);
+    let new_sentences = msgids
+        .iter()
+        .map(|msgid| split_sentences(msgid, &languages.source))
+        .collect::<Vec<_>>();
+    if new_sentences.iter().map(Vec::len).sum::<usize>() != old_sentences.len() {
+        return None;
+    }
+    let ranges = sentence_ranges(&old.msgstr, &languages.target);
+    let fuzzy = ranges.len() != old_sentences.len() || new_sentences.concat() != old_sentences;
+    let mut translations = Vec::new();
+    let mut next = 0;
+    for (idx, sentences) in new_sentences.iter().enumerate() {
+        // The last message gets the remaining sentences, in case the
+        // translation has more of them.
+        let end = match idx + 1 == new_sentences.len() {
+            true => ranges.len(),
+            false => (next + sentences.len()).min(ranges.len()),
+        };
+        let translation = match &ranges[next..end] {
+            [] => "",
+            [first, .., last] => &old.msgstr[first.start..last.end],
+            [only] => &old.msgstr[only.clone()],
+        };
+        translations.push(String::from(translation));
+        next = end;
+    }
+    Some((translations, fuzzy))
+}
+
+/// Find the translations of the `new` messages by splitting the old
+/// messages in `groups` into sentences.
+///
+/// An old message is split if it is the only one aligned with a new
+/// message, and the following new messages have no old messages. Each
+/// translation is returned with its fuzzy flag and the old `msgid`.
+pub fn align_sentences<'a>(
+    new: &[(usize, String)],
+    groups: &[Vec<&'a OldMessage>],
+    languages: &SentenceLanguages,
+) -> Vec<Option<(String, bool, &'a str)>> {
+    let mut result = vec![None; new.len()];
+    let mut idx = 0;
+    while idx < groups.len() {
+        let end = idx
+            + 1
+            + groups[idx + 1..]
+                .iter()
+                .take_while(|group| group.is_empty())
+                .count();
+        if let ([old], true) = (groups[idx].as_slice(), end - idx > 1) {
+            let msgids = new[idx..end]
+                .iter()
+                .map(|(_, msgid)| msgid.as_str())
+                .collect::<Vec<_>>();
+            if let Some((translations, fuzzy)) = sentence_translations(old, &msgids, languages) {
+                for (slot, translation) in result[idx..end].iter_mut().zip(translations) {
+                    if !translation.is_empty() {
+                        *slot = Some((translation, fuzzy, old.msgid.as_str()));
+                    }
+                }
+            }
+        }
+        idx = end;
+    }
+    result
+}
+
+/// Migrate `catalog` to the messages extracted from `files`.
+///
+/// The `files` map paths, as used in the source references, to their
+/// content. Messages from other files are kept as they are. Old
+/// messages are split into sentences if `sentences` is set.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::alignment::migrate;
+/// use mdbook_i18n_helpers::MarkdownConfig;
+/// use polib::catalog::Catalog;
+/// use polib::message::{Message, MessageView};
+/// use polib::metadata::CatalogMetadata;
+/// use std::collections::HashMap;
+///
+/// // The list item used to be extracted as two messages.
+/// let mut catalog = Catalog::new(CatalogMetadata::new());
+/// for (lineno, msgid, msgstr) in [(1, "First", "Første"), (2, "second", "anden")] {
+///     catalog.append_or_update(
+///         Message::build_singular()
+///             .with_source(format!("src/foo.md:{lineno}"))
+///             .with_msgid(String::from(msgid))
+///             .with_msgstr(String::from(msgstr))
+///             .done(),
+///     );
+/// }
+/// let files = HashMap::from([(
+///     String::from("src/foo.md"),
+///     String::from("- First\n  second\n"),
+/// )]);
+///
+/// let (catalog, summary) = migrate(catalog, &files, &MarkdownConfig::default(), None);
+/// let message = catalog.find_message(None, "First second", None).unwrap();
+/// assert_eq!(message.msgstr().unwrap(), "Første\n\nanden");
+/// assert!(message.is_fuzzy());
+/// assert_eq!(summary.migrated, 1);
+/// ```
+pub fn migrate(
+    mut catalog: Catalog,
+    files: &HashMap<String, String>,
+    config: &MarkdownConfig,
+    sentences: Option<&SentenceLanguages>,
+) -> (Catalog, Summary) {
+    let metadata = std::mem::replace(&mut catalog.metadata, CatalogMetadata::new());
+    let mut result = Catalog::new(metadata);
+    let mut summary = Summary::default();
+
+    let mut old_messages: BTreeMap<&str, Vec<OldMessage>> = BTreeMap::new();
+    let mut migrated_msgids = HashSet::new();
+    for message in catalog.messages() {
+        let Ok(msgstr) = message.msgstr() else {
+            continue;
+        };
+        for (path, lineno) in message.source().lines().filter_map(parse_source) {
+            let Some((path, _)) = files.get_key_value(path) else {
+                continue;
+            };
+            migrated_msgids.insert(String::from(message.msgid()));
+            if message.is_translated() {
+                old_messages.entry(path).or_default().push(OldMessage {
+                    lineno,
+                    msgid: String::from(message.msgid()),
+                    msgstr: String::from(msgstr),
+                });
+            }
+        }
+    }
+
+    let mut emitted = HashSet::new();
+    let mut used = HashSet::new();
+    let mut aligned = HashSet::new();
+    let mut messages = Vec::new();
+    for mut message in catalog.messages_mut() {
+        messages.push(message.detach());
+    }
+    let old_catalog = {
+        let mut old_catalog = Catalog::new(CatalogMetadata::new());
+        for message in &messages {
+            if message.is_translated() && migrated_msgids.contains(message.msgid()) {
+                old_catalog.append_or_update(
+                    Message::build_singular()
+                        .with_flags(message.flags().clone())
+                        .with_msgid(String::from(message.msgid()))
+                        .with_msgstr(String::from(message.msgstr().unwrap_or_default()))
+                        .done(),
+                );
+            }
+        }
+        old_catalog
+    };
+
+    for message in messages {
+        if !migrated_msgids.contains(message.msgid()) {
+            add_message(&mut result, message);
+            continue;
+        }
+        for (path, _) in message.source().lines().filter_map(parse_source) {
+            let Some((path, content)) = files.get_key_value(path) else {
+                continue;
+            };
+            if !emitted.insert(path) {
+                continue;
+            }
+            let new = extract_messages_with_comments(content, config);
+            let new_msgids = new
+                .iter()
+                .map(|message| (message.lineno, message.msgid.clone()))
+                .collect::<Vec<_>>();
+            let old = old_messages
+                .get(path.as_str())
+                .map_or(&[][..], Vec::as_slice);
+            let groups = align(&new_msgids, old);
+            aligned.extend(
+                groups
+                    .iter()
+                    .flatten()
+                    .map(|message| message.msgid.as_str()),
+            );
+            let mut sentence_aligned = match sentences {
+                Some(languages) => align_sentences(&new_msgids, &groups, languages),
+                None => vec![None; new_msgids.len()],
+            };
+            for (idx, (new_message, group)) in new.into_iter().zip(groups).enumerate() {
+                let mut flags = MessageFlags::new();
+                let mut comment = new_message.comment;
+                if new_message.no_wrap {
+                    flags.add_flag("no-wrap");
+                }
+                let msgstr =
+                    if let Some(exact) = old_catalog.find_message(None, &new_message.msgid, None) {
+                        summary.unchanged += 1;
+                        used.insert(String::from(exact.msgid()));
+                        if exact.is_fuzzy() {
+                            flags.add_flag("fuzzy");
+                        }
+                        String::from(exact.msgstr().unwrap_or_default())
+                    } else if let Some((msgstr, fuzzy, old_msgid)) = sentence_aligned[idx].take() {
+                        summary.migrated += 1;
+                        used.insert(String::from(old_msgid));
+                        if fuzzy {
+                            flags.add_flag("fuzzy");
+                        }
+                        msgstr
+                    } else if let Some((msgstr, fuzzy)) =
+                        aligned_translation(&new_message.msgid, &group, config)
+                    {
+                        summary.migrated += 1;
+                        used.extend(group.iter().map(|message| message.msgid.clone()));
+                        let confidence =
+                            Confidence::new(new_message.lineno, &new_message.msgid, &group);
+                        if fuzzy && confidence.is_low() {
+                            summary.low_confidence += 1;
+                            comment = match comment.is_empty() {
+                                true => confidence.comment(),
+                                false => format!("{comment}\n{}", confidence.comment()),
+                            };
+                        }
+                        if fuzzy {
+                            flags.add_flag("fuzzy");
+                        }
+                        msgstr
+                    } else {
+                        String::new()
+                    };
+                add_message(
+                    &mut result,
+                    Message::build_singular()
+                        .with_comments(comment)
+                        .with_source(format!("{path}:{}", new_message.lineno))
+                        .with_flags(flags)
+                        .with_msgid(new_message.msgid)
+                        .with_msgstr(msgstr)
+                        .done(),
+                );
+            }
+        }
+    }
+    let mut seen = HashSet::new();
+    for (path, old) in &old_messages {
+        for message in old {
+            if used.contains(&message.msgid) || !seen.insert(message.msgid.as_str()) {
+                continue;
+            }
+            summary.dropped.push(DroppedTranslation {
+                source: format!("{path}:{}", message.lineno),
+                msgid: message.msgid.clone(),
+                reason: match aligned.contains(message.msgid.as_str()) {
+                    true => UNCHANGED_MESSAGE,
+                    false => NO_NEW_MESSAGES,
+                },
+            });
+        }
+    }
+    (result, summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    fn messages(catalog: &Catalog) -> Vec<(&str, &str, &str, bool)> {
+        catalog
+            .messages()
+            .map(|msg| {
+                (
+                    msg.source(),
+                    msg.msgid(),
+                    msg.msgstr().unwrap(),
+                    msg.is_fuzzy(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(parse_source("src/foo.md:12"), Some(("src/foo.md", 12)));
+        assert_eq!(parse_source("src/foo.md"), None);
+    }
+
+    #[test]
+    fn test_migrate() {
+        // An older version extracted the code block without fences and
+        // split the list item in two.
+        let catalog = create_catalog(&[
+            ("src/SUMMARY.md:1", "Foo", "FOO"),
+            ("src/foo.md:1", "*Hello*", "*HEJ*"),
+            ("src/foo.md:3", "fn main() {}", "fn main() {} // DA"),
+            ("src/foo.md:7", "First part", "FØRSTE DEL"),
+            ("src/foo.md:8", "second part", "ANDEN DEL"),
+        ]);
+        let files = HashMap::from([(
+            String::from("src/foo.md"),
+            String::from(
+                "*Hello*\n\
+                 \n\
+                 ```rust\n\
+                 fn main() {}\n\
+                 ```\n\
+                 \n\
+                 - First part\n\
+                 \x20 second part\n",
+            ),
+        )]);
+
+        let (catalog, summary) = migrate(catalog, &files, &MarkdownConfig::default(), None);
+        assert_eq!(
+            messages(&catalog),
+            vec![
+                ("src/SUMMARY.md:1", "Foo", "FOO", false),
+                ("src/foo.md:1", "_Hello_", "_HEJ_", false),
+                (
+                    "src/foo.md:3",
+                    "```rust\nfn main() {}\n```",
+                    "fn main() {} // DA",
+                    true
+                ),
+                (
+                    "src/foo.md:7",
+                    "First part second part",
+                    "FØRSTE DEL\n\nANDEN DEL",
+                    true
+                ),
+            ]
+        );
+        assert_eq!(
+            summary,
+            Summary {
+                unchanged: 0,
+                migrated: 3,
+                low_confidence: 0,
+                dropped: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_migrate_msgids_match_extraction() {
+        // The aligned messages must be split exactly like the messages
+        // extracted with the same configuration, including the
+        // footnotes, definition lists and other optional syntax.
+        let content = "\
+Water is H~2~O.[^note]
+
+Term
+: The definition.
+
+[^note]: A footnote.
+";
+        let config = MarkdownConfig {
+            superscript_subscript: true,
+            ..MarkdownConfig::default()
+        };
+        let catalog = create_catalog(&[
+            (
+                "src/foo.md:1",
+                "Water is H~2~O.[^note]",
+                "Vand er H~2~O.[^note]",
+            ),
+            (
+                "src/foo.md:3",
+                "Term : The definition.",
+                "Begreb : Definitionen.",
+            ),
+            ("src/foo.md:6", "A footnote.", "En fodnote."),
+        ]);
+        let files = HashMap::from([(String::from("src/foo.md"), String::from(content))]);
+
+        let (catalog, _) = migrate(catalog, &files, &config, None);
+        let msgids = catalog
+            .messages()
+            .map(|message| message.msgid())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            msgids,
+            vec![
+                "Water is H~2~O.[^note]",
+                "Term",
+                "The definition.",
+                "A footnote."
+            ]
+        );
+        let extracted = extract_messages_with_config(content, &config);
+        assert_eq!(
+            msgids,
+            extracted
+                .iter()
+                .map(|(_, msgid)| msgid.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_add_message_plural() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        add_message(
+            &mut catalog,
+            Message::build_singular()
+                .with_source(String::from("src/foo.md:1"))
+                .with_msgid(String::from("Apple"))
+                .with_msgstr(String::from("Æble"))
+                .done(),
+        );
+        for source in ["src/bar.md:1", "src/baz.md:1"] {
+            add_message(
+                &mut catalog,
+                Message::build_plural()
+                    .with_source(String::from(source))
+                    .with_msgid(String::from("Apple"))
+                    .with_msgid_plural(String::from("Apples"))
+                    .with_msgstr_plural(vec![String::from("Æble"), String::from("Æbler")])
+                    .done(),
+            );
+        }
+        let singular = catalog.find_message(None, "Apple", None).unwrap();
+        assert_eq!(singular.source(), "src/foo.md:1");
+        assert_eq!(singular.msgstr().unwrap(), "Æble");
+        let plural = catalog.find_message(None, "Apple", Some("Apples")).unwrap();
+        assert_eq!(plural.source(), "src/bar.md:1\nsrc/baz.md:1");
+        assert_eq!(plural.msgstr_plural().unwrap(), &["Æble", "Æbler"]);
+    }
+
+    #[test]
+    fn test_migrate_keeps_unchanged_translations() {
+        let catalog = create_catalog(&[
+            ("src/foo.md:1", "Unchanged", "UÆNDRET"),
+            ("src/foo.md:3", "Removed", "FJERNET"),
+        ]);
+        let files = HashMap::from([(String::from("src/foo.md"), String::from("Unchanged\n"))]);
+
+        let (catalog, summary) = migrate(catalog, &files, &MarkdownConfig::default(), None);
+        assert_eq!(
+            messages(&catalog),
+            vec![("src/foo.md:1", "Unchanged", "UÆNDRET", false)]
+        );
+        assert_eq!(
+            summary,
+            Summary {
+                unchanged: 1,
+                migrated: 0,
+                low_confidence: 0,
+                dropped: vec![DroppedTranslation {
+                    source: String::from("src/foo.md:3"),
+                    msgid: String::from("Removed"),
+                    reason: UNCHANGED_MESSAGE,
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_sentence_translations() {
+        let languages = SentenceLanguages {
+            source: String::from("en"),
+            target: String::from("ja"),
+        };
+        let old = OldMessage {
+            lineno: 1,
+            msgid: String::from("Use e.g. serde. It works. Done."),
+            msgstr: String::from("serdeを使う。動く。完了。"),
+        };
+        assert_eq!(
+            sentence_translations(&old, &["Use e.g. serde. It works.", "Done."], &languages),
+            Some((
+                vec![String::from("serdeを使う。動く。"), String::from("完了。")],
+                false
+            ))
+        );
+        assert_eq!(
+            sentence_translations(&old, &["Use e.g. serde.", "It does not work."], &languages),
+            None
+        );
+
+        let old = OldMessage {
+            lineno: 1,
+            msgid: String::from("One. Two."),
+            msgstr: String::from("一と二。"),
+        };
+        assert_eq!(
+            sentence_translations(&old, &["One.", "Two."], &languages),
+            Some((vec![String::from("一と二。"), String::new()], true))
+        );
+    }
+
+    #[test]
+    fn test_migrate_sentence_alignment() {
+        // The paragraph was split in two since the old extraction.
+        let catalog = create_catalog(&[(
+            "src/foo.md:1",
+            "First sentence. Second sentence.",
+            "Første sætning. Anden sætning.",
+        )]);
+        let files = HashMap::from([(
+            String::from("src/foo.md"),
+            String::from("First sentence.\n\nSecond sentence.\n"),
+        )]);
+        let languages = SentenceLanguages {
+            source: String::from("en"),
+            target: String::from("da"),
+        };
+
+        let (catalog, summary) = migrate(
+            catalog,
+            &files,
+            &MarkdownConfig::default(),
+            Some(&languages),
+        );
+        assert_eq!(
+            messages(&catalog),
+            vec![
+                ("src/foo.md:1", "First sentence.", "Første sætning.", false),
+                ("src/foo.md:3", "Second sentence.", "Anden sætning.", false),
+            ]
+        );
+        assert_eq!(
+            summary,
+            Summary {
+                unchanged: 0,
+                migrated: 2,
+                low_confidence: 0,
+                dropped: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_migrate_reports_dropped() {
+        let catalog = create_catalog(&[
+            ("src/foo.md:1", "Old title", "GAMMEL TITEL"),
+            ("src/foo.md:5", "Kept", "BEHOLDT"),
+            ("src/bar.md:1", "Emptied", "TØMT"),
+        ]);
+        let files = HashMap::from([
+            (String::from("src/foo.md"), String::from("\n\n\n\nKept\n")),
+            (String::from("src/bar.md"), String::from("\n")),
+        ]);
+
+        let (_, summary) = migrate(catalog, &files, &MarkdownConfig::default(), None);
+        assert_eq!(
+            summary.dropped,
+            vec![
+                DroppedTranslation {
+                    source: String::from("src/bar.md:1"),
+                    msgid: String::from("Emptied"),
+                    reason: NO_NEW_MESSAGES,
+                },
+                DroppedTranslation {
+                    source: String::from("src/foo.md:1"),
+                    msgid: String::from("Old title"),
+                    reason: UNCHANGED_MESSAGE,
+                },
+            ]
+        );
+        assert_eq!(
+            summary.dropped_by_chapter(),
+            BTreeMap::from([
+                (("src/bar.md", NO_NEW_MESSAGES), 1),
+                (("src/foo.md", UNCHANGED_MESSAGE), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_migrate_low_confidence() {
+        // The title moved down, and the old introduction started
+        // before it.
+        let catalog = create_catalog(&[
+            ("src/foo.md:1", "Intro", "INTRO"),
+            ("src/foo.md:3", "Title", "TITEL"),
+        ]);
+        let files = HashMap::from([(
+            String::from("src/foo.md"),
+            String::from("\n\n\n\n# A much longer title\n"),
+        )]);
+
+        let (catalog, summary) = migrate(catalog, &files, &MarkdownConfig::default(), None);
+        let message = catalog
+            .find_message(None, "A much longer title", None)
+            .unwrap();
+        assert_eq!(message.msgstr().unwrap(), "INTRO\n\nTITEL");
+        assert!(message.is_fuzzy());
+        assert_eq!(
+            message.comments(),
+            "Migrated with low confidence (0.24): the lengths of the old and new text \
+             differ (58%), 2 old messages were combined, 2 old messages started before \
+             this one."
+        );
+        assert_eq!(summary.migrated, 1);
+        assert_eq!(summary.low_confidence, 1);
+        assert_eq!(summary.dropped, Vec::new());
+    }
+
+    #[test]
+    fn test_confidence() {
+        let old = [
+            OldMessage {
+                lineno: 1,
+                msgid: String::from("First part"),
+                msgstr: String::from("FØRSTE DEL"),
+            },
+            OldMessage {
+                lineno: 2,
+                msgid: String::from("second part"),
+                msgstr: String::from("ANDEN DEL"),
+            },
+        ];
+        let confidence = Confidence::new(1, "First part second part", &[&old[0], &old[1]]);
+        assert_eq!(confidence.length_ratio, 1.0);
+        assert_eq!(confidence.combined, 1);
+        assert_eq!(confidence.earlier, 0);
+        assert_eq!(confidence.score, 0.75);
+        assert!(!confidence.is_low());
+
+        let confidence = Confidence::new(2, "Part", &[&old[0]]);
+        assert_eq!(confidence.earlier, 1);
+        assert!(confidence.is_low());
+        assert_eq!(
+            confidence.comment(),
+            "Migrated with low confidence (0.30): the lengths of the old and new text \
+             differ (40%), 1 old messages started before this one."
+        );
+    }
+}
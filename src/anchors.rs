@@ -0,0 +1,128 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Heading anchors as generated by `mdbook`.
+//!
+//! The HTML renderer of `mdbook` gives each heading an anchor. It is
+//! the explicit id of the heading, such as `# Foo { #bar }`, or an id
+//! generated from the text of the heading. Translating a heading
+//! changes the generated id, which breaks links to the heading.
+
+use mdbook::utils::unique_id_from_content;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::collections::HashMap;
+
+/// Compute the anchors of the headings in `events`.
+///
+/// There is an entry for each heading, in order. Like `mdbook`,
+/// duplicate generated anchors get a numeric suffix.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::anchors::heading_anchors;
+/// use mdbook_i18n_helpers::extract_events;
+///
+/// let events = extract_events("# Foo Bar\n\n# Foo Bar\n\n# Baz { #qux }", None);
+/// assert_eq!(heading_anchors(&events), vec!["foo-bar", "foo-bar-1", "qux"]);
+/// ```
+pub fn heading_anchors(events: &[(usize, Event)]) -> Vec<String> {
+    let mut anchors = Vec::new();
+    let mut id_counter = HashMap::new();
+    let mut content = None;
+    let mut image_depth = 0;
+    for (_, event) in events {
+        match event {
+            Event::Start(Tag::Heading { id, .. }) => match id {
+                Some(id) => anchors.push(id.to_string()),
+                None => content = Some(String::new()),
+            },
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(content) = content.take() {
+                    anchors.push(unique_id_from_content(&content, &mut id_counter));
+                }
+            }
+            Event::Start(Tag::Image { .. }) => image_depth += 1,
+            Event::End(TagEnd::Image) => image_depth -= 1,
+            // The heading is rendered to HTML before `mdbook` computes
+            // the anchor. Tags are removed, as are escaped characters
+            // such as `<`.
+            Event::Text(text) | Event::Code(text) | Event::InlineMath(text) if image_depth == 0 => {
+                if let Some(content) = &mut content {
+                    content.extend(text.chars().filter(|c| !matches!(c, '<' | '>' | '&')));
+                }
+            }
+            Event::InlineHtml(html) => {
+                if let Some(content) = &mut content {
+                    content.push_str(html);
+                }
+            }
+            _ => {}
+        }
+    }
+    anchors
+}
+
+/// Give the headings in `translated_events` the anchors of the
+/// headings in `source_events`.
+///
+/// The headings are matched in order. This works since translating
+/// the events keeps the headings. Headings with an explicit id are
+/// left unchanged.
+pub fn preserve_heading_anchors(
+    source_events: &[(usize, Event)],
+    translated_events: &mut [(usize, Event)],
+) {
+    let headings = translated_events
+        .iter_mut()
+        .filter_map(|(_, event)| match event {
+            Event::Start(Tag::Heading { id, .. }) => Some(id),
+            _ => None,
+        });
+    for (id, anchor) in headings.zip(heading_anchors(source_events)) {
+        if id.is_none() {
+            *id = Some(anchor.into());
+        }
+    }
+}
+
+/// Map the anchors of the headings in `source_events` to the anchors
+/// of the same headings in `translated_events`.
+///
+/// The headings are matched in order, like in
+/// [`preserve_heading_anchors`]. Anchors which stay the same are left
+/// out.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::anchors::translated_anchors;
+/// use mdbook_i18n_helpers::extract_events;
+///
+/// let source = extract_events("# Setup\n\n# Usage { #usage }", None);
+/// let translated = extract_events("# Opsætning\n\n# Brug { #usage }", None);
+/// let anchors = translated_anchors(&source, &translated);
+/// assert_eq!(anchors["setup"], "opsætning");
+/// assert!(!anchors.contains_key("usage"));
+/// ```
+pub fn translated_anchors(
+    source_events: &[(usize, Event)],
+    translated_events: &[(usize, Event)],
+) -> HashMap<String, String> {
+    heading_anchors(source_events)
+        .into_iter()
+        .zip(heading_anchors(translated_events))
+        .filter(|(source, translated)| source != translated)
+        .collect()
+}
@@ -0,0 +1,240 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Find the sources a PO file was made for.
+//!
+//! A translation is made for the sources as they were when the
+//! messages were extracted. When the sources change, a book built from
+//! the current sources mixes translated text with untranslated new
+//! text. Building the book from the sources as of the
+//! `POT-Creation-Date` of the PO file avoids this: the translation is
+//! then complete, but out of date.
+//!
+//! The functions here run `git` to find the last commit before that
+//! date and to copy the sources of that commit to another directory.
+//! The working tree is not changed.
+
+use anyhow::{anyhow, bail, Context};
+use polib::catalog::Catalog;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The `POT-Creation-Date` header of `catalog`, if it is set.
+pub fn pot_creation_date(catalog: &Catalog) -> Option<&str> {
+    Some(catalog.metadata.pot_creation_date.trim()).filter(|date| !date.is_empty())
+}
+
+/// Run `git` in `dir` with `args` and return its output.
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Could not run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Find the root of the git repository with `dir`.
+///
+/// Returns `None` if `dir` is not in a git repository.
+pub fn repository_root(dir: &Path) -> anyhow::Result<Option<PathBuf>> {
+    match git(dir, &["rev-parse", "--show-toplevel"]) {
+        Ok(output) => {
+            let root = String::from_utf8(output).context("Could not parse the output of git")?;
+            Ok(Some(PathBuf::from(root.trim_end())))
+        }
+        Err(_) if !dir.exists() => Err(anyhow!("Could not find {}", dir.display())),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Find the last commit of `HEAD` in `repo` made before `date`.
+///
+/// The `date` can be in any format understood by `git`, such as the
+/// `2023-06-02 10:31+0200` used in PO files. Returns `None` if all
+/// commits are newer.
+pub fn commit_before(repo: &Path, date: &str) -> anyhow::Result<Option<String>> {
+    let output = git(
+        repo,
+        &["rev-list", "-n", "1", &format!("--before={date}"), "HEAD"],
+    )?;
+    let commit = String::from_utf8(output).context("Could not parse the output of git")?;
+    Ok(Some(String::from(commit.trim())).filter(|commit| !commit.is_empty()))
+}
+
+/// Copy the files below `path` in `commit` to `dest`.
+///
+/// The `path` is relative to the root of `repo`, and the files are
+/// written relative to `path`. Symlinks and submodules are skipped.
+pub fn export_tree(repo: &Path, commit: &str, path: &Path, dest: &Path) -> anyhow::Result<()> {
+    let prefix = path.to_string_lossy().replace('\\', "/");
+    let listing = git(repo, &["ls-tree", "-r", "-z", commit, "--", &prefix])?;
+    let mut blobs = Vec::new();
+    for entry in listing.split(|&b| b == 0).filter(|entry| !entry.is_empty()) {
+        let entry = std::str::from_utf8(entry).context("Could not parse the output of git")?;
+        let (info, file) = entry
+            .split_once('\t')
+            .ok_or_else(|| anyhow!("Unexpected output from git ls-tree: {entry:?}"))?;
+        let mut fields = info.split(' ');
+        let (mode, kind, id) = (fields.next(), fields.next(), fields.next());
+        if kind != Some("blob") || mode == Some("120000") {
+            continue;
+        }
+        let relative = Path::new(file)
+            .strip_prefix(path)
+            .unwrap_or(Path::new(file));
+        blobs.push((id.unwrap_or_default().to_owned(), dest.join(relative)));
+    }
+
+    // Read all files with a single git process.
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Could not run git")?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let requests = blobs
+        .iter()
+        .map(|(id, _)| format!("{id}\n"))
+        .collect::<String>();
+    let writer = std::thread::spawn(move || stdin.write_all(requests.as_bytes()));
+    let output = child.wait_with_output().context("Could not run git")?;
+    writer
+        .join()
+        .expect("the writer thread doesn't panic")
+        .context("Could not write to git")?;
+    if !output.status.success() {
+        bail!(
+            "git cat-file failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut rest = &output.stdout[..];
+    for (id, target) in &blobs {
+        let header_end = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| anyhow!("Unexpected end of output from git cat-file"))?;
+        let header = std::str::from_utf8(&rest[..header_end])?;
+        let size = match header.split(' ').collect::<Vec<_>>()[..] {
+            [found, "blob", size] if found == id => size.parse::<usize>()?,
+            _ => bail!("Unexpected output from git cat-file: {header:?}"),
+        };
+        let content = rest
+            .get(header_end + 1..header_end + 1 + size)
+            .ok_or_else(|| anyhow!("Unexpected end of output from git cat-file"))?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Could not create {}", parent.display()))?;
+        }
+        fs::write(target, content)
+            .with_context(|| format!("Could not write {}", target.display()))?;
+        // Skip the content and the newline after it.
+        rest = &rest[(header_end + size + 2).min(rest.len())..];
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Commit `content` to `file` in `repo` at `date`.
+    fn commit(repo: &Path, file: &str, content: &str, date: &str) {
+        let path = repo.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        for args in [
+            vec!["add", "--all"],
+            vec![
+                "-c",
+                "user.name=Translator",
+                "-c",
+                "user.email=translator@example.com",
+                "commit",
+                "--quiet",
+                "--message",
+                file,
+            ],
+        ] {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(repo)
+                .args(&args)
+                .env("GIT_AUTHOR_DATE", date)
+                .env("GIT_COMMITTER_DATE", date)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+    }
+
+    #[test]
+    fn test_export_sources_before() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let repo = tmpdir.path().join("repo");
+        fs::create_dir(&repo)?;
+        git(&repo, &["init", "--quiet"])?;
+        commit(
+            &repo,
+            "book/src/intro.md",
+            "# Intro",
+            "2023-01-01T12:00:00+00:00",
+        );
+        commit(
+            &repo,
+            "book/src/guide/setup.md",
+            "# Setup",
+            "2023-02-01T12:00:00+00:00",
+        );
+        commit(
+            &repo,
+            "book/src/intro.md",
+            "# New intro",
+            "2023-03-01T12:00:00+00:00",
+        );
+
+        let root = repository_root(&repo.join("book"))?.unwrap();
+        assert_eq!(root.canonicalize()?, repo.canonicalize()?);
+        assert_eq!(repository_root(tmpdir.path())?, None);
+
+        assert_eq!(commit_before(&repo, "2022-12-01 10:00+0000")?, None);
+        let commit = commit_before(&repo, "2023-02-15 10:00+0000")?.unwrap();
+        let dest = tmpdir.path().join("export");
+        export_tree(&repo, &commit, Path::new("book/src"), &dest)?;
+        assert_eq!(fs::read_to_string(dest.join("intro.md"))?, "# Intro");
+        assert_eq!(fs::read_to_string(dest.join("guide/setup.md"))?, "# Setup");
+        assert_eq!(
+            fs::read_to_string(repo.join("book/src/intro.md"))?,
+            "# New intro"
+        );
+        Ok(())
+    }
+}
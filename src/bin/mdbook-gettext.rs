@@ -23,22 +23,349 @@
 //! You can set `preprocessor.gettext.po-dir` to specify where to find
 //! PO files. If the PO file is not found, you'll get the untranslated
 //! book.
+//!
+//! Set `preprocessor.gettext.preserve-anchors` to keep the anchors of
+//! the headings: translated headings then get an explicit id based on
+//! the untranslated heading.
+//!
+//! Set `preprocessor.gettext.detect-language` to find the PO file
+//! using its `Language` header instead of its name. This is useful
+//! for files with names such as `final_ko_v2.po`.
+//!
+//! Regional variants fall back to the general language, so `po/pt.po`
+//! is used for `pt-BR` if there is no `po/pt-BR.po`. Other fallbacks
+//! can be added with `preprocessor.gettext.language-aliases`.
+//!
+//! Set `preprocessor.gettext.strict-markup` to ignore translations
+//! which drop or change inline code, links, or HTML tags. A warning
+//! is printed for each of them.
+//!
+//! Set `preprocessor.gettext.require-review` to a review status, such
+//! as `approved`, to only use translations with that status. The
+//! status is recorded with `mdbook-i18n-helpers review mark`.
+//!
+//! Set `preprocessor.gettext.required-reviewers` to only use
+//! translations reviewed by at least that many different people. The
+//! source text is used for the other translations and they are listed
+//! in a warning.
+//!
+//! Set `preprocessor.gettext.heading-case` to a table of languages,
+//! each with a `case` such as `"sentence"`, to adapt the
+//! capitalization of headings which are translated verbatim, such as
+//! "Getting Started". Set `always` for a language to adapt all
+//! translated headings, and `keep` to a list of words which keep their
+//! case.
+//!
+//! Set `preprocessor.gettext.provenance` to mark each translated
+//! paragraph, heading, and list item with a hidden `<span>` which
+//! identifies the message and its source, for use by feedback widgets.
+//!
+//! The values of `output.xgettext.extra-config-keys` cannot be
+//! translated by a preprocessor. A warning is printed for each of them
+//! which has a translation, see `mdbook-i18n-helpers config-env`.
+//!
+//! Set `preprocessor.gettext.coverage-footer` to `true` to add a
+//! footer to each chapter with the percentage of its messages which
+//! are translated and the commit the PO file was last updated from,
+//! found with the `POT-Creation-Date` header. Set it to a string
+//! instead to change the text of the footer: `{percent}`, `{commit}`,
+//! and `{date}` are replaced with the values.
+//!
+//! Set `preprocessor.gettext.chapter-status` to start each chapter
+//! with an HTML comment which records the language, the PO file, and
+//! the number of translated messages of the chapter, for use by later
+//! preprocessors and templates.
+//!
+//! Set `preprocessor.gettext.theme-strings` to translate the labels of
+//! the HTML theme, such as "Print this book". A small script which
+//! replaces them is added to each chapter. Translations for some
+//! languages are included, the PO file can add more.
+//!
+//! Set `preprocessor.gettext.required-translation` to a list of glob
+//! patterns, such as `["legal/**"]`, to fail the build and `mdbook
+//! test` when the matching chapters have untranslated messages. This
+//! is checked for the `preprocessor.gettext.release-languages`, or for
+//! all languages with a PO file if that is not set.
+//!
+//! Set `preprocessor.gettext.numbering` to `"auto"` to write the
+//! section numbers of the sidebar in the digits of the language, such
+//! as Arabic-Indic digits for Arabic and CJK numerals for Chinese, or
+//! to the name of a numbering style.
+//!
+//! Books in languages written from right to left, such as Arabic and
+//! Hebrew, get their chapters wrapped in a `<div dir="rtl">`. Set
+//! `preprocessor.gettext.text-direction` to `"ltr"` or `"rtl"` to
+//! override this, and `preprocessor.gettext.rtl-stylesheet` to a
+//! stylesheet which is then loaded by each chapter.
+//!
+//! Set `preprocessor.gettext.languages` to a list of languages, each
+//! with a `language`, `name`, and `url`, to link the translations.
+//! Then set `preprocessor.gettext.language-switcher` to add a language
+//! menu to the menu bar of the HTML theme, and
+//! `preprocessor.gettext.canonical-urls` to add canonical and
+//! alternate links for search engines. This is also done for the
+//! untranslated book.
+//!
+//! A warning is also printed when the `X-Segmentation-Rules` header of
+//! the PO file shows that the messages were extracted with different
+//! rules than the ones used for translating, for example after
+//! upgrading `mdbook-i18n-helpers` or changing `placeholders`.
 
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
 use mdbook::book::Book;
 use mdbook::preprocess::{CmdPreprocessor, PreprocessorContext};
 use mdbook::BookItem;
-use mdbook_i18n_helpers::{extract_events, reconstruct_markdown, translate_events};
+use mdbook_i18n_helpers::backdate::{commit_before, pot_creation_date, repository_root};
+use mdbook_i18n_helpers::catalog_io::read_to_string;
+use mdbook_i18n_helpers::chapter_status::ChapterStatus;
+use mdbook_i18n_helpers::config_keys::{env_var_name, translate_config};
+use mdbook_i18n_helpers::heading_case::{adapt_heading_case, heading_msgids, HeadingCaseConfig};
+use mdbook_i18n_helpers::language::{find_catalog, is_rtl, same_language, LookupConfig};
+use mdbook_i18n_helpers::language_switcher::{book_languages, canonical_script, switcher_script};
+use mdbook_i18n_helpers::markup::mark_markup_mismatches_fuzzy;
+use mdbook_i18n_helpers::numbering::{numbering_script, NumberingStyle};
+use mdbook_i18n_helpers::segmentation::{read_rules, SegmentationRules};
+use mdbook_i18n_helpers::sources::normalize_path;
+use mdbook_i18n_helpers::state::{
+    mark_missing_reviewers_fuzzy, mark_unreviewed_fuzzy, state_path, StateFile,
+};
+use mdbook_i18n_helpers::theme_strings::{theme_script, theme_translations};
+use mdbook_i18n_helpers::translator::Translator;
+use mdbook_i18n_helpers::{extract_messages_with_config, MarkdownConfig};
 use polib::catalog::Catalog;
-use polib::po_file;
+use polib::metadata::CatalogMetadata;
 use semver::{Version, VersionReq};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::{io, process};
 
-fn translate(text: &str, catalog: &Catalog) -> String {
-    let events = extract_events(text, None);
-    let translated_events = translate_events(&events, catalog);
-    let (translated, _) = reconstruct_markdown(&translated_events, None);
-    translated
+/// Check if `mdbook serve` watches `po_dir` for changes.
+///
+/// Besides the `src_dir`, `mdbook serve` only watches the
+/// `extra_watch_dirs` from the `build` table in `book.toml`. A
+/// preprocessor cannot add to these, so we can only tell the user
+/// when the PO files are not watched.
+fn is_watched(po_dir: &Path, src_dir: &Path, extra_watch_dirs: &[PathBuf]) -> bool {
+    let po_dir = normalize_path(po_dir);
+    std::iter::once(src_dir)
+        .chain(extra_watch_dirs.iter().map(PathBuf::as_path))
+        .any(|dir| po_dir.starts_with(normalize_path(dir)))
+}
+
+/// Check if the book is built by `mdbook serve`.
+///
+/// `mdbook serve` sets `output.html.live-reload-endpoint` before
+/// building the book. Nothing marks a build by `mdbook watch`.
+fn is_serving(config: &mdbook::Config) -> bool {
+    config.get("output.html.live-reload-endpoint").is_some()
+}
+
+/// Read the boolean `key` from the `preprocessor.gettext` table.
+fn get_bool(cfg: &toml::value::Table, key: &str) -> anyhow::Result<bool> {
+    match cfg.get(key) {
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| anyhow!("Could not parse preprocessor.gettext.{key} as a boolean")),
+        None => Ok(false),
+    }
+}
+
+/// Read the list of strings `key` from the `preprocessor.gettext`
+/// table.
+fn get_strings(cfg: &toml::value::Table, key: &str) -> anyhow::Result<Vec<String>> {
+    let Some(value) = cfg.get(key) else {
+        return Ok(Vec::new());
+    };
+    let error = || anyhow!("Expected a list of strings for preprocessor.gettext.{key}");
+    value
+        .as_array()
+        .ok_or_else(error)?
+        .iter()
+        .map(|value| value.as_str().map(String::from).ok_or_else(error))
+        .collect()
+}
+
+/// Find the chapters which must be fully translated, but have
+/// untranslated messages in `catalog`.
+///
+/// The chapters are the ones matching the `patterns`. Returns the path
+/// of each such chapter with its untranslated messages. Fuzzy
+/// translations count as untranslated.
+fn untranslated_required_chapters(
+    book: &Book,
+    catalog: &Catalog,
+    config: &MarkdownConfig,
+    patterns: &[glob::Pattern],
+) -> Vec<(PathBuf, Vec<String>)> {
+    let mut chapters = Vec::new();
+    for item in book.iter() {
+        let BookItem::Chapter(ch) = item else {
+            continue;
+        };
+        let Some(path) = ch.path.as_ref().filter(|path| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches_path_with(path, glob::MatchOptions::new()))
+        }) else {
+            continue;
+        };
+        let untranslated = extract_messages_with_config(&ch.content, config)
+            .into_iter()
+            .map(|(_, msgid)| msgid)
+            .filter(|msgid| {
+                !catalog
+                    .find_message(None, msgid, None)
+                    .is_some_and(|msg| msg.is_translated() && !msg.is_fuzzy())
+            })
+            .collect::<Vec<_>>();
+        if !untranslated.is_empty() {
+            chapters.push((path.clone(), untranslated));
+        }
+    }
+    chapters
+}
+
+/// Check that the chapters matching `preprocessor.gettext.required-translation`
+/// are fully translated.
+///
+/// The check is done for the `preprocessor.gettext.release-languages`,
+/// or for all languages with a PO file if the setting is missing. A
+/// release language without a PO file fails the check.
+fn check_required_translation(
+    cfg: &toml::value::Table,
+    language: &str,
+    book: &Book,
+    catalog: Option<&Catalog>,
+    config: &MarkdownConfig,
+) -> anyhow::Result<()> {
+    let patterns = get_strings(cfg, "required-translation")?
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern).with_context(|| {
+                format!(
+                    "Invalid glob pattern {pattern:?} in preprocessor.gettext.required-translation"
+                )
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let release_languages = get_strings(cfg, "release-languages")?;
+    let is_release_language = release_languages
+        .iter()
+        .any(|release_language| same_language(release_language, language));
+    if patterns.is_empty() || !(is_release_language || release_languages.is_empty()) {
+        return Ok(());
+    }
+    let empty_catalog;
+    let catalog = match catalog {
+        Some(catalog) => catalog,
+        None if is_release_language => {
+            empty_catalog = Catalog::new(CatalogMetadata::new());
+            &empty_catalog
+        }
+        None => return Ok(()),
+    };
+    let chapters = untranslated_required_chapters(book, catalog, config, &patterns);
+    if chapters.is_empty() {
+        return Ok(());
+    }
+    let mut details = String::new();
+    for (path, untranslated) in &chapters {
+        details.push_str(&format!(
+            "\n  {}: {} untranslated messages, such as {:?}",
+            path.display(),
+            untranslated.len(),
+            untranslated[0]
+        ));
+    }
+    bail!("Chapters which require a translation are not fully translated into {language}:{details}")
+}
+
+/// Check if the book should be written from right to left.
+///
+/// This is read from `preprocessor.gettext.text-direction`, which is
+/// `"auto"` by default: right to left for languages such as Arabic
+/// and Hebrew.
+fn is_rtl_book(cfg: &toml::value::Table, language: &str) -> anyhow::Result<bool> {
+    match cfg.get("text-direction").map(|value| value.as_str()) {
+        None | Some(Some("auto")) => Ok(is_rtl(language)),
+        Some(Some("rtl")) => Ok(true),
+        Some(Some("ltr")) => Ok(false),
+        Some(_) => Err(anyhow!(
+            "Expected \"auto\", \"ltr\", or \"rtl\" for preprocessor.gettext.text-direction"
+        )),
+    }
+}
+
+/// Wrap the chapter `content` in a right-to-left `<div>`.
+///
+/// The `stylesheet` is relative to the root of the book, so the link
+/// to it is made relative to the chapter at `path`.
+fn wrap_rtl(content: &str, stylesheet: Option<&str>, path: Option<&Path>) -> String {
+    let link = match stylesheet {
+        Some(stylesheet) => {
+            let depth = path.map_or(0, |path| path.components().count().saturating_sub(1));
+            let href = format!("{}{stylesheet}", "../".repeat(depth));
+            format!("<link rel=\"stylesheet\" href=\"{href}\">\n")
+        }
+        None => String::new(),
+    };
+    format!("{link}<div dir=\"rtl\">\n\n{}\n\n</div>\n", content.trim())
+}
+
+/// The text of the coverage footer when `coverage-footer` is `true`.
+const DEFAULT_COVERAGE_FOOTER: &str = "Translated {percent}% — last updated from commit {commit}";
+
+/// Count the translated messages of the chapter `content`.
+///
+/// Returns the number of translated messages and the number of
+/// messages. Fuzzy translations are not counted.
+fn chapter_coverage(content: &str, catalog: &Catalog, config: &MarkdownConfig) -> (usize, usize) {
+    let messages = extract_messages_with_config(content, config);
+    let translated = messages
+        .iter()
+        .filter(|(_, msgid)| {
+            catalog
+                .find_message(None, msgid, None)
+                .is_some_and(|msg| msg.is_translated() && !msg.is_fuzzy())
+        })
+        .count();
+    (translated, messages.len())
+}
+
+/// Format the coverage footer of a chapter using `template`.
+///
+/// The footer is a paragraph after a horizontal rule, so it works
+/// with all renderers.
+fn coverage_footer(
+    template: &str,
+    (translated, total): (usize, usize),
+    commit: Option<&str>,
+    date: Option<&str>,
+) -> String {
+    let percent = translated * 100 / total.max(1);
+    let text = template
+        .replace("{percent}", &percent.to_string())
+        .replace("{commit}", commit.unwrap_or("unknown"))
+        .replace("{date}", date.unwrap_or("unknown"));
+    format!("---\n\n<small class=\"translation-coverage\">{text}</small>")
+}
+
+/// Check the `X-Segmentation-Rules` header field of the PO file
+/// `content` against the rules used for translating.
+///
+/// PO files without the field are assumed to be compatible.
+fn segmentation_warning(content: &str, config: &MarkdownConfig) -> Option<String> {
+    let found = read_rules(content)?;
+    let current = SegmentationRules::current(config);
+    if found.is_compatible(&current) {
+        return None;
+    }
+    Some(format!(
+        "the messages were extracted with the segmentation rules \"{found}\", \
+         but the book is translated with \"{current}\". Many messages may appear \
+         untranslated. Run `mdbook-i18n-helpers normalize` or \
+         `mdbook-i18n-helpers migrate` to update the PO file."
+    ))
 }
 
 fn translate_book(ctx: &PreprocessorContext, mut book: Book) -> anyhow::Result<Book> {
@@ -54,29 +381,272 @@ fn translate_book(ctx: &PreprocessorContext, mut book: Book) -> anyhow::Result<B
         .get_preprocessor("gettext")
         .ok_or_else(|| anyhow!("Could not read preprocessor.gettext configuration"))?;
     let po_dir = cfg.get("po-dir").and_then(|v| v.as_str()).unwrap_or("po");
-    let path = ctx.root.join(po_dir).join(format!("{language}.po"));
+    let preserve_anchors = get_bool(cfg, "preserve-anchors")?;
+    let provenance = get_bool(cfg, "provenance")?;
+    let lookup_config = LookupConfig::from_book_config(&ctx.config)?;
+    let config = MarkdownConfig::from_book_config(&ctx.config)?;
     // Nothing to do if PO file is missing.
-    if !path.exists() {
+    let Some(found) = find_catalog(&ctx.root.join(po_dir), language, &lookup_config)? else {
+        check_required_translation(cfg, language, &book, None, &config)?;
         return Ok(book);
+    };
+    #[allow(clippy::print_stderr)]
+    for warning in &found.warnings {
+        eprintln!("Warning: {warning}");
+    }
+    let content = read_to_string(&found.path)?;
+    #[allow(clippy::print_stderr)]
+    if let Some(warning) = segmentation_warning(&content, &config) {
+        eprintln!("Warning: {}: {warning}", found.path.display());
+    }
+    let src_dir = ctx.root.join(&ctx.config.book.src);
+    let extra_watch_dirs = ctx
+        .config
+        .build
+        .extra_watch_dirs
+        .iter()
+        .map(|dir| ctx.root.join(dir))
+        .collect::<Vec<_>>();
+    // The warning is only useful while the book is served.
+    #[allow(clippy::print_stderr)]
+    if is_serving(&ctx.config) && !is_watched(&ctx.root.join(po_dir), &src_dir, &extra_watch_dirs) {
+        eprintln!(
+            "Warning: `mdbook serve` won't reload the book when the PO files change. \
+             Add the {po_dir:?} directory to `build.extra-watch-dirs` in book.toml:\n\n\
+             [build]\n\
+             extra-watch-dirs = [{po_dir:?}]\n"
+        );
     }
 
-    let catalog = po_file::parse(&path)
-        .map_err(|err| anyhow!("{err}"))
-        .with_context(|| format!("Could not parse {:?} as PO file", path))?;
+    let mut catalog = found.catalog;
+    if get_bool(cfg, "strict-markup")? {
+        #[allow(clippy::print_stderr)]
+        for mismatch in mark_markup_mismatches_fuzzy(&mut catalog) {
+            eprintln!(
+                "Warning: Ignoring translation of {:?} in {}: markup differs \
+                 (missing: {:?}, extra: {:?})",
+                mismatch.msgid,
+                found.path.display(),
+                mismatch.missing,
+                mismatch.extra
+            );
+        }
+    }
+    let require_review =
+        match cfg.get("require-review") {
+            None => None,
+            Some(status) => Some(status.as_str().ok_or_else(|| {
+                anyhow!("Expected a string for preprocessor.gettext.require-review")
+            })?),
+        };
+    let required_reviewers = match cfg.get("required-reviewers") {
+        None => 0,
+        Some(value) => value
+            .as_integer()
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Expected a non-negative integer for preprocessor.gettext.required-reviewers"
+                )
+            })?,
+    };
+    if require_review.is_some() || required_reviewers > 0 {
+        // The state file belongs to the PO file which was found, which
+        // can be for a fallback language.
+        let po_language = found
+            .path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(language);
+        let state = StateFile::load(&state_path(&ctx.root.join(po_dir), po_language))?;
+        let mut unreviewed = Vec::new();
+        if let Some(status) = require_review {
+            unreviewed.extend(mark_unreviewed_fuzzy(&mut catalog, &state, status));
+        }
+        if required_reviewers > 0 {
+            unreviewed.extend(mark_missing_reviewers_fuzzy(
+                &mut catalog,
+                &state,
+                required_reviewers,
+            ));
+        }
+        #[allow(clippy::print_stderr)]
+        if !unreviewed.is_empty() {
+            eprintln!(
+                "Warning: Using the source text for {} unreviewed translations in {}:",
+                unreviewed.len(),
+                found.path.display()
+            );
+            for msgid in &unreviewed {
+                eprintln!("  {msgid:?}");
+            }
+        }
+    }
+    if let Some(heading_case) = HeadingCaseConfig::from_book_config(&ctx.config, language)? {
+        let mut headings = HashSet::new();
+        for item in book.iter() {
+            if let BookItem::Chapter(ch) = item {
+                headings.insert(ch.name.clone());
+                headings.extend(heading_msgids(&ch.content, &config));
+            }
+        }
+        adapt_heading_case(&mut catalog, &headings, &heading_case);
+    }
+    let mut translator = Translator::new(catalog, config);
+    translator.preserve_anchors = preserve_anchors;
+    // Use the same source paths as in the PO files.
+    translator.provenance_dir = provenance.then(|| ctx.config.book.src.clone());
+    let (catalog, config) = (&translator.catalog, &translator.config);
+    check_required_translation(cfg, language, &book, Some(catalog), config)?;
+    // The renderers read book.toml themselves, so the translated
+    // values must be passed to mdbook as environment variables.
+    #[allow(clippy::print_stderr)]
+    for (key, _) in translate_config(&ctx.config, catalog)? {
+        eprintln!(
+            "Warning: {key} is not translated since preprocessors cannot change book.toml. \
+             Set {} to the translation, see `mdbook-i18n-helpers config-env {language}`.",
+            env_var_name(&key)
+        );
+    }
+    let coverage_template = match cfg.get("coverage-footer") {
+        None => None,
+        Some(toml::Value::Boolean(enabled)) => enabled.then_some(DEFAULT_COVERAGE_FOOTER),
+        Some(toml::Value::String(template)) => Some(template.as_str()),
+        Some(_) => {
+            return Err(anyhow!(
+                "Expected a boolean or a string for preprocessor.gettext.coverage-footer"
+            ))
+        }
+    };
+    // The footer is informational, so the build doesn't fail when the
+    // book is not in a git repository.
+    let coverage_date = pot_creation_date(catalog);
+    let coverage_commit = match (coverage_template, coverage_date) {
+        (Some(_), Some(date)) => repository_root(&ctx.root)
+            .ok()
+            .flatten()
+            .and_then(|repo| commit_before(&repo, date).ok().flatten())
+            .map(|commit| commit.chars().take(7).collect::<String>()),
+        _ => None,
+    };
+    let chapter_status = get_bool(cfg, "chapter-status")?;
+    let catalog_path = found
+        .path
+        .strip_prefix(&ctx.root)
+        .unwrap_or(&found.path)
+        .display()
+        .to_string();
+    // The theme is only used by the HTML renderer.
+    let theme_script = if get_bool(cfg, "theme-strings")? && ctx.renderer == "html" {
+        theme_script(&theme_translations(language, catalog))
+    } else {
+        String::new()
+    };
+    let numbering_script = match NumberingStyle::from_book_config(&ctx.config, language)? {
+        Some(style) if ctx.renderer == "html" => {
+            let sections = book
+                .iter()
+                .filter_map(|item| match item {
+                    BookItem::Chapter(ch) => ch.number.as_ref().map(ToString::to_string),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            numbering_script(style, sections.iter().map(String::as_str))
+        }
+        _ => String::new(),
+    };
+    let rtl = is_rtl_book(cfg, language)? && ctx.renderer == "html";
+    let rtl_stylesheet =
+        match cfg.get("rtl-stylesheet") {
+            None => None,
+            Some(value) => Some(value.as_str().ok_or_else(|| {
+                anyhow!("Expected a string for preprocessor.gettext.rtl-stylesheet")
+            })?),
+        };
     book.for_each_mut(|item| match item {
         BookItem::Chapter(ch) => {
-            ch.content = translate(&ch.content, &catalog);
-            ch.name = translate(&ch.name, &catalog);
+            // Counting the messages means extracting the chapter again,
+            // so it is only done when the coverage is used.
+            let coverage = (coverage_template.is_some() || chapter_status)
+                .then(|| chapter_coverage(&ch.content, catalog, config));
+            translator.translate_chapter(ch);
+            if let (Some(template), Some(coverage)) = (
+                coverage_template,
+                coverage.filter(|coverage| coverage.1 > 0),
+            ) {
+                let footer = coverage_footer(
+                    template,
+                    coverage,
+                    coverage_commit.as_deref(),
+                    coverage_date,
+                );
+                ch.content = format!("{}\n\n{footer}\n", ch.content.trim_end());
+            }
+            if !theme_script.is_empty() {
+                ch.content = format!("{}\n\n{theme_script}", ch.content.trim_end());
+            }
+            if !numbering_script.is_empty() {
+                ch.content = format!("{}\n\n{numbering_script}", ch.content.trim_end());
+            }
+            if rtl {
+                ch.content = wrap_rtl(&ch.content, rtl_stylesheet, ch.path.as_deref());
+            }
+            if let Some((translated, total)) = coverage.filter(|_| chapter_status) {
+                let status = ChapterStatus {
+                    language: language.clone(),
+                    catalog: catalog_path.clone(),
+                    translated,
+                    total,
+                };
+                ch.content = status.add_to(&ch.content);
+            }
         }
         BookItem::Separator => {}
         BookItem::PartTitle(title) => {
-            *title = translate(title, &catalog);
+            *title = translator.translate_str(title);
         }
     });
 
     Ok(book)
 }
 
+/// Add the language switcher and the canonical links to each chapter
+/// of the HTML output.
+///
+/// This is done for the untranslated book too, so it is kept out of
+/// [`translate_book`].
+fn add_language_links(ctx: &PreprocessorContext, mut book: Book) -> anyhow::Result<Book> {
+    let Some(cfg) = ctx.config.get_preprocessor("gettext") else {
+        return Ok(book);
+    };
+    let language_switcher = get_bool(cfg, "language-switcher")?;
+    let canonical_urls = get_bool(cfg, "canonical-urls")?;
+    let languages = book_languages(&ctx.config)?;
+    if languages.is_empty() || ctx.renderer != "html" {
+        return Ok(book);
+    }
+    let current = ctx.config.book.language.as_deref();
+    book.for_each_mut(|item| {
+        let BookItem::Chapter(ch) = item else {
+            return;
+        };
+        let Some(path) = &ch.path else {
+            return;
+        };
+        if canonical_urls {
+            let script = canonical_script(&languages, current, path);
+            ch.content = format!("{script}\n{}", ch.content);
+        }
+        if language_switcher {
+            // The script goes first so the theme strings script can
+            // translate the labels of the switcher.
+            let script = switcher_script(&languages, current, path);
+            ch.content = format!("{script}\n{}", ch.content);
+        }
+    });
+    Ok(book)
+}
+
 fn preprocess() -> anyhow::Result<()> {
     let (ctx, book) = CmdPreprocessor::parse_input(io::stdin())?;
     let book_version = Version::parse(&ctx.mdbook_version)?;
@@ -91,7 +661,7 @@ fn preprocess() -> anyhow::Result<()> {
         );
     }
 
-    let translated_book = translate_book(&ctx, book)?;
+    let translated_book = add_language_links(&ctx, translate_book(&ctx, book)?)?;
     serde_json::to_writer(io::stdout(), &translated_book)?;
 
     Ok(())
@@ -118,6 +688,10 @@ mod tests {
     use polib::metadata::CatalogMetadata;
     use pretty_assertions::assert_eq;
 
+    fn translate(text: &str, catalog: Catalog, config: &MarkdownConfig) -> String {
+        Translator::new(catalog, config.clone()).translate_str(text)
+    }
+
     fn create_catalog(translations: &[(&str, &str)]) -> Catalog {
         let mut catalog = Catalog::new(CatalogMetadata::new());
         for (msgid, msgstr) in translations {
@@ -130,31 +704,89 @@ mod tests {
         catalog
     }
 
+    #[test]
+    fn test_is_rtl_book() {
+        let cfg = |text: &str| toml::from_str::<toml::value::Table>(text).unwrap();
+        assert!(is_rtl_book(&cfg(""), "ar").unwrap());
+        assert!(!is_rtl_book(&cfg(""), "da").unwrap());
+        assert!(is_rtl_book(&cfg("text-direction = \"rtl\""), "da").unwrap());
+        assert!(!is_rtl_book(&cfg("text-direction = \"ltr\""), "ar").unwrap());
+        assert!(is_rtl_book(&cfg("text-direction = \"up\""), "ar").is_err());
+    }
+
+    #[test]
+    fn test_wrap_rtl() {
+        assert_eq!(
+            wrap_rtl("# مرحبا\n", None, Some(Path::new("foo.md"))),
+            "<div dir=\"rtl\">\n\n# مرحبا\n\n</div>\n"
+        );
+        assert_eq!(
+            wrap_rtl("Text", Some("theme/rtl.css"), Some(Path::new("foo/bar.md"))),
+            "<link rel=\"stylesheet\" href=\"../theme/rtl.css\">\n\
+             <div dir=\"rtl\">\n\nText\n\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn test_segmentation_warning() {
+        let config = MarkdownConfig::default();
+        let header = |rules: &str| {
+            format!(
+                "msgid \"\"\n\
+                 msgstr \"\"\n\
+                 \"Language: da\\n\"\n\
+                 \"X-Segmentation-Rules: {rules}\\n\"\n"
+            )
+        };
+        assert_eq!(
+            segmentation_warning("msgid \"\"\nmsgstr \"\"\n", &config),
+            None
+        );
+        assert_eq!(
+            segmentation_warning(&header("0.0.1 rules=1"), &config),
+            None
+        );
+        assert!(segmentation_warning(&header("0.0.1 rules=0"), &config).is_some());
+        assert!(segmentation_warning(&header("0.0.1 rules=1 placeholders"), &config).is_some());
+    }
+
     #[test]
     fn test_translate_single_line() {
         let catalog = create_catalog(&[("foo bar", "FOO BAR")]);
-        assert_eq!(translate("foo bar", &catalog), "FOO BAR");
+        assert_eq!(
+            translate("foo bar", catalog, &MarkdownConfig::default()),
+            "FOO BAR"
+        );
     }
 
     #[test]
     fn test_translate_single_paragraph() {
         let catalog = create_catalog(&[("foo bar", "FOO BAR")]);
         // The output is normalized so the newline disappears.
-        assert_eq!(translate("foo bar\n", &catalog), "FOO BAR");
+        assert_eq!(
+            translate("foo bar\n", catalog, &MarkdownConfig::default()),
+            "FOO BAR"
+        );
     }
 
     #[test]
     fn test_translate_paragraph_with_leading_newlines() {
         let catalog = create_catalog(&[("foo bar", "FOO BAR")]);
         // The output is normalized so the newlines disappear.
-        assert_eq!(translate("\n\n\nfoo bar\n", &catalog), "FOO BAR");
+        assert_eq!(
+            translate("\n\n\nfoo bar\n", catalog, &MarkdownConfig::default()),
+            "FOO BAR"
+        );
     }
 
     #[test]
     fn test_translate_paragraph_with_trailing_newlines() {
         let catalog = create_catalog(&[("foo bar", "FOO BAR")]);
         // The output is normalized so the newlines disappear.
-        assert_eq!(translate("foo bar\n\n\n", &catalog), "FOO BAR");
+        assert_eq!(
+            translate("foo bar\n\n\n", catalog, &MarkdownConfig::default()),
+            "FOO BAR"
+        );
     }
 
     #[test]
@@ -167,7 +799,8 @@ mod tests {
                  foo bar\n\
                  \n\
                  last paragraph\n",
-                &catalog
+                catalog,
+                &MarkdownConfig::default()
             ),
             "first paragraph\n\
              \n\
@@ -193,7 +826,8 @@ mod tests {
                  \n\
                  last\n\
                  paragraph\n",
-                &catalog
+                catalog,
+                &MarkdownConfig::default()
             ),
             "FIRST TRANSLATED PARAGRAPH\n\
              \n\
@@ -221,7 +855,8 @@ mod tests {
                  ```\n\
                  \n\
                  Text after.\n",
-                &catalog
+                catalog,
+                &MarkdownConfig::default()
             ),
             "Text before.\n\
              \n\
@@ -241,7 +876,8 @@ mod tests {
             ("Arrays", "ARRAYS"),
             ("Tuples", "TUPLES"),
         ]);
-        // The alignment is lost when we generate new Markdown.
+        // The cells are no longer padded, but the delimiter row is
+        // kept as written.
         assert_eq!(
             translate(
                 "\
@@ -249,11 +885,12 @@ mod tests {
                 |--------|-------------|-----------------|\n\
                 | Arrays | `[T; N]`    | `[20, 30, 40]`  |\n\
                 | Tuples | `()`, ...   | `()`, `('x',)`  |",
-                &catalog
+                catalog,
+                &MarkdownConfig::default()
             ),
             "\
             ||TYPES|LITERALS|\n\
-            |--|-----|--------|\n\
+            |--------|-------------|-----------------|\n\
             |ARRAYS|`[T; N]`|`[20, 30, 40]`|\n\
             |TUPLES|`()`, ...|`()`, `('x',)`|",
         );
@@ -266,7 +903,11 @@ mod tests {
             ("More details.", "MORE DETAILS."),
         ]);
         assert_eq!(
-            translate("A footnote[^note].\n\n[^note]: More details.", &catalog),
+            translate(
+                "A footnote[^note].\n\n[^note]: More details.",
+                catalog,
+                &MarkdownConfig::default()
+            ),
             "A FOOTNOTE[^note].\n\n[^note]: MORE DETAILS."
         );
     }
@@ -274,7 +915,10 @@ mod tests {
     #[test]
     fn test_strikethrough() {
         let catalog = create_catalog(&[("~~foo~~", "~~FOO~~")]);
-        assert_eq!(translate("~~foo~~", &catalog), "~~FOO~~");
+        assert_eq!(
+            translate("~~foo~~", catalog, &MarkdownConfig::default()),
+            "~~FOO~~"
+        );
     }
 
     #[test]
@@ -286,7 +930,8 @@ mod tests {
                 - [x] Foo\n\
                 - [ ] Bar\n\
                 ",
-                &catalog
+                catalog,
+                &MarkdownConfig::default()
             ),
             "\
             - [x] FOO\n\
@@ -298,8 +943,381 @@ mod tests {
     fn test_heading_attributes() {
         let catalog = create_catalog(&[("Foo", "FOO"), ("Bar", "BAR")]);
         assert_eq!(
-            translate("# Foo { #id .foo }", &catalog),
-            "# FOO {#id .foo}"
+            translate("# Foo { #id .foo }", catalog, &MarkdownConfig::default()),
+            "# FOO { #id .foo }"
         );
     }
+
+    #[test]
+    fn test_translate_math() {
+        let config = MarkdownConfig {
+            math_delimiters: vec![("$$".into(), "$$".into()), ("\\(".into(), "\\)".into())],
+            ..MarkdownConfig::default()
+        };
+        let catalog = create_catalog(&[
+            (
+                "Sum: $$ \\sum_{n=1}^{\\infty} $$",
+                "SUM: $$ \\sum_{n=1}^{\\infty} $$",
+            ),
+            ("The area is \\(\\pi r^2\\).", "THE AREA IS \\(\\pi r^2\\)."),
+        ]);
+        assert_eq!(
+            translate(
+                "Sum: $$ \\sum_{n=1}^{\\infty} $$\n\nThe area is \\(\\pi r^2\\).",
+                catalog,
+                &config
+            ),
+            "SUM: $$ \\sum_{n=1}^{\\infty} $$\n\nTHE AREA IS \\(\\pi r^2\\)."
+        );
+    }
+
+    #[test]
+    fn test_translate_math_untranslated() {
+        let config = MarkdownConfig {
+            math_delimiters: vec![("\\[".into(), "\\]".into())],
+            ..MarkdownConfig::default()
+        };
+        let catalog = create_catalog(&[]);
+        assert_eq!(
+            translate("\\[\na_1 *\n\\]", catalog, &config),
+            "\\[\na_1 *\n\\]"
+        );
+    }
+
+    /// Raw HTML tables with images, as found in real books. The cells
+    /// with text are listed with their translations.
+    const HTML_TABLES: &[(&str, &[(&str, &str)])] = &[
+        (
+            "<table>\n\
+             \x20 <tr>\n\
+             \x20   <td><img src=\"a.png\" alt=\"A\" width=\"100\"></td>\n\
+             \x20   <td>Caption</td>\n\
+             \x20 </tr>\n\
+             </table>",
+            &[("Caption", "CAPTION")],
+        ),
+        (
+            "<table>\n\
+             <tr><td align=\"center\"><img src=\"a.png\"\n\
+             \x20    height=\"20\"> Label text</td></tr>\n\
+             </table>",
+            &[("Label text", "LABEL TEXT")],
+        ),
+        (
+            "<table class=\"x\">\n\
+             <tr>\n\
+             <td>\n\
+             \n\
+             ![Alt](img.png)\n\
+             \n\
+             </td>\n\
+             <td>Text <b>bold</b><br/></td>\n\
+             </tr>\n\
+             </table>",
+            &[
+                ("![Alt](img.png)", "![ALT](img.png)"),
+                ("Text <b>bold</b>", "TEXT <b>BOLD</b>"),
+            ],
+        ),
+        (
+            "<table>\n\
+             <tr><th>Icon</th><th>Name</th></tr>\n\
+             <tr><td><img src='b.svg' alt=\"a > b\"/></td><td>B <img src=\"x.png\"> C</td></tr>\n\
+             </table>",
+            &[
+                ("Icon", "ICON"),
+                ("Name", "NAME"),
+                ("B <img src=\"x.png\"> C", "C <img src=\"x.png\"> B"),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn test_translate_html_tables_untranslated() {
+        let config = MarkdownConfig {
+            extract_html: true,
+            ..MarkdownConfig::default()
+        };
+        let translator = Translator::new(create_catalog(&[]), config);
+        for (table, _) in HTML_TABLES {
+            assert_eq!(translator.translate_str(table), *table);
+        }
+    }
+
+    #[test]
+    fn test_translate_html_tables() {
+        let config = MarkdownConfig {
+            extract_html: true,
+            ..MarkdownConfig::default()
+        };
+        for (table, translations) in HTML_TABLES {
+            let catalog = create_catalog(translations);
+            // Only the text is replaced, the markup around it is kept
+            // byte for byte.
+            let mut expected = String::from(*table);
+            for (msgid, msgstr) in *translations {
+                expected = expected.replace(msgid, msgstr);
+            }
+            assert_eq!(translate(table, catalog, &config), expected);
+        }
+    }
+
+    #[test]
+    fn test_translate_html_text() {
+        let config = MarkdownConfig {
+            extract_html: true,
+            ..MarkdownConfig::default()
+        };
+        let catalog = create_catalog(&[
+            ("Intro", "INTRO"),
+            ("Name", "NAME"),
+            ("A <b>bold</b> value", "A <b>BOLD</b> VALUE"),
+        ]);
+        assert_eq!(
+            translate(
+                "Intro\n\
+                 \n\
+                 <table>\n\
+                 \x20 <tr><th>Name</th><td>A <b>bold</b> value</td></tr>\n\
+                 </table>",
+                catalog,
+                &config
+            ),
+            "INTRO\n\
+             \n\
+             <table>\n\
+             \x20 <tr><th>NAME</th><td>A <b>BOLD</b> VALUE</td></tr>\n\
+             </table>"
+        );
+    }
+
+    #[test]
+    fn test_translate_dollar_math() {
+        let catalog = create_catalog(&[
+            (
+                "The area is $\\pi r_1 * r_2$.",
+                "THE AREA IS $\\pi r_1 * r_2$.",
+            ),
+            (
+                "$$\n\\sum_{n=1}^{\\infty} a_n\n$$",
+                "$$\n\\sum_{n=1}^{\\infty} A_n\n$$",
+            ),
+        ]);
+        let text = "The area is $\\pi r_1 * r_2$.\n\n$$\n\\sum_{n=1}^{\\infty} a_n\n$$";
+        let mut translator = Translator::new(catalog, MarkdownConfig::default());
+        assert_eq!(
+            translator.translate_str(text),
+            "THE AREA IS $\\pi r_1 * r_2$.\n\n$$\n\\sum_{n=1}^{\\infty} A_n\n$$"
+        );
+
+        translator.config = MarkdownConfig {
+            skip_math: true,
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            translator.translate_str(text),
+            "THE AREA IS $\\pi r_1 * r_2$.\n\n$$\n\\sum_{n=1}^{\\infty} a_n\n$$"
+        );
+    }
+
+    #[test]
+    fn test_translate_alert() {
+        let catalog = create_catalog(&[("Be careful.", "BE CAREFUL.")]);
+        assert_eq!(
+            translate(
+                "> [!WARNING]\n> Be careful.",
+                catalog,
+                &MarkdownConfig::default()
+            ),
+            " > [!WARNING]\n > BE CAREFUL."
+        );
+    }
+
+    #[test]
+    fn test_translate_definition_list() {
+        let catalog = create_catalog(&[
+            ("Apple", "APPLE"),
+            ("A red fruit.", "A RED FRUIT."),
+            ("Orange", "ORANGE"),
+        ]);
+        assert_eq!(
+            translate(
+                "Apple\n: A red fruit.\n\nOrange\n: Citrus fruit.",
+                catalog,
+                &MarkdownConfig::default()
+            ),
+            "APPLE\n: A RED FRUIT.\n\nORANGE\n: Citrus fruit."
+        );
+    }
+
+    #[test]
+    fn test_translate_frontmatter() {
+        let catalog = create_catalog(&[
+            ("Getting Started", "GETTING: STARTED"),
+            ("Some text.", "SOME TEXT."),
+        ]);
+        let config = MarkdownConfig {
+            frontmatter_keys: vec![String::from("title")],
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            translate(
+                "---\ntitle: Getting Started\nweight: 10\n---\n\nSome text.",
+                catalog,
+                &config
+            ),
+            "---\ntitle: \"GETTING: STARTED\"\nweight: 10\n---\n\nSOME TEXT."
+        );
+    }
+
+    #[test]
+    fn test_translate_placeholders() {
+        let catalog = create_catalog(&[
+            (
+                "Run {1} from {2}the docs{/2}.",
+                "Fra {2}dokumentationen{/2}, kør {1}.",
+            ),
+            ("See {1}here{/1}.", "Se {1}her."),
+        ]);
+        let config = MarkdownConfig {
+            placeholders: true,
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            translate(
+                "Run `cargo` from [the docs](https://x.org).\n\n\
+                 See [here](https://y.org).",
+                catalog,
+                &config
+            ),
+            "Fra [dokumentationen](https://x.org), kør `cargo`.\n\n\
+             See [here](https://y.org)."
+        );
+    }
+
+    #[test]
+    fn test_translate_preserve_anchors() {
+        let catalog = create_catalog(&[
+            ("Installation", "Installation på `Linux`"),
+            ("Usage", "Brug"),
+            ("Details", "Detaljer"),
+        ]);
+        let mut translator = Translator::new(catalog, MarkdownConfig::default());
+        translator.preserve_anchors = true;
+        assert_eq!(
+            translator.translate_str(
+                "# Installation\n\n## Usage\n\n## Usage\n\n## Details {#more}\n\n## Untranslated & <done>"
+            ),
+            "# Installation på `Linux` { #installation }\n\n\
+             ## Brug { #usage }\n\n\
+             ## Brug { #usage-1 }\n\n\
+             ## Detaljer { #more }\n\n\
+             ## Untranslated & <done> { #untranslated }"
+        );
+    }
+
+    #[test]
+    fn test_translate_provenance() {
+        let catalog = create_catalog(&[("Hello", "Hej")]);
+        let mut translator = Translator::new(catalog, MarkdownConfig::default());
+        translator.provenance_dir = Some(PathBuf::from("src"));
+        let mut chapter = mdbook::book::Chapter::new(
+            "",
+            String::from("Hello\n\n```\nHello\n```"),
+            "foo.md",
+            Vec::new(),
+        );
+        translator.translate_chapter(&mut chapter);
+        assert_eq!(
+            chapter.content,
+            "<span data-i18n-hash=\"63f0bfacf2c00f6b\" \
+             data-i18n-translation-hash=\"6e05598299756030\" \
+             data-i18n-source=\"src/foo.md:1\" hidden></span>Hej\n\n\
+             ```\nHello\n```"
+        );
+    }
+
+    #[test]
+    fn test_coverage_footer() {
+        let catalog = create_catalog(&[("Hello", "Hej"), ("Goodbye", "")]);
+        let coverage = chapter_coverage(
+            "# Hello\n\nGoodbye\n\nThanks",
+            &catalog,
+            &MarkdownConfig::default(),
+        );
+        assert_eq!(coverage, (1, 3));
+        assert_eq!(
+            coverage_footer(DEFAULT_COVERAGE_FOOTER, coverage, Some("1a2b3c4"), None),
+            "---\n\n<small class=\"translation-coverage\">\
+             Translated 33% — last updated from commit 1a2b3c4</small>"
+        );
+        assert_eq!(
+            coverage_footer("{percent}% ({date})", coverage, None, Some("2023-06-02")),
+            "---\n\n<small class=\"translation-coverage\">33% (2023-06-02)</small>"
+        );
+    }
+
+    #[test]
+    fn test_check_required_translation() {
+        let mut book = Book::new();
+        for (name, content) in [("intro.md", "# Intro\n\nWelcome"), ("legal.md", "# Legal")] {
+            book.push_item(mdbook::book::Chapter::new(
+                name,
+                String::from(content),
+                name,
+                Vec::new(),
+            ));
+        }
+        let catalog = create_catalog(&[("Legal", "Jura")]);
+        let config = MarkdownConfig::default();
+        let cfg = |text: &str| toml::from_str::<toml::value::Table>(text).unwrap();
+
+        let required = cfg("required-translation = [\"legal.md\"]");
+        assert!(
+            check_required_translation(&required, "da", &book, Some(&catalog), &config).is_ok()
+        );
+        // Languages without a PO file are only checked when they are
+        // release languages.
+        assert!(check_required_translation(&required, "da", &book, None, &config).is_ok());
+
+        let required = cfg("required-translation = [\"*.md\"]\n\
+                            release-languages = [\"da\"]");
+        let err = check_required_translation(&required, "da", &book, Some(&catalog), &config)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Chapters which require a translation are not fully translated into da:\n  \
+             intro.md: 2 untranslated messages, such as \"Intro\""
+        );
+        assert!(check_required_translation(&required, "da", &book, None, &config).is_err());
+        assert!(check_required_translation(&required, "de", &book, None, &config).is_ok());
+    }
+
+    #[test]
+    fn test_is_serving() {
+        let mut config = mdbook::Config::default();
+        assert!(!is_serving(&config));
+        config
+            .set("output.html.live-reload-endpoint", "__livereload")
+            .unwrap();
+        assert!(is_serving(&config));
+    }
+
+    #[test]
+    fn test_is_watched() {
+        let root = Path::new("/book");
+        let src_dir = root.join("src");
+        assert!(!is_watched(&root.join("po"), &src_dir, &[]));
+        assert!(is_watched(&root.join("src/po"), &src_dir, &[]));
+        assert!(is_watched(
+            &root.join("./po"),
+            &src_dir,
+            &[root.join("po/")]
+        ));
+        assert!(is_watched(
+            &root.join("translations/po"),
+            &src_dir,
+            &[root.join("src/../translations")]
+        ));
+    }
 }
@@ -0,0 +1,417 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Show how long ago the translations of each chapter were touched.
+//!
+//! Translations which haven't been touched for a long time tend to
+//! fall behind the source text. This command uses `git blame` to find
+//! when the `msgstr` lines of each message were last changed. The
+//! messages are grouped into chapters using their source references,
+//! and the result is an HTML table with a row per chapter and a
+//! column per language, colored by the age of the translations.
+
+use crate::release_notes::chapter_titles;
+use anyhow::{anyhow, bail, Context};
+use mdbook_i18n_helpers::catalog_io::{read_catalog, read_to_string};
+use polib::catalog::Catalog;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book. When given, chapters are shown
+    /// with their titles instead of their file names.
+    #[arg(long)]
+    book_dir: Option<PathBuf>,
+
+    /// Write the HTML report to this file instead of stdout.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// PO files to report on. They must be checked into Git.
+    #[arg(required = true)]
+    po_files: Vec<PathBuf>,
+}
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Unquote a string in a PO file, such as `"foo\n"`.
+fn unquote(quoted: &str) -> String {
+    let inner = quoted
+        .trim()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or("");
+    let mut text = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(c) => text.push(c),
+                None => {}
+            },
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+/// Find the line numbers of the `msgstr` of each message in the PO
+/// file `content`.
+///
+/// Returns the `msgid` and the one-based line numbers for each
+/// message. Obsolete messages are skipped.
+fn msgstr_lines(content: &str) -> Vec<(String, Vec<usize>)> {
+    enum Field {
+        None,
+        Msgid,
+        Msgstr,
+    }
+    let mut messages = Vec::new();
+    let mut msgid = String::new();
+    let mut lines = Vec::new();
+    let mut field = Field::None;
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim_end();
+        if line.starts_with("msgid ") || line.starts_with("msgctxt ") || line.is_empty() {
+            if !lines.is_empty() {
+                messages.push((std::mem::take(&mut msgid), std::mem::take(&mut lines)));
+            }
+            msgid.clear();
+            field = Field::None;
+        }
+        if let Some(rest) = line.strip_prefix("msgid ") {
+            msgid = unquote(rest);
+            field = Field::Msgid;
+        } else if line.starts_with("msgstr") {
+            lines.push(idx + 1);
+            field = Field::Msgstr;
+        } else if line.starts_with('"') {
+            match field {
+                Field::Msgid => msgid.push_str(&unquote(line)),
+                Field::Msgstr => lines.push(idx + 1),
+                Field::None => {}
+            }
+        } else if !line.starts_with("msgctxt ") {
+            // Comments and `msgid_plural` end the current field.
+            field = Field::None;
+        }
+    }
+    if !lines.is_empty() {
+        messages.push((msgid, lines));
+    }
+    messages
+}
+
+/// Parse the output of `git blame --line-porcelain`.
+///
+/// Returns the author time of each one-based line number.
+fn parse_blame(output: &str) -> HashMap<usize, i64> {
+    let mut times = HashMap::new();
+    let mut lineno = None;
+    let mut time = None;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            if let (Some(lineno), Some(time)) = (lineno, time) {
+                times.insert(lineno, time);
+            }
+        } else if let Some(value) = line.strip_prefix("author-time ") {
+            time = value.parse().ok();
+        } else {
+            let fields = line.split(' ').collect::<Vec<_>>();
+            if fields.len() >= 3 && fields[0].len() == 40 {
+                lineno = fields[2].parse().ok();
+            }
+        }
+    }
+    times
+}
+
+/// Run `git blame` on `path` and return the time of each line.
+fn blame(path: &Path) -> anyhow::Result<HashMap<usize, i64>> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file", path.display()))?;
+    let output = Command::new("git")
+        .args(["blame", "--line-porcelain", "--"])
+        .arg(file_name)
+        .current_dir(dir)
+        .output()
+        .with_context(|| format!("Could not run git blame on {}", path.display()))?;
+    if !output.status.success() {
+        bail!(
+            "git blame failed on {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_blame(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Find when the translations of each source file were last touched.
+///
+/// Only translated messages are taken into account. The `lines` and
+/// `times` are the output of [`msgstr_lines`] and [`parse_blame`].
+fn chapter_times(
+    catalog: &Catalog,
+    lines: &[(String, Vec<usize>)],
+    times: &HashMap<usize, i64>,
+) -> HashMap<String, i64> {
+    let mut chapter_times = HashMap::new();
+    for (msgid, lines) in lines {
+        let Some(message) = catalog.find_message(None, msgid, None) else {
+            continue;
+        };
+        if !message.is_translated() {
+            continue;
+        }
+        let Some(time) = lines.iter().filter_map(|lineno| times.get(lineno)).max() else {
+            continue;
+        };
+        for path in message.source().lines() {
+            let path = path.rsplit_once(':').map_or(path, |(path, _)| path);
+            let chapter_time = chapter_times.entry(String::from(path)).or_insert(*time);
+            *chapter_time = (*chapter_time).max(*time);
+        }
+    }
+    chapter_times
+}
+
+/// The background color for translations which are `days` old.
+fn age_color(days: i64) -> &'static str {
+    match days {
+        ..=29 => "#1a9850",
+        30..=89 => "#91cf60",
+        90..=179 => "#fee08b",
+        180..=364 => "#fc8d59",
+        _ => "#d73027",
+    }
+}
+
+/// Escape `text` for use in HTML.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render the heatmap as an HTML page.
+///
+/// The `languages` are pairs of languages and the times from
+/// [`chapter_times`]. The rows follow the order of `chapters`, which
+/// also give the titles. Other source files are listed last, sorted.
+fn render_report(
+    languages: &[(String, HashMap<String, i64>)],
+    chapters: &[(String, String)],
+    now: i64,
+) -> String {
+    let mut rows = chapters
+        .iter()
+        .map(|(path, title)| (path.clone(), title.clone()))
+        .collect::<Vec<_>>();
+    let mut other_paths = languages
+        .iter()
+        .flat_map(|(_, times)| times.keys())
+        .filter(|path| !chapters.iter().any(|(p, _)| p == *path))
+        .cloned()
+        .collect::<Vec<_>>();
+    other_paths.sort();
+    other_paths.dedup();
+    rows.extend(other_paths.into_iter().map(|path| (path.clone(), path)));
+
+    let mut html = String::from(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>Translation age</title>\n\
+         <style>\n\
+         table { border-collapse: collapse; }\n\
+         th, td { border: 1px solid #ccc; padding: 4px 8px; }\n\
+         td.age { text-align: right; }\n\
+         </style>\n\
+         </head>\n\
+         <body>\n\
+         <h1>Translation age</h1>\n\
+         <p>Days since the translations of each chapter were last changed.</p>\n\
+         <table>\n\
+         <tr><th>Chapter</th>",
+    );
+    for (language, _) in languages {
+        html.push_str(&format!("<th>{}</th>", escape_html(language)));
+    }
+    html.push_str("</tr>\n");
+    for (path, title) in rows {
+        html.push_str(&format!("<tr><td>{}</td>", escape_html(&title)));
+        for (_, times) in languages {
+            match times.get(&path) {
+                Some(time) => {
+                    let days = (now - time).max(0) / SECONDS_PER_DAY;
+                    html.push_str(&format!(
+                        "<td class=\"age\" style=\"background: {}\">{days}</td>",
+                        age_color(days)
+                    ));
+                }
+                None => html.push_str("<td class=\"age\">&mdash;</td>"),
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let chapters = match &args.book_dir {
+        Some(book_dir) => chapter_titles(book_dir)?,
+        None => Vec::new(),
+    };
+
+    let mut languages = Vec::new();
+    for path in &args.po_files {
+        let content = read_to_string(path)?;
+        let catalog = read_catalog(path)?;
+        let times = chapter_times(&catalog, &msgstr_lines(&content), &blame(path)?);
+        let language = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        languages.push((language, times));
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let report = render_report(&languages, &chapters, now);
+    match &args.output {
+        Some(output) => fs::write(output, report)
+            .with_context(|| format!("Could not write {}", output.display()))?,
+        #[allow(clippy::print_stdout)]
+        None => print!("{report}"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    const PO_FILE: &str = r#"msgid ""
+msgstr ""
+"Language: da\n"
+
+#: src/intro.md:1
+msgid "Hello"
+msgstr "Hej"
+
+#: src/intro.md:3 src/other.md:5
+msgid ""
+"Long "
+"text"
+msgstr ""
+"Lang "
+"tekst"
+
+#: src/other.md:9
+msgid "Missing"
+msgstr ""
+
+#~ msgid "Old"
+#~ msgstr "Gammel"
+"#;
+
+    #[test]
+    fn test_msgstr_lines() {
+        assert_eq!(
+            msgstr_lines(PO_FILE),
+            vec![
+                (String::new(), vec![2, 3]),
+                (String::from("Hello"), vec![7]),
+                (String::from("Long text"), vec![13, 14, 15]),
+                (String::from("Missing"), vec![19]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_blame() {
+        let output = "\
+0123456789012345678901234567890123456789 1 1 2\n\
+author Someone\n\
+author-time 1000\n\
+\tfirst\n\
+0123456789012345678901234567890123456789 2 2\n\
+author Someone\n\
+author-time 2000\n\
+\tsecond\n";
+        assert_eq!(parse_blame(output), HashMap::from([(1, 1000), (2, 2000)]));
+    }
+
+    #[test]
+    fn test_chapter_times() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (source, msgid, msgstr) in [
+            ("src/intro.md:1", "Hello", "Hej"),
+            ("src/intro.md:3\nsrc/other.md:5", "Long text", "Lang tekst"),
+            ("src/other.md:9", "Missing", ""),
+        ] {
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_source(String::from(source))
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let times = HashMap::from([(7, 300), (13, 100), (14, 200), (15, 100), (19, 900)]);
+        assert_eq!(
+            chapter_times(&catalog, &msgstr_lines(PO_FILE), &times),
+            HashMap::from([
+                (String::from("src/intro.md"), 300),
+                (String::from("src/other.md"), 200),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_render_report() {
+        let languages = vec![(
+            String::from("da"),
+            HashMap::from([(String::from("src/intro.md"), 0)]),
+        )];
+        let chapters = vec![
+            (String::from("src/intro.md"), String::from("Intro & more")),
+            (String::from("src/other.md"), String::from("Other")),
+        ];
+        let html = render_report(&languages, &chapters, 100 * SECONDS_PER_DAY);
+        assert!(html.contains(
+            "<tr><td>Intro &amp; more</td><td class=\"age\" style=\"background: #fee08b\">100</td></tr>"
+        ));
+        assert!(html.contains("<tr><td>Other</td><td class=\"age\">&mdash;</td></tr>"));
+    }
+}
@@ -0,0 +1,288 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Apply suggested translations to a PO file.
+//!
+//! The suggestions are read from a feedback file, see the `feedback`
+//! command for the format. A suggestion is only applied if the
+//! translation is unchanged since the suggestion was made: its
+//! `translation-hash` must match the current translation. Suggestions
+//! without a `translation-hash` can only fill in missing translations.
+//! The other suggestions are reported as conflicts.
+
+use crate::feedback::{parse_feedback, FeedbackEntry};
+use crate::normalize::load_write_options;
+use crate::review::po_state_path;
+use mdbook_i18n_helpers::catalog_io::{read_catalog, write_catalog};
+use mdbook_i18n_helpers::state::{message_checksum, translation_checksum, update_state, StateFile};
+use polib::catalog::Catalog;
+use polib::message::{MessageMutView, MessageView};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    ///
+    /// The PO file is written with the `output.xgettext` encoding,
+    /// line endings and header fields from `book.toml`.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// JSON file with the suggestions.
+    suggestions_file: PathBuf,
+
+    /// PO file to update.
+    po_file: PathBuf,
+
+    /// How to mark the applied translations.
+    #[arg(long, value_enum, default_value_t = Mark::Fuzzy)]
+    mark: Mark,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Mark {
+    /// Mark the translations fuzzy, so they are checked by a
+    /// translator before they are used.
+    Fuzzy,
+    /// Use the translations right away and record them as `approved`
+    /// in the state file.
+    Approved,
+}
+
+/// A suggestion which could not be applied.
+#[derive(Debug, PartialEq)]
+struct Conflict {
+    hash: String,
+    msgid: Option<String>,
+    reason: &'static str,
+}
+
+/// The result of applying suggestions.
+#[derive(Debug, Default, PartialEq)]
+struct Report {
+    /// The `msgid` of the updated messages.
+    applied: Vec<String>,
+    conflicts: Vec<Conflict>,
+}
+
+/// Apply the `suggestions` to `catalog`.
+///
+/// With [`Mark::Approved`], the new translations are recorded as
+/// approved in `state`.
+fn apply(
+    catalog: &mut Catalog,
+    state: &mut StateFile,
+    suggestions: Vec<FeedbackEntry>,
+    mark: Mark,
+) -> Report {
+    let msgids = catalog
+        .messages()
+        .map(|message| {
+            (
+                message_checksum(message.msgid()),
+                String::from(message.msgid()),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Different suggestions for the same message must be resolved by
+    // hand.
+    let mut by_hash: HashMap<&str, Vec<&FeedbackEntry>> = HashMap::new();
+    for suggestion in &suggestions {
+        by_hash
+            .entry(&suggestion.hash)
+            .or_default()
+            .push(suggestion);
+    }
+
+    let mut report = Report::default();
+    let mut seen = Vec::new();
+    for suggestion in &suggestions {
+        if seen.contains(&suggestion.hash) {
+            continue;
+        }
+        seen.push(suggestion.hash.clone());
+        let conflict = |msgid: Option<&String>, reason| Conflict {
+            hash: suggestion.hash.clone(),
+            msgid: msgid.cloned(),
+            reason,
+        };
+
+        let Some(msgid) = msgids.get(&suggestion.hash) else {
+            report.conflicts.push(conflict(None, "no such message"));
+            continue;
+        };
+        let candidates = &by_hash[suggestion.hash.as_str()];
+        if candidates
+            .iter()
+            .any(|other| other.suggestion != suggestion.suggestion)
+        {
+            report
+                .conflicts
+                .push(conflict(Some(msgid), "different suggestions"));
+            continue;
+        }
+        let Some(mut message) = catalog.find_message_mut(None, msgid, None) else {
+            continue;
+        };
+        let Ok(msgstr) = message.msgstr() else {
+            report
+                .conflicts
+                .push(conflict(Some(msgid), "message has plural forms"));
+            continue;
+        };
+        let current_hash = (!msgstr.is_empty()).then(|| translation_checksum(msgid, msgstr));
+        if candidates
+            .iter()
+            .any(|other| other.translation_hash != current_hash)
+        {
+            report.conflicts.push(conflict(
+                Some(msgid),
+                "translation changed since the suggestion was made",
+            ));
+            continue;
+        }
+
+        message
+            .set_msgstr(suggestion.suggestion.clone())
+            .expect("message is singular");
+        match mark {
+            Mark::Fuzzy => message.flags_mut().add_flag("fuzzy"),
+            Mark::Approved => {
+                message.flags_mut().remove_flag("fuzzy");
+                state.set_review_status(msgid, &suggestion.suggestion, "approved");
+            }
+        }
+        report.applied.push(msgid.clone());
+    }
+    report
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let suggestions = parse_feedback(&args.suggestions_file)?;
+    let mut catalog = read_catalog(&args.po_file)?;
+    let report = update_state(&po_state_path(&args.po_file)?, |state| {
+        Ok(apply(&mut catalog, state, suggestions, args.mark))
+    })?;
+    write_catalog(
+        &catalog,
+        &args.po_file,
+        &load_write_options(&args.book_dir)?,
+    )?;
+
+    #[allow(clippy::print_stdout)]
+    {
+        println!(
+            "{}: applied {} suggestions, {} conflicts",
+            args.po_file.display(),
+            report.applied.len(),
+            report.conflicts.len()
+        );
+        for conflict in &report.conflicts {
+            match &conflict.msgid {
+                Some(msgid) => println!("  {} {msgid:?}: {}", conflict.hash, conflict.reason),
+                None => println!("  {}: {}", conflict.hash, conflict.reason),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    fn suggestion(msgid: &str, seen: Option<&str>, text: &str) -> FeedbackEntry {
+        FeedbackEntry {
+            hash: message_checksum(msgid),
+            translation_hash: seen.map(|msgstr| translation_checksum(msgid, msgstr)),
+            suggestion: String::from(text),
+            reporter: None,
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut catalog = create_catalog(&[
+            ("", "Hello", "Hej"),
+            ("", "Bye", "Farvel"),
+            ("", "Thanks", ""),
+            ("", "Yes", "Ja"),
+        ]);
+        let mut state = StateFile::default();
+        let report = apply(
+            &mut catalog,
+            &mut state,
+            vec![
+                suggestion("Hello", Some("Hej"), "Hejsa"),
+                suggestion("Bye", Some("Farvel!"), "Hej hej"),
+                suggestion("Thanks", None, "Tak"),
+                suggestion("Yes", Some("Ja"), "Jo"),
+                suggestion("Yes", Some("Ja"), "Jep"),
+                suggestion("Missing", None, "Mangler"),
+            ],
+            Mark::Fuzzy,
+        );
+
+        assert_eq!(report.applied, vec!["Hello", "Thanks"]);
+        assert_eq!(
+            report
+                .conflicts
+                .iter()
+                .map(|conflict| (conflict.msgid.as_deref(), conflict.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Some("Bye"),
+                    "translation changed since the suggestion was made"
+                ),
+                (Some("Yes"), "different suggestions"),
+                (None, "no such message"),
+            ]
+        );
+        let message = catalog.find_message(None, "Hello", None).unwrap();
+        assert_eq!(message.msgstr().unwrap(), "Hejsa");
+        assert!(message.is_fuzzy());
+        assert_eq!(
+            catalog
+                .find_message(None, "Bye", None)
+                .unwrap()
+                .msgstr()
+                .unwrap(),
+            "Farvel"
+        );
+    }
+
+    #[test]
+    fn test_apply_approved() {
+        let mut catalog = create_catalog(&[("", "Hello", "Hej")]);
+        let mut state = StateFile::default();
+        let report = apply(
+            &mut catalog,
+            &mut state,
+            vec![suggestion("Hello", Some("Hej"), "Hejsa")],
+            Mark::Approved,
+        );
+
+        assert_eq!(report.applied, vec!["Hello"]);
+        assert!(!catalog
+            .find_message(None, "Hello", None)
+            .unwrap()
+            .is_fuzzy());
+        assert_eq!(state.review_status("Hello", "Hejsa"), Some("approved"));
+    }
+}
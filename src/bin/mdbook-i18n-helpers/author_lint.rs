@@ -0,0 +1,263 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Find Markdown which is hard to translate.
+//!
+//! Some Markdown constructs make the messages change more often than
+//! the text, or split sentences into pieces which cannot be translated
+//! on their own. This command finds them in the chapters, so authors
+//! can fix them before the text is sent to translators:
+//!
+//! - very long paragraphs, where any small edit makes the whole
+//!   translation fuzzy,
+//! - inline HTML in a paragraph, which splits it into several messages,
+//! - shortcut and collapsed reference links, such as `[foo]`, whose
+//!   label is the link text,
+//! - tabs in the indentation of list items, which are expanded
+//!   differently by different tools.
+//!
+//! The command fails if something is found, so it can be used as a
+//! pre-commit hook.
+
+use crate::normalize::load_markdown_config;
+use anyhow::{bail, Context};
+use mdbook_i18n_helpers::MarkdownConfig;
+use mdbook_i18n_helpers::{extract_events_with_config, extract_messages_with_comments};
+use pulldown_cmark::{Event, LinkType, Tag, TagEnd};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Report messages longer than this many characters.
+    #[arg(long, default_value_t = 800)]
+    max_length: usize,
+
+    /// Markdown files or directories with Markdown files.
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+/// A construct which is hard to translate.
+#[derive(Debug, PartialEq)]
+pub struct AuthorLint {
+    /// The line of the construct, starting from 1.
+    pub lineno: usize,
+    /// What was found.
+    pub message: String,
+    /// How to fix it.
+    pub suggestion: &'static str,
+}
+
+/// Find the constructs in `document` which are hard to translate.
+///
+/// Messages longer than `max_length` characters are reported, except
+/// for code blocks.
+pub fn lint_document(
+    document: &str,
+    config: &MarkdownConfig,
+    max_length: usize,
+) -> Vec<AuthorLint> {
+    let mut lints = Vec::new();
+    for message in extract_messages_with_comments(document, config) {
+        let length = message.msgid.chars().count();
+        if !message.no_wrap && length > max_length {
+            lints.push(AuthorLint {
+                lineno: message.lineno,
+                message: format!("paragraph of {length} characters"),
+                suggestion: "split it into shorter paragraphs, so edits change fewer messages",
+            });
+        }
+    }
+
+    let lines = document.lines().collect::<Vec<_>>();
+    let mut list_lines = BTreeSet::new();
+    let mut list_depth = 0;
+    // The paragraph we are in: if it has text and if inline HTML was
+    // reported for it.
+    let mut paragraph = None;
+    for (lineno, event) in extract_events_with_config(document, None, config) {
+        if list_depth > 0 {
+            list_lines.insert(lineno);
+        }
+        match event {
+            Event::Start(Tag::List(_)) => list_depth += 1,
+            Event::End(TagEnd::List(_)) => list_depth -= 1,
+            Event::Start(Tag::Paragraph) => paragraph = Some((false, false)),
+            Event::End(TagEnd::Paragraph) => paragraph = None,
+            Event::Text(text) if !text.trim().is_empty() => {
+                if let Some((has_text, _)) = &mut paragraph {
+                    *has_text = true;
+                }
+            }
+            Event::InlineHtml(html) if html.starts_with('<') && !html.starts_with("<!--") => {
+                let Some((has_text, reported)) = &mut paragraph else {
+                    continue;
+                };
+                if *has_text && !*reported {
+                    *reported = true;
+                    lints.push(AuthorLint {
+                        lineno,
+                        message: format!("inline HTML {html} in a sentence"),
+                        suggestion: "use Markdown instead, or put the HTML on its own lines, \
+                                     since it splits the sentence into several messages",
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // The events above have the reference links turned into inline
+    // links, so we look for them in the unprocessed Markdown.
+    for (event, range) in config.parser(document).into_offset_iter() {
+        let Event::Start(Tag::Link { link_type, .. }) = event else {
+            continue;
+        };
+        let kind = match link_type {
+            LinkType::Shortcut => "shortcut",
+            LinkType::Collapsed => "collapsed",
+            _ => continue,
+        };
+        lints.push(AuthorLint {
+            lineno: document[..range.start].matches('\n').count() + 1,
+            message: format!("{kind} reference link"),
+            suggestion: "write it as `[text][label]`, so the link text can be \
+                         translated without changing the label",
+        });
+    }
+
+    for lineno in list_lines {
+        let Some(line) = lines.get(lineno.saturating_sub(1)) else {
+            continue;
+        };
+        let indentation = &line[..line.len() - line.trim_start().len()];
+        if indentation.contains('\t') {
+            lints.push(AuthorLint {
+                lineno,
+                message: String::from("tab in the indentation of a list"),
+                suggestion: "indent with spaces, since tools expand tabs differently",
+            });
+        }
+    }
+
+    lints.sort_by_key(|lint| lint.lineno);
+    lints
+}
+
+/// Find the Markdown files in `path`, which is a file or a directory.
+fn markdown_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let pattern = path.join("**").join("*.md");
+    let pattern = pattern.to_string_lossy();
+    glob::glob(&pattern)
+        .with_context(|| format!("Invalid glob pattern {pattern:?}"))?
+        .map(|entry| entry.with_context(|| format!("Could not read {}", path.display())))
+        .collect()
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let config = load_markdown_config(&args.book_dir)?;
+    let mut count = 0;
+    for path in &args.paths {
+        for file in markdown_files(path)? {
+            let document = fs::read_to_string(&file)
+                .with_context(|| format!("Could not read {}", file.display()))?;
+            let lints = lint_document(&document, &config, args.max_length);
+            #[allow(clippy::print_stdout)]
+            for lint in &lints {
+                println!(
+                    "{}:{}: {}: {}",
+                    file.display(),
+                    lint.lineno,
+                    lint.message,
+                    lint.suggestion
+                );
+            }
+            count += lints.len();
+        }
+    }
+    if count > 0 {
+        bail!("Found {count} constructs which are hard to translate");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn lines_and_messages(lints: &[AuthorLint]) -> Vec<(usize, &str)> {
+        lints
+            .iter()
+            .map(|lint| (lint.lineno, lint.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_document() {
+        let document = "# Setup\n\
+                        \n\
+                        Run <b>this</b> now, see [the guide] and [Cargo][].\n\
+                        \n\
+                        <div>\n\
+                        Raw HTML on its own.\n\
+                        </div>\n\
+                        \n\
+                        - First item\n\
+                        \tcontinued with a tab\n\
+                        - Second item\n\
+                        \n\
+                        A paragraph which is far too long for comfort.\n\
+                        \n\
+                        [the guide]: https://example.com/guide\n\
+                        [cargo]: https://example.com/cargo\n";
+        assert_eq!(
+            lines_and_messages(&lint_document(document, &MarkdownConfig::default(), 40)),
+            vec![
+                (3, "paragraph of 88 characters"),
+                (3, "inline HTML <b> in a sentence"),
+                (3, "shortcut reference link"),
+                (3, "collapsed reference link"),
+                (10, "tab in the indentation of a list"),
+                (13, "paragraph of 46 characters"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_document_clean() {
+        let document = "# Setup\n\
+                        \n\
+                        Run [the guide](https://example.com) now.\n\
+                        \n\
+                        ```rust\n\
+                        fn main() {\n\
+                        \tprintln!(\"Tabs in code blocks are fine\");\n\
+                        }\n\
+                        ```\n";
+        assert_eq!(
+            lint_document(document, &MarkdownConfig::default(), 60),
+            Vec::new()
+        );
+    }
+}
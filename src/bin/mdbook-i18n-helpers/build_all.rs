@@ -0,0 +1,631 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Build every translation of a book.
+//!
+//! The languages are taken from `preprocessor.gettext.languages`. If
+//! that is not set, a language is built for each PO file in the PO
+//! directory. Each language is built with
+//!
+//! ```shell
+//! MDBOOK_BOOK__LANGUAGE=xx mdbook build -d book/xx
+//! ```
+//!
+//! together with the translated `book.toml` values printed by the
+//! `config-env` command. A failed build doesn't stop the other builds,
+//! the failures are reported at the end.
+//!
+//! The languages are built in parallel, with at most `--jobs` builds at
+//! a time. A fingerprint of the inputs of each language is stored in
+//! `.build-all.json` in the destination directory, and languages whose
+//! inputs haven't changed since the last build are skipped. The inputs
+//! are `book.toml`, the source and theme directories, the PO file, and
+//! the environment of the build. Use `--force` to build everything.
+//!
+//! If the book is in a git repository, each language is built from
+//! the sources as of the `POT-Creation-Date` of its PO file, see
+//! [`mdbook_i18n_helpers::backdate`]. The sources are copied to
+//! `.sources` in the destination directory for the build, the working
+//! tree is not changed. Use `--no-backdate` to build from the current
+//! sources.
+//!
+//! A language can override files of the theme, such as a translated
+//! footer, with `preprocessor.gettext.themes`:
+//!
+//! ```toml
+//! [preprocessor.gettext]
+//! themes = { ko = "theme-ko" }
+//! ```
+//!
+//! The files in `theme-ko` are then used instead of the ones in the
+//! theme directory when building Korean, the other files of the theme
+//! are kept. The merged theme is written to `.themes` in the
+//! destination directory for the build.
+
+use anyhow::{anyhow, bail, Context};
+use mdbook::MDBook;
+use mdbook_i18n_helpers::backdate::{
+    commit_before, export_tree, pot_creation_date, repository_root,
+};
+use mdbook_i18n_helpers::config_keys::{env_var_name, translate_config};
+use mdbook_i18n_helpers::language::{find_catalog, same_language, FoundCatalog, LookupConfig};
+use mdbook_i18n_helpers::language_switcher::book_languages;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+
+/// The file in the destination directory with the fingerprints of the
+/// last successful builds.
+const FINGERPRINTS_FILE: &str = ".build-all.json";
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Directory for the translations, relative to the book root.
+    #[arg(long, default_value = "book")]
+    dest_dir: PathBuf,
+
+    /// The `mdbook` program to run.
+    #[arg(long, default_value = "mdbook")]
+    mdbook: PathBuf,
+
+    /// Number of languages to build at the same time, defaults to the
+    /// number of CPUs.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Build all languages, even if their inputs haven't changed.
+    #[arg(long)]
+    force: bool,
+
+    /// Build from the current sources instead of the sources as of the
+    /// `POT-Creation-Date` of each PO file.
+    #[arg(long)]
+    no_backdate: bool,
+
+    /// Languages to build instead of the configured languages.
+    languages: Vec<String>,
+}
+
+/// Find the languages with a PO file in `po_dir`, sorted.
+///
+/// The language is taken from the file name, so `pt_BR.po` is built
+/// as `pt-BR`.
+fn po_languages(po_dir: &Path) -> anyhow::Result<Vec<String>> {
+    let mut languages = Vec::new();
+    for entry in fs::read_dir(po_dir).with_context(|| format!("Could not read {:?}", po_dir))? {
+        let path = entry
+            .with_context(|| format!("Could not read {:?}", po_dir))?
+            .path();
+        if path.extension().is_some_and(|ext| ext == "po") {
+            if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                languages.push(stem.replace('_', "-"));
+            }
+        }
+    }
+    languages.sort();
+    Ok(languages)
+}
+
+/// Environment variables as pairs of names and values.
+type Env = Vec<(String, String)>;
+
+/// The environment variables for building the book in `language`.
+///
+/// The values are JSON encoded, otherwise `mdbook` would turn values
+/// such as `true` or `42` into booleans and numbers. Also returns the
+/// PO file, if there is one.
+pub fn build_env(
+    config: &mdbook::Config,
+    po_dir: &Path,
+    language: &str,
+) -> anyhow::Result<(Env, Option<FoundCatalog>)> {
+    let encode = |value: &str| serde_json::Value::String(String::from(value)).to_string();
+    let mut env = vec![(env_var_name("book.language"), encode(language))];
+    let lookup_config = LookupConfig::from_book_config(config)?;
+    let Some(found) = find_catalog(po_dir, language, &lookup_config)? else {
+        return Ok((env, None));
+    };
+    for (key, translation) in translate_config(config, &found.catalog)? {
+        env.push((env_var_name(&key), encode(&translation)));
+    }
+    Ok((env, Some(found)))
+}
+
+/// Find the theme directory of `language` in
+/// `preprocessor.gettext.themes`, relative to the book root.
+///
+/// Regional variants, such as `pt-BR`, fall back to the general
+/// language.
+fn language_theme(config: &mdbook::Config, language: &str) -> anyhow::Result<Option<PathBuf>> {
+    let Some(value) = config.get("preprocessor.gettext.themes") else {
+        return Ok(None);
+    };
+    let table = value
+        .as_table()
+        .ok_or_else(|| anyhow!("Expected a table for preprocessor.gettext.themes"))?;
+    let general = language.split_once(['-', '_']).map(|(general, _)| general);
+    let Some((key, dir)) = table
+        .iter()
+        .find(|(key, _)| same_language(key, language))
+        .or_else(|| {
+            let general = general?;
+            table.iter().find(|(key, _)| same_language(key, general))
+        })
+    else {
+        return Ok(None);
+    };
+    let dir = dir
+        .as_str()
+        .ok_or_else(|| anyhow!("Expected a string for preprocessor.gettext.themes.{key}"))?;
+    Ok(Some(PathBuf::from(dir)))
+}
+
+/// Copy the files in `from` into `to`, replacing existing files.
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("Could not create {}", to.display()))?;
+    for entry in fs::read_dir(from).with_context(|| format!("Could not read {:?}", from))? {
+        let path = entry
+            .with_context(|| format!("Could not read {:?}", from))?
+            .path();
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        if path.is_dir() {
+            copy_dir(&path, &to.join(name))?;
+        } else {
+            fs::copy(&path, to.join(name))
+                .with_context(|| format!("Could not copy {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the files of `theme_dir`, overridden by the files of
+/// `language_dir`, to `dir`.
+fn merge_themes(theme_dir: &Path, language_dir: &Path, dir: &Path) -> anyhow::Result<()> {
+    if dir.exists() {
+        fs::remove_dir_all(dir).with_context(|| format!("Could not remove {}", dir.display()))?;
+    }
+    if theme_dir.is_dir() {
+        copy_dir(theme_dir, dir)?;
+    }
+    copy_dir(language_dir, dir)
+}
+
+/// Hash the file or directory at `path` into `hasher`.
+///
+/// Directories are hashed recursively in a fixed order. Missing paths
+/// are hashed as such.
+fn hash_path(path: &Path, hasher: &mut DefaultHasher) -> anyhow::Result<()> {
+    path.hash(hasher);
+    if path.is_dir() {
+        let mut entries = fs::read_dir(path)
+            .with_context(|| format!("Could not read {:?}", path))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Could not read {:?}", path))?;
+        entries.sort();
+        for entry in entries {
+            hash_path(&entry, hasher)?;
+        }
+    } else if path.exists() {
+        fs::read(path)
+            .with_context(|| format!("Could not read {}", path.display()))?
+            .hash(hasher);
+    }
+    Ok(())
+}
+
+/// Fingerprint the inputs at `paths`, the `env`, and the source
+/// `commit` of a build.
+fn fingerprint(
+    paths: &[PathBuf],
+    env: &[(String, String)],
+    commit: Option<&str>,
+) -> anyhow::Result<String> {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    env.hash(&mut hasher);
+    commit.hash(&mut hasher);
+    for path in paths {
+        hash_path(path, &mut hasher)?;
+    }
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+/// A language which needs to be built.
+struct Job {
+    language: String,
+    dest_dir: PathBuf,
+    env: Env,
+    /// The commit with the sources to build from.
+    commit: Option<String>,
+    /// The theme files of the language, see [`language_theme`].
+    theme_dir: Option<PathBuf>,
+    fingerprint: String,
+}
+
+/// Run `jobs` with at most `parallelism` builds at the same time.
+///
+/// Returns the jobs with the result of their build.
+fn run_jobs(
+    jobs: Vec<Job>,
+    parallelism: usize,
+    build: impl Fn(&Job) -> anyhow::Result<()> + Sync,
+) -> Vec<(Job, anyhow::Result<()>)> {
+    let queue = Mutex::new(jobs.into_iter());
+    let results = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..parallelism.max(1) {
+            scope.spawn(|| loop {
+                let Some(job) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let result = build(&job);
+                results.lock().unwrap().push((job, result));
+            });
+        }
+    });
+    results.into_inner().unwrap()
+}
+
+fn read_fingerprints(path: &Path) -> BTreeMap<String, String> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let book = MDBook::load(&args.book_dir)?;
+    let po_dir = book.root.join(
+        book.config
+            .get("preprocessor.gettext.po-dir")
+            .and_then(|value| value.as_str())
+            .unwrap_or("po"),
+    );
+    let mut languages = args.languages;
+    if languages.is_empty() {
+        languages = book_languages(&book.config)?
+            .into_iter()
+            .map(|language| language.language)
+            .collect();
+    }
+    if languages.is_empty() {
+        languages = po_languages(&po_dir)?;
+    }
+    if languages.is_empty() {
+        bail!("No languages found, set preprocessor.gettext.languages in book.toml");
+    }
+
+    let fingerprints_path = book.root.join(&args.dest_dir).join(FINGERPRINTS_FILE);
+    let mut fingerprints = if args.force {
+        BTreeMap::new()
+    } else {
+        read_fingerprints(&fingerprints_path)
+    };
+    let repository = match args.no_backdate {
+        true => None,
+        false => repository_root(&book.root)?,
+    };
+    let mut failed = Vec::new();
+    let mut jobs = Vec::new();
+    for language in &languages {
+        let dest_dir = args.dest_dir.join(language);
+        let job = build_env(&book.config, &po_dir, language).and_then(|(env, found)| {
+            let date = found
+                .as_ref()
+                .and_then(|found| pot_creation_date(&found.catalog));
+            let commit = match (&repository, date) {
+                (Some(repository), Some(date)) => commit_before(repository, date)?,
+                _ => None,
+            };
+            let theme_dir = language_theme(&book.config, language)?.map(|dir| book.root.join(dir));
+            // The sources of a commit are fingerprinted by the commit.
+            let mut paths = vec![book.root.join("book.toml"), book.theme_dir()];
+            paths.extend(theme_dir.clone());
+            if commit.is_none() {
+                paths.push(book.source_dir());
+            }
+            paths.extend(found.map(|found| found.path));
+            let fingerprint = fingerprint(&paths, &env, commit.as_deref())?;
+            Ok(Job {
+                language: language.clone(),
+                dest_dir,
+                env,
+                commit,
+                theme_dir,
+                fingerprint,
+            })
+        });
+        match job {
+            Ok(job)
+                if fingerprints.get(language) == Some(&job.fingerprint)
+                    && book.root.join(&job.dest_dir).is_dir() =>
+            {
+                #[allow(clippy::print_stderr)]
+                {
+                    eprintln!("Skipping {language}, nothing changed");
+                }
+            }
+            Ok(job) => jobs.push(job),
+            Err(err) => failed.push(format!("{language} ({err:#})")),
+        }
+    }
+
+    let parallelism = args.jobs.unwrap_or_else(|| {
+        thread::available_parallelism().map_or(1, |parallelism| parallelism.get())
+    });
+    // The book cannot be shared between threads.
+    let (root, source_dir, theme_dir) = (book.root.clone(), book.source_dir(), book.theme_dir());
+    let results = run_jobs(jobs, parallelism, |job| {
+        let mut env = job.env.clone();
+        if let Some(language_dir) = &job.theme_dir {
+            let dir = root
+                .join(&args.dest_dir)
+                .join(".themes")
+                .join(&job.language);
+            merge_themes(&theme_dir, language_dir, &dir)?;
+            let dir = dir.canonicalize()?;
+            env.push((
+                env_var_name("output.html.theme"),
+                serde_json::Value::String(dir.to_string_lossy().into_owned()).to_string(),
+            ));
+        }
+        let mut sources_dir = None;
+        if let (Some(repository), Some(commit)) = (&repository, &job.commit) {
+            #[allow(clippy::print_stderr)]
+            {
+                eprintln!(
+                    "Building {} into {} from the sources of {commit}",
+                    job.language,
+                    job.dest_dir.display()
+                );
+            }
+            let src_dir = source_dir.canonicalize()?;
+            let relative = src_dir
+                .strip_prefix(repository.canonicalize()?)
+                .with_context(|| format!("{} is not in the repository", src_dir.display()))?;
+            let dir = root
+                .join(&args.dest_dir)
+                .join(".sources")
+                .join(&job.language);
+            if dir.exists() {
+                fs::remove_dir_all(&dir)
+                    .with_context(|| format!("Could not remove {}", dir.display()))?;
+            }
+            export_tree(repository, commit, relative, &dir)?;
+            let dir = dir.canonicalize()?;
+            env.push((
+                env_var_name("book.src"),
+                serde_json::Value::String(dir.to_string_lossy().into_owned()).to_string(),
+            ));
+            sources_dir = Some(dir);
+        } else {
+            #[allow(clippy::print_stderr)]
+            {
+                eprintln!("Building {} into {}", job.language, job.dest_dir.display());
+            }
+        }
+        let status = Command::new(&args.mdbook)
+            .arg("build")
+            .arg("--dest-dir")
+            .arg(&job.dest_dir)
+            .arg(&root)
+            .envs(env)
+            .status()
+            .with_context(|| format!("Could not run {}", args.mdbook.display()));
+        if let Some(dir) = sources_dir {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Could not remove {}", dir.display()))?;
+        }
+        let status = status?;
+        if !status.success() {
+            bail!("{status}");
+        }
+        Ok(())
+    });
+    for (job, result) in results {
+        match result {
+            Ok(()) => {
+                fingerprints.insert(job.language, job.fingerprint);
+            }
+            Err(err) => {
+                fingerprints.remove(&job.language);
+                failed.push(format!("{} ({err:#})", job.language));
+            }
+        }
+    }
+    if let Some(parent) = fingerprints_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Could not create {}", parent.display()))?;
+    }
+    fs::write(
+        &fingerprints_path,
+        serde_json::to_string_pretty(&fingerprints)? + "\n",
+    )
+    .with_context(|| format!("Could not write {}", fingerprints_path.display()))?;
+
+    if !failed.is_empty() {
+        return Err(anyhow!(
+            "Could not build {} of {} languages: {}",
+            failed.len(),
+            languages.len(),
+            failed.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::catalog::Catalog;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use polib::po_file;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_po_languages() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        for name in ["da.po", "pt_BR.po", "messages.pot", "README.md"] {
+            fs::write(tmpdir.path().join(name), "")?;
+        }
+        assert_eq!(po_languages(tmpdir.path())?, vec!["da", "pt-BR"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_env() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_msgid(String::from("Edit"))
+                .with_msgstr(String::from("Rediger"))
+                .done(),
+        );
+        po_file::write(&catalog, &tmpdir.path().join("da.po"))?;
+        let config: mdbook::Config = "[output.html]\n\
+                                      label = \"Edit\"\n\
+                                      [output.xgettext]\n\
+                                      extra-config-keys = [\"output.html.label\"]\n"
+            .parse()?;
+        assert_eq!(
+            build_env(&config, tmpdir.path(), "da")?.0,
+            vec![
+                (
+                    String::from("MDBOOK_BOOK__LANGUAGE"),
+                    String::from("\"da\"")
+                ),
+                (
+                    String::from("MDBOOK_OUTPUT__HTML__LABEL"),
+                    String::from("\"Rediger\"")
+                ),
+            ]
+        );
+        let (env, found) = build_env(&config, tmpdir.path(), "fr")?;
+        assert_eq!(
+            env,
+            vec![(
+                String::from("MDBOOK_BOOK__LANGUAGE"),
+                String::from("\"fr\"")
+            )]
+        );
+        assert!(found.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_language_theme() -> anyhow::Result<()> {
+        let config: mdbook::Config = "[preprocessor.gettext]\n\
+                                      themes = { ko = \"theme-ko\", pt = \"theme-pt\" }\n"
+            .parse()?;
+        assert_eq!(
+            language_theme(&config, "ko")?,
+            Some(PathBuf::from("theme-ko"))
+        );
+        assert_eq!(
+            language_theme(&config, "pt-BR")?,
+            Some(PathBuf::from("theme-pt"))
+        );
+        assert_eq!(language_theme(&config, "da")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_themes() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let theme_dir = tmpdir.path().join("theme");
+        let language_dir = tmpdir.path().join("theme-ko");
+        fs::create_dir_all(theme_dir.join("css"))?;
+        fs::create_dir_all(&language_dir)?;
+        fs::write(theme_dir.join("index.hbs"), "index")?;
+        fs::write(theme_dir.join("footer.hbs"), "footer")?;
+        fs::write(theme_dir.join("css").join("extra.css"), "css")?;
+        fs::write(language_dir.join("footer.hbs"), "바닥글")?;
+
+        let dir = tmpdir.path().join("merged");
+        merge_themes(&theme_dir, &language_dir, &dir)?;
+        assert_eq!(fs::read_to_string(dir.join("index.hbs"))?, "index");
+        assert_eq!(fs::read_to_string(dir.join("footer.hbs"))?, "바닥글");
+        assert_eq!(
+            fs::read_to_string(dir.join("css").join("extra.css"))?,
+            "css"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let src_dir = tmpdir.path().join("src");
+        fs::create_dir(&src_dir)?;
+        fs::write(src_dir.join("index.md"), "# Hello")?;
+        let paths = vec![src_dir.clone(), tmpdir.path().join("da.po")];
+        let env = vec![(String::from("MDBOOK_BOOK__LANGUAGE"), String::from("da"))];
+
+        let first = fingerprint(&paths, &env, None)?;
+        assert_eq!(fingerprint(&paths, &env, None)?, first);
+        assert_ne!(fingerprint(&paths, &[], None)?, first);
+        assert_ne!(fingerprint(&paths, &env, Some("abc123"))?, first);
+        fs::write(tmpdir.path().join("da.po"), "")?;
+        let second = fingerprint(&paths, &env, None)?;
+        assert_ne!(second, first);
+        fs::write(src_dir.join("index.md"), "# Hi")?;
+        assert_ne!(fingerprint(&paths, &env, None)?, second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_jobs() {
+        let jobs = ["da", "de", "fr"]
+            .iter()
+            .map(|language| Job {
+                language: String::from(*language),
+                dest_dir: PathBuf::from(language),
+                env: Vec::new(),
+                commit: None,
+                theme_dir: None,
+                fingerprint: String::new(),
+            })
+            .collect();
+        let mut results = run_jobs(jobs, 2, |job| match job.language.as_str() {
+            "de" => bail!("failed"),
+            _ => Ok(()),
+        })
+        .into_iter()
+        .map(|(job, result)| (job.language, result.is_ok()))
+        .collect::<Vec<_>>();
+        results.sort();
+        assert_eq!(
+            results,
+            vec![
+                (String::from("da"), true),
+                (String::from("de"), false),
+                (String::from("fr"), true)
+            ]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Find links to headings which break in a translated book.
+//!
+//! A link such as `setup.md#installation` points to the anchor of a
+//! heading. The anchor is generated from the text of the heading, so
+//! it changes when the heading is translated. This command translates
+//! the book into each language, like `mdbook-gettext` does, and
+//! reports the links to anchors which no longer exist.
+
+use crate::normalize::get_bool;
+use anyhow::{anyhow, bail};
+use mdbook::{BookItem, MDBook};
+use mdbook_i18n_helpers::anchors::{heading_anchors, preserve_heading_anchors};
+use mdbook_i18n_helpers::language::{find_catalog, LookupConfig};
+use mdbook_i18n_helpers::{
+    extract_events_with_config, translate_events_with_config, MarkdownConfig,
+};
+use polib::catalog::Catalog;
+use pulldown_cmark::{Event, Tag};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Languages to check, such as `da` for `po/da.po`.
+    #[arg(required = true)]
+    languages: Vec<String>,
+}
+
+/// A link to a heading anchor which doesn't exist.
+#[derive(Debug, PartialEq)]
+pub struct BrokenLink {
+    /// The chapter with the link.
+    pub chapter: PathBuf,
+    /// The destination of the link, such as `setup.md#installation`.
+    pub destination: String,
+}
+
+/// Resolve `path` relative to the directory of `chapter`.
+///
+/// Links to the rendered `.html` files are mapped back to the `.md`
+/// source files.
+fn resolve_path(chapter: &Path, path: &str) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    let joined = match path {
+        "" => chapter.to_path_buf(),
+        _ => chapter.parent().unwrap_or(Path::new("")).join(path),
+    };
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => resolved.push(component),
+        }
+    }
+    if resolved.extension().is_some_and(|ext| ext == "html") {
+        resolved.set_extension("md");
+    }
+    resolved
+}
+
+/// Translate the `chapters` using `catalog` and find the links to
+/// heading anchors which don't exist in the translation.
+///
+/// The `chapters` are pairs of paths and Markdown text. Links to
+/// files outside of the chapters and links to other sites are
+/// ignored.
+pub fn find_broken_links(
+    chapters: &[(PathBuf, String)],
+    catalog: &Catalog,
+    config: &MarkdownConfig,
+    preserve_anchors: bool,
+) -> Vec<BrokenLink> {
+    let mut anchors = HashMap::new();
+    let mut links = Vec::new();
+    for (path, content) in chapters {
+        let events = extract_events_with_config(content, None, config);
+        let mut translated_events = translate_events_with_config(&events, catalog, config);
+        if preserve_anchors {
+            preserve_heading_anchors(&events, &mut translated_events);
+        }
+        anchors.insert(
+            path.clone(),
+            heading_anchors(&translated_events)
+                .into_iter()
+                .collect::<HashSet<_>>(),
+        );
+        for (_, event) in &translated_events {
+            if let Event::Start(Tag::Link { dest_url, .. }) = event {
+                links.push((path.clone(), dest_url.to_string()));
+            }
+        }
+    }
+
+    let mut broken_links = Vec::new();
+    for (chapter, destination) in links {
+        if destination.contains("://") || destination.starts_with("mailto:") {
+            continue;
+        }
+        let Some((path, anchor)) = destination.split_once('#') else {
+            continue;
+        };
+        let target = resolve_path(&chapter, path);
+        if let Some(target_anchors) = anchors.get(&target) {
+            if !anchor.is_empty() && !target_anchors.contains(anchor) {
+                broken_links.push(BrokenLink {
+                    chapter,
+                    destination,
+                });
+            }
+        }
+    }
+    broken_links
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let book = MDBook::load(&args.book_dir)?;
+    let config = MarkdownConfig::from_book_config(&book.config)?;
+    let po_dir = book
+        .config
+        .get("preprocessor.gettext.po-dir")
+        .and_then(|value| value.as_str())
+        .unwrap_or("po");
+    let lookup_config = LookupConfig::from_book_config(&book.config)?;
+    let preserve_anchors = get_bool(&book.config, "preprocessor.gettext.preserve-anchors")?;
+
+    let chapters = book
+        .book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(ch) => ch
+                .path
+                .as_ref()
+                .map(|path| (path.clone(), ch.content.clone())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut broken_count = 0;
+    for language in &args.languages {
+        let found = find_catalog(&book.root.join(po_dir), language, &lookup_config)?
+            .ok_or_else(|| anyhow!("Could not find a PO file for {language:?} in {po_dir}"))?;
+        #[allow(clippy::print_stderr)]
+        for warning in &found.warnings {
+            eprintln!("Warning: {warning}");
+        }
+        let broken_links = find_broken_links(&chapters, &found.catalog, &config, preserve_anchors);
+        #[allow(clippy::print_stdout)]
+        for link in &broken_links {
+            println!(
+                "{language}: {}: broken link to {}",
+                link.chapter.display(),
+                link.destination
+            );
+        }
+        broken_count += broken_links.len();
+    }
+
+    if broken_count > 0 {
+        bail!("Found {broken_count} broken links");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    fn chapters() -> Vec<(PathBuf, String)> {
+        vec![
+            (
+                PathBuf::from("intro.md"),
+                String::from(
+                    "# Introduction\n\n\
+                     See [setup](guide/setup.md#installation), [the intro](#introduction), \
+                     [the API](https://example.com/#api) and [the end](#end).\n\n\
+                     # End { #end }",
+                ),
+            ),
+            (
+                PathBuf::from("guide/setup.md"),
+                String::from("# Installation\n\nBack to [the intro](../intro.html#introduction)."),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        assert_eq!(
+            resolve_path(Path::new("a/b.md"), "../c.html"),
+            PathBuf::from("c.md")
+        );
+        assert_eq!(
+            resolve_path(Path::new("a/b.md"), ""),
+            PathBuf::from("a/b.md")
+        );
+    }
+
+    #[test]
+    fn test_find_broken_links() {
+        let catalog = create_catalog(&[
+            ("", "Installation", "Installation af værktøjer"),
+            ("", "End", "Slut"),
+        ]);
+        assert_eq!(
+            find_broken_links(&chapters(), &catalog, &MarkdownConfig::default(), false),
+            vec![BrokenLink {
+                chapter: PathBuf::from("intro.md"),
+                destination: String::from("guide/setup.md#installation"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_broken_links_preserve_anchors() {
+        let catalog = create_catalog(&[("", "Installation", "Installation af værktøjer")]);
+        assert_eq!(
+            find_broken_links(&chapters(), &catalog, &MarkdownConfig::default(), true),
+            vec![]
+        );
+    }
+}
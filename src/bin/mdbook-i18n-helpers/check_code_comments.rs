@@ -0,0 +1,83 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Find translated code blocks whose comments lost their markers.
+//!
+//! A comment line such as `// Add one` which is translated without
+//! the `//` breaks the code. The comment lines of each translated code
+//! block are compared with the code block in the `msgid`, see
+//! [`mdbook_i18n_helpers::code_blocks::missing_comment_markers`]. The
+//! rules for the comment markers are taken from
+//! `output.xgettext.code-rules`, or the default rules.
+//!
+//! `mdbook-gettext` puts the markers back when it translates the book,
+//! so this check is for the PO files themselves.
+
+use crate::normalize::load_markdown_config;
+use anyhow::bail;
+use mdbook_i18n_helpers::catalog_io::read_catalog;
+use mdbook_i18n_helpers::code_blocks::{default_rules, missing_comment_markers};
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file with the
+    /// rules for the comment markers.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// PO files to check.
+    #[arg(required = true)]
+    po_files: Vec<PathBuf>,
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let rules = load_markdown_config(&args.book_dir)?
+        .code_rules
+        .unwrap_or_else(default_rules);
+    let mut problem_count = 0;
+    for path in &args.po_files {
+        let catalog = read_catalog(path)?;
+        for message in catalog.messages() {
+            if !message.is_translated() || message.is_fuzzy() {
+                continue;
+            }
+            let Ok(msgstr) = message.msgstr() else {
+                continue;
+            };
+            let lines = missing_comment_markers(message.msgid(), msgstr, &rules);
+            if lines.is_empty() {
+                continue;
+            }
+            let source = message.source().lines().next().unwrap_or_default();
+            let lines = lines
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            #[allow(clippy::print_stdout)]
+            {
+                println!(
+                    "{}: {source}: comment marker missing on line {lines} of the code block",
+                    path.display()
+                );
+            }
+            problem_count += 1;
+        }
+    }
+    if problem_count > 0 {
+        bail!("Found {problem_count} translated code blocks with missing comment markers");
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Check translations against the rules in a rules file.
+//!
+//! See [`mdbook_i18n_helpers::content_rules`] for the format of the
+//! rules file. The language of each PO file is taken from its
+//! `Language` header, or from the file name if there is no header.
+
+use anyhow::{bail, Context};
+use mdbook_i18n_helpers::catalog_io::read_catalog;
+use mdbook_i18n_helpers::content_rules::{check_rules, parse_rules, Severity};
+use mdbook_i18n_helpers::language::catalog_language;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The rules file.
+    #[arg(long)]
+    rules: PathBuf,
+
+    /// PO files to check.
+    #[arg(required = true)]
+    po_files: Vec<PathBuf>,
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let content = fs::read_to_string(&args.rules)
+        .with_context(|| format!("Could not read {}", args.rules.display()))?;
+    let rules = parse_rules(&content)
+        .with_context(|| format!("Could not parse {}", args.rules.display()))?;
+    let mut error_count = 0;
+    for path in &args.po_files {
+        let catalog = read_catalog(path)?;
+        let language = catalog_language(&catalog).map(String::from).or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().replace('_', "-"))
+        });
+        for violation in check_rules(&catalog, language.as_deref(), &rules) {
+            let severity = match violation.severity {
+                Severity::Warning => "warning",
+                Severity::Error => {
+                    error_count += 1;
+                    "error"
+                }
+            };
+            #[allow(clippy::print_stdout)]
+            {
+                println!(
+                    "{}: {severity}: {:?}: {}",
+                    path.display(),
+                    violation.msgid,
+                    violation.message
+                );
+            }
+        }
+    }
+    if error_count > 0 {
+        bail!("Found {error_count} translations which break the rules");
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Find translations whose surrounding text has changed.
+//!
+//! This needs PO files extracted with `output.xgettext.source-hashes`
+//! enabled. The source hashes in the PO files are compared with the
+//! hashes of the current chapters: a translated message is reported
+//! when its `msgid` is still in the book, but the paragraphs around it
+//! have changed.
+
+use anyhow::{anyhow, bail};
+use mdbook::{BookItem, MDBook};
+use mdbook_i18n_helpers::language::{find_catalog, LookupConfig};
+use mdbook_i18n_helpers::source_hash::{current_source_hashes, find_stale_messages};
+use mdbook_i18n_helpers::{extract_messages_with_comments, MarkdownConfig};
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Languages to check, such as `da` for `po/da.po`.
+    #[arg(required = true)]
+    languages: Vec<String>,
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let book = MDBook::load(&args.book_dir)?;
+    let config = MarkdownConfig {
+        source_hashes: true,
+        ..MarkdownConfig::from_book_config(&book.config)?
+    };
+    let po_dir = book
+        .config
+        .get("preprocessor.gettext.po-dir")
+        .and_then(|value| value.as_str())
+        .unwrap_or("po");
+    let lookup_config = LookupConfig::from_book_config(&book.config)?;
+
+    let messages = book
+        .book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(ch) if ch.path.is_some() => Some(&ch.content),
+            _ => None,
+        })
+        .flat_map(|content| extract_messages_with_comments(content, &config))
+        .collect::<Vec<_>>();
+    let current = current_source_hashes(&messages);
+
+    let mut stale_count = 0;
+    for language in &args.languages {
+        let found = find_catalog(&book.root.join(po_dir), language, &lookup_config)?
+            .ok_or_else(|| anyhow!("Could not find a PO file for {language:?} in {po_dir}"))?;
+        #[allow(clippy::print_stderr)]
+        for warning in &found.warnings {
+            eprintln!("Warning: {warning}");
+        }
+        let stale_messages = find_stale_messages(&found.catalog, &current);
+        #[allow(clippy::print_stdout)]
+        for message in &stale_messages {
+            let source = message.source.lines().next().unwrap_or_default();
+            println!(
+                "{language}: {source}: the text around {:?} has changed",
+                message.msgid
+            );
+        }
+        stale_count += stale_messages.len();
+    }
+
+    if stale_count > 0 {
+        bail!("Found {stale_count} translations with changed surroundings");
+    }
+    Ok(())
+}
@@ -0,0 +1,59 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Find translations which drop or add emoji.
+//!
+//! The emoji of each translation are compared with the emoji of the
+//! untranslated message, see [`mdbook_i18n_helpers::symbols`]. Use
+//! `--allow` for emoji which translations may freely drop or add.
+
+use anyhow::bail;
+use mdbook_i18n_helpers::catalog_io::read_catalog;
+use mdbook_i18n_helpers::symbols::find_symbol_mismatches;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Emoji which may differ between a message and its translation.
+    #[arg(long = "allow", value_name = "SYMBOL")]
+    allowed: Vec<String>,
+
+    /// PO files to check.
+    #[arg(required = true)]
+    po_files: Vec<PathBuf>,
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let allowed = args.allowed.iter().map(String::as_str).collect::<Vec<_>>();
+    let mut mismatch_count = 0;
+    for path in &args.po_files {
+        let catalog = read_catalog(path)?;
+        let mismatches = find_symbol_mismatches(&catalog, &allowed);
+        #[allow(clippy::print_stdout)]
+        for mismatch in &mismatches {
+            println!(
+                "{}: {:?}: missing {:?}, extra {:?}",
+                path.display(),
+                mismatch.msgid,
+                mismatch.missing,
+                mismatch.extra
+            );
+        }
+        mismatch_count += mismatches.len();
+    }
+    if mismatch_count > 0 {
+        bail!("Found {mismatch_count} translations with different emoji");
+    }
+    Ok(())
+}
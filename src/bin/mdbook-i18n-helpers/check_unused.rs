@@ -0,0 +1,208 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Find translations which don't match a message of the book.
+//!
+//! A translation is only used if its `msgid` is exactly the same as a
+//! message extracted by `mdbook-xgettext`. PO files which are written
+//! by hand, or recovered from an existing translation of the book,
+//! often have messages which differ in small ways, such as spacing or
+//! escaping. This command compares the PO files with a PO template and
+//! reports each translated message which is not in the template,
+//! together with the most similar message of the template.
+
+use anyhow::bail;
+use mdbook_i18n_helpers::catalog_io::read_catalog;
+use polib::catalog::Catalog;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Messages which share less of their words are not suggested.
+const MIN_SIMILARITY: f64 = 0.5;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// PO template with the current messages, as generated by
+    /// `mdbook-xgettext`.
+    #[arg(long)]
+    pot: PathBuf,
+
+    /// PO files to check.
+    #[arg(required = true)]
+    po_files: Vec<PathBuf>,
+}
+
+/// A translated message which is not in the template.
+#[derive(Debug, PartialEq)]
+struct UnusedMessage<'a> {
+    /// The first source reference of the message.
+    source: &'a str,
+    msgid: &'a str,
+    /// The most similar message of the template, if any.
+    suggestion: Option<&'a str>,
+}
+
+/// The share of words which `a` and `b` have in common, from 0.0 to
+/// 1.0.
+fn similarity(a: &str, b: &str) -> f64 {
+    let mut counts = HashMap::<&str, usize>::new();
+    for word in a.split_whitespace() {
+        *counts.entry(word).or_default() += 1;
+    }
+    let mut common = 0;
+    for word in b.split_whitespace() {
+        if let Some(count) = counts.get_mut(word).filter(|count| **count > 0) {
+            *count -= 1;
+            common += 1;
+        }
+    }
+    let total = a.split_whitespace().count() + b.split_whitespace().count();
+    if total == 0 {
+        return 0.0;
+    }
+    2.0 * common as f64 / total as f64
+}
+
+/// Find the message in `candidates` which is most similar to `msgid`.
+fn closest_message<'a>(msgid: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (similarity(msgid, candidate), *candidate))
+        .filter(|(score, _)| *score >= MIN_SIMILARITY)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate)
+}
+
+/// Find the translated messages of `catalog` which are not in
+/// `template`.
+///
+/// Also returns the number of translated messages.
+fn unused_messages<'a>(
+    catalog: &'a Catalog,
+    template: &'a Catalog,
+) -> (Vec<UnusedMessage<'a>>, usize) {
+    let msgids = template
+        .messages()
+        .map(|message| message.msgid())
+        .collect::<Vec<_>>();
+    let known = msgids.iter().copied().collect::<HashSet<_>>();
+    let mut translated = 0;
+    let mut unused = Vec::new();
+    for message in catalog.messages() {
+        if !message.is_translated() || message.is_fuzzy() {
+            continue;
+        }
+        translated += 1;
+        if known.contains(message.msgid()) {
+            continue;
+        }
+        unused.push(UnusedMessage {
+            source: message.source().lines().next().unwrap_or_default(),
+            msgid: message.msgid(),
+            suggestion: closest_message(message.msgid(), &msgids),
+        });
+    }
+    (unused, translated)
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let template = read_catalog(&args.pot)?;
+    let mut unused_count = 0;
+    for path in &args.po_files {
+        let catalog = read_catalog(path)?;
+        let (unused, translated) = unused_messages(&catalog, &template);
+        #[allow(clippy::print_stdout)]
+        {
+            for message in &unused {
+                let location = match message.source {
+                    "" => path.display().to_string(),
+                    source => format!("{}: {source}", path.display()),
+                };
+                match message.suggestion {
+                    Some(suggestion) => println!(
+                        "{location}: {:?} is not in the template, did you mean {suggestion:?}?",
+                        message.msgid
+                    ),
+                    None => println!("{location}: {:?} is not in the template", message.msgid),
+                }
+            }
+            println!(
+                "{}: {} of {translated} translations match the template",
+                path.display(),
+                translated - unused.len()
+            );
+        }
+        unused_count += unused.len();
+    }
+
+    if unused_count > 0 {
+        bail!("Found {unused_count} translations which don't match the template");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity("a b c d", "a b c d"), 1.0);
+        assert_eq!(similarity("a b c d", "a b x y"), 0.5);
+        assert_eq!(similarity("a a", "a b"), 0.5);
+        assert_eq!(similarity("", ""), 0.0);
+    }
+
+    #[test]
+    fn test_unused_messages() {
+        let template = create_catalog(&[
+            ("src/foo.md:1", "Install the **cargo** tool first.", ""),
+            ("src/foo.md:1", "Run the tests.", ""),
+            ("src/foo.md:1", "Something else entirely", ""),
+        ]);
+        let po = create_catalog(&[
+            ("src/foo.md:1", "Run the tests.", "Kør testene."),
+            (
+                "src/foo.md:1",
+                "Install the *cargo* tool first.",
+                "Installer først *cargo*.",
+            ),
+            (
+                "src/foo.md:1",
+                "Completely unrelated text",
+                "Helt anden tekst",
+            ),
+            ("src/foo.md:1", "Not translated", ""),
+        ]);
+        let (unused, translated) = unused_messages(&po, &template);
+        assert_eq!(translated, 3);
+        assert_eq!(
+            unused,
+            vec![
+                UnusedMessage {
+                    source: "src/foo.md:1",
+                    msgid: "Install the *cargo* tool first.",
+                    suggestion: Some("Install the **cargo** tool first."),
+                },
+                UnusedMessage {
+                    source: "src/foo.md:1",
+                    msgid: "Completely unrelated text",
+                    suggestion: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Print the translated `book.toml` values as environment variables.
+//!
+//! The values of the keys in `output.xgettext.extra-config-keys` are
+//! extracted by `mdbook-xgettext`, but `mdbook-gettext` cannot pass
+//! the translations on to the renderers. Instead, `mdbook` reads them
+//! from `MDBOOK_*` environment variables:
+//!
+//! ```shell
+//! eval "$(mdbook-i18n-helpers config-env da)"
+//! MDBOOK_BOOK__LANGUAGE=da mdbook build -d book/da
+//! ```
+
+use anyhow::anyhow;
+use mdbook::MDBook;
+use mdbook_i18n_helpers::config_keys::{env_var_name, translate_config};
+use mdbook_i18n_helpers::language::{find_catalog, LookupConfig};
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Language to use, such as `da` for `po/da.po`.
+    language: String,
+}
+
+/// Quote `text` for a POSIX shell.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Format an `export` statement which sets `key` to `value`.
+///
+/// The value is JSON encoded, otherwise `mdbook` would turn values
+/// such as `true` or `42` into booleans and numbers.
+fn export_statement(key: &str, value: &str) -> String {
+    let value = serde_json::Value::String(String::from(value)).to_string();
+    format!("export {}={}", env_var_name(key), shell_quote(&value))
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let book = MDBook::load(&args.book_dir)?;
+    let po_dir = book
+        .config
+        .get("preprocessor.gettext.po-dir")
+        .and_then(|value| value.as_str())
+        .unwrap_or("po");
+    let lookup_config = LookupConfig::from_book_config(&book.config)?;
+    let found = find_catalog(&book.root.join(po_dir), &args.language, &lookup_config)?.ok_or_else(
+        || {
+            anyhow!(
+                "Could not find a PO file for {:?} in {po_dir}",
+                args.language
+            )
+        },
+    )?;
+    for (key, translation) in translate_config(&book.config, &found.catalog)? {
+        #[allow(clippy::print_stdout)]
+        {
+            println!("{}", export_statement(&key, &translation));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_export_statement() {
+        assert_eq!(
+            export_statement("output.html.label", "Rediger"),
+            r#"export MDBOOK_OUTPUT__HTML__LABEL='"Rediger"'"#
+        );
+        assert_eq!(
+            export_statement("output.html.label", "Don't \"quote\""),
+            r#"export MDBOOK_OUTPUT__HTML__LABEL='"Don'\''t \"quote\""'"#
+        );
+    }
+}
@@ -0,0 +1,303 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compare the translation progress of two versions of the PO files.
+//!
+//! The PO files are matched by name between two directories, such as
+//! the `po` directories of two releases. For each language, the
+//! number of translated, fuzzy, and untranslated messages is shown
+//! along with the change since the old version. With `--messages`,
+//! the messages which changed state are listed too.
+
+use anyhow::Context;
+use mdbook_i18n_helpers::catalog_io::read_catalog;
+use polib::catalog::Catalog;
+use polib::metadata::CatalogMetadata;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Directory with the old PO files.
+    old_dir: PathBuf,
+
+    /// Directory with the new PO files.
+    new_dir: PathBuf,
+
+    /// List the messages which changed state, not just the counts.
+    #[arg(long)]
+    messages: bool,
+}
+
+/// The translation state of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Translated,
+    Fuzzy,
+    Untranslated,
+}
+
+/// Number of messages in each state.
+#[derive(Debug, Default, PartialEq)]
+pub struct Counts {
+    pub translated: usize,
+    pub fuzzy: usize,
+    pub untranslated: usize,
+}
+
+/// How the state of a message changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    /// The message is translated now, but wasn't before.
+    Translated,
+    /// The message is fuzzy now, but wasn't before.
+    Fuzzy,
+    /// The message was translated, but is now untranslated or gone.
+    Lost,
+}
+
+impl Change {
+    fn label(self) -> &'static str {
+        match self {
+            Change::Translated => "newly translated",
+            Change::Fuzzy => "became fuzzy",
+            Change::Lost => "lost",
+        }
+    }
+}
+
+/// The state of each message in `catalog`, by `msgid`.
+fn message_states(catalog: &Catalog) -> HashMap<&str, State> {
+    catalog
+        .messages()
+        .map(|message| {
+            let state = if message.is_fuzzy() {
+                State::Fuzzy
+            } else if message.is_translated() {
+                State::Translated
+            } else {
+                State::Untranslated
+            };
+            (message.msgid(), state)
+        })
+        .collect()
+}
+
+/// Count the messages in `catalog` in each state.
+pub fn count_messages(catalog: &Catalog) -> Counts {
+    let mut counts = Counts::default();
+    for state in message_states(catalog).into_values() {
+        match state {
+            State::Translated => counts.translated += 1,
+            State::Fuzzy => counts.fuzzy += 1,
+            State::Untranslated => counts.untranslated += 1,
+        }
+    }
+    counts
+}
+
+/// Find the messages which changed state between `old` and `new`.
+///
+/// The changes are sorted by kind and then in the order of the
+/// messages in `new`. Lost messages which are gone from `new` come
+/// last, in the order of `old`.
+pub fn diff_messages(old: &Catalog, new: &Catalog) -> Vec<(Change, String)> {
+    let old_states = message_states(old);
+    let new_states = message_states(new);
+    let mut changes = Vec::new();
+    for message in new.messages() {
+        let old_state = old_states.get(message.msgid()).copied();
+        let change = match (old_state, new_states[message.msgid()]) {
+            (Some(State::Translated), State::Translated) => None,
+            (_, State::Translated) => Some(Change::Translated),
+            (Some(State::Fuzzy), State::Fuzzy) => None,
+            (_, State::Fuzzy) => Some(Change::Fuzzy),
+            (Some(State::Translated), State::Untranslated) => Some(Change::Lost),
+            (_, State::Untranslated) => None,
+        };
+        if let Some(change) = change {
+            changes.push((change, String::from(message.msgid())));
+        }
+    }
+    for message in old.messages() {
+        if old_states[message.msgid()] == State::Translated
+            && !new_states.contains_key(message.msgid())
+        {
+            changes.push((Change::Lost, String::from(message.msgid())));
+        }
+    }
+    // The sort is stable, so the messages keep their order.
+    changes.sort_by_key(|(change, _)| *change);
+    changes
+}
+
+/// Find the PO files in `dir`, by language.
+pub fn find_po_files(dir: &Path) -> anyhow::Result<BTreeMap<String, PathBuf>> {
+    let mut po_files = BTreeMap::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Could not read {:?}", dir))? {
+        let path = entry
+            .with_context(|| format!("Could not read {:?}", dir))?
+            .path();
+        if path.extension().is_some_and(|ext| ext == "po") {
+            if let Some(language) = path.file_stem().and_then(|stem| stem.to_str()) {
+                po_files.insert(String::from(language), path.clone());
+            }
+        }
+    }
+    Ok(po_files)
+}
+
+/// Parse the PO file at `path`, if any.
+///
+/// A missing PO file is treated like an empty one, which is the case
+/// for languages added or removed between the versions.
+pub fn parse_catalog(path: Option<&PathBuf>) -> anyhow::Result<Catalog> {
+    match path {
+        Some(path) => read_catalog(path),
+        None => Ok(Catalog::new(CatalogMetadata::new())),
+    }
+}
+
+/// Format `new` along with the change from `old`.
+fn format_count(new: usize, old: usize) -> String {
+    match new.cmp(&old) {
+        std::cmp::Ordering::Equal => format!("{new}"),
+        std::cmp::Ordering::Greater => format!("{new} (+{})", new - old),
+        std::cmp::Ordering::Less => format!("{new} (-{})", old - new),
+    }
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let old_files = find_po_files(&args.old_dir)?;
+    let new_files = find_po_files(&args.new_dir)?;
+    let mut languages = old_files.keys().chain(new_files.keys()).collect::<Vec<_>>();
+    languages.sort();
+    languages.dedup();
+
+    for language in languages {
+        let old = parse_catalog(old_files.get(language))?;
+        let new = parse_catalog(new_files.get(language))?;
+        let (old_counts, new_counts) = (count_messages(&old), count_messages(&new));
+        #[allow(clippy::print_stdout)]
+        {
+            println!(
+                "{language}: translated {}, fuzzy {}, untranslated {}",
+                format_count(new_counts.translated, old_counts.translated),
+                format_count(new_counts.fuzzy, old_counts.fuzzy),
+                format_count(new_counts.untranslated, old_counts.untranslated),
+            );
+            if args.messages {
+                for (change, msgid) in diff_messages(&old, &new) {
+                    println!("  {}: {msgid:?}", change.label());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_catalog, mark_fuzzy};
+    use mdbook_i18n_helpers::catalog_io::{write_catalog, Encoding, WriteOptions};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_count_messages() {
+        let mut catalog = create_catalog(&[
+            ("", "Foo", "FOO"),
+            ("", "Bar", "BAR"),
+            ("", "Baz", ""),
+            ("", "Qux", "QUX"),
+        ]);
+        mark_fuzzy(&mut catalog, "Bar");
+        assert_eq!(
+            count_messages(&catalog),
+            Counts {
+                translated: 2,
+                fuzzy: 1,
+                untranslated: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_diff_messages() {
+        let mut old = create_catalog(&[
+            ("", "Same", "SAME"),
+            ("", "Fixed", "FIXED?"),
+            ("", "Changed", "CHANGED"),
+            ("", "Cleared", "CLEARED"),
+            ("", "Removed", "REMOVED"),
+            ("", "Removed untranslated", ""),
+        ]);
+        mark_fuzzy(&mut old, "Fixed");
+        let mut new = create_catalog(&[
+            ("", "Same", "SAME"),
+            ("", "Fixed", "FIXED"),
+            ("", "Changed", "CHANGED"),
+            ("", "Cleared", ""),
+            ("", "Added", "ADDED"),
+            ("", "Added fuzzy", "ADDED?"),
+            ("", "Added untranslated", ""),
+        ]);
+        mark_fuzzy(&mut new, "Changed");
+        mark_fuzzy(&mut new, "Added fuzzy");
+        assert_eq!(
+            diff_messages(&old, &new),
+            vec![
+                (Change::Translated, String::from("Fixed")),
+                (Change::Translated, String::from("Added")),
+                (Change::Fuzzy, String::from("Changed")),
+                (Change::Fuzzy, String::from("Added fuzzy")),
+                (Change::Lost, String::from("Cleared")),
+                (Change::Lost, String::from("Removed")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_catalog_latin1() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let path = tmpdir.path().join("de.po");
+        let mut catalog = create_catalog(&[("", "Greeting", "Grüß Gott")]);
+        catalog.metadata.content_type = String::from("text/plain; charset=UTF-8");
+        let options = WriteOptions {
+            encoding: Encoding::Latin1,
+            ..WriteOptions::default()
+        };
+        write_catalog(&catalog, &path, &options)?;
+
+        let catalog = parse_catalog(Some(&path))?;
+        assert_eq!(
+            count_messages(&catalog),
+            Counts {
+                translated: 1,
+                fuzzy: 0,
+                untranslated: 0
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(5, 5), "5");
+        assert_eq!(format_count(7, 5), "7 (+2)");
+        assert_eq!(format_count(3, 5), "3 (-2)");
+    }
+}
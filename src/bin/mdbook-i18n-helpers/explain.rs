@@ -0,0 +1,192 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Show the messages translators will see for a chapter.
+//!
+//! The Markdown of a chapter is normalized when the messages are
+//! extracted: lines are joined, reference links are expanded, and
+//! special characters are escaped. This command prints each paragraph
+//! of a chapter with its messages and explains why they differ from
+//! the source, which helps authors write Markdown which is easy to
+//! translate.
+
+use crate::normalize::load_markdown_config;
+use anyhow::Context;
+use mdbook_i18n_helpers::{extract_messages_with_comments, ExtractedMessage};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// The Markdown file to explain, such as `src/foo.md`.
+    file: PathBuf,
+}
+
+/// A paragraph of the source and the messages extracted from it.
+#[derive(Debug, PartialEq)]
+struct Paragraph<'a> {
+    /// The first line of the paragraph, starting from 1.
+    lineno: usize,
+    /// The lines of the paragraph.
+    source: String,
+    /// The messages, each with the reasons it differs from the
+    /// source.
+    messages: Vec<(&'a str, Vec<&'static str>)>,
+}
+
+/// Explain why `msgid` differs from the `source` it is extracted
+/// from.
+///
+/// The `split` flag says if the source has more than one message.
+fn differences(source: &str, message: &ExtractedMessage, split: bool) -> Vec<&'static str> {
+    let msgid = message.msgid.as_str();
+    let mut reasons = Vec::new();
+    if source.lines().count() > msgid.lines().count() && !split {
+        reasons.push("line breaks are joined with spaces");
+    }
+    if source.matches("__").count() > msgid.matches("__").count() && msgid.contains("**") {
+        reasons.push("`__` is written as `**`");
+    }
+    if msgid.matches("](").count() > source.matches("](").count() {
+        reasons.push("reference links are expanded to inline links");
+    }
+    if msgid.matches('\\').count() > source.matches('\\').count() {
+        reasons.push("special characters are escaped with a backslash");
+    }
+    if msgid.contains("{1}") && message.comment.contains("{1}:") {
+        reasons.push("inline markup is replaced by placeholders");
+    }
+    if split {
+        reasons.push("the paragraph is split into several messages");
+    }
+    reasons
+}
+
+/// Group the messages of `document` by the paragraph they come from.
+fn explain<'a>(document: &str, messages: &'a [ExtractedMessage]) -> Vec<Paragraph<'a>> {
+    let lines = document.lines().collect::<Vec<_>>();
+    let mut paragraphs = Vec::<(usize, usize, Vec<&ExtractedMessage>)>::new();
+    for message in messages {
+        match paragraphs.last_mut() {
+            Some((_, end, group)) if message.lineno <= *end => {
+                *end = (*end).max(message.end_lineno);
+                group.push(message);
+            }
+            _ => paragraphs.push((message.lineno, message.end_lineno, vec![message])),
+        }
+    }
+    paragraphs
+        .into_iter()
+        .map(|(lineno, end_lineno, group)| {
+            let source = lines
+                .get(lineno.saturating_sub(1)..end_lineno.min(lines.len()))
+                .unwrap_or_default()
+                .join("\n");
+            let split = group.len() > 1;
+            let messages = group
+                .into_iter()
+                .map(|message| (message.msgid.as_str(), differences(&source, message, split)))
+                .collect();
+            Paragraph {
+                lineno,
+                source,
+                messages,
+            }
+        })
+        .collect()
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let config = load_markdown_config(&args.book_dir)?;
+    let document = fs::read_to_string(&args.file)
+        .with_context(|| format!("Could not read {}", args.file.display()))?;
+    let messages = extract_messages_with_comments(&document, &config);
+
+    #[allow(clippy::print_stdout)]
+    for paragraph in explain(&document, &messages) {
+        println!("{}:{}:", args.file.display(), paragraph.lineno);
+        for line in paragraph.source.lines() {
+            println!("  | {line}");
+        }
+        for (msgid, reasons) in &paragraph.messages {
+            println!("  msgid: {msgid:?}");
+            for reason in reasons {
+                println!("    - {reason}");
+            }
+        }
+        println!();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdbook_i18n_helpers::MarkdownConfig;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_explain() {
+        let document = "# Setup\n\
+                        \n\
+                        Use __cargo__ and\n\
+                        [the guide][guide].\n\
+                        \n\
+                        * Run 2*3 <b>now</b>.\n\
+                        \n\
+                        [guide]: https://example.com\n";
+        let messages = extract_messages_with_comments(document, &MarkdownConfig::default());
+        assert_eq!(
+            explain(document, &messages),
+            vec![
+                Paragraph {
+                    lineno: 1,
+                    source: String::from("# Setup"),
+                    messages: vec![("Setup", vec![])],
+                },
+                Paragraph {
+                    lineno: 3,
+                    source: String::from("Use __cargo__ and\n[the guide][guide]."),
+                    messages: vec![(
+                        "Use **cargo** and [the guide](https://example.com).",
+                        vec![
+                            "line breaks are joined with spaces",
+                            "`__` is written as `**`",
+                            "reference links are expanded to inline links",
+                        ]
+                    )],
+                },
+                Paragraph {
+                    lineno: 6,
+                    source: String::from("* Run 2*3 <b>now</b>."),
+                    messages: vec![
+                        (
+                            "Run 2\\*3 ",
+                            vec![
+                                "special characters are escaped with a backslash",
+                                "the paragraph is split into several messages",
+                            ]
+                        ),
+                        ("now", vec!["the paragraph is split into several messages"]),
+                        (".", vec!["the paragraph is split into several messages"]),
+                    ],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Extract the messages of a book into a PO template.
+//!
+//! This runs
+//!
+//! ```shell
+//! MDBOOK_OUTPUT='{"xgettext": {"pot-file": "messages.pot"}}' mdbook build -d po
+//! ```
+//!
+//! but keeps the `output.xgettext` table of `book.toml`, which the
+//! `MDBOOK_OUTPUT` variable would otherwise replace. Only the
+//! `mdbook-xgettext` renderer runs, and `pot-file` defaults to
+//! `messages.pot`. Arguments after `--` are passed on to `mdbook`.
+
+use anyhow::{bail, Context};
+use mdbook::MDBook;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Directory for the PO template, relative to the book root.
+    #[arg(long, default_value = "po")]
+    dest_dir: PathBuf,
+
+    /// The `mdbook` program to run.
+    #[arg(long, default_value = "mdbook")]
+    mdbook: PathBuf,
+
+    /// Extra arguments for `mdbook build`.
+    #[arg(last = true)]
+    mdbook_args: Vec<String>,
+}
+
+/// The `MDBOOK_OUTPUT` environment variable which only enables the
+/// `xgettext` renderer, with its settings from `config`.
+fn output_env(config: &mdbook::Config) -> anyhow::Result<(String, String)> {
+    let mut xgettext = match config.get("output.xgettext") {
+        Some(value) => {
+            serde_json::to_value(value).context("Could not convert output.xgettext to JSON")?
+        }
+        None => serde_json::Value::Object(serde_json::Map::new()),
+    };
+    let Some(table) = xgettext.as_object_mut() else {
+        bail!("Expected a table for output.xgettext");
+    };
+    table
+        .entry("pot-file")
+        .or_insert_with(|| serde_json::Value::from("messages.pot"));
+    let output = serde_json::json!({ "xgettext": xgettext });
+    Ok((String::from("MDBOOK_OUTPUT"), output.to_string()))
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let book = MDBook::load(&args.book_dir)?;
+    let (name, value) = output_env(&book.config)?;
+
+    let status = Command::new(&args.mdbook)
+        .arg("build")
+        .arg("--dest-dir")
+        .arg(&args.dest_dir)
+        .args(&args.mdbook_args)
+        .arg(&book.root)
+        .env(name, value)
+        .status()
+        .with_context(|| format!("Could not run {}", args.mdbook.display()))?;
+    if !status.success() {
+        bail!("mdbook build failed: {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_output_env() -> anyhow::Result<()> {
+        let config = mdbook::Config::from_str("")?;
+        assert_eq!(
+            output_env(&config)?,
+            (
+                String::from("MDBOOK_OUTPUT"),
+                String::from(r#"{"xgettext":{"pot-file":"messages.pot"}}"#)
+            )
+        );
+
+        let config = mdbook::Config::from_str(
+            r#"
+            [output.html]
+            [output.xgettext]
+            granularity = 10
+            pot-file = "book.pot"
+            "#,
+        )?;
+        assert_eq!(
+            output_env(&config)?.1,
+            r#"{"xgettext":{"granularity":10,"pot-file":"book.pot"}}"#
+        );
+        Ok(())
+    }
+}
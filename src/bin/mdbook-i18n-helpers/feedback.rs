@@ -0,0 +1,228 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Import translations suggested by readers.
+//!
+//! Feedback is a JSON file with a list of suggestions:
+//!
+//! ```json
+//! [
+//!   {
+//!     "hash": "63f0bfacf2c00f6b",
+//!     "translation-hash": "6e05598299756030",
+//!     "suggestion": "Hejsa",
+//!     "reporter": "reader@example.com"
+//!   }
+//! ]
+//! ```
+//!
+//! The `hash` identifies the message and the `translation-hash` the
+//! translation which the reader saw. They are the `data-i18n-hash` and
+//! `data-i18n-translation-hash` attributes added by `mdbook-gettext`
+//! with the `provenance` setting. The `translation-hash` and
+//! `reporter` are optional. The suggestions are stored in the state
+//! file next to the PO file, the PO file itself is not changed.
+
+use crate::review::po_state_path;
+use anyhow::Context;
+use clap::Subcommand;
+use mdbook_i18n_helpers::catalog_io::read_catalog;
+use mdbook_i18n_helpers::state::{message_checksum, update_state, StateFile, Suggestion};
+use polib::catalog::Catalog;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: FeedbackCommand,
+}
+
+#[derive(Subcommand)]
+enum FeedbackCommand {
+    /// Import suggested translations from a feedback file.
+    Import {
+        /// PO file with the translations.
+        po_file: PathBuf,
+        /// JSON file with the feedback.
+        feedback_file: PathBuf,
+    },
+    /// List the suggested translations.
+    List {
+        /// PO file with the translations.
+        po_file: PathBuf,
+    },
+}
+
+/// A single suggestion in a feedback file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FeedbackEntry {
+    /// The checksum of the `msgid`.
+    pub hash: String,
+    /// The checksum of the translation seen by the reader.
+    #[serde(default)]
+    pub translation_hash: Option<String>,
+    /// The suggested translation.
+    pub suggestion: String,
+    /// Who made the suggestion.
+    #[serde(default)]
+    pub reporter: Option<String>,
+}
+
+/// Read the feedback file at `path`.
+pub fn parse_feedback(path: &Path) -> anyhow::Result<Vec<FeedbackEntry>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Could not read {:?}", path))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Could not parse {:?} as feedback", path))
+}
+
+/// The result of importing feedback.
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    added: usize,
+    duplicates: usize,
+    /// Hashes which don't match a message.
+    unknown: Vec<String>,
+}
+
+/// Add the suggestions in `feedback` for the messages in `catalog` to
+/// `state`.
+fn import(catalog: &Catalog, state: &mut StateFile, feedback: Vec<FeedbackEntry>) -> Summary {
+    let msgids = catalog
+        .messages()
+        .map(|message| (message_checksum(message.msgid()), message.msgid()))
+        .collect::<HashMap<_, _>>();
+    let mut summary = Summary::default();
+    for entry in feedback {
+        let Some(msgid) = msgids.get(&entry.hash) else {
+            summary.unknown.push(entry.hash);
+            continue;
+        };
+        let suggestion = Suggestion {
+            msgstr: entry.suggestion,
+            reporter: entry.reporter,
+            translation_hash: entry.translation_hash,
+        };
+        if state.add_suggestion(msgid, suggestion) {
+            summary.added += 1;
+        } else {
+            summary.duplicates += 1;
+        }
+    }
+    summary
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    match args.command {
+        FeedbackCommand::Import {
+            po_file,
+            feedback_file,
+        } => {
+            let catalog = read_catalog(&po_file)?;
+            let feedback = parse_feedback(&feedback_file)?;
+            let summary = update_state(&po_state_path(&po_file)?, |state| {
+                Ok(import(&catalog, state, feedback))
+            })?;
+            #[allow(clippy::print_stdout)]
+            {
+                println!(
+                    "{}: added {} suggestions, skipped {} duplicates",
+                    po_file.display(),
+                    summary.added,
+                    summary.duplicates
+                );
+            }
+            #[allow(clippy::print_stderr)]
+            for hash in &summary.unknown {
+                eprintln!(
+                    "Warning: No message in {} has hash {hash}",
+                    po_file.display()
+                );
+            }
+        }
+        FeedbackCommand::List { po_file } => {
+            let catalog = read_catalog(&po_file)?;
+            let state = StateFile::load(&po_state_path(&po_file)?)?;
+            for message in catalog.messages() {
+                let suggestions = state.suggestions(message.msgid());
+                if suggestions.is_empty() {
+                    continue;
+                }
+                #[allow(clippy::print_stdout)]
+                {
+                    println!("msgid: {:?}", message.msgid());
+                    println!("msgstr: {:?}", message.msgstr().unwrap_or_default());
+                    for suggestion in suggestions {
+                        match &suggestion.reporter {
+                            Some(reporter) => {
+                                println!("suggested by {reporter}: {:?}", suggestion.msgstr)
+                            }
+                            None => println!("suggested: {:?}", suggestion.msgstr),
+                        }
+                    }
+                    println!();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_import() -> anyhow::Result<()> {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_msgid(String::from("Hello"))
+                .with_msgstr(String::from("Hej"))
+                .done(),
+        );
+        let feedback = serde_json::from_str(
+            r#"[
+                {"hash": "63f0bfacf2c00f6b", "suggestion": "Hejsa", "reporter": "reader"},
+                {"hash": "63f0bfacf2c00f6b", "suggestion": "Hejsa"},
+                {"hash": "0000000000000000", "suggestion": "Farvel"}
+            ]"#,
+        )?;
+
+        let mut state = StateFile::default();
+        assert_eq!(
+            import(&catalog, &mut state, feedback),
+            Summary {
+                added: 1,
+                duplicates: 1,
+                unknown: vec![String::from("0000000000000000")],
+            }
+        );
+        assert_eq!(
+            state.suggestions("Hello"),
+            [Suggestion {
+                msgstr: String::from("Hejsa"),
+                reporter: Some(String::from("reader")),
+                translation_hash: None,
+            }]
+        );
+        Ok(())
+    }
+}
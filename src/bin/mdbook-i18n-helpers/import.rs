@@ -0,0 +1,745 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Create a PO file from a book and an existing translation of it.
+//!
+//! Some books were translated by copying the Markdown files before
+//! they used Gettext. This command reads the `SUMMARY.md` of both
+//! books and pairs their chapters:
+//!
+//! 1. Chapters with the same path are paired.
+//! 2. Chapters with the same section number, such as `1.2.`, are
+//!    paired.
+//! 3. Between two such pairs, the remaining unnumbered chapters are
+//!    paired in order if both books have the same number of chapters
+//!    there.
+//!
+//! The chapter titles are not compared since they are in different
+//! languages. A warning is printed for each chapter which could not be
+//! paired.
+//!
+//! The messages of a pair of chapters are then paired in order, if
+//! both chapters have the same number of messages. The result is a
+//! single PO file for the whole book, with the messages extracted like
+//! `mdbook-xgettext` would and the translations extracted the same way.
+//! Messages which could not be paired are left untranslated.
+//!
+//! If the output PO file already exists, its translations are kept:
+//! only messages which are untranslated there get the translations
+//! paired here. Running the command again is thus safe, and edits made
+//! to the PO file in the meantime are not lost.
+//!
+//! With `--review`, each newly paired translation is shown before the
+//! PO file is written. The translation can be accepted, rejected,
+//! edited, or marked fuzzy.
+
+use crate::normalize::{load_markdown_config, load_write_options};
+use anyhow::Context;
+use mdbook::{BookItem, MDBook};
+use mdbook_i18n_helpers::alignment::add_message;
+use mdbook_i18n_helpers::catalog_io::{convert_file, read_catalog};
+use mdbook_i18n_helpers::segmentation::{write_rules_to_file, SegmentationRules};
+use mdbook_i18n_helpers::sources::build_source;
+use mdbook_i18n_helpers::{extract_messages_with_comments, MarkdownConfig};
+use polib::catalog::Catalog;
+use polib::message::{Message, MessageFlags, MessageMutView, MessageView};
+use polib::metadata::CatalogMetadata;
+use polib::po_file;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    source_book: PathBuf,
+
+    /// Root directory of the translated book.
+    translated_book: PathBuf,
+
+    /// Where to write the PO file.
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Review each pair of messages before writing the PO file.
+    #[arg(long)]
+    review: bool,
+}
+
+/// A chapter of a book, in the order of its `SUMMARY.md`.
+#[derive(Debug)]
+struct BookChapter {
+    name: String,
+    /// The path of the chapter, relative to the `src` directory.
+    path: PathBuf,
+    /// The section number, such as `1.2.`, if the chapter has one.
+    number: Option<String>,
+    content: String,
+}
+
+/// The chapters of `book` which have a file.
+fn book_chapters(book: &MDBook) -> Vec<BookChapter> {
+    book.book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(ch) => Some(BookChapter {
+                name: ch.name.clone(),
+                path: ch.path.clone()?,
+                number: ch.number.as_ref().map(ToString::to_string),
+                content: ch.content.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The chapters paired by [`pair_chapters`].
+#[derive(Debug, Default, PartialEq)]
+struct Pairing {
+    /// Indices of the paired source and translated chapters.
+    pairs: Vec<(usize, usize)>,
+    /// Indices of the source chapters without a translated chapter.
+    unmatched_source: Vec<usize>,
+    /// Indices of the translated chapters without a source chapter.
+    unmatched_translated: Vec<usize>,
+}
+
+/// Pair the `source` chapters with the `translated` chapters.
+///
+/// Chapters with the same path or section number are paired first.
+/// They are kept in order: a later pair which would cross an earlier
+/// one is left out. The unnumbered chapters between two pairs are then
+/// paired in order if there are as many chapters on both sides.
+fn pair_chapters(source: &[BookChapter], translated: &[BookChapter]) -> Pairing {
+    let mut anchors: Vec<(usize, usize)> = Vec::new();
+    for (idx, chapter) in source.iter().enumerate() {
+        let found = translated
+            .iter()
+            .position(|other| other.path == chapter.path);
+        let found = found.or_else(|| {
+            chapter.number.as_ref().and_then(|number| {
+                translated
+                    .iter()
+                    .position(|other| other.number.as_ref() == Some(number))
+            })
+        });
+        if let Some(other) = found {
+            if anchors.last().is_none_or(|&(_, last)| last < other) {
+                anchors.push((idx, other));
+            }
+        }
+    }
+
+    let mut pairing = Pairing::default();
+    let (mut next_source, mut next_translated) = (0, 0);
+    let ends = anchors
+        .iter()
+        .map(|&(idx, other)| (idx, other, true))
+        .chain([(source.len(), translated.len(), false)]);
+    for (end_source, end_translated, is_anchor) in ends {
+        let gap_source = next_source..end_source;
+        let gap_translated = next_translated..end_translated;
+        if gap_source.len() == gap_translated.len() {
+            // Chapters with different section numbers are different
+            // chapters.
+            for (idx, other) in gap_source.zip(gap_translated) {
+                if source[idx].number.is_none() && translated[other].number.is_none() {
+                    pairing.pairs.push((idx, other));
+                } else {
+                    pairing.unmatched_source.push(idx);
+                    pairing.unmatched_translated.push(other);
+                }
+            }
+        } else {
+            pairing.unmatched_source.extend(gap_source);
+            pairing.unmatched_translated.extend(gap_translated);
+        }
+        if is_anchor {
+            pairing.pairs.push((end_source, end_translated));
+        }
+        (next_source, next_translated) = (end_source + 1, end_translated + 1);
+    }
+    pairing
+}
+
+/// Add the messages of `source`, a chapter at `path`, to `catalog`.
+///
+/// The messages are translated with the messages of `translated` if
+/// both have the same number of messages. Returns whether they were.
+fn import_chapter(
+    catalog: &mut Catalog,
+    path: &Path,
+    source: &str,
+    translated: Option<&str>,
+    config: &MarkdownConfig,
+) -> bool {
+    let messages = extract_messages_with_comments(source, config);
+    let translations = translated
+        .map(|translated| extract_messages_with_comments(translated, config))
+        .filter(|translations| translations.len() == messages.len());
+    let imported = translations.is_some();
+    let mut translations = translations.unwrap_or_default().into_iter();
+    for message in messages {
+        let mut flags = MessageFlags::new();
+        if message.no_wrap {
+            flags.add_flag("no-wrap");
+        }
+        let msgstr = translations
+            .next()
+            .map(|translation| translation.msgid)
+            .unwrap_or_default();
+        add_message(
+            catalog,
+            Message::build_singular()
+                .with_comments(message.comment)
+                .with_source(build_source(path, message.lineno, 1))
+                .with_flags(flags)
+                .with_msgid(message.msgid)
+                .with_msgstr(msgstr)
+                .done(),
+        );
+    }
+    imported
+}
+
+/// Use the translations of `existing` for the messages of `catalog`.
+///
+/// Messages are matched by their msgid. A translated message in
+/// `existing` replaces the translation paired by this command, and
+/// keeps its fuzzy flag. Returns the msgids of the reused
+/// translations.
+fn reuse_translations(catalog: &mut Catalog, existing: &Catalog) -> HashSet<String> {
+    let mut reused = HashSet::new();
+    for mut message in catalog.messages_mut() {
+        let Some(old) = existing.find_message(None, message.msgid(), None) else {
+            continue;
+        };
+        let Ok(msgstr) = old.msgstr() else {
+            continue;
+        };
+        if msgstr.is_empty() {
+            continue;
+        }
+        message
+            .set_msgstr(String::from(msgstr))
+            .expect("message is singular");
+        if old.is_fuzzy() {
+            message.flags_mut().add_flag("fuzzy");
+        } else {
+            message.flags_mut().remove_flag("fuzzy");
+        }
+        reused.insert(String::from(message.msgid()));
+    }
+    reused
+}
+
+/// The decisions made with `--review`.
+#[derive(Debug, Default, PartialEq)]
+struct ReviewSummary {
+    accepted: usize,
+    rejected: usize,
+    edited: usize,
+    fuzzy: usize,
+}
+
+/// Ask `output` what to do with `prompt`, reading the answer from
+/// `input`.
+///
+/// Returns `None` at the end of the input.
+fn ask(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    prompt: &str,
+) -> io::Result<Option<String>> {
+    write!(output, "{prompt}")?;
+    output.flush()?;
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(String::from(answer.trim_end_matches(['\r', '\n']))))
+}
+
+/// Walk the translated messages of `catalog` and let the user accept,
+/// reject, edit, or mark each translation fuzzy.
+///
+/// Messages whose msgid is in `reused` are skipped. The prompts are written to `output` and the answers are read from
+/// `input`. When the user quits, or the input ends, the remaining
+/// translations are accepted.
+fn review(
+    catalog: &mut Catalog,
+    reused: &HashSet<String>,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> anyhow::Result<ReviewSummary> {
+    let mut summary = ReviewSummary::default();
+    let total = catalog
+        .messages()
+        .filter(|message| message.is_translated() && !reused.contains(message.msgid()))
+        .count();
+    let mut quit = false;
+    let mut idx = 0;
+    for mut message in catalog.messages_mut() {
+        if !message.is_translated() || reused.contains(message.msgid()) {
+            continue;
+        }
+        idx += 1;
+        if quit {
+            summary.accepted += 1;
+            continue;
+        }
+        writeln!(output, "\n[{idx}/{total}] {}", message.source())?;
+        writeln!(output, "msgid:  {}", message.msgid())?;
+        writeln!(output, "msgstr: {}", message.msgstr().unwrap_or_default())?;
+        loop {
+            let prompt = "[a]ccept, [r]eject, [e]dit, [f]uzzy, [q]uit and accept the rest? ";
+            let answer = ask(&mut input, &mut output, prompt)?;
+            match answer.as_deref().map(str::trim) {
+                Some("a" | "") => summary.accepted += 1,
+                Some("r") => {
+                    message.set_msgstr(String::new())?;
+                    summary.rejected += 1;
+                }
+                Some("e") => {
+                    let prompt = "New translation (write \\n for a line break): ";
+                    let Some(msgstr) = ask(&mut input, &mut output, prompt)? else {
+                        quit = true;
+                        summary.accepted += 1;
+                        break;
+                    };
+                    message.set_msgstr(msgstr.replace("\\n", "\n"))?;
+                    summary.edited += 1;
+                }
+                Some("f") => {
+                    message.flags_mut().add_flag("fuzzy");
+                    summary.fuzzy += 1;
+                }
+                Some("q") | None => {
+                    quit = true;
+                    summary.accepted += 1;
+                }
+                Some(_) => continue,
+            }
+            break;
+        }
+    }
+    Ok(summary)
+}
+
+/// Find the line of the title `name` in `summary`, starting from 1.
+fn summary_lineno(summary: &str, name: &str) -> usize {
+    summary
+        .lines()
+        .position(|line| line.contains(name))
+        .map_or(1, |idx| idx + 1)
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let source_book = MDBook::load(&args.source_book).context("Could not load the book")?;
+    let translated_book =
+        MDBook::load(&args.translated_book).context("Could not load the translated book")?;
+    let config = load_markdown_config(&args.source_book)?;
+
+    let mut metadata = CatalogMetadata::new();
+    if let Some(language) = &translated_book.config.book.language {
+        metadata.language = language.clone();
+    }
+    metadata.mime_version = String::from("1.0");
+    metadata.content_type = String::from("text/plain; charset=UTF-8");
+    metadata.content_transfer_encoding = String::from("8bit");
+    let mut catalog = Catalog::new(metadata);
+
+    let src = &source_book.config.book.src;
+    let translated_src = &translated_book.config.book.src;
+    let summary_path = src.join("SUMMARY.md");
+    let summary = std::fs::read_to_string(args.source_book.join(&summary_path))
+        .with_context(|| format!("Could not read {}", summary_path.display()))?;
+
+    let source = book_chapters(&source_book);
+    let translated = book_chapters(&translated_book);
+    let pairing = pair_chapters(&source, &translated);
+    let partners = pairing.pairs.iter().copied().collect::<HashMap<_, _>>();
+
+    for (idx, chapter) in source.iter().enumerate() {
+        let translation = partners.get(&idx).map(|&other| &translated[other]);
+        let msgstr = translation.map_or(String::new(), |other| other.name.clone());
+        add_message(
+            &mut catalog,
+            Message::build_singular()
+                .with_source(build_source(
+                    &summary_path,
+                    summary_lineno(&summary, &chapter.name),
+                    1,
+                ))
+                .with_msgid(chapter.name.clone())
+                .with_msgstr(msgstr)
+                .done(),
+        );
+        let path = src.join(&chapter.path);
+        let imported = import_chapter(
+            &mut catalog,
+            &path,
+            &chapter.content,
+            translation.map(|other| other.content.as_str()),
+            &config,
+        );
+        #[allow(clippy::print_stderr)]
+        if let (Some(other), false) = (translation, imported) {
+            eprintln!(
+                "Warning: {} and {} have a different number of messages, \
+                 leaving them untranslated",
+                path.display(),
+                translated_src.join(&other.path).display()
+            );
+        }
+    }
+
+    #[allow(clippy::print_stderr)]
+    {
+        for &idx in &pairing.unmatched_source {
+            eprintln!(
+                "Warning: {} has no translated chapter",
+                src.join(&source[idx].path).display()
+            );
+        }
+        for &idx in &pairing.unmatched_translated {
+            eprintln!(
+                "Warning: translated chapter {} has no source chapter",
+                translated_src.join(&translated[idx].path).display()
+            );
+        }
+    }
+
+    let reused = if args.output.exists() {
+        let existing = read_catalog(&args.output)?;
+        reuse_translations(&mut catalog, &existing)
+    } else {
+        HashSet::new()
+    };
+
+    if args.review {
+        let summary = review(
+            &mut catalog,
+            &reused,
+            io::stdin().lock(),
+            io::stdout().lock(),
+        )?;
+        #[allow(clippy::print_stdout)]
+        {
+            println!(
+                "Reviewed: {} accepted, {} rejected, {} edited, {} marked fuzzy",
+                summary.accepted, summary.rejected, summary.edited, summary.fuzzy
+            );
+        }
+    }
+
+    po_file::write(&catalog, &args.output)
+        .with_context(|| format!("Could not write {}", args.output.display()))?;
+    write_rules_to_file(&args.output, &SegmentationRules::current(&config))?;
+    convert_file(&args.output, &load_write_options(&args.source_book)?)?;
+    #[allow(clippy::print_stdout)]
+    {
+        println!(
+            "{}: paired {} of {} chapters, kept {} existing translations",
+            args.output.display(),
+            pairing.pairs.len(),
+            source.len(),
+            reused.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdbook_i18n_helpers::catalog_io::{write_catalog, Encoding, WriteOptions};
+    use pretty_assertions::assert_eq;
+
+    fn chapters(chapters: &[(&str, Option<&str>)]) -> Vec<BookChapter> {
+        chapters
+            .iter()
+            .map(|(path, number)| BookChapter {
+                name: String::from(*path),
+                path: PathBuf::from(path),
+                number: number.map(String::from),
+                content: String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pair_chapters() {
+        let source = chapters(&[
+            ("intro.md", None),
+            ("basics.md", Some("1.")),
+            ("types.md", Some("1.1.")),
+            ("async.md", Some("2.")),
+            ("pin.md", Some("2.1.")),
+            ("credits.md", None),
+        ]);
+        // The translation renamed its files, left out a chapter, and
+        // added one.
+        let translated = chapters(&[
+            ("intro.md", None),
+            ("grundlagen.md", Some("1.")),
+            ("typen.md", Some("1.1.")),
+            ("async.md", Some("2.")),
+            ("extra.md", Some("3.")),
+            ("dank.md", None),
+        ]);
+        assert_eq!(
+            pair_chapters(&source, &translated),
+            Pairing {
+                pairs: vec![(0, 0), (1, 1), (2, 2), (3, 3), (5, 5)],
+                unmatched_source: vec![4],
+                unmatched_translated: vec![4],
+            }
+        );
+    }
+
+    #[test]
+    fn test_pair_chapters_by_position() {
+        let source = chapters(&[("a.md", None), ("b.md", None), ("c.md", None)]);
+        let translated = chapters(&[("a.md", None), ("x.md", None), ("c.md", None)]);
+        assert_eq!(
+            pair_chapters(&source, &translated),
+            Pairing {
+                pairs: vec![(0, 0), (1, 1), (2, 2)],
+                ..Pairing::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_import_chapter() {
+        let config = MarkdownConfig::default();
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        let path = Path::new("src/foo.md");
+        assert!(import_chapter(
+            &mut catalog,
+            path,
+            "# Hello\n\nThe *world*.\n",
+            Some("# Hej\n\nVerdenen _verden_.\n"),
+            &config,
+        ));
+        assert!(!import_chapter(
+            &mut catalog,
+            Path::new("src/bar.md"),
+            "Extra\n",
+            Some("Ekstra\n\nMere\n"),
+            &config,
+        ));
+        let messages = catalog
+            .messages()
+            .map(|message| (message.source(), message.msgid(), message.msgstr().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                ("src/foo.md:1", "Hello", "Hej"),
+                ("src/foo.md:3", "The _world_.", "Verdenen _verden_."),
+                ("src/bar.md:1", "Extra", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_review() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (msgid, msgstr) in [
+            ("One", "Eins"),
+            ("Two", "Zwo"),
+            ("Three", "Drei"),
+            ("Four", "Vier"),
+            ("Empty", ""),
+            ("Five", "Fünf"),
+        ] {
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_source(String::from("src/foo.md:1"))
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let mut output = Vec::new();
+        let input = "a\nx\ne\nZwei\\nzwei\nr\nf\n";
+        let reused = HashSet::new();
+        let summary = review(&mut catalog, &reused, input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            summary,
+            ReviewSummary {
+                accepted: 2,
+                rejected: 1,
+                edited: 1,
+                fuzzy: 1,
+            }
+        );
+        let messages = catalog
+            .messages()
+            .map(|message| (message.msgstr().unwrap(), message.is_fuzzy()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                ("Eins", false),
+                ("Zwei\nzwei", false),
+                ("", false),
+                ("Vier", true),
+                ("", false),
+                ("Fünf", false),
+            ]
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("[2/5] src/foo.md:1\nmsgid:  Two\nmsgstr: Zwo\n"));
+    }
+
+    /// Write a book with a single chapter to `dir`.
+    fn write_book(dir: &Path, book_toml: &str, title: &str, content: &str) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dir.join("src"))?;
+        std::fs::write(dir.join("book.toml"), book_toml)?;
+        std::fs::write(
+            dir.join("src/SUMMARY.md"),
+            format!("# Summary\n\n- [{title}](greeting.md)\n"),
+        )?;
+        std::fs::write(dir.join("src/greeting.md"), content)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_twice_latin1() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let source_book = tmpdir.path().join("en");
+        let translated_book = tmpdir.path().join("de");
+        let output = tmpdir.path().join("de.po");
+        write_book(
+            &source_book,
+            "[book]\n\n[output.xgettext]\nencoding = \"ISO-8859-1\"\n",
+            "Greeting",
+            "# Greeting\n\nGood day.\n",
+        )?;
+        write_book(
+            &translated_book,
+            "[book]\nlanguage = \"de\"\n",
+            "Gruß",
+            "# Gruß\n\nGuten Tag.\n",
+        )?;
+        let args = || Args {
+            source_book: source_book.clone(),
+            translated_book: translated_book.clone(),
+            output: output.clone(),
+            review: false,
+        };
+
+        run(args())?;
+        assert!(std::fs::read(&output)?.contains(&0xdf));
+        // Edit a translation in ISO-8859-1, like a translator would.
+        let mut catalog = read_catalog(&output)?;
+        catalog
+            .find_message_mut(None, "Good day.", None)
+            .unwrap()
+            .set_msgstr(String::from("Schönen Tag."))?;
+        let options = WriteOptions {
+            encoding: Encoding::Latin1,
+            ..WriteOptions::default()
+        };
+        write_catalog(&catalog, &output, &options)?;
+
+        run(args())?;
+        let catalog = read_catalog(&output)?;
+        let messages = catalog
+            .messages()
+            .map(|message| (message.msgid(), message.msgstr().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![("Greeting", "Gruß"), ("Good day.", "Schönen Tag."),]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reuse_translations() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (msgid, msgstr) in [
+            ("One", "Eins"),
+            ("Two", "Zwo"),
+            ("Three", ""),
+            ("Four", "Vier"),
+        ] {
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let mut existing = Catalog::new(CatalogMetadata::new());
+        for (msgid, msgstr, fuzzy) in [
+            ("One", "Eins", false),
+            ("Two", "Zwei", false),
+            ("Three", "Drei", true),
+            // Rejected in an earlier review.
+            ("Four", "", false),
+            ("Obsolete", "Veraltet", false),
+        ] {
+            let mut flags = MessageFlags::new();
+            if fuzzy {
+                flags.add_flag("fuzzy");
+            }
+            existing.append_or_update(
+                Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .with_flags(flags)
+                    .done(),
+            );
+        }
+
+        let reused = reuse_translations(&mut catalog, &existing);
+        let mut reused = reused.into_iter().collect::<Vec<_>>();
+        reused.sort();
+        assert_eq!(reused, vec!["One", "Three", "Two"]);
+        let messages = catalog
+            .messages()
+            .map(|message| (message.msgstr().unwrap(), message.is_fuzzy()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                ("Eins", false),
+                ("Zwei", false),
+                ("Drei", true),
+                ("Vier", false),
+            ]
+        );
+
+        // Only the newly paired translation is reviewed.
+        let reused = HashSet::from([String::from("One"), String::from("Two")]);
+        let mut output = Vec::new();
+        let summary = review(&mut catalog, &reused, "r\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            summary,
+            ReviewSummary {
+                accepted: 1,
+                rejected: 1,
+                ..ReviewSummary::default()
+            }
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("[1/2]"));
+        assert!(output.contains("msgid:  Three"));
+    }
+}
@@ -0,0 +1,399 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Localize the 404 page and the redirects of a translated build.
+//!
+//! `mdbook` renders the 404 page without running the preprocessors, so
+//! it stays untranslated. Its `<base>` element and the absolute
+//! targets of `output.html.redirect` also point to the untranslated
+//! book. Run this command on the HTML output of a translated build:
+//!
+//! ```shell
+//! MDBOOK_BOOK__LANGUAGE=da mdbook build -d book/da
+//! mdbook-i18n-helpers localize-pages da book/da
+//! ```
+//!
+//! The messages of the 404 page are only in the PO files when
+//! `output.xgettext.extract-404` is set.
+//!
+//! The translated book is assumed to be served at `{site-url}{language}/`,
+//! where `site-url` is `output.html.site-url`. Use `--base-url` if it
+//! is served elsewhere.
+//!
+//! Redirects to a heading, such as `setup.html#installation`, are
+//! pointed to the anchor of the translated heading, unless
+//! `preprocessor.gettext.preserve-anchors` keeps the anchors of the
+//! untranslated book.
+
+use crate::normalize::get_bool;
+use anyhow::{anyhow, bail, Context};
+use mdbook::book::BookItem;
+use mdbook::MDBook;
+use mdbook_i18n_helpers::anchors::translated_anchors;
+use mdbook_i18n_helpers::language::{find_catalog, LookupConfig};
+use mdbook_i18n_helpers::language_switcher::html_path;
+use mdbook_i18n_helpers::{
+    extract_events_with_config, reconstruct_document, restore_table_delimiter_rows,
+    translate_events_with_config, MarkdownConfig,
+};
+use polib::catalog::Catalog;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The 404 page used by `mdbook` when there is no `404.md` file.
+const DEFAULT_404: &str = "# Document not found (404)\n\n\
+                           This URL is invalid, sorry. Please use the \
+                           navigation bar or search to continue.";
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// URL where the translated book is served, such as `/da/`.
+    #[arg(long)]
+    base_url: Option<String>,
+
+    /// Language to use, such as `da` for `po/da.po`.
+    language: String,
+
+    /// Directory with the HTML output of the translated build.
+    html_dir: PathBuf,
+}
+
+fn translate_markdown(text: &str, catalog: &Catalog, config: &MarkdownConfig) -> String {
+    let events = extract_events_with_config(text, None, config);
+    let translated_events = translate_events_with_config(&events, catalog, config);
+    restore_table_delimiter_rows(text, &reconstruct_document(&translated_events), config)
+}
+
+/// Replace the rendered 404 page `source` in `html` by `translated`.
+///
+/// The `<base>` element is pointed from `site_url` to `base_url`.
+fn localize_404(
+    html: &str,
+    source: &str,
+    translated: &str,
+    site_url: &str,
+    base_url: &str,
+) -> anyhow::Result<String> {
+    if !html.contains(source) {
+        bail!("Could not find the 404 page, was it built with a different version of mdbook?");
+    }
+    Ok(html.replacen(source, translated, 1).replacen(
+        &format!("<base href=\"{site_url}\">"),
+        &format!("<base href=\"{base_url}\">"),
+        1,
+    ))
+}
+
+/// Point a redirect `target` below `site_url` to `base_url`.
+///
+/// Relative targets and URLs are returned unchanged.
+fn scope_redirect(target: &str, site_url: &str, base_url: &str) -> Option<String> {
+    let rest = target.strip_prefix(site_url)?;
+    Some(format!("{base_url}{rest}"))
+}
+
+/// Translate the chapters of `book` and map the output path of each
+/// chapter to its changed heading anchors.
+fn chapter_anchors(
+    book: &MDBook,
+    catalog: &Catalog,
+    config: &MarkdownConfig,
+) -> HashMap<String, HashMap<String, String>> {
+    book.book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(ch) => Some((ch.path.as_ref()?, &ch.content)),
+            _ => None,
+        })
+        .map(|(path, content)| {
+            let events = extract_events_with_config(content, None, config);
+            let translated_events = translate_events_with_config(&events, catalog, config);
+            (
+                html_path(path),
+                translated_anchors(&events, &translated_events),
+            )
+        })
+        .collect()
+}
+
+/// Point the fragment of a redirect `target` to the translated heading.
+///
+/// The redirect is served at `original`. Targets below `site_url` and
+/// relative targets are resolved to a chapter, whose translated
+/// anchors are looked up in `anchors`. Returns `None` if the fragment
+/// doesn't change.
+fn translate_fragment(
+    original: &str,
+    target: &str,
+    site_url: &str,
+    anchors: &HashMap<String, HashMap<String, String>>,
+) -> Option<String> {
+    let (path, fragment) = target.split_once('#')?;
+    let joined = match path.strip_prefix(site_url) {
+        Some(rest) => PathBuf::from(rest),
+        None if path.contains("://") || path.starts_with('/') => return None,
+        None => Path::new(original.trim_start_matches('/'))
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(path),
+    };
+    let mut resolved = Vec::new();
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(part) => resolved.push(part.to_string_lossy()),
+            _ => {}
+        }
+    }
+    let translated = anchors.get(&resolved.join("/"))?.get(fragment)?;
+    Some(format!("{path}#{translated}"))
+}
+
+/// Replace the `target` of the redirect page `html` by `scoped`.
+fn localize_redirect(html: &str, target: &str, scoped: &str) -> String {
+    html.replace(&format!("URL={target}\""), &format!("URL={scoped}\""))
+        .replace(&format!("\"{target}\""), &format!("\"{scoped}\""))
+        .replace(&format!(">{target}<"), &format!(">{scoped}<"))
+}
+
+fn get_string<'a>(book: &'a MDBook, key: &str, default: &'a str) -> anyhow::Result<&'a str> {
+    match book.config.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .as_str()
+            .ok_or_else(|| anyhow!("Expected a string for {key}")),
+    }
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let book = MDBook::load(&args.book_dir)?;
+    let config = MarkdownConfig::from_book_config(&book.config)?;
+    let po_dir = get_string(&book, "preprocessor.gettext.po-dir", "po")?;
+    let lookup_config = LookupConfig::from_book_config(&book.config)?;
+    let found = find_catalog(&book.root.join(po_dir), &args.language, &lookup_config)?.ok_or_else(
+        || {
+            anyhow!(
+                "Could not find a PO file for {:?} in {po_dir}",
+                args.language
+            )
+        },
+    )?;
+    let mut site_url = String::from(get_string(&book, "output.html.site-url", "/")?);
+    if !site_url.ends_with('/') {
+        site_url.push('/');
+    }
+    let base_url = args
+        .base_url
+        .unwrap_or_else(|| format!("{site_url}{}/", args.language));
+    let curly_quotes = book
+        .config
+        .get("output.html.curly-quotes")
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false);
+
+    let input_404 = get_string(&book, "output.html.input-404", "404.md")?;
+    if !input_404.is_empty() {
+        let src_path = book.source_dir().join(input_404);
+        let content = match fs::read_to_string(&src_path) {
+            Ok(content) => content,
+            Err(_) if input_404 == "404.md" => String::from(DEFAULT_404),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Could not read {}", src_path.display()))
+            }
+        };
+        let translated = translate_markdown(&content, &found.catalog, &config);
+        let path = args.html_dir.join(input_404.replace(".md", ".html"));
+        let html = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        let html = localize_404(
+            &html,
+            &mdbook::utils::render_markdown(&content, curly_quotes),
+            &mdbook::utils::render_markdown(&translated, curly_quotes),
+            &site_url,
+            &base_url,
+        )
+        .with_context(|| format!("Could not localize {}", path.display()))?;
+        fs::write(&path, html).with_context(|| format!("Could not write {}", path.display()))?;
+    }
+
+    let anchors = if get_bool(&book.config, "preprocessor.gettext.preserve-anchors")? {
+        HashMap::new()
+    } else {
+        chapter_anchors(&book, &found.catalog, &config)
+    };
+    let redirects = match book.config.get("output.html.redirect") {
+        None => Default::default(),
+        Some(value) => value
+            .as_table()
+            .cloned()
+            .ok_or_else(|| anyhow!("Expected a table for output.html.redirect"))?,
+    };
+    for (original, target) in &redirects {
+        let target = target
+            .as_str()
+            .ok_or_else(|| anyhow!("Expected a string for the redirect of {original}"))?;
+        let mut localized = translate_fragment(original, target, &site_url, &anchors)
+            .unwrap_or_else(|| String::from(target));
+        if let Some(scoped) = scope_redirect(&localized, &site_url, &base_url) {
+            localized = scoped;
+        }
+        if localized == target {
+            continue;
+        }
+        let path = args.html_dir.join(original.trim_start_matches('/'));
+        let html = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        fs::write(&path, localize_redirect(&html, target, &localized))
+            .with_context(|| format!("Could not write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_translate_default_404() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_msgid(String::from("Document not found (404)"))
+                .with_msgstr(String::from("Siden blev ikke fundet (404)"))
+                .done(),
+        );
+        let translated = translate_markdown(DEFAULT_404, &catalog, &MarkdownConfig::default());
+        assert!(translated.starts_with("# Siden blev ikke fundet (404)\n\nThis URL"));
+    }
+
+    #[test]
+    fn test_localize_404() {
+        let html = "<base href=\"/\">\n<main><h1>Not found</h1></main>";
+        assert_eq!(
+            localize_404(
+                html,
+                "<h1>Not found</h1>",
+                "<h1>Ikke fundet</h1>",
+                "/",
+                "/da/"
+            )
+            .unwrap(),
+            "<base href=\"/da/\">\n<main><h1>Ikke fundet</h1></main>"
+        );
+        assert!(localize_404(html, "<h1>Missing</h1>", "", "/", "/da/").is_err());
+    }
+
+    #[test]
+    fn test_scope_redirect() {
+        assert_eq!(
+            scope_redirect("/new.html", "/", "/da/"),
+            Some(String::from("/da/new.html"))
+        );
+        assert_eq!(
+            scope_redirect("/book/new.html", "/book/", "/book/da/"),
+            Some(String::from("/book/da/new.html"))
+        );
+        assert_eq!(scope_redirect("new.html", "/", "/da/"), None);
+        assert_eq!(scope_redirect("https://example.com/", "/", "/da/"), None);
+    }
+
+    #[test]
+    fn test_translate_fragment() {
+        let anchors = HashMap::from([(
+            String::from("guide/setup.html"),
+            HashMap::from([(String::from("install"), String::from("installation"))]),
+        )]);
+        assert_eq!(
+            translate_fragment("/old.html", "/guide/setup.html#install", "/", &anchors),
+            Some(String::from("/guide/setup.html#installation"))
+        );
+        assert_eq!(
+            translate_fragment("/guide/old.html", "setup.html#install", "/", &anchors),
+            Some(String::from("setup.html#installation"))
+        );
+        assert_eq!(
+            translate_fragment("/a/old.html", "../guide/setup.html#install", "/", &anchors),
+            Some(String::from("../guide/setup.html#installation"))
+        );
+        assert_eq!(
+            translate_fragment("/old.html", "/guide/setup.html#usage", "/", &anchors),
+            None
+        );
+        assert_eq!(
+            translate_fragment("/old.html", "/guide/setup.html", "/", &anchors),
+            None
+        );
+        assert_eq!(
+            translate_fragment(
+                "/old.html",
+                "https://example.com/guide/setup.html#install",
+                "/",
+                &anchors
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_chapter_anchors() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("book.toml"), "[book]\n").unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("src/SUMMARY.md"),
+            "- [Setup](setup.md)\n- [Intro](intro/README.md)\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/setup.md"), "# Setup\n\n## Install\n").unwrap();
+        fs::create_dir(dir.path().join("src/intro")).unwrap();
+        fs::write(dir.path().join("src/intro/README.md"), "# Intro\n").unwrap();
+        let book = MDBook::load(dir.path()).unwrap();
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_msgid(String::from("Install"))
+                .with_msgstr(String::from("Installation"))
+                .done(),
+        );
+        let anchors = chapter_anchors(&book, &catalog, &MarkdownConfig::default());
+        assert_eq!(
+            anchors["setup.html"],
+            HashMap::from([(String::from("install"), String::from("installation"))])
+        );
+        assert!(anchors["intro/index.html"].is_empty());
+    }
+
+    #[test]
+    fn test_localize_redirect() {
+        let html = "<meta http-equiv=\"refresh\" content=\"0; URL=/\">\n\
+                    <link rel=\"canonical\" href=\"/\">\n\
+                    <p>Redirecting to... <a href=\"/\">/</a>.</p>";
+        assert_eq!(
+            localize_redirect(html, "/", "/da/"),
+            "<meta http-equiv=\"refresh\" content=\"0; URL=/da/\">\n\
+             <link rel=\"canonical\" href=\"/da/\">\n\
+             <p>Redirecting to... <a href=\"/da/\">/da/</a>.</p>"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Maintenance commands for `mdbook` translations
+//!
+//! This program bundles the tools which work on the PO files
+//! directly, outside of an `mdbook` build, as well as commands which
+//! run `mdbook` with the right settings for extracting, building, and
+//! serving translations. Run it with `--help` to see the available
+//! commands.
+//!
+//! The `mdbook-i18n` program runs this program, so it can be used
+//! under the shorter name.
+
+use clap::{Parser, Subcommand};
+
+mod age_report;
+mod apply_suggestions;
+mod author_lint;
+mod build_all;
+mod check_anchors;
+mod check_code_comments;
+mod check_rules;
+mod check_stale;
+mod check_symbols;
+mod check_unused;
+mod config_env;
+mod diff;
+mod explain;
+mod extract;
+mod feedback;
+mod import;
+mod localize_pages;
+mod migrate;
+mod normalize;
+mod release_notes;
+mod report;
+mod review;
+mod scrub;
+mod serve;
+mod sitemap;
+mod subset;
+#[cfg(test)]
+#[path = "../../test_utils.rs"]
+mod test_utils;
+mod update;
+mod update_sources;
+mod upgrade_check;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show how long ago the translations of each chapter changed.
+    AgeReport(age_report::Args),
+    /// Apply suggested translations which don't conflict with changes.
+    ApplySuggestions(apply_suggestions::Args),
+    /// Find Markdown which is hard to translate.
+    AuthorLint(author_lint::Args),
+    /// Build the book in every language.
+    #[command(alias = "translate")]
+    BuildAll(build_all::Args),
+    /// Find links to headings which break in the translations.
+    CheckAnchors(check_anchors::Args),
+    /// Find translated code blocks whose comments lost their markers.
+    CheckCodeComments(check_code_comments::Args),
+    /// Check translations against project specific rules.
+    CheckRules(check_rules::Args),
+    /// Find translations whose surrounding text has changed.
+    CheckStale(check_stale::Args),
+    /// Find translations which drop or add emoji.
+    CheckSymbols(check_symbols::Args),
+    /// Find translations which don't match a message of the book.
+    CheckUnused(check_unused::Args),
+    /// Print the translated book.toml values as environment variables.
+    ConfigEnv(config_env::Args),
+    /// Compare the translation progress of two sets of PO files.
+    Diff(diff::Args),
+    /// Show the messages translators will see for a chapter.
+    Explain(explain::Args),
+    /// Extract the messages of the book into a PO template.
+    Extract(extract::Args),
+    /// Import and list translations suggested by readers.
+    Feedback(feedback::Args),
+    /// Create a PO file from a book and an existing translation of it.
+    Import(import::Args),
+    /// Translate the 404 page and scope the redirects of a translated build.
+    LocalizePages(localize_pages::Args),
+    /// Carry translations over to the messages of the current extraction.
+    #[command(alias = "align")]
+    Migrate(migrate::Args),
+    /// Bring the messages in PO files in line with the current extraction.
+    Normalize(normalize::Args),
+    /// Summarize the newly translated chapters as Markdown.
+    ReleaseNotes(release_notes::Args),
+    /// Show the translation progress of each language as a table.
+    Report(report::Args),
+    /// Record and show the review status of translations.
+    Review(review::Args),
+    /// Remove source references and header fields before sharing PO files.
+    Scrub(scrub::Args),
+    /// Serve a translation and reload it when the source or PO file changes.
+    Serve(serve::Args),
+    /// Write a sitemap with the chapters of every translation.
+    Sitemap(sitemap::Args),
+    /// Split off the messages of some chapters and merge them back.
+    Subset(subset::Args),
+    /// Update PO files with the messages from a PO template.
+    Update(update::Args),
+    /// Refresh the source references in PO files.
+    UpdateSources(update_sources::Args),
+    /// Report how many messages change when upgrading the helpers.
+    UpgradeCheck(upgrade_check::Args),
+}
+
+fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Command::AgeReport(args) => age_report::run(args),
+        Command::ApplySuggestions(args) => apply_suggestions::run(args),
+        Command::AuthorLint(args) => author_lint::run(args),
+        Command::BuildAll(args) => build_all::run(args),
+        Command::CheckAnchors(args) => check_anchors::run(args),
+        Command::CheckCodeComments(args) => check_code_comments::run(args),
+        Command::CheckRules(args) => check_rules::run(args),
+        Command::CheckStale(args) => check_stale::run(args),
+        Command::CheckSymbols(args) => check_symbols::run(args),
+        Command::CheckUnused(args) => check_unused::run(args),
+        Command::ConfigEnv(args) => config_env::run(args),
+        Command::Diff(args) => diff::run(args),
+        Command::Explain(args) => explain::run(args),
+        Command::Extract(args) => extract::run(args),
+        Command::Feedback(args) => feedback::run(args),
+        Command::Import(args) => import::run(args),
+        Command::LocalizePages(args) => localize_pages::run(args),
+        Command::Migrate(args) => migrate::run(args),
+        Command::Normalize(args) => normalize::run(args),
+        Command::ReleaseNotes(args) => release_notes::run(args),
+        Command::Report(args) => report::run(args),
+        Command::Review(args) => review::run(args),
+        Command::Scrub(args) => scrub::run(args),
+        Command::Serve(args) => serve::run(args),
+        Command::Sitemap(args) => sitemap::run(args),
+        Command::Subset(args) => subset::run(args),
+        Command::Update(args) => update::run(args),
+        Command::UpdateSources(args) => update_sources::run(args),
+        Command::UpgradeCheck(args) => upgrade_check::run(args),
+    }
+}
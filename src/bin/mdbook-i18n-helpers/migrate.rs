@@ -0,0 +1,194 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Migrate PO files made by older versions of `mdbook-xgettext`.
+//!
+//! Older versions split the Markdown into messages differently, for
+//! example in how code blocks and HTML were handled. The messages in
+//! such PO files no longer match the extracted messages, and the
+//! `normalize` command cannot always fix this since the old messages
+//! lack the surrounding Markdown.
+//!
+//! This command extracts the messages again from the chapters named
+//! in the `#:` source references. The old messages are then aligned
+//! with the new messages by their line numbers: an old message
+//! belongs to the new message which starts at or before its line.
+//! Translations which are carried over this way are marked fuzzy,
+//! unless the old message was only formatted differently. Those with
+//! a low confidence score get a comment which explains the score.
+//!
+//! The alignment needs line numbers, so source references written
+//! with `granularity` or the `path-only` source style cannot be used.
+//! Messages without usable source references are kept as they are.
+//!
+//! The `X-Segmentation-Rules` header field is set to the current rules.
+//!
+//! With `--sentence-alignment`, an old message whose text now belongs
+//! to several new messages is split into sentences, and the sentences
+//! of its translation are given to the new messages in order. This
+//! needs the `Language` header field of the PO file. The translations
+//! are marked fuzzy if the number of sentences differs between the
+//! message and its translation.
+//!
+//! The command prints how many translations were dropped from each
+//! chapter and why. Use `--report` to also write the dropped
+//! translations to a JSON file.
+//!
+//! Large books can be migrated a few chapters at a time with `--only`.
+//! The PO file is written after each run, so the messages of the
+//! chapters which are done already match the extraction and are kept
+//! when the next chapters are migrated.
+//!
+//! The alignment is done by [`mdbook_i18n_helpers::alignment`].
+
+use crate::normalize::{load_book_config, load_markdown_config, load_write_options};
+use anyhow::{bail, Context};
+use glob::Pattern;
+use mdbook_i18n_helpers::alignment::{migrate, parse_source, SentenceLanguages};
+use mdbook_i18n_helpers::catalog_io::{convert_file, read_catalog};
+use mdbook_i18n_helpers::obsolete::{read_obsolete, write_with_obsolete};
+use mdbook_i18n_helpers::segmentation::{write_rules_to_file, SegmentationRules};
+use polib::catalog::Catalog;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// PO file to migrate in place.
+    po_file: PathBuf,
+
+    /// Root directory of the book. The source references in the PO
+    /// file are relative to this directory.
+    #[arg(default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Only migrate the chapters matching this glob pattern, such as
+    /// `src/async/**`. Can be given several times.
+    #[arg(long)]
+    only: Vec<String>,
+
+    /// Split old messages which now belong to several new messages by
+    /// sentences.
+    #[arg(long)]
+    sentence_alignment: bool,
+
+    /// Write a JSON report with the dropped translations to this file.
+    #[arg(long)]
+    report: Option<PathBuf>,
+}
+
+/// Read the chapters named in the source references of `catalog`.
+///
+/// Only the chapters matching one of the `only` patterns are read, or
+/// all chapters if there are no patterns. Files which cannot be read
+/// are skipped, as is `SUMMARY.md`: the chapter titles are extracted
+/// from it verbatim.
+fn read_files(catalog: &Catalog, book_dir: &Path, only: &[Pattern]) -> HashMap<String, String> {
+    let mut files = HashMap::new();
+    for message in catalog.messages() {
+        for (path, _) in message.source().lines().filter_map(parse_source) {
+            if files.contains_key(path) || Path::new(path).ends_with("SUMMARY.md") {
+                continue;
+            }
+            if !only.is_empty() && !only.iter().any(|pattern| pattern.matches(path)) {
+                continue;
+            }
+            if let Ok(content) = fs::read_to_string(book_dir.join(path)) {
+                files.insert(String::from(path), content);
+            }
+        }
+    }
+    files
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let config = load_markdown_config(&args.book_dir)?;
+    let catalog = read_catalog(&args.po_file)?;
+    let only = args
+        .only
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern).with_context(|| format!("Invalid glob pattern {pattern:?}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let files = read_files(&catalog, &args.book_dir, &only);
+    let sentences = if args.sentence_alignment {
+        let target = catalog.metadata.language.clone();
+        if target.is_empty() {
+            bail!(
+                "{} has no Language header field, which is needed for --sentence-alignment",
+                args.po_file.display()
+            );
+        }
+        let source = load_book_config(&args.book_dir)?
+            .and_then(|book_config| book_config.book.language)
+            .unwrap_or_else(|| String::from("en"));
+        Some(SentenceLanguages { source, target })
+    } else {
+        None
+    };
+    let (catalog, summary) = migrate(catalog, &files, &config, sentences.as_ref());
+    let obsolete = read_obsolete(&args.po_file)?;
+    write_with_obsolete(&catalog, &obsolete, &args.po_file)?;
+    write_rules_to_file(&args.po_file, &SegmentationRules::current(&config))?;
+    convert_file(&args.po_file, &load_write_options(&args.book_dir)?)?;
+    #[allow(clippy::print_stdout)]
+    {
+        println!(
+            "{}: {} translations unchanged, {} migrated ({} with low confidence), {} dropped",
+            args.po_file.display(),
+            summary.unchanged,
+            summary.migrated,
+            summary.low_confidence,
+            summary.dropped.len()
+        );
+        for ((path, reason), count) in summary.dropped_by_chapter() {
+            println!("  {path}: {count} dropped, {reason}");
+        }
+    }
+    if let Some(report) = &args.report {
+        let json = serde_json::to_string_pretty(&summary)?;
+        fs::write(report, json).with_context(|| format!("Could not write {}", report.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_read_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/async")).unwrap();
+        fs::write(dir.path().join("src/async/pin.md"), "Pinning\n").unwrap();
+        fs::write(dir.path().join("src/basics.md"), "Basics\n").unwrap();
+        let catalog = create_catalog(&[
+            ("src/async/pin.md:1", "Pinning", ""),
+            ("src/basics.md:1", "Basics", ""),
+            ("src/missing.md:1", "Missing", ""),
+        ]);
+        let files = read_files(&catalog, dir.path(), &[]);
+        assert_eq!(files.len(), 2);
+        let only = [Pattern::new("src/async/**").unwrap()];
+        let files = read_files(&catalog, dir.path(), &only);
+        assert_eq!(
+            files,
+            HashMap::from([(String::from("src/async/pin.md"), String::from("Pinning\n"))])
+        );
+    }
+}
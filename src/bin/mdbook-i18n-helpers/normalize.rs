@@ -0,0 +1,367 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bring the messages in PO files in line with the current extraction.
+//!
+//! When the way Markdown is turned into messages changes, such as
+//! after upgrading `mdbook-xgettext`, the messages in existing PO
+//! files no longer match the extracted messages. `msgmerge` then
+//! turns them into fuzzy entries and the translations are not used.
+//!
+//! This command runs each `msgid` and `msgstr` through the extraction
+//! again. A message which now becomes several messages is split, and
+//! the translation is split along with it. If the translation cannot
+//! be split the same way, it is kept on the first message, which is
+//! marked fuzzy.
+//!
+//! Running it on a catalog produced by the current `mdbook-xgettext`
+//! does nothing. Use `--check` to verify this, for example in CI.
+//!
+//! The `X-Segmentation-Rules` header field of the normalized PO files
+//! is set to the current rules.
+
+use anyhow::{anyhow, bail, Context};
+use mdbook_i18n_helpers::alignment::{add_message, extract};
+use mdbook_i18n_helpers::catalog_io::{convert_file, read_catalog, WriteOptions};
+use mdbook_i18n_helpers::obsolete::{read_obsolete, write_with_obsolete};
+use mdbook_i18n_helpers::segmentation::{write_rules_to_file, SegmentationRules};
+use mdbook_i18n_helpers::MarkdownConfig;
+use polib::catalog::Catalog;
+use polib::message::{CatalogMessageMutView, Message, MessageView};
+use polib::metadata::CatalogMetadata;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    ///
+    /// The `output.xgettext` settings from `book.toml` are used when
+    /// extracting the messages.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Report the PO files which are not normalized instead of
+    /// changing them.
+    #[arg(long)]
+    check: bool,
+
+    /// PO files to normalize in place.
+    #[arg(required = true)]
+    po_files: Vec<PathBuf>,
+}
+
+/// Statistics from normalizing a catalog.
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    /// The `msgid` of the messages which were changed.
+    changed: Vec<String>,
+    /// Number of translations which could not be split.
+    fuzzy: usize,
+}
+
+/// Normalize the messages in `catalog`.
+fn normalize(mut catalog: Catalog, config: &MarkdownConfig) -> (Catalog, Summary) {
+    let metadata = std::mem::replace(&mut catalog.metadata, CatalogMetadata::new());
+    let mut result = Catalog::new(metadata);
+    let mut summary = Summary::default();
+
+    for mut message in catalog.messages_mut() {
+        let message = message.detach();
+        let msgstr = match message.msgstr() {
+            Ok(msgstr) => msgstr,
+            Err(_) => {
+                result.append_or_update(message);
+                continue;
+            }
+        };
+        let msgids = extract(message.msgid(), config);
+        if msgids.len() == 1 && msgids[0] == message.msgid() {
+            add_message(&mut result, message);
+            continue;
+        }
+
+        summary.changed.push(String::from(message.msgid()));
+        let msgstrs = if msgstr.is_empty() {
+            Vec::new()
+        } else {
+            extract(msgstr, config)
+        };
+        let split = msgstrs.len() == msgids.len();
+        for (idx, msgid) in msgids.into_iter().enumerate() {
+            let mut flags = message.flags().clone();
+            let msgstr = if split {
+                msgstrs[idx].clone()
+            } else if idx == 0 && !msgstr.is_empty() {
+                // The translation doesn't fit the new messages, so it
+                // must be split by hand.
+                flags.add_flag("fuzzy");
+                summary.fuzzy += 1;
+                String::from(msgstr)
+            } else {
+                String::new()
+            };
+            add_message(
+                &mut result,
+                Message::build_singular()
+                    .with_comments(String::from(message.comments()))
+                    .with_source(String::from(message.source()))
+                    .with_flags(flags)
+                    .with_msgid(msgid)
+                    .with_msgstr(msgstr)
+                    .done(),
+            );
+        }
+    }
+    (result, summary)
+}
+
+/// Read the `book.toml` file in `book_dir`, if there is one.
+pub fn load_book_config(book_dir: &Path) -> anyhow::Result<Option<mdbook::Config>> {
+    let book_toml = book_dir.join("book.toml");
+    if !book_toml.exists() {
+        return Ok(None);
+    }
+    mdbook::Config::from_disk(&book_toml)
+        .map(Some)
+        .map_err(|err| anyhow!("{err}"))
+        .with_context(|| format!("Could not read {:?}", book_toml))
+}
+
+/// Read the boolean `key` from the `book_config`.
+///
+/// A missing key is `false`, any other value than a boolean is an
+/// error.
+pub fn get_bool(book_config: &mdbook::Config, key: &str) -> anyhow::Result<bool> {
+    match book_config.get(key) {
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| anyhow!("Could not parse {key} as a boolean")),
+        None => Ok(false),
+    }
+}
+
+/// Read the extraction settings from the `book.toml` file in
+/// `book_dir`, if there is one.
+pub fn load_markdown_config(book_dir: &Path) -> anyhow::Result<MarkdownConfig> {
+    match load_book_config(book_dir)? {
+        Some(book_config) => MarkdownConfig::from_book_config(&book_config),
+        None => Ok(MarkdownConfig::default()),
+    }
+}
+
+/// Read the settings for writing PO files from the `book.toml` file
+/// in `book_dir`, if there is one.
+pub fn load_write_options(book_dir: &Path) -> anyhow::Result<WriteOptions> {
+    match load_book_config(book_dir)? {
+        Some(book_config) => WriteOptions::from_book_config(&book_config),
+        None => Ok(WriteOptions::default()),
+    }
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let config = load_markdown_config(&args.book_dir)?;
+    let write_options = load_write_options(&args.book_dir)?;
+
+    let mut unnormalized = Vec::new();
+    for path in &args.po_files {
+        let catalog = read_catalog(path)?;
+        let (catalog, summary) = normalize(catalog, &config);
+        #[allow(clippy::print_stdout)]
+        if args.check {
+            for msgid in &summary.changed {
+                println!("{}: {msgid:?} is not normalized", path.display());
+            }
+        } else {
+            let obsolete = read_obsolete(path)?;
+            write_with_obsolete(&catalog, &obsolete, path)?;
+            write_rules_to_file(path, &SegmentationRules::current(&config))?;
+            convert_file(path, &write_options)?;
+            println!(
+                "{}: normalized {} messages, {} translations marked fuzzy",
+                path.display(),
+                summary.changed.len(),
+                summary.fuzzy
+            );
+        }
+        if !summary.changed.is_empty() {
+            unnormalized.push(path.display().to_string());
+        }
+    }
+
+    if args.check && !unnormalized.is_empty() {
+        bail!("Not normalized: {}", unnormalized.join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use mdbook_i18n_helpers::extract_messages_with_comments;
+    use polib::message::MessageFlags;
+    use pretty_assertions::assert_eq;
+
+    fn messages(catalog: &Catalog) -> Vec<(&str, &str, bool)> {
+        catalog
+            .messages()
+            .map(|msg| (msg.msgid(), msg.msgstr().unwrap(), msg.is_fuzzy()))
+            .collect()
+    }
+
+    /// Build a catalog the way `mdbook-xgettext` does.
+    fn extract_catalog(document: &str, config: &MarkdownConfig) -> Catalog {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for message in extract_messages_with_comments(document, config) {
+            let mut flags = MessageFlags::new();
+            if message.no_wrap {
+                flags.add_flag("no-wrap");
+            }
+            add_message(
+                &mut catalog,
+                Message::build_singular()
+                    .with_comments(message.comment)
+                    .with_source(format!("src/foo.md:{}", message.lineno))
+                    .with_flags(flags)
+                    .with_msgid(message.msgid)
+                    .done(),
+            );
+        }
+        catalog
+    }
+
+    #[test]
+    fn test_get_bool() {
+        let config = <mdbook::Config as std::str::FromStr>::from_str(
+            "[preprocessor.gettext]\npreserve-anchors = true\nprovenance = \"true\"\n",
+        )
+        .unwrap();
+        assert!(get_bool(&config, "preprocessor.gettext.preserve-anchors").unwrap());
+        assert!(!get_bool(&config, "preprocessor.gettext.missing").unwrap());
+        assert!(get_bool(&config, "preprocessor.gettext.provenance").is_err());
+    }
+
+    #[test]
+    fn test_normalize_splits_messages() {
+        let catalog = create_catalog(&[
+            ("src/foo.md:1", "Foo\n\nBar", "FOO\n\nBAR"),
+            ("src/foo.md:1", "Baz\n\nQuux", "BAZ QUUX"),
+            ("src/foo.md:1", "*Emphasis*", "*EMPHASIS*"),
+        ]);
+
+        let (catalog, summary) = normalize(catalog, &MarkdownConfig::default());
+        assert_eq!(
+            messages(&catalog),
+            vec![
+                ("Foo", "FOO", false),
+                ("Bar", "BAR", false),
+                ("Baz", "BAZ QUUX", true),
+                ("Quux", "", false),
+                ("_Emphasis_", "_EMPHASIS_", false),
+            ]
+        );
+        assert_eq!(summary.changed.len(), 3);
+        assert_eq!(summary.fuzzy, 1);
+    }
+
+    #[test]
+    fn test_normalize_merges_duplicates() {
+        let catalog = create_catalog(&[
+            ("src/foo.md:1", "Foo", ""),
+            ("src/foo.md:1", "Foo\n\nBar", "FOO\n\nBAR"),
+        ]);
+
+        let (catalog, _) = normalize(catalog, &MarkdownConfig::default());
+        assert_eq!(
+            messages(&catalog),
+            vec![("Foo", "FOO", false), ("Bar", "BAR", false)]
+        );
+    }
+
+    #[test]
+    fn test_normalize_extracted_catalog_is_unchanged() {
+        let document = "# Title with `code` and *emphasis*\n\
+                        \n\
+                        Paragraph with **strong**, ~~strike~~, a [link](https://example.com \"Title\"),\n\
+                        a [reference][ref] and an ![image](foo.png).\n\
+                        Hard break follows  \n\
+                        next line.\n\
+                        \n\
+                        [ref]: https://example.org\n\
+                        \n\
+                        - Item one\n\
+                        - Item **two**\n  1. Nested\n\
+                        \n\
+                        > Quote with\n\
+                        > two lines\n\
+                        \n\
+                        | Name | Value |\n\
+                        |:-----|------:|\n\
+                        | `a`  | *b*   |\n\
+                        \n\
+                        ```rust,editable\n\
+                        fn main() {\n    println!(\"Hi\");\n}\n\
+                        ```\n\
+                        \n    indented code\n\
+                        \n\
+                        Text with <span>inline html</span> here.\n\
+                        \n\
+                        Footnote[^1] and \\* escaped text.\n\
+                        \n\
+                        [^1]: The footnote.\n\
+                        \n\
+                        - [ ] Task\n\
+                        \n\
+                        Term\n\
+                        : Definition\n\
+                        \n\
+                        Run `cargo` with $x^2$.\n\
+                        \n\
+                        <table>\n\
+                        <tr><td>Some <b>bold</b> text</td></tr>\n\
+                        </table>\n";
+        for config in [
+            MarkdownConfig::default(),
+            MarkdownConfig {
+                placeholders: true,
+                kind_comments: true,
+                ..MarkdownConfig::default()
+            },
+            MarkdownConfig {
+                extract_html: true,
+                ..MarkdownConfig::default()
+            },
+        ] {
+            let catalog = extract_catalog(document, &config);
+            let expected = messages(&catalog)
+                .into_iter()
+                .map(|(msgid, msgstr, fuzzy)| (String::from(msgid), String::from(msgstr), fuzzy))
+                .collect::<Vec<_>>();
+
+            let (catalog, summary) = normalize(catalog, &config);
+            assert_eq!(summary, Summary::default());
+            assert_eq!(
+                messages(&catalog)
+                    .into_iter()
+                    .map(|(msgid, msgstr, fuzzy)| (
+                        String::from(msgid),
+                        String::from(msgstr),
+                        fuzzy
+                    ))
+                    .collect::<Vec<_>>(),
+                expected
+            );
+        }
+    }
+}
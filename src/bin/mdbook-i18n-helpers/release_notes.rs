@@ -0,0 +1,196 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Summarize the newly translated messages as release notes.
+//!
+//! This combines the message-level comparison of the `diff` command
+//! with the source references of the messages. The result is a
+//! Markdown fragment which lists, for each language, the chapters
+//! with newly translated messages. It can be pasted into the
+//! announcement of a release.
+
+use crate::diff::{diff_messages, find_po_files, parse_catalog, Change};
+use mdbook::{BookItem, MDBook};
+use mdbook_i18n_helpers::sources::rebucket_sources;
+use polib::catalog::Catalog;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Directory with the PO files of the previous release.
+    old_dir: PathBuf,
+
+    /// Directory with the PO files of the new release.
+    new_dir: PathBuf,
+
+    /// Root directory of the book. When given, chapters are shown
+    /// with their titles instead of their file names.
+    #[arg(long)]
+    book_dir: Option<PathBuf>,
+}
+
+/// Count the newly translated messages in `new` by source file.
+///
+/// Messages found in several files are counted for each file. The
+/// files are in the order of their first message in `new`.
+pub fn count_by_source(old: &Catalog, new: &Catalog) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for (change, msgid) in diff_messages(old, new) {
+        if change != Change::Translated {
+            continue;
+        }
+        let Some(message) = new.find_message(None, &msgid, None) else {
+            continue;
+        };
+        for path in rebucket_sources(message.source(), 0).lines() {
+            match counts.iter_mut().find(|(p, _)| p == path) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((String::from(path), 1)),
+            }
+        }
+    }
+    counts
+}
+
+/// Write the release notes for `language` as Markdown.
+///
+/// The `chapters` map source files to chapter titles and give the
+/// order of the list. Other source files are listed last.
+/// Returns `None` if there are no newly translated messages.
+pub fn release_notes(
+    language: &str,
+    old: &Catalog,
+    new: &Catalog,
+    chapters: &[(String, String)],
+) -> Option<String> {
+    let mut counts = count_by_source(old, new);
+    if counts.is_empty() {
+        return None;
+    }
+    let total = diff_messages(old, new)
+        .iter()
+        .filter(|(change, _)| *change == Change::Translated)
+        .count();
+    counts.sort_by_key(|(path, _)| {
+        chapters
+            .iter()
+            .position(|(chapter_path, _)| chapter_path == path)
+            .unwrap_or(chapters.len())
+    });
+
+    let plural = |count: usize| if count == 1 { "message" } else { "messages" };
+    let mut notes = format!(
+        "### {language}\n\n{total} {} newly translated:\n\n",
+        if total == 1 {
+            "message was"
+        } else {
+            "messages were"
+        }
+    );
+    for (path, count) in counts {
+        match chapters
+            .iter()
+            .find(|(chapter_path, _)| *chapter_path == path)
+        {
+            Some((_, title)) => notes.push_str(&format!("- {title}: {count} {}\n", plural(count))),
+            None => notes.push_str(&format!("- `{path}`: {count} {}\n", plural(count))),
+        }
+    }
+    Some(notes)
+}
+
+/// Find the source file and title of each chapter in the book at
+/// `book_dir`.
+///
+/// The source files are relative to the book root, like the source
+/// references in the PO files.
+pub fn chapter_titles(book_dir: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let book = MDBook::load(book_dir)?;
+    let src = &book.config.book.src;
+    Ok(book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(ch) => ch
+                .path
+                .as_ref()
+                .map(|path| (src.join(path).display().to_string(), ch.name.clone())),
+            _ => None,
+        })
+        .collect())
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let chapters = match &args.book_dir {
+        Some(book_dir) => chapter_titles(book_dir)?,
+        None => Vec::new(),
+    };
+
+    let old_files = find_po_files(&args.old_dir)?;
+    let new_files = find_po_files(&args.new_dir)?;
+    let mut fragments = Vec::new();
+    for (language, path) in &new_files {
+        let old = parse_catalog(old_files.get(language))?;
+        let new = parse_catalog(Some(path))?;
+        fragments.extend(release_notes(language, &old, &new, &chapters));
+    }
+    #[allow(clippy::print_stdout)]
+    {
+        println!("{}", fragments.join("\n"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_count_by_source() {
+        let old = create_catalog(&[("src/a.md:1", "Foo", "FOO")]);
+        let new = create_catalog(&[
+            ("src/a.md:1", "Foo", "FOO"),
+            ("src/b.md:1\nsrc/a.md:5", "Bar", "BAR"),
+            ("src/b.md:3", "Baz", "BAZ"),
+            ("src/c.md:3", "Qux", ""),
+        ]);
+        assert_eq!(
+            count_by_source(&old, &new),
+            vec![(String::from("src/b.md"), 2), (String::from("src/a.md"), 1)]
+        );
+    }
+
+    #[test]
+    fn test_release_notes() {
+        let old = create_catalog(&[]);
+        let new = create_catalog(&[
+            ("src/other.md:1", "Foo", "FOO"),
+            ("src/intro.md:1", "Bar", "BAR"),
+            ("src/intro.md:3", "Baz", "BAZ"),
+        ]);
+        let chapters = vec![(String::from("src/intro.md"), String::from("Introduction"))];
+        assert_eq!(
+            release_notes("da", &old, &new, &chapters).as_deref(),
+            Some(
+                "### da\n\n\
+                 3 messages were newly translated:\n\n\
+                 - Introduction: 2 messages\n\
+                 - `src/other.md`: 1 message\n"
+            )
+        );
+        assert_eq!(release_notes("da", &new, &new, &chapters), None);
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Report the translation progress of each language.
+//!
+//! The PO files are found in `preprocessor.gettext.po-dir`, which is
+//! `po` by default. The report is a Markdown table with the number of
+//! translated, fuzzy, and untranslated messages of each language, so
+//! it can be pasted into an issue or a CI summary. Use the `diff`
+//! command to compare the progress between two versions.
+
+use crate::diff::{count_messages, find_po_files, parse_catalog, Counts};
+use crate::normalize::load_book_config;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+}
+
+/// Format the progress of each language as a Markdown table.
+fn format_report(languages: &[(String, Counts)]) -> String {
+    let mut report = String::from(
+        "| Language | Translated | Fuzzy | Untranslated | Progress |\n\
+         |----------|-----------:|------:|-------------:|---------:|\n",
+    );
+    for (language, counts) in languages {
+        let total = counts.translated + counts.fuzzy + counts.untranslated;
+        let progress = match total {
+            0 => String::from("-"),
+            _ => format!("{}%", 100 * counts.translated / total),
+        };
+        report.push_str(&format!(
+            "| {language} | {} | {} | {} | {progress} |\n",
+            counts.translated, counts.fuzzy, counts.untranslated
+        ));
+    }
+    report
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let po_dir = load_book_config(&args.book_dir)?
+        .and_then(|config| {
+            config
+                .get("preprocessor.gettext.po-dir")
+                .and_then(|value| value.as_str())
+                .map(PathBuf::from)
+        })
+        .unwrap_or_else(|| PathBuf::from("po"));
+    let mut languages = Vec::new();
+    for (language, path) in find_po_files(&args.book_dir.join(po_dir))? {
+        let catalog = parse_catalog(Some(&path))?;
+        languages.push((language, count_messages(&catalog)));
+    }
+    #[allow(clippy::print_stdout)]
+    {
+        print!("{}", format_report(&languages));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_format_report() {
+        let languages = vec![
+            (
+                String::from("da"),
+                Counts {
+                    translated: 2,
+                    fuzzy: 1,
+                    untranslated: 1,
+                },
+            ),
+            (String::from("de"), Counts::default()),
+        ];
+        assert_eq!(
+            format_report(&languages),
+            "| Language | Translated | Fuzzy | Untranslated | Progress |\n\
+             |----------|-----------:|------:|-------------:|---------:|\n\
+             | da | 2 | 1 | 1 | 50% |\n\
+             | de | 0 | 0 | 0 | - |\n"
+        );
+    }
+}
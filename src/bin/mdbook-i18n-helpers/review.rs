@@ -0,0 +1,245 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Record and show the review status of translations.
+//!
+//! The review status is kept in the state file next to the PO file,
+//! see [`mdbook_i18n_helpers::state`]. It only applies to the
+//! translation which was reviewed: when a translation changes, it is
+//! unreviewed again.
+
+use anyhow::anyhow;
+use clap::Subcommand;
+use mdbook_i18n_helpers::catalog_io::read_catalog;
+use mdbook_i18n_helpers::state::{state_path, update_state, StateFile};
+use polib::catalog::Catalog;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: ReviewCommand,
+}
+
+#[derive(Subcommand)]
+enum ReviewCommand {
+    /// Set the review status of translations.
+    Mark(MarkArgs),
+    /// Count the translations by review status.
+    Status(StatusArgs),
+}
+
+#[derive(clap::Args)]
+struct MarkArgs {
+    /// PO file with the translations.
+    po_file: PathBuf,
+
+    /// Only mark the messages from this source file or directory,
+    /// such as `src/foo.md`.
+    #[arg(long)]
+    source: Option<String>,
+
+    /// The review status, such as `approved`.
+    #[arg(long, required_unless_present = "reviewer")]
+    state: Option<String>,
+
+    /// Record the name of the reviewer. A translation can have
+    /// several reviewers.
+    #[arg(long)]
+    reviewer: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct StatusArgs {
+    /// PO files with the translations.
+    #[arg(required = true)]
+    po_files: Vec<PathBuf>,
+}
+
+/// Status of translations which have not been reviewed, or which
+/// changed after they were reviewed.
+const UNREVIEWED: &str = "unreviewed";
+
+/// The path of the state file for the PO file at `po_file`.
+pub fn po_state_path(po_file: &Path) -> anyhow::Result<PathBuf> {
+    let language = po_file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| anyhow!("Could not find the language of {}", po_file.display()))?;
+    Ok(state_path(
+        po_file.parent().unwrap_or(Path::new("")),
+        language,
+    ))
+}
+
+/// Check if one of the newline-separated `sources` is in `source`,
+/// which is a file or a directory.
+fn has_source(sources: &str, source: &str) -> bool {
+    let source = source.trim_end_matches('/');
+    sources.lines().any(|line| {
+        let path = line.rsplit_once(':').map_or(line, |(path, _)| path);
+        path == source || path.starts_with(&format!("{source}/"))
+    })
+}
+
+/// Set the review status or add a reviewer to the translated
+/// messages in `catalog`.
+///
+/// Only messages from `source` are marked, if given. Fuzzy and
+/// untranslated messages are skipped. Returns the number of marked
+/// messages.
+fn mark(
+    catalog: &Catalog,
+    state: &mut StateFile,
+    source: Option<&str>,
+    status: Option<&str>,
+    reviewer: Option<&str>,
+) -> usize {
+    let mut count = 0;
+    for message in catalog.messages() {
+        if source.is_some_and(|source| !has_source(message.source(), source)) {
+            continue;
+        }
+        let Ok(msgstr) = message.msgstr() else {
+            continue;
+        };
+        if message.is_fuzzy() || msgstr.is_empty() {
+            continue;
+        }
+        if let Some(status) = status {
+            state.set_review_status(message.msgid(), msgstr, status);
+        }
+        if let Some(reviewer) = reviewer {
+            state.add_reviewer(message.msgid(), msgstr, reviewer);
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Count the translated messages in `catalog` by review status.
+fn count_by_status<'a>(catalog: &Catalog, state: &'a StateFile) -> BTreeMap<&'a str, usize> {
+    let mut counts = BTreeMap::new();
+    for message in catalog.messages() {
+        let Ok(msgstr) = message.msgstr() else {
+            continue;
+        };
+        if message.is_fuzzy() || msgstr.is_empty() {
+            continue;
+        }
+        let status = state
+            .review_status(message.msgid(), msgstr)
+            .unwrap_or(UNREVIEWED);
+        *counts.entry(status).or_default() += 1;
+    }
+    counts
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    match args.command {
+        ReviewCommand::Mark(args) => {
+            let catalog = read_catalog(&args.po_file)?;
+            let count = update_state(&po_state_path(&args.po_file)?, |state| {
+                Ok(mark(
+                    &catalog,
+                    state,
+                    args.source.as_deref(),
+                    args.state.as_deref(),
+                    args.reviewer.as_deref(),
+                ))
+            })?;
+            let what = match (&args.state, &args.reviewer) {
+                (Some(status), Some(reviewer)) => format!("{status} by {reviewer}"),
+                (Some(status), None) => status.clone(),
+                (None, Some(reviewer)) => format!("reviewed by {reviewer}"),
+                (None, None) => unreachable!("clap requires --state or --reviewer"),
+            };
+            #[allow(clippy::print_stdout)]
+            {
+                println!(
+                    "{}: marked {count} messages as {what}",
+                    args.po_file.display()
+                );
+            }
+        }
+        ReviewCommand::Status(args) => {
+            for po_file in &args.po_files {
+                let catalog = read_catalog(po_file)?;
+                let state = StateFile::load(&po_state_path(po_file)?)?;
+                let mut counts = count_by_status(&catalog, &state)
+                    .into_iter()
+                    .map(|(status, count)| format!("{status} {count}"))
+                    .collect::<Vec<_>>();
+                let suggestions = catalog
+                    .messages()
+                    .filter(|message| !state.suggestions(message.msgid()).is_empty())
+                    .count();
+                if suggestions > 0 {
+                    counts.push(format!("with suggestions {suggestions}"));
+                }
+                #[allow(clippy::print_stdout)]
+                {
+                    println!("{}: {}", po_file.display(), counts.join(", "));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_has_source() {
+        assert!(has_source("src/foo.md:10\nsrc/bar.md:3", "src/bar.md"));
+        assert!(has_source("src/guide/foo.md", "src/guide/"));
+        assert!(!has_source("src/foo.md:10", "src/fo"));
+    }
+
+    #[test]
+    fn test_mark_and_count() {
+        let catalog = create_catalog(&[
+            ("src/foo.md:1", "Foo", "FOO"),
+            ("src/bar.md:1", "Bar", "BAR"),
+            ("src/foo.md:5", "Missing", ""),
+        ]);
+        let mut state = StateFile::default();
+        assert_eq!(
+            mark(
+                &catalog,
+                &mut state,
+                Some("src/foo.md"),
+                Some("approved"),
+                Some("alice")
+            ),
+            1
+        );
+        assert_eq!(state.reviewers("Foo", "FOO"), ["alice"]);
+        assert_eq!(
+            count_by_status(&catalog, &state),
+            BTreeMap::from([("approved", 1), ("unreviewed", 1)])
+        );
+
+        let changed = create_catalog(&[("src/foo.md:1", "Foo", "FOO!")]);
+        assert_eq!(
+            count_by_status(&changed, &state),
+            BTreeMap::from([("unreviewed", 1)])
+        );
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Remove internal information from PO files before sharing them.
+//!
+//! The source references can reveal internal file paths and the
+//! header can contain email addresses. This command removes them and
+//! writes the removed source references to a mapping file. When the
+//! translations come back, `--restore` puts the source references
+//! back from the mapping file.
+
+use crate::normalize::load_write_options;
+use anyhow::{bail, Context};
+use mdbook_i18n_helpers::catalog_io::{read_catalog, write_catalog};
+use polib::catalog::Catalog;
+use polib::message::{MessageMutView, MessageView};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    ///
+    /// The result is written with the `output.xgettext` encoding,
+    /// line endings and header fields from `book.toml`.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// PO or POT file to scrub.
+    input: PathBuf,
+
+    /// Where to write the result.
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Remove the source references.
+    #[arg(long)]
+    strip_sources: bool,
+
+    /// Clear header fields, such as `Last-Translator`.
+    #[arg(long, value_delimiter = ',')]
+    strip_header_fields: Vec<String>,
+
+    /// JSON file for the removed source references.
+    #[arg(long, requires = "strip_sources")]
+    mapping: Option<PathBuf>,
+
+    /// Put back the source references from this mapping file.
+    #[arg(long, conflicts_with_all = ["strip_sources", "strip_header_fields", "mapping"])]
+    restore: Option<PathBuf>,
+}
+
+/// Source references by `msgid`.
+type SourceMapping = BTreeMap<String, String>;
+
+/// Clear the header field called `name` in `catalog`.
+fn strip_header_field(catalog: &mut Catalog, name: &str) -> anyhow::Result<()> {
+    let metadata = &mut catalog.metadata;
+    let field = match name.to_ascii_lowercase().as_str() {
+        "project-id-version" => &mut metadata.project_id_version,
+        "pot-creation-date" => &mut metadata.pot_creation_date,
+        "po-revision-date" => &mut metadata.po_revision_date,
+        "last-translator" => &mut metadata.last_translator,
+        "language-team" => &mut metadata.language_team,
+        _ => bail!("Cannot strip the {name:?} header field"),
+    };
+    field.clear();
+    Ok(())
+}
+
+/// Remove the source references from `catalog`.
+///
+/// Returns the removed references.
+fn strip_sources(catalog: &mut Catalog) -> SourceMapping {
+    let mut mapping = SourceMapping::new();
+    for mut message in catalog.messages_mut() {
+        let source = std::mem::take(message.source_mut());
+        if !source.is_empty() {
+            mapping.insert(String::from(message.msgid()), source);
+        }
+    }
+    mapping
+}
+
+/// Put the source references in `mapping` back into `catalog`.
+///
+/// Returns the number of messages which are not in `mapping`, such as
+/// messages added by the translators.
+fn restore_sources(catalog: &mut Catalog, mapping: &SourceMapping) -> usize {
+    let mut missing = 0;
+    for mut message in catalog.messages_mut() {
+        match mapping.get(message.msgid()) {
+            Some(source) => *message.source_mut() = source.clone(),
+            None => missing += 1,
+        }
+    }
+    missing
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let mut catalog = read_catalog(&args.input)?;
+
+    if let Some(path) = &args.restore {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Could not read {:?}", path))?;
+        let mapping: SourceMapping = serde_json::from_str(&content)
+            .with_context(|| format!("Could not parse {:?} as a mapping file", path))?;
+        let missing = restore_sources(&mut catalog, &mapping);
+        #[allow(clippy::print_stderr)]
+        if missing > 0 {
+            eprintln!("Warning: {missing} messages are not in {}", path.display());
+        }
+    }
+
+    for name in &args.strip_header_fields {
+        strip_header_field(&mut catalog, name)?;
+    }
+    if args.strip_sources {
+        let mapping = strip_sources(&mut catalog);
+        if let Some(path) = &args.mapping {
+            let content = serde_json::to_string_pretty(&mapping)?;
+            fs::write(path, content + "\n")
+                .with_context(|| format!("Could not write {}", path.display()))?;
+        }
+    }
+
+    write_catalog(&catalog, &args.output, &load_write_options(&args.book_dir)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    fn internal_catalog() -> Catalog {
+        let mut catalog = create_catalog(&[
+            ("internal/foo.md:1\ninternal/bar.md:5", "Foo", ""),
+            ("", "Bar", ""),
+        ]);
+        catalog.metadata.last_translator = String::from("Someone <someone@example.com>");
+        catalog
+    }
+
+    #[test]
+    fn test_strip_and_restore_sources() {
+        let mut catalog = internal_catalog();
+        let mapping = strip_sources(&mut catalog);
+        assert_eq!(
+            mapping,
+            SourceMapping::from([(
+                String::from("Foo"),
+                String::from("internal/foo.md:1\ninternal/bar.md:5")
+            )])
+        );
+        assert!(catalog
+            .messages()
+            .all(|message| message.source().is_empty()));
+
+        assert_eq!(restore_sources(&mut catalog, &mapping), 1);
+        assert_eq!(
+            catalog.find_message(None, "Foo", None).unwrap().source(),
+            "internal/foo.md:1\ninternal/bar.md:5"
+        );
+    }
+
+    #[test]
+    fn test_strip_header_field() {
+        let mut catalog = internal_catalog();
+        strip_header_field(&mut catalog, "Last-Translator").unwrap();
+        assert_eq!(catalog.metadata.last_translator, "");
+        assert!(strip_header_field(&mut catalog, "Content-Type").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serve a translation of a book and reload it when it changes.
+//!
+//! This runs `mdbook serve` with the same environment as `build-all`
+//! uses for the language. The PO directory is added to
+//! `build.extra-watch-dirs`, so the book is translated again and
+//! reloaded in the browser when the PO file changes, not only when the
+//! source changes. Arguments after `--` are passed on to `mdbook`:
+//!
+//! ```shell
+//! mdbook-i18n-helpers serve --language da -- --open --port 3001
+//! ```
+
+use crate::build_all::build_env;
+use anyhow::{bail, Context};
+use mdbook::MDBook;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Language to serve, such as `da` for `po/da.po`.
+    #[arg(short, long)]
+    language: String,
+
+    /// Directory for the translation, relative to the book root.
+    /// Defaults to `book/<language>`.
+    #[arg(long)]
+    dest_dir: Option<PathBuf>,
+
+    /// The `mdbook` program to run.
+    #[arg(long, default_value = "mdbook")]
+    mdbook: PathBuf,
+
+    /// Extra arguments for `mdbook serve`.
+    #[arg(last = true)]
+    mdbook_args: Vec<String>,
+}
+
+/// The `build.extra-watch-dirs` environment variable with `po_dir`
+/// added to the `extra_watch_dirs` from `book.toml`.
+///
+/// Returns `None` if the directory is already watched.
+fn watch_dirs_env(extra_watch_dirs: &[PathBuf], po_dir: &Path) -> Option<(String, String)> {
+    if extra_watch_dirs.iter().any(|dir| dir == po_dir) {
+        return None;
+    }
+    let dirs = extra_watch_dirs
+        .iter()
+        .chain(std::iter::once(&po_dir.to_path_buf()))
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    Some((
+        String::from("MDBOOK_BUILD__EXTRA_WATCH_DIRS"),
+        serde_json::to_string(&dirs).expect("a list of strings can be serialized"),
+    ))
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let book = MDBook::load(&args.book_dir)?;
+    let po_dir = PathBuf::from(
+        book.config
+            .get("preprocessor.gettext.po-dir")
+            .and_then(|value| value.as_str())
+            .unwrap_or("po"),
+    );
+    let (mut env, _) = build_env(&book.config, &book.root.join(&po_dir), &args.language)?;
+    env.extend(watch_dirs_env(&book.config.build.extra_watch_dirs, &po_dir));
+    let dest_dir = args
+        .dest_dir
+        .unwrap_or_else(|| Path::new("book").join(&args.language));
+
+    let status = Command::new(&args.mdbook)
+        .arg("serve")
+        .arg("--dest-dir")
+        .arg(&dest_dir)
+        .args(&args.mdbook_args)
+        .arg(&book.root)
+        .envs(env)
+        .status()
+        .with_context(|| format!("Could not run {}", args.mdbook.display()))?;
+    if !status.success() {
+        bail!("mdbook serve failed: {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_watch_dirs_env() {
+        assert_eq!(
+            watch_dirs_env(&[PathBuf::from("theme")], Path::new("po")),
+            Some((
+                String::from("MDBOOK_BUILD__EXTRA_WATCH_DIRS"),
+                String::from(r#"["theme","po"]"#)
+            ))
+        );
+        assert_eq!(
+            watch_dirs_env(&[PathBuf::from("po")], Path::new("po")),
+            None
+        );
+    }
+}
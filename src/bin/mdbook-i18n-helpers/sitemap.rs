@@ -0,0 +1,126 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Write a `sitemap.xml` file for the translations of a book.
+//!
+//! The chapters are listed in each language of
+//! `preprocessor.gettext.languages`, with links to the other
+//! translations of the chapter. Run this command on the HTML output of
+//! each build to give every translation its own sitemap:
+//!
+//! ```shell
+//! MDBOOK_BOOK__LANGUAGE=da mdbook build -d book/da
+//! mdbook-i18n-helpers sitemap --language da book/da
+//! ```
+//!
+//! Sitemaps need absolute URLs. Use `--origin` if the languages are
+//! configured with URLs such as `/da/`.
+
+use anyhow::{bail, Context};
+use mdbook::book::BookItem;
+use mdbook::MDBook;
+use mdbook_i18n_helpers::language_switcher::{book_languages, sitemap, BookLanguage};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// Scheme and host of the site, such as `https://example.com`, for
+    /// languages with URLs such as `/da/`.
+    #[arg(long)]
+    origin: Option<String>,
+
+    /// Only list the chapters of this language, such as `da`.
+    #[arg(long)]
+    language: Option<String>,
+
+    /// Directory where `sitemap.xml` is written.
+    html_dir: PathBuf,
+}
+
+/// Make the URL of `language` absolute with `origin`.
+fn absolute_url(language: &BookLanguage, origin: Option<&str>) -> anyhow::Result<String> {
+    let url = &language.url;
+    if url.contains("://") {
+        return Ok(url.clone());
+    }
+    match origin {
+        Some(origin) if url.starts_with('/') => {
+            Ok(format!("{}{url}", origin.trim_end_matches('/')))
+        }
+        _ => bail!(
+            "The URL {url:?} of {:?} is not absolute, use --origin to set the host",
+            language.language
+        ),
+    }
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let book = MDBook::load(&args.book_dir)?;
+    let mut languages = book_languages(&book.config)?;
+    if languages.is_empty() {
+        bail!("No languages found in preprocessor.gettext.languages");
+    }
+    if let Some(language) = &args.language {
+        if !languages.iter().any(|l| &l.language == language) {
+            bail!("Could not find {language:?} in preprocessor.gettext.languages");
+        }
+    }
+    for language in &mut languages {
+        language.url = absolute_url(language, args.origin.as_deref())?;
+    }
+
+    let chapter_paths = book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(chapter) => chapter.path.as_deref(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let xml = sitemap(&languages, args.language.as_deref(), chapter_paths);
+    let path = args.html_dir.join("sitemap.xml");
+    fs::write(&path, xml).with_context(|| format!("Could not write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn language(url: &str) -> BookLanguage {
+        BookLanguage {
+            language: String::from("da"),
+            name: String::from("Dansk"),
+            url: String::from(url),
+        }
+    }
+
+    #[test]
+    fn test_absolute_url() {
+        assert_eq!(
+            absolute_url(&language("https://example.com/da/"), None).unwrap(),
+            "https://example.com/da/"
+        );
+        assert_eq!(
+            absolute_url(&language("/da/"), Some("https://example.com/")).unwrap(),
+            "https://example.com/da/"
+        );
+        assert!(absolute_url(&language("/da/"), None).is_err());
+        assert!(absolute_url(&language("da/"), Some("https://example.com")).is_err());
+    }
+}
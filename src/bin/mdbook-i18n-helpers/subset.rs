@@ -0,0 +1,508 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Split off the messages of some chapters and merge them back.
+//!
+//! A translator hired for one part of a book only needs the messages
+//! of that part. This command writes the messages whose source
+//! references match the `--include` patterns to a new PO file:
+//!
+//! ```shell
+//! mdbook-i18n-helpers subset po/ko.po --include 'src/async/**' -o ko-async.po
+//! ```
+//!
+//! With `--per-file-output`, the messages are instead written to one PO
+//! file per chapter, such as `ko/src/async/intro.po`:
+//!
+//! ```shell
+//! mdbook-i18n-helpers subset po/ko.po --include 'src/async/**' --per-file-output ko
+//! ```
+//!
+//! With `--kind code`, only the messages from code blocks are kept.
+//! They are recognized by the `no-wrap` flag which `mdbook-xgettext`
+//! gives them. This lets a technical reviewer check the translated code
+//! comments, which can break the code examples:
+//!
+//! ```shell
+//! mdbook-i18n-helpers subset po/ko.po --kind code -o ko-code.po
+//! ```
+//!
+//! When the translations come back, `--merge` copies them into the
+//! full PO file:
+//!
+//! ```shell
+//! mdbook-i18n-helpers subset po/ko.po --merge ko-async.po -o po/ko.po
+//! ```
+//!
+//! Several subsets can be merged at once. They are merged in the order
+//! they are given, so a message which is translated differently in two
+//! subsets gets the translation of the last one. A warning is printed
+//! for such messages.
+
+use crate::normalize::load_write_options;
+use anyhow::{anyhow, Context};
+use glob::Pattern;
+use mdbook_i18n_helpers::catalog_io::{read_catalog, write_catalog, WriteOptions};
+use polib::catalog::Catalog;
+use polib::message::{CatalogMessageMutView, Message, MessageFlags, MessageMutView, MessageView};
+use polib::metadata::CatalogMetadata;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    ///
+    /// The PO files are written with the `output.xgettext` encoding,
+    /// line endings and header fields from `book.toml`.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// PO file to take the messages from, or to merge into.
+    input: PathBuf,
+
+    /// Where to write the result.
+    #[arg(short, long, required_unless_present = "per_file_output")]
+    output: Option<PathBuf>,
+
+    /// Write the messages of each file to its own PO file in this
+    /// directory, instead of to `--output`.
+    #[arg(long, conflicts_with_all = ["output", "merge"])]
+    per_file_output: Option<PathBuf>,
+
+    /// Keep the messages from files matching this glob pattern, such
+    /// as `src/async/**`.
+    #[arg(long, required_unless_present_any = ["merge", "per_file_output", "kind"])]
+    include: Vec<String>,
+
+    /// Leave out the messages which are only in files matching this
+    /// glob pattern.
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Keep only the messages of this kind.
+    #[arg(long, value_enum)]
+    kind: Option<Kind>,
+
+    /// Copy the translations from this subset into the input. Can be
+    /// given several times.
+    #[arg(long, conflicts_with_all = ["include", "exclude", "kind"])]
+    merge: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Kind {
+    /// Messages from code blocks, which have the `no-wrap` flag.
+    Code,
+    /// All other messages.
+    Text,
+}
+
+impl Kind {
+    /// Check if a message with the given `flags` is of this kind.
+    fn matches(self, flags: &MessageFlags) -> bool {
+        let code = flags.contains("no-wrap");
+        match self {
+            Kind::Code => code,
+            Kind::Text => !code,
+        }
+    }
+}
+
+/// The paths in the source references `source`, without line numbers.
+fn source_paths(source: &str) -> impl Iterator<Item = &str> {
+    source
+        .split_whitespace()
+        .map(|reference| match reference.rsplit_once(':') {
+            Some((path, lineno)) if lineno.chars().all(|c| c.is_ascii_digit()) => path,
+            _ => reference,
+        })
+}
+
+/// Check if a message with the source references `source` belongs to
+/// the subset.
+///
+/// A message belongs to the subset if one of its files matches an
+/// `include` pattern and doesn't match an `exclude` pattern.
+fn is_included(source: &str, include: &[Pattern], exclude: &[Pattern]) -> bool {
+    source_paths(source).any(|path| {
+        include.iter().any(|pattern| pattern.matches(path))
+            && !exclude.iter().any(|pattern| pattern.matches(path))
+    })
+}
+
+/// Remove the messages which don't belong to the subset from
+/// `catalog`, or which are not of the given `kind`.
+///
+/// Returns the number of messages which are left.
+fn extract_subset(
+    catalog: &mut Catalog,
+    include: &[Pattern],
+    exclude: &[Pattern],
+    kind: Option<Kind>,
+) -> usize {
+    let mut count = 0;
+    for mut message in catalog.messages_mut() {
+        if is_included(message.source(), include, exclude)
+            && kind.is_none_or(|kind| kind.matches(message.flags()))
+        {
+            count += 1;
+        } else {
+            message.delete();
+        }
+    }
+    count
+}
+
+/// Copy the translations in `subset` into `catalog`.
+///
+/// The `fuzzy` flag is copied along with the translation. Returns the
+/// messages of `subset` which are not in `catalog`.
+fn merge_subset(catalog: &mut Catalog, subset: &Catalog) -> Vec<String> {
+    let mut missing = Vec::new();
+    for message in subset.messages() {
+        let msgctxt = Some(message.msgctxt()).filter(|msgctxt| !msgctxt.is_empty());
+        let Some(mut target) =
+            catalog.find_message_mut(msgctxt, message.msgid(), message.msgid_plural().ok())
+        else {
+            missing.push(String::from(message.msgid()));
+            continue;
+        };
+        if let (Ok(msgstr), Ok(target_msgstr)) = (message.msgstr(), target.msgstr_mut()) {
+            *target_msgstr = String::from(msgstr);
+        }
+        if let (Ok(msgstr), Ok(target_msgstr)) =
+            (message.msgstr_plural(), target.msgstr_plural_mut())
+        {
+            *target_msgstr = msgstr.clone();
+        }
+        if message.is_fuzzy() {
+            target.flags_mut().add_flag("fuzzy");
+        } else {
+            target.flags_mut().remove_flag("fuzzy");
+        }
+    }
+    missing
+}
+
+/// Find the messages which are translated differently in `subsets`.
+///
+/// The `subsets` are pairs of names and catalogs. Returns the `msgid`
+/// of each such message, together with the names of the subsets with
+/// the first and the last of the translations.
+fn find_conflicts(subsets: &[(String, Catalog)]) -> Vec<(&str, &str, &str)> {
+    // The latest translation of each message, and the subsets with
+    // its first and latest translation.
+    let mut translations = HashMap::<(&str, &str), (&str, &str, &str)>::new();
+    let mut conflicts = Vec::new();
+    for (name, subset) in subsets {
+        for message in subset.messages() {
+            let msgstr = message.msgstr().unwrap_or_default();
+            if msgstr.is_empty() {
+                continue;
+            }
+            let key = (message.msgctxt(), message.msgid());
+            match translations.get_mut(&key) {
+                None => {
+                    translations.insert(key, (msgstr, name, name));
+                }
+                Some((latest, first, last)) if *latest != msgstr => {
+                    if first == last {
+                        conflicts.push(key);
+                    }
+                    *latest = msgstr;
+                    *last = name;
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    conflicts
+        .into_iter()
+        .map(|key| {
+            let (_, first, last) = translations[&key];
+            (key.1, first, last)
+        })
+        .collect()
+}
+
+/// Copy `message` into a new message.
+fn copy_message(message: &dyn MessageView) -> Message {
+    let mut builder = if message.is_plural() {
+        Message::build_plural()
+    } else {
+        Message::build_singular()
+    };
+    builder
+        .with_comments(String::from(message.comments()))
+        .with_source(String::from(message.source()))
+        .with_flags(message.flags().clone())
+        .with_msgctxt(String::from(message.msgctxt()))
+        .with_msgid(String::from(message.msgid()));
+    match (message.msgstr(), message.msgstr_plural()) {
+        (Ok(msgstr), _) => builder.with_msgstr(String::from(msgstr)),
+        (_, Ok(msgstr_plural)) => builder
+            .with_msgid_plural(String::from(message.msgid_plural().unwrap_or_default()))
+            .with_msgstr_plural(msgstr_plural.clone()),
+        _ => &mut builder,
+    };
+    builder.done()
+}
+
+/// Split `catalog` into a catalog per file in the source references.
+///
+/// A message with references to several files is put in the catalog
+/// of each of them. Messages without source references are left out.
+fn split_by_file(catalog: &Catalog) -> anyhow::Result<BTreeMap<String, Catalog>> {
+    let header = catalog.metadata.export_for_po();
+    let mut catalogs = BTreeMap::new();
+    for message in catalog.messages() {
+        let mut paths = source_paths(message.source()).collect::<Vec<_>>();
+        paths.dedup();
+        for path in paths {
+            if !catalogs.contains_key(path) {
+                let metadata = CatalogMetadata::parse(&header)
+                    .map_err(|err| anyhow!("Could not parse the PO header: {err}"))?;
+                catalogs.insert(String::from(path), Catalog::new(metadata));
+            }
+            let file_catalog = catalogs.get_mut(path).expect("the catalog was just added");
+            file_catalog.append_or_update(copy_message(message));
+        }
+    }
+    Ok(catalogs)
+}
+
+/// Write each of the `catalogs` to `dir`, with the path of its file
+/// and a `.po` extension.
+fn write_per_file(
+    catalogs: &BTreeMap<String, Catalog>,
+    dir: &Path,
+    write_options: &WriteOptions,
+) -> anyhow::Result<()> {
+    for (path, catalog) in catalogs {
+        let output = dir.join(path).with_extension("po");
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Could not create {}", parent.display()))?;
+        }
+        write_catalog(catalog, &output, write_options)?;
+    }
+    Ok(())
+}
+
+fn parse_patterns(patterns: &[String]) -> anyhow::Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern).with_context(|| format!("Invalid glob pattern {pattern:?}"))
+        })
+        .collect()
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let write_options = load_write_options(&args.book_dir)?;
+    let mut catalog = read_catalog(&args.input)?;
+
+    if args.merge.is_empty() {
+        let include = match args.include.as_slice() {
+            [] => parse_patterns(&[String::from("*")])?,
+            include => parse_patterns(include)?,
+        };
+        let exclude = parse_patterns(&args.exclude)?;
+        let count = extract_subset(&mut catalog, &include, &exclude, args.kind);
+        #[allow(clippy::print_stderr)]
+        if count == 0 {
+            eprintln!("Warning: No messages match the --include patterns and --kind");
+        }
+        if let Some(dir) = &args.per_file_output {
+            return write_per_file(&split_by_file(&catalog)?, dir, &write_options);
+        }
+    } else {
+        let mut subsets = Vec::new();
+        for path in &args.merge {
+            let subset = read_catalog(path)?;
+            subsets.push((path.display().to_string(), subset));
+        }
+        #[allow(clippy::print_stderr)]
+        for (msgid, first, last) in find_conflicts(&subsets) {
+            eprintln!(
+                "Warning: {msgid:?} is translated differently in {first} and {last}, \
+                 using the translation from {last}"
+            );
+        }
+        for (name, subset) in &subsets {
+            let missing = merge_subset(&mut catalog, subset);
+            #[allow(clippy::print_stderr)]
+            if !missing.is_empty() {
+                eprintln!(
+                    "Warning: {} messages from {name} are not in {}:",
+                    missing.len(),
+                    args.input.display()
+                );
+                for msgid in &missing {
+                    eprintln!("  {msgid:?}");
+                }
+            }
+        }
+    }
+
+    let output = args
+        .output
+        .as_ref()
+        .expect("clap requires --output without --per-file-output");
+    write_catalog(&catalog, output, &write_options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_catalog, mark_fuzzy};
+    use pretty_assertions::assert_eq;
+
+    fn msgids(catalog: &Catalog) -> Vec<&str> {
+        catalog.messages().map(|message| message.msgid()).collect()
+    }
+
+    #[test]
+    fn test_extract_subset() {
+        let mut catalog = create_catalog(&[
+            ("src/async/intro.md:1", "Async", ""),
+            ("src/basics.md:3\nsrc/async/pin.md:10", "Pinning", ""),
+            ("src/async/todo.md:5", "Todo", ""),
+            ("src/basics.md:1", "Basics", ""),
+        ]);
+        let include = parse_patterns(&[String::from("src/async/**")]).unwrap();
+        let exclude = parse_patterns(&[String::from("src/async/todo.md")]).unwrap();
+        assert_eq!(extract_subset(&mut catalog, &include, &exclude, None), 2);
+        assert_eq!(msgids(&catalog), vec!["Async", "Pinning"]);
+    }
+
+    #[test]
+    fn test_extract_subset_kind() {
+        let mut catalog = create_catalog(&[
+            ("src/basics.md:1", "Basics", ""),
+            ("src/basics.md:3", "```rust\n// Hello\n```", ""),
+        ]);
+        for mut message in catalog.messages_mut() {
+            if message.msgid().starts_with("```") {
+                message.flags_mut().add_flag("no-wrap");
+            }
+        }
+        let include = parse_patterns(&[String::from("*")]).unwrap();
+        assert_eq!(
+            extract_subset(&mut catalog, &include, &[], Some(Kind::Code)),
+            1
+        );
+        assert_eq!(msgids(&catalog), vec!["```rust\n// Hello\n```"]);
+        assert_eq!(
+            extract_subset(&mut catalog, &include, &[], Some(Kind::Text)),
+            0
+        );
+    }
+
+    #[test]
+    fn test_merge_subset() {
+        let mut catalog = create_catalog(&[
+            ("src/async/intro.md:1", "Async", ""),
+            ("src/basics.md:1", "Basics", "기초"),
+        ]);
+        mark_fuzzy(&mut catalog, "Async");
+        let subset = create_catalog(&[
+            ("src/async/intro.md:1", "Async", "비동기"),
+            ("src/async/old.md:1", "Removed", "삭제됨"),
+        ]);
+        assert_eq!(merge_subset(&mut catalog, &subset), vec!["Removed"]);
+        let message = catalog.find_message(None, "Async", None).unwrap();
+        assert_eq!(message.msgstr().unwrap(), "비동기");
+        assert!(!message.is_fuzzy());
+        assert_eq!(
+            catalog
+                .find_message(None, "Basics", None)
+                .unwrap()
+                .msgstr()
+                .unwrap(),
+            "기초"
+        );
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let subsets = vec![
+            (
+                String::from("a.po"),
+                create_catalog(&[("src/a.md:1", "Async", "비동기"), ("src/a.md:2", "Pin", "")]),
+            ),
+            (
+                String::from("b.po"),
+                create_catalog(&[
+                    ("src/a.md:1", "Async", "비동기"),
+                    ("src/a.md:2", "Pin", "핀"),
+                ]),
+            ),
+            (
+                String::from("c.po"),
+                create_catalog(&[
+                    ("src/a.md:1", "Async", "async"),
+                    ("src/a.md:2", "Pin", "핀"),
+                ]),
+            ),
+        ];
+        assert_eq!(find_conflicts(&subsets), vec![("Async", "a.po", "c.po")]);
+    }
+
+    #[test]
+    fn test_split_by_file() {
+        let catalog = create_catalog(&[
+            ("src/async/intro.md:1", "Async", "비동기"),
+            (
+                "src/basics.md:3\nsrc/async/pin.md:10 src/async/pin.md:12",
+                "Pinning",
+                "",
+            ),
+            ("", "No source", ""),
+        ]);
+        let catalogs = split_by_file(&catalog).unwrap();
+        assert_eq!(
+            catalogs
+                .iter()
+                .map(|(path, catalog)| (path.as_str(), msgids(catalog)))
+                .collect::<Vec<_>>(),
+            vec![
+                ("src/async/intro.md", vec!["Async"]),
+                ("src/async/pin.md", vec!["Pinning"]),
+                ("src/basics.md", vec!["Pinning"]),
+            ]
+        );
+        let message = catalogs["src/async/intro.md"]
+            .find_message(None, "Async", None)
+            .unwrap();
+        assert_eq!(message.msgstr().unwrap(), "비동기");
+        assert_eq!(message.source(), "src/async/intro.md:1");
+    }
+
+    #[test]
+    fn test_write_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let catalog = create_catalog(&[("src/async/intro.md:1", "Async", "비동기")]);
+        write_per_file(
+            &split_by_file(&catalog).unwrap(),
+            dir.path(),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+        let written = read_catalog(&dir.path().join("src/async/intro.po")).unwrap();
+        assert_eq!(msgids(&written), vec!["Async"]);
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Update PO files with the messages from a PO template.
+//!
+//! This works like `msgmerge --update --no-fuzzy-matching`: the
+//! messages are taken from the template in order, together with their
+//! source references and comments, and the translations are kept.
+//!
+//! Translations of messages which are no longer in the template are
+//! dropped, unless `--keep-obsolete` is given. They are then kept as
+//! `#~` obsolete entries at the end of the PO file and are used again
+//! if the message comes back.
+//!
+//! The `X-Segmentation-Rules` header field is copied from the template.
+
+use crate::normalize::load_write_options;
+use mdbook_i18n_helpers::catalog_io::{convert_file, read_catalog, read_to_string};
+use mdbook_i18n_helpers::obsolete::{read_obsolete, write_with_obsolete, ObsoleteMessage};
+use mdbook_i18n_helpers::segmentation::{read_rules, write_rules_to_file};
+use polib::catalog::Catalog;
+use polib::message::{Message, MessageMutView, MessageView};
+use polib::metadata::CatalogMetadata;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    ///
+    /// The PO files are written with the `output.xgettext` encoding,
+    /// line endings and header fields from `book.toml`.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// PO template with the current messages, as generated by
+    /// `mdbook-xgettext`.
+    #[arg(long)]
+    pot: PathBuf,
+
+    /// Keep translations of removed messages as obsolete entries.
+    #[arg(long)]
+    keep_obsolete: bool,
+
+    /// PO files to update in place.
+    #[arg(required = true)]
+    po_files: Vec<PathBuf>,
+}
+
+/// Statistics from updating a catalog.
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    /// Messages which were not in the catalog.
+    new: usize,
+    /// Messages which were translated by an obsolete entry.
+    revived: usize,
+    /// Translations which were turned into obsolete entries.
+    obsoleted: usize,
+    /// Translations which were removed.
+    dropped: usize,
+}
+
+/// Copy `message` without its translation.
+fn untranslated_copy(message: &dyn MessageView) -> Message {
+    let mut builder = match message.msgid_plural() {
+        Ok(msgid_plural) => {
+            let mut builder = Message::build_plural();
+            builder.with_msgid_plural(String::from(msgid_plural));
+            builder
+        }
+        Err(_) => Message::build_singular(),
+    };
+    builder
+        .with_comments(String::from(message.comments()))
+        .with_source(String::from(message.source()))
+        .with_flags(message.flags().clone())
+        .with_msgid(String::from(message.msgid()))
+        .done()
+}
+
+/// Update `catalog` and its `obsolete` entries with the messages in
+/// `template`.
+///
+/// Returns the new catalog and obsolete entries.
+fn update(
+    mut catalog: Catalog,
+    mut obsolete: Vec<ObsoleteMessage>,
+    template: &Catalog,
+    keep_obsolete: bool,
+) -> (Catalog, Vec<ObsoleteMessage>, Summary) {
+    let metadata = std::mem::replace(&mut catalog.metadata, CatalogMetadata::new());
+    let mut result = Catalog::new(metadata);
+    let mut summary = Summary::default();
+
+    for template_message in template.messages() {
+        let msgid = template_message.msgid();
+        if let Some(mut message) =
+            catalog.detach_message(None, msgid, template_message.msgid_plural().ok())
+        {
+            let fuzzy = message.is_fuzzy();
+            *message.source_mut() = String::from(template_message.source());
+            *message.comments_mut() = String::from(template_message.comments());
+            *message.flags_mut() = template_message.flags().clone();
+            if fuzzy {
+                message.flags_mut().add_flag("fuzzy");
+            }
+            result.append_or_update(message);
+            continue;
+        }
+
+        let mut message = untranslated_copy(template_message);
+        match obsolete.iter().position(|entry| entry.msgid == msgid) {
+            Some(idx) if message.is_singular() => {
+                let entry = obsolete.remove(idx);
+                message
+                    .set_msgstr(entry.msgstr)
+                    .expect("message is singular");
+                if entry.fuzzy {
+                    message.flags_mut().add_flag("fuzzy");
+                }
+                summary.revived += 1;
+            }
+            _ => summary.new += 1,
+        }
+        result.append_or_update(message);
+    }
+
+    let mut removed = catalog
+        .messages()
+        .filter(|message| message.is_translated())
+        .filter_map(|message| {
+            Some(ObsoleteMessage {
+                msgid: String::from(message.msgid()),
+                msgstr: String::from(message.msgstr().ok()?),
+                fuzzy: message.is_fuzzy(),
+            })
+        })
+        .collect::<Vec<_>>();
+    if keep_obsolete {
+        summary.obsoleted = removed.len();
+        obsolete.append(&mut removed);
+    } else {
+        summary.dropped = removed.len() + obsolete.len();
+        obsolete.clear();
+    }
+    (result, obsolete, summary)
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let write_options = load_write_options(&args.book_dir)?;
+    let template = read_catalog(&args.pot)?;
+    let rules = read_rules(&read_to_string(&args.pot)?);
+
+    for path in &args.po_files {
+        let catalog = read_catalog(path)?;
+        let obsolete = read_obsolete(path)?;
+        let (catalog, obsolete, summary) = update(catalog, obsolete, &template, args.keep_obsolete);
+        write_with_obsolete(&catalog, &obsolete, path)?;
+        if let Some(rules) = &rules {
+            write_rules_to_file(path, rules)?;
+        }
+        convert_file(path, &write_options)?;
+        #[allow(clippy::print_stdout)]
+        {
+            println!(
+                "{}: {} new messages, {} revived, {} made obsolete, {} translations dropped",
+                path.display(),
+                summary.new,
+                summary.revived,
+                summary.obsoleted,
+                summary.dropped
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    fn messages(catalog: &Catalog) -> Vec<(&str, &str, &str)> {
+        catalog
+            .messages()
+            .map(|msg| (msg.source(), msg.msgid(), msg.msgstr().unwrap()))
+            .collect()
+    }
+
+    fn obsolete_message(msgid: &str, msgstr: &str) -> ObsoleteMessage {
+        ObsoleteMessage {
+            msgid: String::from(msgid),
+            msgstr: String::from(msgstr),
+            fuzzy: false,
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let catalog = create_catalog(&[
+            ("src/foo.md:1", "Foo", "FOO"),
+            ("src/foo.md:3", "Removed", "REMOVED"),
+            ("src/foo.md:5", "Untranslated", ""),
+        ]);
+        let template = create_catalog(&[("src/foo.md:1", "New", ""), ("src/foo.md:3", "Foo", "")]);
+
+        let (catalog, obsolete, summary) = update(catalog, Vec::new(), &template, false);
+        assert_eq!(
+            messages(&catalog),
+            vec![("src/foo.md:1", "New", ""), ("src/foo.md:3", "Foo", "FOO")]
+        );
+        assert_eq!(obsolete, Vec::new());
+        assert_eq!(
+            summary,
+            Summary {
+                new: 1,
+                dropped: 1,
+                ..Summary::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_update_keep_obsolete() {
+        let catalog = create_catalog(&[("src/foo.md:1", "Removed", "REMOVED")]);
+        let template = create_catalog(&[("src/foo.md:1", "Foo", "")]);
+
+        let (catalog, obsolete, summary) = update(catalog, Vec::new(), &template, true);
+        assert_eq!(messages(&catalog), vec![("src/foo.md:1", "Foo", "")]);
+        assert_eq!(obsolete, vec![obsolete_message("Removed", "REMOVED")]);
+        assert_eq!(
+            summary,
+            Summary {
+                new: 1,
+                obsoleted: 1,
+                ..Summary::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_update_revives_obsolete() {
+        let catalog = create_catalog(&[]);
+        let template = create_catalog(&[("src/foo.md:1", "Foo", "")]);
+        let obsolete = vec![
+            ObsoleteMessage {
+                fuzzy: true,
+                ..obsolete_message("Foo", "FOO")
+            },
+            obsolete_message("Bar", "BAR"),
+        ];
+
+        let (catalog, obsolete, summary) = update(catalog, obsolete, &template, true);
+        assert_eq!(messages(&catalog), vec![("src/foo.md:1", "Foo", "FOO")]);
+        assert!(catalog.find_message(None, "Foo", None).unwrap().is_fuzzy());
+        assert_eq!(obsolete, vec![obsolete_message("Bar", "BAR")]);
+        assert_eq!(
+            summary,
+            Summary {
+                revived: 1,
+                ..Summary::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Refresh the `#:` source references of existing PO files.
+//!
+//! Line numbers drift whenever the source text is edited, even when
+//! the messages themselves stay the same. This command copies the
+//! source references from a freshly extracted PO template into the
+//! PO files without touching the translations or their flags.
+//!
+//! Use `--granularity` with the value used when extracting the
+//! template. References which only differ in how the line numbers
+//! were rounded are then left alone.
+//!
+//! Obsolete `#~` entries in the PO files are kept as they are.
+
+use crate::normalize::load_write_options;
+use mdbook_i18n_helpers::catalog_io::{convert_file, read_catalog};
+use mdbook_i18n_helpers::obsolete::{read_obsolete, write_with_obsolete};
+use mdbook_i18n_helpers::sources::sources_equal;
+use polib::catalog::Catalog;
+use polib::message::{MessageMutView, MessageView};
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    ///
+    /// The PO files are written with the `output.xgettext` encoding,
+    /// line endings and header fields from `book.toml`.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// PO template with the current source references, as generated
+    /// by `mdbook-xgettext`.
+    #[arg(long)]
+    pot: PathBuf,
+
+    /// Granularity of the line numbers in the template, see
+    /// `output.xgettext.granularity`.
+    #[arg(long, default_value_t = 1)]
+    granularity: usize,
+
+    /// PO files to update in place.
+    #[arg(required = true)]
+    po_files: Vec<PathBuf>,
+}
+
+/// Statistics from updating the source references in a catalog.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    /// Number of messages with new source references.
+    pub updated: usize,
+    /// Number of messages which are not found in the template.
+    pub missing: usize,
+}
+
+/// Copy the source references from `template` into `catalog`.
+///
+/// Messages are matched by their `msgid`. Messages which are not
+/// found in `template` keep their current source references. The
+/// source references are compared modulo `granularity`.
+pub fn update_sources(catalog: &mut Catalog, template: &Catalog, granularity: usize) -> Summary {
+    let mut summary = Summary::default();
+    for mut message in catalog.messages_mut() {
+        match template.find_message(None, message.msgid(), None) {
+            Some(template_message)
+                if !sources_equal(template_message.source(), message.source(), granularity) =>
+            {
+                *message.source_mut() = String::from(template_message.source());
+                summary.updated += 1;
+            }
+            Some(_) => {}
+            None => summary.missing += 1,
+        }
+    }
+    summary
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let write_options = load_write_options(&args.book_dir)?;
+    let template = read_catalog(&args.pot)?;
+
+    for path in &args.po_files {
+        let mut catalog = read_catalog(path)?;
+        let obsolete = read_obsolete(path)?;
+        let summary = update_sources(&mut catalog, &template, args.granularity);
+        write_with_obsolete(&catalog, &obsolete, path)?;
+        convert_file(path, &write_options)?;
+        #[allow(clippy::print_stdout)]
+        {
+            println!(
+                "{}: updated {} messages, {} messages not found in {}",
+                path.display(),
+                summary.updated,
+                summary.missing,
+                args.pot.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_catalog, mark_fuzzy};
+    use pretty_assertions::assert_eq;
+
+    fn sources(catalog: &Catalog) -> Vec<(&str, &str)> {
+        catalog
+            .messages()
+            .map(|msg| (msg.msgid(), msg.source()))
+            .collect()
+    }
+
+    #[test]
+    fn test_update_sources() {
+        let mut catalog = create_catalog(&[
+            ("src/foo.md:1", "Foo", "FOO"),
+            ("src/foo.md:3", "Bar", "BAR"),
+        ]);
+        let template = create_catalog(&[
+            ("src/foo.md:1", "Foo", ""),
+            ("src/foo.md:5\nsrc/bar.md:10", "Bar", ""),
+        ]);
+
+        let summary = update_sources(&mut catalog, &template, 1);
+        assert_eq!(
+            summary,
+            Summary {
+                updated: 1,
+                missing: 0
+            }
+        );
+        assert_eq!(
+            sources(&catalog),
+            vec![
+                ("Foo", "src/foo.md:1"),
+                ("Bar", "src/foo.md:5\nsrc/bar.md:10")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_sources_keeps_translations() {
+        let mut catalog = create_catalog(&[("src/foo.md:1", "Foo", "FOO")]);
+        mark_fuzzy(&mut catalog, "Foo");
+        let template = create_catalog(&[("src/foo.md:7", "Foo", "")]);
+
+        update_sources(&mut catalog, &template, 1);
+        let message = catalog.find_message(None, "Foo", None).unwrap();
+        assert_eq!(message.source(), "src/foo.md:7");
+        assert_eq!(message.msgstr().unwrap(), "FOO");
+        assert!(message.is_fuzzy());
+    }
+
+    #[test]
+    fn test_update_sources_missing_message() {
+        let mut catalog = create_catalog(&[("src/foo.md:1", "Old text", "OLD TEXT")]);
+        let template = create_catalog(&[("src/foo.md:1", "New text", "")]);
+
+        let summary = update_sources(&mut catalog, &template, 1);
+        assert_eq!(
+            summary,
+            Summary {
+                updated: 0,
+                missing: 1
+            }
+        );
+        assert_eq!(sources(&catalog), vec![("Old text", "src/foo.md:1")]);
+    }
+
+    #[test]
+    fn test_update_sources_granularity() {
+        let mut catalog = create_catalog(&[
+            ("src/foo.md:12", "Foo", "FOO"),
+            ("src/foo.md:14", "Bar", "BAR"),
+        ]);
+        let template =
+            create_catalog(&[("src/foo.md:10", "Foo", ""), ("src/foo.md:20", "Bar", "")]);
+
+        let summary = update_sources(&mut catalog, &template, 10);
+        assert_eq!(
+            summary,
+            Summary {
+                updated: 1,
+                missing: 0
+            }
+        );
+        assert_eq!(
+            sources(&catalog),
+            vec![("Foo", "src/foo.md:12"), ("Bar", "src/foo.md:20")]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Estimate the churn of upgrading `mdbook-i18n-helpers`.
+//!
+//! Run this command from the new version on a PO file made by the old
+//! version. It extracts the messages of each chapter like the new
+//! `mdbook-xgettext` would and reports how many of the existing
+//! messages would change. Such messages become fuzzy when the PO
+//! files are updated, unless `normalize` or `migrate` can carry the
+//! translations over. The messages which `normalize` can fix are
+//! counted separately.
+
+use mdbook::{BookItem, MDBook};
+use mdbook_i18n_helpers::alignment::extract;
+use mdbook_i18n_helpers::catalog_io::read_catalog;
+use mdbook_i18n_helpers::{extract_messages_with_config, MarkdownConfig};
+use polib::catalog::Catalog;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Root directory of the book, with the `book.toml` file.
+    #[arg(long, default_value = ".")]
+    book_dir: PathBuf,
+
+    /// PO or POT file made by the old version.
+    po_file: PathBuf,
+}
+
+/// The expected changes to the messages of a chapter.
+#[derive(Debug, Default, PartialEq)]
+struct ChapterReport {
+    /// Number of existing messages from the chapter.
+    messages: usize,
+    /// Number of existing messages which would change.
+    changed: usize,
+    /// Number of the changed messages which `normalize` can fix.
+    normalizable: usize,
+}
+
+/// Find the `msgid` of the messages in `catalog` from `path`.
+fn chapter_msgids<'a>(catalog: &'a Catalog, path: &Path) -> Vec<&'a str> {
+    catalog
+        .messages()
+        .filter(|message| {
+            message.source().lines().any(|source| {
+                let source = source.rsplit_once(':').map_or(source, |(path, _)| path);
+                Path::new(source) == path
+            })
+        })
+        .map(|message| message.msgid())
+        .collect()
+}
+
+/// Compare the existing messages `old_msgids` with the messages
+/// extracted from `content`.
+fn compare(old_msgids: &[&str], content: &str, config: &MarkdownConfig) -> ChapterReport {
+    let new_msgids = extract_messages_with_config(content, config)
+        .into_iter()
+        .map(|(_, msgid)| msgid)
+        .collect::<HashSet<_>>();
+    let mut report = ChapterReport {
+        messages: old_msgids.len(),
+        ..ChapterReport::default()
+    };
+    for msgid in old_msgids {
+        if new_msgids.contains(*msgid) {
+            continue;
+        }
+        report.changed += 1;
+        let normalized = extract(msgid, config);
+        if !normalized.is_empty() && normalized.iter().all(|msgid| new_msgids.contains(msgid)) {
+            report.normalizable += 1;
+        }
+    }
+    report
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let book = MDBook::load(&args.book_dir)?;
+    let config = MarkdownConfig::from_book_config(&book.config)?;
+    let catalog = read_catalog(&args.po_file)?;
+
+    let mut total = ChapterReport::default();
+    for item in book.book.iter() {
+        let BookItem::Chapter(chapter) = item else {
+            continue;
+        };
+        let Some(path) = &chapter.path else {
+            continue;
+        };
+        let path = book.config.book.src.join(path);
+        let report = compare(&chapter_msgids(&catalog, &path), &chapter.content, &config);
+        total.messages += report.messages;
+        total.changed += report.changed;
+        total.normalizable += report.normalizable;
+        #[allow(clippy::print_stdout)]
+        if report.changed > 0 {
+            println!(
+                "{}: {} of {} messages change, {} can be normalized",
+                path.display(),
+                report.changed,
+                report.messages,
+                report.normalizable
+            );
+        }
+    }
+    #[allow(clippy::print_stdout)]
+    {
+        println!(
+            "Total: {} of {} messages change, {} can be normalized",
+            total.changed, total.messages, total.normalizable
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_chapter_msgids() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (source, msgid) in [
+            ("src/foo.md:1", "Foo"),
+            ("src/bar.md:1\nsrc/foo.md:5", "Shared"),
+            ("src/foo.md.bak:1", "Backup"),
+            ("src/foo.md", "Path only"),
+        ] {
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_source(String::from(source))
+                    .with_msgid(String::from(msgid))
+                    .done(),
+            );
+        }
+        assert_eq!(
+            chapter_msgids(&catalog, Path::new("src/foo.md")),
+            vec!["Foo", "Shared", "Path only"]
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let content = "# Title\n\
+                       \n\
+                       Some _emphasis_.\n\
+                       \n\
+                       New paragraph.\n";
+        assert_eq!(
+            compare(
+                &["Title", "Some *emphasis*.", "Old paragraph."],
+                content,
+                &MarkdownConfig::default()
+            ),
+            ChapterReport {
+                messages: 3,
+                changed: 2,
+                normalizable: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A shorter name for `mdbook-i18n-helpers`.
+//!
+//! This runs the `mdbook-i18n-helpers` program installed next to it
+//! with the same arguments, so `mdbook-i18n extract` is the same as
+//! `mdbook-i18n-helpers extract`.
+
+use anyhow::Context;
+use std::env;
+use std::process::{exit, Command};
+
+fn main() -> anyhow::Result<()> {
+    let helpers = env::current_exe()
+        .context("Could not find the mdbook-i18n program")?
+        .with_file_name(format!("mdbook-i18n-helpers{}", env::consts::EXE_SUFFIX));
+    let status = Command::new(&helpers)
+        .args(env::args_os().skip(1))
+        .status()
+        .with_context(|| format!("Could not run {}", helpers.display()))?;
+    exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,341 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Share assets between the translations of a book.
+//!
+//! Each translation is built into its own directory, which gets a copy
+//! of every image and other file from the `src` directory, as well as
+//! the files of the theme. This renderer runs after the HTML renderer
+//! and moves these files to a directory shared by all translations.
+//! The references in the HTML files are rewritten to point there.
+//!
+//! The shared directory is set with `output.shared-assets.shared-dir`,
+//! relative to the book root, and defaults to `book/assets`. It is
+//! served at `output.shared-assets.url`, which defaults to `/assets/`.
+//!
+//! The first build to share a file decides its content. A translation
+//! with a different file at the same path, such as a translated
+//! screenshot, keeps its own copy.
+
+use anyhow::{anyhow, bail, Context};
+use mdbook::renderer::RenderContext;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+use std::{fs, io, thread};
+
+/// Files which belong to a single translation.
+const UNSHARED_FILES: &[&str] = &["searchindex.js", "searchindex.json", ".nojekyll", "CNAME"];
+
+/// Find the files in `dir`, relative to `dir`.
+fn find_files(dir: &Path, prefix: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let path = dir.join(prefix);
+    for entry in fs::read_dir(&path).with_context(|| format!("Could not read {:?}", path))? {
+        let entry = entry.with_context(|| format!("Could not read {:?}", path))?;
+        let relative_path = prefix.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            find_files(dir, &relative_path, files)?;
+        } else {
+            files.push(relative_path);
+        }
+    }
+    Ok(())
+}
+
+fn is_shareable(path: &Path) -> bool {
+    let is_html = path.extension().is_some_and(|ext| ext == "html");
+    let is_unshared = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| UNSHARED_FILES.contains(&name));
+    !is_html && !is_unshared
+}
+
+/// Move the files in `output_dir` to `shared_dir`.
+///
+/// Files which are already in `shared_dir` with the same content are
+/// removed from `output_dir`. Returns the paths of the moved and
+/// removed files, relative to `output_dir`.
+fn share_assets(output_dir: &Path, shared_dir: &Path) -> anyhow::Result<HashSet<PathBuf>> {
+    let mut files = Vec::new();
+    find_files(output_dir, Path::new(""), &mut files)?;
+    let mut shared = HashSet::new();
+    for path in files.into_iter().filter(|path| is_shareable(path)) {
+        let source = output_dir.join(&path);
+        if source.starts_with(shared_dir) {
+            continue;
+        }
+        let target = shared_dir.join(&path);
+        let content =
+            fs::read(&source).with_context(|| format!("Could not read {}", source.display()))?;
+        match fs::read(&target) {
+            Ok(existing) if existing == content => {}
+            Ok(_) => continue,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("Could not create {}", parent.display()))?;
+                }
+                fs::write(&target, &content)
+                    .with_context(|| format!("Could not write {}", target.display()))?;
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("Could not read {}", target.display()))
+            }
+        }
+        fs::remove_file(&source)
+            .with_context(|| format!("Could not remove {}", source.display()))?;
+        shared.insert(path);
+    }
+    Ok(shared)
+}
+
+/// Resolve `reference` from the HTML file at `html_path`.
+///
+/// Returns `None` for absolute references and URLs.
+fn resolve(html_path: &Path, reference: &str) -> Option<PathBuf> {
+    if reference.is_empty() || reference.starts_with(['/', '#']) || reference.contains(':') {
+        return None;
+    }
+    let mut path = html_path.parent().map(PathBuf::from).unwrap_or_default();
+    for component in Path::new(reference).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !path.pop() {
+                    return None;
+                }
+            }
+            Component::Normal(name) => path.push(name),
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+/// Point the `src` and `href` attributes in `html` to the shared
+/// assets.
+///
+/// The `html` is from the file at `html_path`, and `shared` has the
+/// paths of the shared assets. Both are relative to the output
+/// directory.
+fn rewrite_references(
+    html: &str,
+    html_path: &Path,
+    shared: &HashSet<PathBuf>,
+    url: &str,
+) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = ["src=\"", "href=\""]
+        .iter()
+        .filter_map(|attr| rest.find(attr).map(|idx| idx + attr.len()))
+        .min()
+    {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find('"').unwrap_or(rest.len());
+        let value = &rest[..end];
+        let (reference, suffix) = value.split_at(value.find(['?', '#']).unwrap_or(value.len()));
+        match resolve(html_path, reference).filter(|path| shared.contains(path)) {
+            Some(path) => {
+                let path = path.components().map(|c| c.as_os_str().to_string_lossy());
+                result.push_str(url);
+                result.push_str(&path.collect::<Vec<_>>().join("/"));
+                result.push_str(suffix);
+            }
+            None => result.push_str(value),
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Rewrite the references to the `shared` assets in the HTML `files`
+/// in `html_dir`, see [`rewrite_references`].
+///
+/// The files are split between `parallelism` threads, since large
+/// books have thousands of pages.
+fn rewrite_html_files(
+    html_dir: &Path,
+    files: &[PathBuf],
+    shared: &HashSet<PathBuf>,
+    url: &str,
+    parallelism: usize,
+) -> anyhow::Result<()> {
+    let chunk_size = files.len().div_ceil(parallelism.max(1)).max(1);
+    thread::scope(|scope| {
+        let handles = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    for path in chunk {
+                        let full_path = html_dir.join(path);
+                        let html = fs::read_to_string(&full_path)
+                            .with_context(|| format!("Could not read {}", full_path.display()))?;
+                        fs::write(&full_path, rewrite_references(&html, path, shared, url))
+                            .with_context(|| format!("Could not write {}", full_path.display()))?;
+                    }
+                    anyhow::Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("the thread does not panic"))
+    })
+}
+
+fn get_string(ctx: &RenderContext, key: &str, default: &str) -> anyhow::Result<String> {
+    match ctx.config.get(&format!("output.shared-assets.{key}")) {
+        None => Ok(String::from(default)),
+        Some(value) => value
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| anyhow!("Expected a string for output.shared-assets.{key}")),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let ctx = RenderContext::from_json(&mut io::stdin()).context("Parsing stdin")?;
+    let shared_dir = ctx
+        .root
+        .join(get_string(&ctx, "shared-dir", "book/assets")?);
+    let mut url = get_string(&ctx, "url", "/assets/")?;
+    if !url.ends_with('/') {
+        url.push('/');
+    }
+    // With more than one renderer, each renderer has its own directory
+    // in the build directory.
+    let html_dir = ctx
+        .destination
+        .parent()
+        .map(|dir| dir.join("html"))
+        .filter(|dir| dir.is_dir());
+    let Some(html_dir) = html_dir else {
+        bail!(
+            "Could not find the output of the HTML renderer, \
+             add output.html before output.shared-assets in book.toml"
+        );
+    };
+
+    let start = Instant::now();
+    let shared = share_assets(&html_dir, &shared_dir)?;
+    let mut files = Vec::new();
+    find_files(&html_dir, Path::new(""), &mut files)?;
+    files.retain(|path| path.extension().is_some_and(|ext| ext == "html"));
+    let parallelism = thread::available_parallelism().map_or(1, |parallelism| parallelism.get());
+    rewrite_html_files(&html_dir, &files, &shared, &url, parallelism)?;
+    #[allow(clippy::print_stderr)]
+    {
+        eprintln!(
+            "Shared {} assets and rewrote {} HTML files in {:.1?}",
+            shared.len(),
+            files.len(),
+            start.elapsed()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_share_assets() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let (output_da, output_de) = (tmpdir.path().join("da"), tmpdir.path().join("de"));
+        let shared_dir = tmpdir.path().join("assets");
+        for (dir, screenshot) in [(&output_da, "da"), (&output_de, "de")] {
+            fs::create_dir_all(dir.join("images"))?;
+            fs::write(dir.join("index.html"), "<img src=\"images/logo.png\">")?;
+            fs::write(dir.join("searchindex.js"), "index")?;
+            fs::write(dir.join("images/logo.png"), "logo")?;
+            fs::write(dir.join("images/screenshot.png"), screenshot)?;
+        }
+
+        assert_eq!(
+            share_assets(&output_da, &shared_dir)?,
+            HashSet::from([
+                PathBuf::from("images/logo.png"),
+                PathBuf::from("images/screenshot.png")
+            ])
+        );
+        assert_eq!(
+            share_assets(&output_de, &shared_dir)?,
+            HashSet::from([PathBuf::from("images/logo.png")])
+        );
+        assert!(!output_de.join("images/logo.png").exists());
+        assert!(output_de.join("images/screenshot.png").exists());
+        assert!(output_de.join("searchindex.js").exists());
+        assert_eq!(
+            fs::read_to_string(shared_dir.join("images/screenshot.png"))?,
+            "da"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_html_files() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let files = (0..5)
+            .map(|n| PathBuf::from(format!("page{n}.html")))
+            .collect::<Vec<_>>();
+        for path in &files {
+            fs::write(tmpdir.path().join(path), "<img src=\"logo.png\">")?;
+        }
+        let shared = HashSet::from([PathBuf::from("logo.png")]);
+        rewrite_html_files(tmpdir.path(), &files, &shared, "/assets/", 2)?;
+        for path in &files {
+            assert_eq!(
+                fs::read_to_string(tmpdir.path().join(path))?,
+                "<img src=\"/assets/logo.png\">"
+            );
+        }
+        assert!(rewrite_html_files(
+            tmpdir.path(),
+            &[PathBuf::from("missing.html")],
+            &shared,
+            "/assets/",
+            2
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_references() {
+        let shared = HashSet::from([
+            PathBuf::from("images/logo.png"),
+            PathBuf::from("css/general.css"),
+        ]);
+        let html = "<link rel=\"stylesheet\" href=\"../css/general.css?v=1\">\n\
+                    <img src=\"../images/logo.png\">\n\
+                    <img src=\"local.png\">\n\
+                    <a href=\"https://example.com/images/logo.png\">\n\
+                    <a href=\"#images\">";
+        assert_eq!(
+            rewrite_references(html, Path::new("intro/index.html"), &shared, "/assets/"),
+            "<link rel=\"stylesheet\" href=\"/assets/css/general.css?v=1\">\n\
+             <img src=\"/assets/images/logo.png\">\n\
+             <img src=\"local.png\">\n\
+             <a href=\"https://example.com/images/logo.png\">\n\
+             <a href=\"#images\">"
+        );
+    }
+}
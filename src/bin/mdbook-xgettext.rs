@@ -18,28 +18,431 @@
 //! translatable strings from your book. The strings are saved in a
 //! GNU Gettext `messages.pot` file in your build directory (typically
 //! `po/messages.pot`).
+//!
+//! Messages from code blocks get the `no-wrap` flag, so tools such as
+//! `msgmerge` keep their lines as they are.
+//!
+//! Set `output.xgettext.resolve-includes` to `true` to extract the
+//! messages from Markdown files pulled in by `{{#include}}`
+//! directives. The messages are attributed to the included files.
+//!
+//! Set `output.xgettext.granularity` to round the line numbers in
+//! the source references down to a multiple of the granularity. A
+//! granularity of 0 removes the line numbers.
+//!
+//! Set `output.xgettext.source-style` to `"path-only"` to leave out
+//! the line numbers, or to `"url"` to write the source references as
+//! URLs. The URLs start with `output.xgettext.source-url`.
+//!
+//! Set `output.xgettext.ignore` to a list of glob patterns, such as
+//! `["appendix/**"]`, to skip the contents of the matching chapters.
+//! The chapter titles in `SUMMARY.md` are still extracted.
+//!
+//! Set `output.xgettext.chapters` to a list of glob patterns to only
+//! extract the matching chapters and their titles. This is meant for
+//! previewing how a new chapter is split into messages, such as with
+//! `MDBOOK_OUTPUT__XGETTEXT__CHAPTERS='["async/**"]'`.
+//!
+//! Set `output.xgettext.extra-config-keys` to a list of keys in
+//! `book.toml`, such as `["output.html.edit-url-template-label"]`, to
+//! extract their values. The key is added as a comment.
+//!
+//! Set `output.xgettext.theme-strings` to `true` to extract the labels
+//! of the HTML theme, such as "Print this book", and the texts shown
+//! above the search results.
+//!
+//! The header of the PO template can be filled in with the
+//! `copyright-holder`, `report-msgid-bugs-to`, `language-team`, and
+//! `plural-forms` settings in the `output.xgettext` table. The
+//! `X-Segmentation-Rules` header field records the version and
+//! settings used to split the Markdown into messages.
+//!
+//! The PO template is written as UTF-8 with LF line endings. Set
+//! `output.xgettext.encoding` to `"ISO-8859-1"` and
+//! `output.xgettext.line-endings` to `"crlf"` for tools which need
+//! this. Set `output.xgettext.header-fields` to a table of header
+//! fields to set or override.
+//!
+//! The output only depends on the book: the messages are written in
+//! the order of the book and the `POT-Creation-Date` header is left
+//! empty. Set the `SOURCE_DATE_EPOCH` environment variable to fill in
+//! the header with a fixed date instead. Set
+//! `output.xgettext.omit-creation-date` to `true` to always leave it
+//! empty.
 
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
 use mdbook::renderer::RenderContext;
+use mdbook::utils::{take_anchored_lines, take_lines};
 use mdbook::BookItem;
-use mdbook_i18n_helpers::extract_messages;
+use mdbook_i18n_helpers::catalog_io::{convert, escape, WriteOptions};
+use mdbook_i18n_helpers::config_keys::config_messages;
+use mdbook_i18n_helpers::segmentation::{write_rules, SegmentationRules};
+use mdbook_i18n_helpers::sources::{normalize_path, SourceStyle};
+use mdbook_i18n_helpers::theme_strings::{SEARCH_STRINGS, THEME_STRINGS};
+use mdbook_i18n_helpers::{extract_messages_with_comments, MarkdownConfig};
 use polib::catalog::Catalog;
-use polib::message::Message;
+use polib::message::{Message, MessageFlags};
 use polib::metadata::CatalogMetadata;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 
-fn add_message(catalog: &mut Catalog, msgid: &str, source: &str) {
-    let sources = match catalog.find_message(None, msgid, None) {
-        Some(msg) => format!("{}\n{}", msg.source(), source),
-        None => String::from(source),
+/// Maximum nesting of `{{#include}}` directives, same as `mdbook`.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Add `msgid` to `catalog`, or add `source`, `comment`, and `flags`
+/// to the message if it is already there.
+fn add_message(catalog: &mut Catalog, msgid: &str, source: &str, comment: &str, flags: &[&str]) {
+    let (sources, comments, mut message_flags) = match catalog.find_message(None, msgid, None) {
+        Some(msg) => {
+            let sources = if msg.source().lines().any(|line| line == source) {
+                String::from(msg.source())
+            } else {
+                format!("{}\n{}", msg.source(), source)
+            };
+            // The same message can have placeholders for different
+            // markup, so we keep all the descriptions.
+            let mut comments = msg.comments().lines().map(String::from).collect::<Vec<_>>();
+            for line in comment.lines() {
+                if !comments.iter().any(|c| c == line) {
+                    comments.push(String::from(line));
+                }
+            }
+            (sources, comments.join("\n"), msg.flags().clone())
+        }
+        None => (
+            String::from(source),
+            String::from(comment),
+            MessageFlags::new(),
+        ),
     };
+    for flag in flags {
+        message_flags.add_flag(flag);
+    }
     let message = Message::build_singular()
         .with_source(sources)
+        .with_comments(comments)
+        .with_flags(message_flags)
         .with_msgid(String::from(msgid))
         .done();
     catalog.append_or_update(message);
 }
 
+/// Read the `output.xgettext.granularity` setting.
+fn granularity(ctx: &RenderContext) -> anyhow::Result<usize> {
+    match ctx.config.get("output.xgettext.granularity") {
+        None => Ok(1),
+        Some(value) => value
+            .as_integer()
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or_else(|| {
+                anyhow!("Expected a non-negative integer for output.xgettext.granularity")
+            }),
+    }
+}
+
+/// Read the `output.xgettext.source-style` setting and the settings
+/// it depends on.
+fn source_style(ctx: &RenderContext) -> anyhow::Result<SourceStyle> {
+    let granularity = granularity(ctx)?;
+    match get_string(ctx, "source-style")? {
+        None | Some("line") => Ok(SourceStyle::Line { granularity }),
+        Some("path-only") => Ok(SourceStyle::PathOnly),
+        Some("url") => {
+            let base_url = get_string(ctx, "source-url")?.ok_or_else(|| {
+                anyhow!("Missing output.xgettext.source-url for the \"url\" source style")
+            })?;
+            Ok(SourceStyle::Url {
+                base_url: String::from(base_url),
+                granularity,
+            })
+        }
+        Some(style) => bail!(
+            "Unknown output.xgettext.source-style {style:?}, \
+             expected \"path-only\", \"line\", or \"url\""
+        ),
+    }
+}
+
+/// Read a list of glob patterns from `output.xgettext.<key>`, such as
+/// the `ignore` setting.
+///
+/// The patterns are matched against the chapter paths relative to
+/// the `src` directory. A `*` does not match a `/`, use `**` for that.
+/// Returns `None` if the setting is missing.
+fn glob_patterns(ctx: &RenderContext, key: &str) -> anyhow::Result<Option<Vec<glob::Pattern>>> {
+    let Some(value) = ctx.config.get(&format!("output.xgettext.{key}")) else {
+        return Ok(None);
+    };
+    let patterns = value
+        .as_array()
+        .ok_or_else(|| anyhow!("Expected a list of strings for output.xgettext.{key}"))?;
+    patterns
+        .iter()
+        .map(|pattern| {
+            let pattern = pattern
+                .as_str()
+                .ok_or_else(|| anyhow!("Expected a list of strings for output.xgettext.{key}"))?;
+            glob::Pattern::new(pattern).with_context(|| {
+                format!("Invalid glob pattern {pattern:?} in output.xgettext.{key}")
+            })
+        })
+        .collect::<anyhow::Result<_>>()
+        .map(Some)
+}
+
+/// Check if the chapter at `path` matches one of the `patterns`.
+fn matches_any(path: &Path, patterns: &[glob::Pattern]) -> bool {
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..glob::MatchOptions::new()
+    };
+    patterns
+        .iter()
+        .any(|pattern| pattern.matches_path_with(path, options))
+}
+
+/// Read the optional string `key` from the `output.xgettext` table.
+fn get_string<'a>(ctx: &'a RenderContext, key: &str) -> anyhow::Result<Option<&'a str>> {
+    match ctx.config.get(&format!("output.xgettext.{key}")) {
+        None => Ok(None),
+        Some(value) => value
+            .as_str()
+            .map(Some)
+            .ok_or_else(|| anyhow!("Expected a string for output.xgettext.{key}")),
+    }
+}
+
+/// Read the boolean `key` from the `output.xgettext` table.
+///
+/// A missing key is `false`.
+fn get_bool(ctx: &RenderContext, key: &str) -> anyhow::Result<bool> {
+    match ctx.config.get(&format!("output.xgettext.{key}")) {
+        None => Ok(false),
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| anyhow!("Expected a boolean for output.xgettext.{key}")),
+    }
+}
+
+/// Format the Unix time `epoch` like `xgettext` formats dates.
+///
+/// The date is in UTC, such as `2023-04-05 12:34+0000`.
+fn format_creation_date(epoch: i64) -> String {
+    let days = epoch.div_euclid(86400);
+    let seconds = epoch.rem_euclid(86400);
+    // Convert the days since 1970-01-01 to a date in the proleptic
+    // Gregorian calendar, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}+0000",
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+/// Find the `POT-Creation-Date` header.
+///
+/// The date comes from `source_date_epoch`, which is the value of the
+/// `SOURCE_DATE_EPOCH` environment variable. The header is empty if
+/// this is unset or if `output.xgettext.omit-creation-date` is set.
+fn creation_date(ctx: &RenderContext, source_date_epoch: Option<&str>) -> anyhow::Result<String> {
+    let omit = get_bool(ctx, "omit-creation-date")?;
+    match source_date_epoch {
+        Some(epoch) if !omit => {
+            let epoch = epoch.trim().parse::<i64>().with_context(|| {
+                format!("Expected a Unix time in SOURCE_DATE_EPOCH, got {epoch:?}")
+            })?;
+            Ok(format_creation_date(epoch))
+        }
+        _ => Ok(String::new()),
+    }
+}
+
+/// Set the `Plural-Forms` header of `metadata` to `plural_forms`.
+///
+/// The type of the plural rules in `polib` is private, so we go
+/// through the textual form of the header to parse them.
+fn with_plural_forms(
+    metadata: &CatalogMetadata,
+    plural_forms: &str,
+) -> anyhow::Result<CatalogMetadata> {
+    let header = metadata
+        .export_for_po()
+        .lines()
+        .map(|line| match line.strip_prefix("Plural-Forms:") {
+            Some(_) => format!("Plural-Forms: {plural_forms}"),
+            None => String::from(line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    CatalogMetadata::parse(&header)
+        .map_err(|err| anyhow!("Could not parse output.xgettext.plural-forms: {err}"))
+}
+
+/// Add the header fields which `polib` doesn't support to the PO
+/// file `content`.
+///
+/// The `copyright_holder` is added as a comment at the top of the
+/// file and `report_bugs_to` as the `Report-Msgid-Bugs-To` field
+/// after the `Project-Id-Version` field, like `xgettext` does.
+fn add_header_fields(
+    content: &str,
+    copyright_holder: Option<&str>,
+    report_bugs_to: Option<&str>,
+) -> String {
+    let mut result = String::with_capacity(content.len());
+    if let Some(holder) = copyright_holder {
+        result.push_str(&format!("# Copyright (C) {holder}\n"));
+    }
+    let mut pending_field =
+        report_bugs_to.map(|to| format!("\"Report-Msgid-Bugs-To: {}\\n\"\n", escape(to)));
+    for line in content.split_inclusive('\n') {
+        result.push_str(line);
+        if line.starts_with("\"Project-Id-Version: ") {
+            if let Some(field) = pending_field.take() {
+                result.push_str(&field);
+            }
+        }
+    }
+    result
+}
+
+/// Parse `msgid` as a stand-alone `{{#include path}}` directive.
+///
+/// Returns the path and the optional line range or anchor.
+fn parse_include(msgid: &str) -> Option<(&str, Option<&str>)> {
+    let args = msgid
+        .trim()
+        .strip_prefix("{{")?
+        .strip_suffix("}}")?
+        .trim()
+        .strip_prefix("#include")?;
+    // Require whitespace after the directive name to avoid matching
+    // something like `#include_str`.
+    if !args.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut parts = args.trim().splitn(2, ':');
+    let path = parts.next().filter(|path| !path.is_empty())?;
+    Some((path, parts.next()))
+}
+
+/// Select the included lines from `content`.
+///
+/// The range or anchor is interpreted like `mdbook` does. Returns the
+/// selected text and the number of lines skipped before it.
+fn select_lines(content: &str, range: Option<&str>) -> (String, usize) {
+    let mut parts = range.unwrap_or("").splitn(3, ':');
+    let start = match parts.next() {
+        Some("") | None => None,
+        Some(start) => match start.parse::<usize>() {
+            Ok(start) => Some(start.saturating_sub(1)),
+            Err(_) => {
+                let anchor = format!("ANCHOR: {start}");
+                let offset = content
+                    .lines()
+                    .position(|line| line.contains(&anchor))
+                    .map_or(0, |idx| idx + 1);
+                return (take_anchored_lines(content, start), offset);
+            }
+        },
+    };
+    let end = parts.next().map(|end| end.parse::<usize>());
+    let text = match (start, end) {
+        (Some(start), Some(Ok(end))) => take_lines(content, start..end),
+        (Some(start), Some(Err(_))) => take_lines(content, start..),
+        (Some(start), None) => take_lines(content, start..start + 1),
+        (None, Some(Ok(end))) => take_lines(content, ..end),
+        (None, Some(Err(_)) | None) => String::from(content),
+    };
+    (text, start.unwrap_or(0))
+}
+
+/// The settings for extracting the messages of a chapter, read once
+/// from `book.toml`.
+struct ExtractSettings {
+    markdown_config: MarkdownConfig,
+    source_style: SourceStyle,
+    /// Extract the files pulled in by `{{#include}}`, see
+    /// `output.xgettext.resolve-includes`.
+    resolve_includes: bool,
+}
+
+impl ExtractSettings {
+    fn from_render_context(ctx: &RenderContext) -> anyhow::Result<Self> {
+        Ok(Self {
+            markdown_config: MarkdownConfig::from_book_config(&ctx.config)?,
+            source_style: source_style(ctx)?,
+            resolve_includes: get_bool(ctx, "resolve-includes")?,
+        })
+    }
+}
+
+/// Add the messages from `content` to `catalog`.
+///
+/// The `path` is relative to the book root and is used for the
+/// source references. Line numbers are shifted by `line_offset`.
+fn add_messages(
+    ctx: &RenderContext,
+    settings: &ExtractSettings,
+    catalog: &mut Catalog,
+    path: &Path,
+    content: &str,
+    line_offset: usize,
+    depth: usize,
+) -> anyhow::Result<()> {
+    for message in extract_messages_with_comments(content, &settings.markdown_config) {
+        let (lineno, msgid) = (message.lineno, message.msgid);
+        if let Some((include_path, range)) =
+            parse_include(&msgid).filter(|_| settings.resolve_includes)
+        {
+            if depth >= MAX_INCLUDE_DEPTH {
+                bail!(
+                    "Maximum include depth of {MAX_INCLUDE_DEPTH} exceeded in {}",
+                    path.display()
+                );
+            }
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let included_path = normalize_path(&dir.join(include_path));
+            let included =
+                fs::read_to_string(ctx.root.join(&included_path)).with_context(|| {
+                    format!(
+                        "Could not read {} included from {}:{}",
+                        included_path.display(),
+                        path.display(),
+                        lineno + line_offset
+                    )
+                })?;
+            let (text, offset) = select_lines(&included, range);
+            add_messages(
+                ctx,
+                settings,
+                catalog,
+                &included_path,
+                &text,
+                offset,
+                depth + 1,
+            )?;
+            continue;
+        }
+        let source = settings
+            .source_style
+            .build_source(path, lineno + line_offset);
+        let flags: &[&str] = if message.no_wrap { &["no-wrap"] } else { &[] };
+        add_message(catalog, &msgid, &source, &message.comment, flags);
+    }
+    Ok(())
+}
+
 fn create_catalog(ctx: &RenderContext) -> anyhow::Result<Catalog> {
     let mut metadata = CatalogMetadata::new();
     if let Some(title) = &ctx.config.book.title {
@@ -48,9 +451,15 @@ fn create_catalog(ctx: &RenderContext) -> anyhow::Result<Catalog> {
     if let Some(lang) = &ctx.config.book.language {
         metadata.language = String::from(lang);
     }
+    if let Some(language_team) = get_string(ctx, "language-team")? {
+        metadata.language_team = String::from(language_team);
+    }
     metadata.mime_version = String::from("1.0");
     metadata.content_type = String::from("text/plain; charset=UTF-8");
     metadata.content_transfer_encoding = String::from("8bit");
+    if let Some(plural_forms) = get_string(ctx, "plural-forms")? {
+        metadata = with_plural_forms(&metadata, plural_forms)?;
+    }
     let mut catalog = Catalog::new(metadata);
 
     // First, add all chapter names and part titles from SUMMARY.md.
@@ -60,11 +469,20 @@ fn create_catalog(ctx: &RenderContext) -> anyhow::Result<Catalog> {
     let summary_path = ctx.config.book.src.join("SUMMARY.md");
     let summary = std::fs::read_to_string(ctx.root.join(&summary_path))
         .with_context(|| anyhow!("Failed to read {}", summary_path.display()))?;
+    let settings = ExtractSettings::from_render_context(ctx)?;
+    let source_style = &settings.source_style;
+    // With `output.xgettext.chapters`, only the selected chapters are
+    // extracted, which gives a quick preview of their messages.
+    let chapter_patterns = glob_patterns(ctx, "chapters")?;
+    let is_selected = |path: Option<&PathBuf>| match &chapter_patterns {
+        Some(patterns) => path.is_some_and(|path| matches_any(path, patterns)),
+        None => true,
+    };
     let mut last_idx = 0;
     for item in ctx.book.iter() {
-        let line = match item {
-            BookItem::Chapter(chapter) => &chapter.name,
-            BookItem::PartTitle(title) => title,
+        let (line, selected) = match item {
+            BookItem::Chapter(chapter) => (&chapter.name, is_selected(chapter.path.as_ref())),
+            BookItem::PartTitle(title) => (title, chapter_patterns.is_none()),
             BookItem::Separator => continue,
         };
 
@@ -77,21 +495,59 @@ fn create_catalog(ctx: &RenderContext) -> anyhow::Result<Catalog> {
         })?;
         last_idx += idx;
         let lineno = summary[..last_idx].lines().count();
-        let source = format!("{}:{}", summary_path.display(), lineno);
-        add_message(&mut catalog, line, &source);
+        if selected {
+            let source = source_style.build_source(&summary_path, lineno);
+            add_message(&mut catalog, line, &source, "", &[]);
+        }
+    }
+
+    // Then the strings from book.toml used by renderers and other
+    // preprocessors.
+    if chapter_patterns.is_none() {
+        for (key, value) in config_messages(&ctx.config)? {
+            add_message(&mut catalog, &value, "book.toml", &key, &[]);
+        }
+    }
+
+    // The labels of the HTML theme, see mdbook-gettext.
+    if chapter_patterns.is_none() && get_bool(ctx, "theme-strings")? {
+        for msgid in THEME_STRINGS {
+            add_message(&mut catalog, msgid, "theme/index.hbs", "", &[]);
+        }
+        for msgid in SEARCH_STRINGS {
+            add_message(&mut catalog, msgid, "theme/searcher.js", "", &[]);
+        }
     }
 
     // Next, we add the chapter contents.
+    let ignore_patterns = glob_patterns(ctx, "ignore")?.unwrap_or_default();
     for item in ctx.book.iter() {
         if let BookItem::Chapter(chapter) = item {
+            if !is_selected(chapter.path.as_ref()) {
+                continue;
+            }
             let path = match &chapter.path {
+                Some(path) if matches_any(path, &ignore_patterns) => continue,
                 Some(path) => ctx.config.book.src.join(path),
                 None => continue,
             };
-            for (lineno, msgid) in extract_messages(&chapter.content) {
-                let source = format!("{}:{}", path.display(), lineno);
-                add_message(&mut catalog, &msgid, &source);
-            }
+            add_messages(ctx, &settings, &mut catalog, &path, &chapter.content, 0, 0)?;
+        }
+    }
+
+    // Finally the 404 page, which `mdbook` renders without running the
+    // preprocessors. It is only translated by `mdbook-i18n-helpers
+    // localize-pages`, so it is only extracted when asked for.
+    let extract_404 = get_bool(ctx, "extract-404")?;
+    let input_404 = ctx
+        .config
+        .get("output.html.input-404")
+        .and_then(|v| v.as_str())
+        .unwrap_or("404.md");
+    if extract_404 && !input_404.is_empty() && chapter_patterns.is_none() {
+        let path = ctx.config.book.src.join(input_404);
+        if let Ok(content) = fs::read_to_string(ctx.root.join(&path)) {
+            add_messages(ctx, &settings, &mut catalog, &path, &content, 0, 0)?;
         }
     }
 
@@ -112,9 +568,25 @@ fn main() -> anyhow::Result<()> {
     fs::create_dir_all(&ctx.destination)
         .with_context(|| format!("Could not create {}", ctx.destination.display()))?;
     let output_path = ctx.destination.join(path);
-    let catalog = create_catalog(&ctx).context("Extracting messages")?;
+    let mut catalog = create_catalog(&ctx).context("Extracting messages")?;
+    let source_date_epoch = std::env::var("SOURCE_DATE_EPOCH").ok();
+    catalog.metadata.pot_creation_date = creation_date(&ctx, source_date_epoch.as_deref())?;
     polib::po_file::write(&catalog, &output_path)
         .with_context(|| format!("Writing messages to {}", output_path.display()))?;
+    let copyright_holder = get_string(&ctx, "copyright-holder")?;
+    let report_bugs_to = get_string(&ctx, "report-msgid-bugs-to")?;
+    // Record the segmentation rules so mdbook-gettext can detect PO
+    // files made with incompatible rules.
+    let rules = SegmentationRules::current(&MarkdownConfig::from_book_config(&ctx.config)?);
+    let content = fs::read_to_string(&output_path)
+        .with_context(|| format!("Could not read {}", output_path.display()))?;
+    let content = add_header_fields(&content, copyright_holder, report_bugs_to);
+    let content = convert(
+        &write_rules(&content, &rules),
+        &WriteOptions::from_book_config(&ctx.config)?,
+    )?;
+    fs::write(&output_path, content)
+        .with_context(|| format!("Writing messages to {}", output_path.display()))?;
 
     Ok(())
 }
@@ -133,8 +605,13 @@ mod tests {
             .context("Could not create src/ directory")?;
 
         for (path, contents) in files {
-            std::fs::write(tmpdir.path().join(path), contents)
-                .with_context(|| format!("Could not write {path}"))?;
+            let path = tmpdir.path().join(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Could not create {}", parent.display()))?;
+            }
+            std::fs::write(&path, contents)
+                .with_context(|| format!("Could not write {}", path.display()))?;
         }
 
         let mdbook = MDBook::load(tmpdir.path()).context("Could not load book")?;
@@ -174,6 +651,65 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_create_catalog_header_settings() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 language-team = \"Docs Team <docs@example.com>\"\n\
+                 plural-forms = \"nplurals=2; plural=(n != 1);\"",
+            ),
+            ("src/SUMMARY.md", ""),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog.metadata.language_team,
+            "Docs Team <docs@example.com>"
+        );
+        assert_eq!(
+            catalog.metadata.plural_rules.dump(),
+            "nplurals=2; plural=(n != 1);"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_bad_plural_forms() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n[output.xgettext]\nplural-forms = \"plural=n\"",
+            ),
+            ("src/SUMMARY.md", ""),
+        ])?;
+
+        assert!(create_catalog(&ctx).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_header_fields() {
+        let content = "\n\
+            msgid \"\"\n\
+            msgstr \"\"\n\
+            \"Project-Id-Version: My Book\\n\"\n\
+            \"POT-Creation-Date: \\n\"\n";
+        assert_eq!(
+            add_header_fields(content, Some("2023 Google LLC"), Some("docs@example.com")),
+            "# Copyright (C) 2023 Google LLC\n\
+             \n\
+             msgid \"\"\n\
+             msgstr \"\"\n\
+             \"Project-Id-Version: My Book\\n\"\n\
+             \"Report-Msgid-Bugs-To: docs@example.com\\n\"\n\
+             \"POT-Creation-Date: \\n\"\n"
+        );
+        assert_eq!(add_header_fields(content, None, None), content);
+    }
+
     #[test]
     fn test_create_catalog_summary_formatting() -> anyhow::Result<()> {
         // It is an error to include formatting in the summary file:
@@ -222,4 +758,593 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_create_catalog_ignore() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 ignore = [\"appendix/**\", \"generated/*.md\"]",
+            ),
+            (
+                "src/SUMMARY.md",
+                "- [Foo](foo.md)\n\
+                 - [Appendix](appendix/a/index.md)\n\
+                 - [Generated](generated/bar.md)\n\
+                 - [Nested](generated/nested/baz.md)",
+            ),
+            ("src/foo.md", "Foo text"),
+            ("src/appendix/a/index.md", "Appendix text"),
+            ("src/generated/bar.md", "Generated text"),
+            ("src/generated/nested/baz.md", "Nested text"),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| msg.msgid())
+                .collect::<Vec<&str>>(),
+            &[
+                "Foo",
+                "Appendix",
+                "Generated",
+                "Nested",
+                "Foo text",
+                "Nested text"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_chapters() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.html]\n\
+                 edit-url-template-label = \"Edit\"\n\
+                 [output.xgettext]\n\
+                 chapters = [\"async/**\"]",
+            ),
+            (
+                "src/SUMMARY.md",
+                "- [Foo](foo.md)\n\
+                 # Advanced\n\
+                 - [Async](async/index.md)\n  \
+                   - [Pinning](async/pin.md)",
+            ),
+            ("src/foo.md", "Foo text"),
+            ("src/async/index.md", "Async text"),
+            ("src/async/pin.md", "Pinning text"),
+            ("src/404.md", "Not found"),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| (msg.source(), msg.msgid()))
+                .collect::<Vec<_>>(),
+            &[
+                ("src/SUMMARY.md:3", "Async"),
+                ("src/SUMMARY.md:4", "Pinning"),
+                ("src/async/index.md:1", "Async text"),
+                ("src/async/pin.md:1", "Pinning text"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_bad_ignore() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            ("book.toml", "[book]\n[output.xgettext]\nignore = \"*.md\""),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "Foo text"),
+        ])?;
+
+        assert!(create_catalog(&ctx).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_extra_config_keys() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 extra-config-keys = [\"output.foo.label\"]\n\
+                 [output.foo]\n\
+                 label = \"Edit this page\"",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "Foo text"),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| (msg.source(), msg.msgid(), msg.comments()))
+                .collect::<Vec<_>>(),
+            &[
+                ("src/SUMMARY.md:1", "Foo", ""),
+                ("book.toml", "Edit this page", "output.foo.label"),
+                ("src/foo.md:1", "Foo text", ""),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_theme_strings() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 theme-strings = true",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "Foo text"),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        let messages = catalog
+            .messages()
+            .map(|msg| (msg.source(), msg.msgid()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages.len(),
+            THEME_STRINGS.len() + SEARCH_STRINGS.len() + 2
+        );
+        assert!(messages.contains(&("theme/index.hbs", "Print this book")));
+        assert_eq!(messages.last(), Some(&("src/foo.md:1", "Foo text")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_404() -> anyhow::Result<()> {
+        let files = [
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "Foo text"),
+            ("src/404.md", "# Not Found"),
+        ];
+        let (ctx, _tmp) =
+            create_render_context(&[&[("book.toml", "[book]")], &files[..]].concat())?;
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(catalog.count(), 2);
+
+        let (ctx, _tmp) = create_render_context(
+            &[
+                &[(
+                    "book.toml",
+                    "[book]\n\
+                     [output.xgettext]\n\
+                     extract-404 = true",
+                )],
+                &files[..],
+            ]
+            .concat(),
+        )?;
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| (msg.source(), msg.msgid()))
+                .collect::<Vec<_>>(),
+            &[
+                ("src/SUMMARY.md:1", "Foo"),
+                ("src/foo.md:1", "Foo text"),
+                ("src/404.md:1", "Not Found"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_granularity() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 granularity = 5",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            (
+                "src/foo.md",
+                "Same\n\nSame\n\nSame\n\nOther\n\nSame\n\nSame",
+            ),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| (msg.source(), msg.msgid()))
+                .collect::<Vec<_>>(),
+            &[
+                ("src/SUMMARY.md:1", "Foo"),
+                ("src/foo.md:1\nsrc/foo.md:5\nsrc/foo.md:10", "Same"),
+                ("src/foo.md:5", "Other"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_no_line_numbers() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 granularity = 0",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "Same\n\nSame"),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| (msg.source(), msg.msgid()))
+                .collect::<Vec<_>>(),
+            &[("src/SUMMARY.md", "Foo"), ("src/foo.md", "Same")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_source_style_url() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 source-style = \"url\"\n\
+                 source-url = \"https://github.com/org/repo/blob/main/\"",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "Text\n\nMore"),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| (msg.source(), msg.msgid()))
+                .collect::<Vec<_>>(),
+            &[
+                (
+                    "https://github.com/org/repo/blob/main/src/SUMMARY.md#L1",
+                    "Foo"
+                ),
+                (
+                    "https://github.com/org/repo/blob/main/src/foo.md#L1",
+                    "Text"
+                ),
+                (
+                    "https://github.com/org/repo/blob/main/src/foo.md#L3",
+                    "More"
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_source_style_path_only() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 source-style = \"path-only\"",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "Text"),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| msg.source())
+                .collect::<Vec<_>>(),
+            &["src/SUMMARY.md", "src/foo.md"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_bad_source_style() -> anyhow::Result<()> {
+        for settings in ["source-style = \"lines\"", "source-style = \"url\""] {
+            let (ctx, _tmp) = create_render_context(&[
+                (
+                    "book.toml",
+                    &format!("[book]\n[output.xgettext]\n{settings}"),
+                ),
+                ("src/SUMMARY.md", ""),
+            ])?;
+            assert!(create_catalog(&ctx).is_err(), "{settings}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_bad_granularity() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 granularity = -1",
+            ),
+            ("src/SUMMARY.md", ""),
+        ])?;
+
+        assert!(create_catalog(&ctx).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_creation_date() {
+        assert_eq!(format_creation_date(0), "1970-01-01 00:00+0000");
+        assert_eq!(format_creation_date(951_827_696), "2000-02-29 12:34+0000");
+        assert_eq!(format_creation_date(1_703_980_800), "2023-12-31 00:00+0000");
+    }
+
+    #[test]
+    fn test_creation_date() -> anyhow::Result<()> {
+        let (ctx, _tmp) =
+            create_render_context(&[("book.toml", "[book]"), ("src/SUMMARY.md", "")])?;
+        assert_eq!(creation_date(&ctx, None)?, "");
+        assert_eq!(
+            creation_date(&ctx, Some("1703980800"))?,
+            "2023-12-31 00:00+0000"
+        );
+        assert!(creation_date(&ctx, Some("yesterday")).is_err());
+
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 omit-creation-date = true",
+            ),
+            ("src/SUMMARY.md", ""),
+        ])?;
+        assert_eq!(creation_date(&ctx, Some("1703980800"))?, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_reproducible() -> anyhow::Result<()> {
+        let (ctx, tmp) = create_render_context(&[
+            ("book.toml", "[book]"),
+            ("src/SUMMARY.md", "- [Foo](foo.md)\n- [Bar](bar.md)"),
+            ("src/foo.md", "# Foo\n\nShared text.\n\nFoo text."),
+            ("src/bar.md", "# Bar\n\nShared text.\n\nBar text."),
+        ])?;
+
+        let first = tmp.path().join("first.pot");
+        let second = tmp.path().join("second.pot");
+        polib::po_file::write(&create_catalog(&ctx)?, &first)?;
+        polib::po_file::write(&create_catalog(&ctx)?, &second)?;
+        assert_eq!(fs::read_to_string(&first)?, fs::read_to_string(&second)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_math() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 math-delimiters = [[\"$$\", \"$$\"]]",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "Sum: $$ \\sum_{n=1}^{\\infty} $$"),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| msg.msgid())
+                .collect::<Vec<&str>>(),
+            &["Foo", "Sum: $$ \\sum_{n=1}^{\\infty} $$"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_placeholders() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n[output.xgettext]\nplaceholders = true",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "Run `cargo` from [the docs](https://x.org)."),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        let message = catalog.find_message(None, "Run {1} from {2}the docs{/2}.", None);
+        assert_eq!(
+            message.map(|msg| msg.comments()),
+            Some("{1}: `cargo`\n{2}...{/2}: link to https://x.org")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_code_block_no_wrap() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            ("book.toml", "[book]"),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            (
+                "src/foo.md",
+                "Text\n\n```rust\nfn main() {\n    // Hi\n}\n```",
+            ),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| (msg.msgid(), msg.flags().to_string()))
+                .collect::<Vec<_>>(),
+            &[
+                ("Foo", String::new()),
+                ("Text", String::new()),
+                (
+                    "```rust\nfn main() {\n    // Hi\n}\n```",
+                    String::from("no-wrap")
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_include() {
+        assert_eq!(parse_include("{{#include foo.md}}"), Some(("foo.md", None)));
+        assert_eq!(
+            parse_include("{{ #include ../foo.md:2:5 }}"),
+            Some(("../foo.md", Some("2:5")))
+        );
+        assert_eq!(
+            parse_include("{{#include foo.md:anchor}}"),
+            Some(("foo.md", Some("anchor")))
+        );
+        assert_eq!(parse_include("{{#include}}"), None);
+        assert_eq!(parse_include("{{#include_str foo.md}}"), None);
+        assert_eq!(parse_include("See {{#include foo.md}}"), None);
+    }
+
+    #[test]
+    fn test_select_lines() {
+        let content = "one\ntwo\nthree\nfour";
+        assert_eq!(select_lines(content, None), (String::from(content), 0));
+        assert_eq!(select_lines(content, Some("2")), (String::from("two"), 1));
+        assert_eq!(
+            select_lines(content, Some("2:3")),
+            (String::from("two\nthree"), 1)
+        );
+        assert_eq!(
+            select_lines(content, Some("3:")),
+            (String::from("three\nfour"), 2)
+        );
+        assert_eq!(
+            select_lines("a\n// ANCHOR: x\nb\n// ANCHOR_END: x\nc", Some("x")),
+            (String::from("b"), 2)
+        );
+    }
+
+    #[test]
+    fn test_create_catalog_includes_ignored() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            ("book.toml", "[book]"),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "{{#include snippet.md}}"),
+            ("src/snippet.md", "Snippet text."),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| msg.msgid())
+                .collect::<Vec<&str>>(),
+            &["Foo", "{{#include snippet.md}}"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_bool() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 resolve-includes = true\n\
+                 theme-strings = \"yes\"",
+            ),
+            ("src/SUMMARY.md", ""),
+        ])?;
+        assert!(get_bool(&ctx, "resolve-includes")?);
+        assert!(!get_bool(&ctx, "extract-404")?);
+        assert!(get_bool(&ctx, "theme-strings").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_resolve_includes() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 resolve-includes = true",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo/foo.md)"),
+            (
+                "src/foo/foo.md",
+                "Before.\n\n{{#include ../snippet.md:3:}}\n\nAfter.",
+            ),
+            (
+                "src/snippet.md",
+                "Skipped.\n\
+                 \n\
+                 First snippet paragraph.\n\
+                 \n\
+                 {{#include nested.md}}",
+            ),
+            ("src/nested.md", "Nested paragraph."),
+        ])?;
+
+        let catalog = create_catalog(&ctx)?;
+        assert_eq!(
+            catalog
+                .messages()
+                .map(|msg| (msg.source(), msg.msgid()))
+                .collect::<Vec<_>>(),
+            &[
+                ("src/SUMMARY.md:1", "Foo"),
+                ("src/foo/foo.md:1", "Before."),
+                ("src/snippet.md:3", "First snippet paragraph."),
+                ("src/nested.md:1", "Nested paragraph."),
+                ("src/foo/foo.md:5", "After."),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_catalog_resolve_includes_missing_file() -> anyhow::Result<()> {
+        let (ctx, _tmp) = create_render_context(&[
+            (
+                "book.toml",
+                "[book]\n\
+                 [output.xgettext]\n\
+                 resolve-includes = true",
+            ),
+            ("src/SUMMARY.md", "- [Foo](foo.md)"),
+            ("src/foo.md", "{{#include missing.md}}"),
+        ])?;
+
+        assert!(create_catalog(&ctx).is_err());
+        Ok(())
+    }
 }
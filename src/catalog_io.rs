@@ -0,0 +1,422 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read and write PO files in the format required by other tools.
+//!
+//! PO files are written as UTF-8 with LF line endings by default.
+//! Some tools need another encoding, such as ISO-8859-1, CRLF line
+//! endings, or extra header fields. These are set with
+//! [`WriteOptions`], which `mdbook-xgettext` and the commands which
+//! rewrite PO files read from the `output.xgettext` table:
+//!
+//! ```toml
+//! [output.xgettext]
+//! encoding = "ISO-8859-1"
+//! line-endings = "crlf"
+//! header-fields = { "Plural-Forms" = "nplurals=2; plural=(n != 1);" }
+//! ```
+//!
+//! PO files in ISO-8859-1 are read with [`read_catalog`] and
+//! [`read_to_string`], which decode them according to the `charset`
+//! in their `Content-Type` header field.
+
+use anyhow::{anyhow, bail, Context};
+use polib::catalog::Catalog;
+use polib::po_file;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// The character encoding of a PO file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8, which can encode all characters.
+    #[default]
+    Utf8,
+    /// ISO-8859-1, also known as Latin-1, for Western European
+    /// languages.
+    Latin1,
+}
+
+impl Encoding {
+    /// Parse an encoding name, such as `UTF-8` or `ISO-8859-1`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "iso-8859-1" | "iso8859-1" | "latin1" | "latin-1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    /// The name of the encoding for the `Content-Type` header field.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "ISO-8859-1",
+        }
+    }
+}
+
+/// The line endings of a PO file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`, as used on Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, as used on Windows.
+    Crlf,
+}
+
+/// How PO files are written.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// The character encoding.
+    pub encoding: Encoding,
+    /// The line endings.
+    pub line_ending: LineEnding,
+    /// Header fields to set, such as `Plural-Forms`. Existing fields
+    /// are replaced.
+    pub header_fields: Vec<(String, String)>,
+}
+
+impl WriteOptions {
+    /// Read the options from the `output.xgettext` table.
+    ///
+    /// The `encoding` key is an encoding name, the `line-endings` key
+    /// is `"lf"` or `"crlf"`, and `header-fields` is a table of header
+    /// fields.
+    pub fn from_book_config(config: &mdbook::Config) -> anyhow::Result<Self> {
+        let mut options = WriteOptions::default();
+        if let Some(value) = config.get("output.xgettext.encoding") {
+            let name = value
+                .as_str()
+                .ok_or_else(|| anyhow!("Expected a string for output.xgettext.encoding"))?;
+            options.encoding = Encoding::from_name(name).ok_or_else(|| {
+                anyhow!(
+                    "Unknown output.xgettext.encoding {name:?}, \
+                     expected \"UTF-8\" or \"ISO-8859-1\""
+                )
+            })?;
+        }
+        if let Some(value) = config.get("output.xgettext.line-endings") {
+            options.line_ending = match value.as_str() {
+                Some("lf") => LineEnding::Lf,
+                Some("crlf") => LineEnding::Crlf,
+                _ => bail!("Expected \"lf\" or \"crlf\" for output.xgettext.line-endings"),
+            };
+        }
+        if let Some(value) = config.get("output.xgettext.header-fields") {
+            let table = value
+                .as_table()
+                .ok_or_else(|| anyhow!("Expected a table for output.xgettext.header-fields"))?;
+            for (name, value) in table {
+                let value = value.as_str().ok_or_else(|| {
+                    anyhow!("Expected a string for output.xgettext.header-fields.{name}")
+                })?;
+                options
+                    .header_fields
+                    .push((String::from(name), String::from(value)));
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Escape `text` for use in a quoted PO string.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::catalog_io::escape;
+///
+/// assert_eq!(escape("Say \"hi\"\n"), "Say \\\"hi\\\"\\n");
+/// ```
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+}
+
+/// Undo the escaping of a quoted PO string.
+pub fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Find the header field `name` in the PO file `content`.
+///
+/// The header field can be wrapped over several lines, as done by
+/// `msgmerge` for long lines.
+pub fn header_field(content: &str, name: &str) -> Option<String> {
+    let mut header = String::new();
+    let lines = content.lines().skip_while(|line| line.trim().is_empty());
+    for line in lines.take_while(|line| !line.trim().is_empty()) {
+        if let Some(line) = line
+            .trim()
+            .strip_prefix('"')
+            .and_then(|l| l.strip_suffix('"'))
+        {
+            header.push_str(&unescape(line));
+        }
+    }
+    header
+        .lines()
+        .find_map(|field| field.strip_prefix(name)?.strip_prefix(": "))
+        .map(String::from)
+}
+
+/// Set the header field `name` in the PO file `content` to `value`.
+///
+/// An existing field is replaced, otherwise the field is added at the
+/// end of the header.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::catalog_io::{header_field, set_header_field};
+///
+/// let content = "msgid \"\"\nmsgstr \"\"\n\"Language: da\\n\"\n\nmsgid \"Foo\"\nmsgstr \"\"\n";
+/// let content = set_header_field(content, "X-Generator", "mdbook");
+/// assert_eq!(header_field(&content, "X-Generator").as_deref(), Some("mdbook"));
+/// ```
+pub fn set_header_field(content: &str, name: &str, value: &str) -> String {
+    let prefix = format!("\"{name}: ");
+    let field = format!("{prefix}{}\\n\"\n", escape(value));
+    let mut result = String::with_capacity(content.len() + field.len());
+    let mut pending = Some(field);
+    let mut seen_header = false;
+    let mut in_field = false;
+    for line in content.split_inclusive('\n') {
+        if line.starts_with(&prefix) {
+            result.push_str(&pending.take().unwrap_or_default());
+            in_field = true;
+        }
+        if in_field {
+            // Skip the old field, including its continuation lines.
+            in_field = !line.trim_end().ends_with("\\n\"");
+            continue;
+        }
+        if line.starts_with("msgstr \"\"") {
+            seen_header = true;
+        }
+        if seen_header && line.trim().is_empty() {
+            if let Some(field) = pending.take() {
+                result.push_str(&field);
+            }
+        }
+        result.push_str(line);
+    }
+    if let Some(field) = pending.filter(|_| seen_header) {
+        result.push_str(&field);
+    }
+    result
+}
+
+/// Convert the PO file `content` to the format given by `options`.
+///
+/// The `charset` in the `Content-Type` header field is changed to the
+/// encoding. It is an error if `content` has characters which cannot
+/// be encoded.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::catalog_io::{convert, Encoding, LineEnding, WriteOptions};
+///
+/// let options = WriteOptions {
+///     encoding: Encoding::Latin1,
+///     line_ending: LineEnding::Crlf,
+///     ..WriteOptions::default()
+/// };
+/// let content = "msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n\n\
+///                msgid \"Hello\"\nmsgstr \"Grüß Gott\"\n";
+/// assert_eq!(
+///     convert(content, &options).unwrap(),
+///     b"msgid \"\"\r\nmsgstr \"\"\r\n\"Content-Type: text/plain; charset=ISO-8859-1\\n\"\r\n\r\n\
+///       msgid \"Hello\"\r\nmsgstr \"Gr\xfc\xdf Gott\"\r\n"
+/// );
+/// ```
+pub fn convert(content: &str, options: &WriteOptions) -> anyhow::Result<Vec<u8>> {
+    let mut content = content.to_owned();
+    for (name, value) in &options.header_fields {
+        content = set_header_field(&content, name, value);
+    }
+    if let Some(content_type) = header_field(&content, "Content-Type") {
+        if let Some((mime_type, _)) = content_type.split_once("charset=") {
+            let content_type = format!("{mime_type}charset={}", options.encoding.name());
+            content = set_header_field(&content, "Content-Type", &content_type);
+        }
+    }
+    if options.line_ending == LineEnding::Crlf {
+        content = content.replace("\r\n", "\n").replace('\n', "\r\n");
+    }
+    match options.encoding {
+        Encoding::Utf8 => Ok(content.into_bytes()),
+        Encoding::Latin1 => content
+            .chars()
+            .map(|c| {
+                u8::try_from(u32::from(c))
+                    .map_err(|_| anyhow!("Cannot encode {c:?} in {}", options.encoding.name()))
+            })
+            .collect(),
+    }
+}
+
+/// Convert the PO file at `path` to the format given by `options`.
+///
+/// Use this after writing a PO file with `polib` and the other
+/// functions in this crate, which write UTF-8 with LF line endings.
+pub fn convert_file(path: &Path, options: &WriteOptions) -> anyhow::Result<()> {
+    if *options == WriteOptions::default() {
+        return Ok(());
+    }
+    let content = read_to_string(path)?;
+    let converted =
+        convert(&content, options).with_context(|| format!("Could not convert {:?}", path))?;
+    fs::write(path, converted).with_context(|| format!("Could not write {:?}", path))
+}
+
+/// Write `catalog` to `path` in the format given by `options`.
+pub fn write_catalog(catalog: &Catalog, path: &Path, options: &WriteOptions) -> anyhow::Result<()> {
+    po_file::write(catalog, path).with_context(|| format!("Could not write {:?}", path))?;
+    convert_file(path, options)
+}
+
+/// Decode the PO file `bytes` according to the `charset` in its
+/// `Content-Type` header field.
+fn decode(bytes: Vec<u8>) -> anyhow::Result<String> {
+    let bytes = match String::from_utf8(bytes) {
+        Ok(content) => return Ok(content),
+        Err(err) => err.into_bytes(),
+    };
+    // The header is ASCII, so we can look for the charset first.
+    let latin1 = bytes.iter().map(|&b| char::from(b)).collect::<String>();
+    let charset = header_field(&latin1, "Content-Type")
+        .and_then(|content_type| Some(String::from(content_type.split_once("charset=")?.1)));
+    match charset.as_deref().and_then(Encoding::from_name) {
+        Some(Encoding::Latin1) => Ok(latin1),
+        _ => bail!(
+            "Could not decode the file as UTF-8 or with the charset {:?}",
+            charset.unwrap_or_default()
+        ),
+    }
+}
+
+/// Read the PO file at `path` into a string.
+///
+/// Files in ISO-8859-1 are decoded according to their header.
+pub fn read_to_string(path: &Path) -> anyhow::Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("Could not read {:?}", path))?;
+    decode(bytes).with_context(|| format!("Could not read {:?}", path))
+}
+
+/// Parse the PO file at `path`.
+///
+/// Files in ISO-8859-1 are decoded according to their header, the
+/// `Content-Type` header field of the catalog then still says
+/// ISO-8859-1.
+pub fn read_catalog(path: &Path) -> anyhow::Result<Catalog> {
+    let bytes = fs::read(path).with_context(|| format!("Could not read {:?}", path))?;
+    if std::str::from_utf8(&bytes).is_ok() {
+        return po_file::parse(path)
+            .map_err(|err| anyhow!("{err}"))
+            .with_context(|| format!("Could not parse {:?} as PO file", path));
+    }
+    // `polib` only parses UTF-8 files, so the decoded file is parsed
+    // from a temporary copy.
+    let content = decode(bytes).with_context(|| format!("Could not read {:?}", path))?;
+    let mut tmp_file = tempfile::Builder::new()
+        .suffix(".po")
+        .tempfile()
+        .context("Could not create a temporary file")?;
+    tmp_file
+        .write_all(content.as_bytes())
+        .with_context(|| format!("Could not write {:?}", tmp_file.path()))?;
+    po_file::parse(tmp_file.path())
+        .map_err(|err| anyhow!("{err}"))
+        .with_context(|| format!("Could not parse {:?} as PO file", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_write_and_read_latin1() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let path = tmpdir.path().join("de.po");
+        let mut metadata = CatalogMetadata::new();
+        metadata.content_type = String::from("text/plain; charset=UTF-8");
+        let mut catalog = Catalog::new(metadata);
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_msgid(String::from("Greeting"))
+                .with_msgstr(String::from("Grüß Gott"))
+                .done(),
+        );
+        let options = WriteOptions {
+            encoding: Encoding::Latin1,
+            line_ending: LineEnding::Crlf,
+            header_fields: vec![(String::from("X-Tool"), String::from("legacy"))],
+        };
+        write_catalog(&catalog, &path, &options)?;
+
+        let bytes = fs::read(&path)?;
+        assert!(bytes.windows(2).any(|w| w == b"\xfc\xdf"));
+        assert!(!bytes.windows(2).any(|w| w[0] != b'\r' && w[1] == b'\n'));
+        let content = read_to_string(&path)?;
+        assert_eq!(header_field(&content, "X-Tool").as_deref(), Some("legacy"));
+        assert_eq!(
+            header_field(&content, "Content-Type").as_deref(),
+            Some("text/plain; charset=ISO-8859-1")
+        );
+        let catalog = read_catalog(&path)?;
+        assert_eq!(
+            catalog
+                .find_message(None, "Greeting", None)
+                .unwrap()
+                .msgstr()?,
+            "Grüß Gott"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_unencodable() {
+        let options = WriteOptions {
+            encoding: Encoding::Latin1,
+            ..WriteOptions::default()
+        };
+        assert!(convert("msgid \"Hi\"\nmsgstr \"你好\"\n", &options).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The translation status of chapters for later preprocessors.
+//!
+//! Preprocessors cannot change `book.toml`, so `mdbook-gettext` passes
+//! the language and the coverage of each chapter on in the chapter
+//! itself: with `preprocessor.gettext.chapter-status` enabled, each
+//! translated chapter starts with an HTML comment such as
+//!
+//! ```html
+//! <!-- mdbook-i18n-helpers-status: {"language":"da","catalog":"po/da.po","translated":12,"total":15} -->
+//! ```
+//!
+//! The comment is invisible in the rendered HTML. Preprocessors and
+//! templates which run later can read it, see
+//! [`ChapterStatus::from_content`].
+
+use serde::{Deserialize, Serialize};
+
+/// The start of the status comment.
+const STATUS_PREFIX: &str = "<!-- mdbook-i18n-helpers-status: ";
+
+/// The end of the status comment.
+const STATUS_SUFFIX: &str = " -->";
+
+/// The translation status of a chapter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChapterStatus {
+    /// The language of the book, such as `pt-BR`.
+    pub language: String,
+    /// The PO file used for the translation, relative to the book
+    /// root. This can be for a fallback language, such as `po/pt.po`.
+    pub catalog: String,
+    /// The number of messages in the chapter with a translation.
+    pub translated: usize,
+    /// The number of messages in the chapter.
+    pub total: usize,
+}
+
+impl ChapterStatus {
+    /// Format the status as an HTML comment.
+    pub fn to_comment(&self) -> String {
+        let json = serde_json::to_string(self).expect("the status can be serialized");
+        // A `--` would end the comment, so it is escaped. This is
+        // only possible in strings, where the escape is valid JSON.
+        format!(
+            "{STATUS_PREFIX}{}{STATUS_SUFFIX}",
+            json.replace("--", "-\\u002d")
+        )
+    }
+
+    /// Read the status from the first line of the chapter `content`.
+    ///
+    /// Returns `None` if the chapter has no status comment.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::chapter_status::ChapterStatus;
+    ///
+    /// let status = ChapterStatus {
+    ///     language: String::from("da"),
+    ///     catalog: String::from("po/da.po"),
+    ///     translated: 12,
+    ///     total: 15,
+    /// };
+    /// let content = status.add_to("# Hej\n");
+    /// assert_eq!(ChapterStatus::from_content(&content), Some(status));
+    /// assert_eq!(ChapterStatus::from_content("# Hej\n"), None);
+    /// ```
+    pub fn from_content(content: &str) -> Option<Self> {
+        let json = content
+            .lines()
+            .next()?
+            .strip_prefix(STATUS_PREFIX)?
+            .strip_suffix(STATUS_SUFFIX)?;
+        serde_json::from_str(json).ok()
+    }
+
+    /// Add the status comment to the start of the chapter `content`.
+    pub fn add_to(&self, content: &str) -> String {
+        format!("{}\n\n{content}", self.to_comment())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_to_comment() {
+        let status = ChapterStatus {
+            language: String::from("da"),
+            catalog: String::from("po/da--old.po"),
+            translated: 1,
+            total: 2,
+        };
+        assert_eq!(
+            status.to_comment(),
+            "<!-- mdbook-i18n-helpers-status: {\"language\":\"da\",\
+             \"catalog\":\"po/da-\\u002dold.po\",\"translated\":1,\"total\":2} -->"
+        );
+        assert_eq!(
+            ChapterStatus::from_content(&status.add_to("Text")),
+            Some(status)
+        );
+    }
+}
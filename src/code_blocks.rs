@@ -0,0 +1,810 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Split code blocks into their comments and strings.
+//!
+//! Code blocks are normally extracted as a whole, so translators see
+//! the code along with the comments they translate. With
+//! [`MarkdownConfig::code_rules`](crate::MarkdownConfig::code_rules),
+//! only the comments and string literals of a code block become
+//! messages. What counts as a comment or a string depends on the
+//! language of the code block, which is given by a [`CodeRule`] for
+//! each fence token, such as `rust` in ```` ```rust ````.
+//!
+//! The default rules are:
+//!
+//! | Fence tokens                                         | Comments  | Strings    |
+//! |------------------------------------------------------|-----------|------------|
+//! | `rust`, `c`, `cpp`, `go`, `java`, `kotlin`, `swift`  | `//`      | `"`        |
+//! | `javascript`, `js`, `typescript`, `ts`               | `//`      | `"`, `'`   |
+//! | `python`, `py`, `ruby`, `sh`, `bash`, `shell`        | `#`       | `"`, `'`   |
+//! | `toml`                                               | `#`       | `"`        |
+//! | `yaml`, `yml`                                        | `#`       |            |
+//! | `sql`                                                | `--`      | `'`        |
+//! | `haskell`, `lua`                                     | `--`      | `"`        |
+//!
+//! Comments run to the end of the line. Strings end at the next
+//! delimiter on the same line which isn't escaped with a backslash.
+//! Code blocks in other languages are extracted as a whole. When
+//! translating, see [`translate_code`], comments at the end of a line
+//! stay in their column.
+//!
+//! Code blocks which are extracted as a whole contain the comment
+//! markers, and translators sometimes delete them by mistake. See
+//! [`missing_comment_markers`] and [`repair_comment_markers`].
+//!
+//! With
+//! [`MarkdownConfig::detect_code_languages`](crate::MarkdownConfig::detect_code_languages),
+//! the language of a code block without a fence token is guessed with
+//! [`detect_language`].
+
+use anyhow::{anyhow, Context};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// How to find the comments and strings in the code of a language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeRule {
+    /// The markers which start a comment running to the end of the
+    /// line, such as `//`.
+    pub comments: Vec<String>,
+    /// The delimiters of string literals, such as `"`.
+    pub strings: Vec<String>,
+}
+
+/// The default rules: fence tokens, comment markers, and string
+/// delimiters.
+const DEFAULT_RULES: &[(&[&str], &[&str], &[&str])] = &[
+    (
+        &["rust", "c", "cpp", "go", "java", "kotlin", "swift"],
+        &["//"],
+        &["\""],
+    ),
+    (
+        &["javascript", "js", "typescript", "ts"],
+        &["//"],
+        &["\"", "'"],
+    ),
+    (
+        &["python", "py", "ruby", "sh", "bash", "shell"],
+        &["#"],
+        &["\"", "'"],
+    ),
+    (&["toml"], &["#"], &["\""]),
+    (&["yaml", "yml"], &["#"], &[]),
+    (&["sql"], &["--"], &["'"]),
+    (&["haskell", "lua"], &["--"], &["\""]),
+];
+
+/// The default rules for each fence token.
+pub fn default_rules() -> BTreeMap<String, CodeRule> {
+    let mut rules = BTreeMap::new();
+    for (tokens, comments, strings) in DEFAULT_RULES {
+        for token in *tokens {
+            rules.insert(
+                String::from(*token),
+                CodeRule {
+                    comments: comments
+                        .iter()
+                        .map(|marker| String::from(*marker))
+                        .collect(),
+                    strings: strings.iter().map(|delim| String::from(*delim)).collect(),
+                },
+            );
+        }
+    }
+    rules
+}
+
+/// Parse a list of non-empty strings.
+fn parse_markers(value: Option<&toml::Value>) -> anyhow::Result<Vec<String>> {
+    let Some(value) = value else {
+        return Ok(Vec::new());
+    };
+    let values = value
+        .as_array()
+        .ok_or_else(|| anyhow!("Expected a list of strings, got {value}"))?;
+    values
+        .iter()
+        .map(|value| match value.as_str() {
+            Some(marker) if !marker.is_empty() => Ok(String::from(marker)),
+            _ => Err(anyhow!("Expected a non-empty string, got {value}")),
+        })
+        .collect()
+}
+
+/// Parse a table of rules, such as
+/// `{ sql = { comments = ["--"], strings = ["'"] } }`.
+///
+/// The rules are added to the default rules, replacing the default
+/// rule of the same fence token.
+pub fn parse_rules(value: &toml::Value) -> anyhow::Result<BTreeMap<String, CodeRule>> {
+    let table = value
+        .as_table()
+        .ok_or_else(|| anyhow!("Expected a table of fence tokens"))?;
+    let mut rules = default_rules();
+    for (token, rule) in table {
+        let rule = rule
+            .as_table()
+            .ok_or_else(|| anyhow!("Expected a table for {token:?}, got {rule}"))?;
+        let comments = parse_markers(rule.get("comments"))
+            .with_context(|| format!("Could not parse the comments of {token:?}"))?;
+        let strings = parse_markers(rule.get("strings"))
+            .with_context(|| format!("Could not parse the strings of {token:?}"))?;
+        rules.insert(token.clone(), CodeRule { comments, strings });
+    }
+    Ok(rules)
+}
+
+/// Interpreters named in shebang lines and their fence tokens.
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "python"),
+    ("bash", "bash"),
+    ("sh", "sh"),
+    ("zsh", "sh"),
+    ("ruby", "ruby"),
+    ("node", "js"),
+    ("lua", "lua"),
+];
+
+/// Words which start lines of code in a language, and the fence token
+/// of the language.
+///
+/// A word can be listed for several languages, such as `import` for
+/// Python and JavaScript.
+const KEYWORDS: &[(&str, &[&str])] = &[
+    (
+        "python",
+        &["def", "import", "from", "elif", "class", "print", "with"],
+    ),
+    (
+        "sh",
+        &[
+            "echo", "export", "fi", "then", "do", "done", "cd", "sudo", "source",
+        ],
+    ),
+    (
+        "rust",
+        &[
+            "fn", "let", "use", "impl", "pub", "struct", "enum", "mod", "match", "println",
+        ],
+    ),
+    (
+        "js",
+        &[
+            "function", "const", "let", "var", "console", "import", "export",
+        ],
+    ),
+    (
+        "sql",
+        &[
+            "SELECT", "INSERT", "UPDATE", "DELETE", "CREATE", "FROM", "WHERE",
+        ],
+    ),
+];
+
+/// Find the fence token of the interpreter in a shebang line, such as
+/// `#!/usr/bin/env python3`.
+fn shebang_language(line: &str) -> Option<&'static str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    // Versioned interpreters, such as `python3.12`, count as well.
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETERS
+        .iter()
+        .find(|(name, _)| *name == program)
+        .map(|(_, token)| *token)
+}
+
+/// Guess the language of `code`, returning its fence token.
+///
+/// A shebang line on the first line decides the language. Otherwise,
+/// the first word of each line is looked up in a small table of
+/// keywords, and the language with the most matching lines wins. Lines
+/// starting with a `$` prompt count for shell. At least two lines must
+/// match, and ties are not broken, so code which doesn't look like any
+/// of the languages gives `None`.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::code_blocks::detect_language;
+///
+/// assert_eq!(detect_language("#!/usr/bin/env python3\nprint(1)\n"), Some("python"));
+/// assert_eq!(detect_language("fn main() {\n    let x = 1;\n}\n"), Some("rust"));
+/// assert_eq!(detect_language("Hello world\n"), None);
+/// ```
+pub fn detect_language(code: &str) -> Option<&'static str> {
+    if let Some(token) = code.lines().next().and_then(shebang_language) {
+        return Some(token);
+    }
+    let mut scores = vec![0; KEYWORDS.len()];
+    for line in code.lines() {
+        let line = line.trim_start();
+        if line.starts_with("$ ") {
+            if let Some(idx) = KEYWORDS.iter().position(|(token, _)| *token == "sh") {
+                scores[idx] += 1;
+            }
+            continue;
+        }
+        let word = line
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .next()
+            .unwrap_or_default();
+        if word.is_empty() {
+            continue;
+        }
+        for (idx, (_, keywords)) in KEYWORDS.iter().enumerate() {
+            if keywords.contains(&word) {
+                scores[idx] += 1;
+            }
+        }
+    }
+    let best = *scores.iter().max()?;
+    if best < 2 || scores.iter().filter(|score| **score == best).count() > 1 {
+        return None;
+    }
+    let idx = scores.iter().position(|score| *score == best)?;
+    Some(KEYWORDS[idx].0)
+}
+
+/// Check if `text` is worth translating.
+fn is_translatable(text: &str) -> bool {
+    text.chars().any(char::is_alphabetic)
+}
+
+/// Find the end of a string which starts at the beginning of `text`
+/// and ends with `delim`.
+fn string_end(text: &str, delim: &str) -> Option<usize> {
+    let mut chars = text.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if text[idx..].starts_with(delim) {
+            return Some(idx);
+        }
+    }
+    None
+}
+
+/// A comment or string in a code block.
+struct Segment {
+    /// The text of the comment or string.
+    range: Range<usize>,
+    /// The start of the comment marker, for comments.
+    marker: Option<usize>,
+}
+
+/// Find the byte ranges of the comments and strings in `code` which
+/// should be translated.
+///
+/// The ranges don't include the comment markers, the string
+/// delimiters, or surrounding whitespace of comments. Comments and
+/// strings without letters are left out, and so is a shebang line,
+/// such as `#!/bin/sh`, on the first line.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::code_blocks::{code_segments, default_rules};
+///
+/// let rules = default_rules();
+/// let code = "let x = \"Hello\"; // Greet the user\n";
+/// let segments = code_segments(code, &rules["rust"]);
+/// let texts = segments.iter().map(|range| &code[range.clone()]).collect::<Vec<_>>();
+/// assert_eq!(texts, vec!["Hello", "Greet the user"]);
+/// ```
+pub fn code_segments(code: &str, rule: &CodeRule) -> Vec<Range<usize>> {
+    segments(code, rule)
+        .into_iter()
+        .map(|segment| segment.range)
+        .collect()
+}
+
+/// Find the comments and strings in `code`, see [`code_segments`].
+fn segments(code: &str, rule: &CodeRule) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut line_start = 0;
+    for line in code.split_inclusive('\n') {
+        if line_start == 0 && line.starts_with("#!") {
+            line_start += line.len();
+            continue;
+        }
+        let mut chars = line.char_indices();
+        while let Some((idx, _)) = chars.next() {
+            let rest = &line[idx..];
+            if let Some(marker) = rule
+                .comments
+                .iter()
+                .find(|marker| rest.starts_with(*marker))
+            {
+                // Skip doc comment markers such as `///` and `//!`.
+                let after = rest[marker.len()..]
+                    .trim_start_matches(|c: char| marker.contains(c) || c == '!')
+                    .trim_start();
+                let text = after.trim_end();
+                if is_translatable(text) {
+                    let start = line_start + line.len() - after.len();
+                    segments.push(Segment {
+                        range: start..start + text.len(),
+                        marker: Some(line_start + idx),
+                    });
+                }
+                break;
+            }
+            if let Some(delim) = rule.strings.iter().find(|delim| rest.starts_with(*delim)) {
+                let inner = &rest[delim.len()..];
+                let Some(end) = string_end(inner, delim) else {
+                    break;
+                };
+                if is_translatable(&inner[..end]) {
+                    let start = line_start + idx + delim.len();
+                    segments.push(Segment {
+                        range: start..start + end,
+                        marker: None,
+                    });
+                }
+                // Continue after the closing delimiter.
+                let next = idx + delim.len() + end + delim.len();
+                while chars.as_str().len() > line.len() - next {
+                    chars.next();
+                }
+            }
+        }
+        line_start += line.len();
+    }
+    segments
+}
+
+/// Check if `msgstr` can replace the code at `range` in `code`.
+///
+/// The translation must stay on one line, and the translation of a
+/// string must not end the string early.
+pub fn is_valid_translation(
+    code: &str,
+    range: &Range<usize>,
+    msgstr: &str,
+    rule: &CodeRule,
+) -> bool {
+    if msgstr.contains(['\n', '\r']) {
+        return false;
+    }
+    let before = &code[..range.start];
+    match rule.strings.iter().find(|delim| before.ends_with(*delim)) {
+        Some(delim) => string_end(msgstr, delim).is_none(),
+        None => true,
+    }
+}
+
+/// The column of the end of `text`, counted in characters from the
+/// start of its last line.
+fn column(text: &str) -> usize {
+    let line_start = text.rfind('\n').map_or(0, |idx| idx + 1);
+    text[line_start..].chars().count()
+}
+
+/// Translate the comments and strings of `code` with `translate`.
+///
+/// Translations which are not valid, see [`is_valid_translation`], are
+/// not used. When translated strings change the length of a line, a
+/// comment after them is moved back to its column by changing the
+/// spaces before it, keeping at least one space. This keeps comments
+/// on neighboring lines aligned. Comments which are not preceded by
+/// spaces are left where they are.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::code_blocks::{default_rules, translate_code};
+///
+/// let rules = default_rules();
+/// let code = "greet(\"Hi\");    // Say hello\n\
+///             greet(\"Bye\");   // Say goodbye\n";
+/// let translated = translate_code(code, &rules["rust"], |msgid| match msgid {
+///     "Hi" => Some("Hallo"),
+///     "Say hello" => Some("Sag hallo"),
+///     _ => None,
+/// });
+/// assert_eq!(
+///     translated,
+///     "greet(\"Hallo\"); // Sag hallo\n\
+///      greet(\"Bye\");   // Say goodbye\n"
+/// );
+/// ```
+pub fn translate_code<'a>(
+    code: &str,
+    rule: &CodeRule,
+    mut translate: impl FnMut(&str) -> Option<&'a str>,
+) -> String {
+    let mut translated = String::with_capacity(code.len());
+    let mut last = 0;
+    for Segment { range, marker } in segments(code, rule) {
+        if let Some(marker) = marker {
+            let before = &code[last..marker];
+            let padding = &before[before.trim_end_matches(' ').len()..];
+            let is_padded = !padding.is_empty() && padding.len() < before.len();
+            translated.push_str(before);
+            let target = column(&code[..marker]);
+            if is_padded && column(&translated) != target {
+                translated.truncate(translated.trim_end_matches(' ').len());
+                let width = target.saturating_sub(column(&translated)).max(1);
+                translated.push_str(&" ".repeat(width));
+            }
+            last = marker;
+        }
+        translated.push_str(&code[last..range.start]);
+        match translate(&code[range.clone()]) {
+            Some(msgstr) if is_valid_translation(code, &range, msgstr, rule) => {
+                translated.push_str(msgstr);
+            }
+            _ => translated.push_str(&code[range.clone()]),
+        }
+        last = range.end;
+    }
+    translated.push_str(&code[last..]);
+    translated
+}
+
+/// Split a message with a fenced code block into its fence token and
+/// the lines of code.
+fn fenced_code(message: &str) -> Option<(&str, Vec<&str>)> {
+    let lines = message.split('\n').collect::<Vec<_>>();
+    let [first, code @ .., last] = lines.as_slice() else {
+        return None;
+    };
+    let fence_char = first.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let info = first.trim_start_matches(fence_char);
+    let fence = &first[..first.len() - info.len()];
+    if fence.len() < 3 || !last.trim().starts_with(fence) {
+        return None;
+    }
+    let token = info.trim().split([' ', ',']).next().unwrap_or_default();
+    Some((token, code.to_vec()))
+}
+
+/// The comment marker and indentation which start `line`, such as
+/// `    /// ` for a doc comment.
+fn comment_prefix<'l>(line: &'l str, rule: &CodeRule) -> Option<&'l str> {
+    let text = line.trim_start();
+    let marker = rule
+        .comments
+        .iter()
+        .find(|marker| text.starts_with(marker.as_str()))?;
+    let after = text[marker.len()..]
+        .trim_start_matches(|c: char| marker.contains(c) || c == '!')
+        .trim_start();
+    Some(&line[..line.len() - after.len()])
+}
+
+/// Find the comment lines of the code block in `msgid` whose
+/// translation in `msgstr` lost its comment marker.
+///
+/// Both messages must be fenced code blocks with the same number of
+/// lines, so the lines can be compared in order, and there must be a
+/// rule for the fence token. Only comments on their own line are
+/// checked. Returns the lines of the code, counted from 1, paired
+/// with the repaired line.
+fn marker_repairs(
+    msgid: &str,
+    msgstr: &str,
+    rules: &BTreeMap<String, CodeRule>,
+) -> Vec<(usize, String)> {
+    let (Some((token, source_lines)), Some((_, translated_lines))) =
+        (fenced_code(msgid), fenced_code(msgstr))
+    else {
+        return Vec::new();
+    };
+    let Some(rule) = rules.get(token) else {
+        return Vec::new();
+    };
+    if source_lines.len() != translated_lines.len() {
+        return Vec::new();
+    }
+    let mut repairs = Vec::new();
+    for (idx, (source, translated)) in source_lines.iter().zip(&translated_lines).enumerate() {
+        let Some(prefix) = comment_prefix(source, rule) else {
+            continue;
+        };
+        let text = translated.trim_start();
+        if text.is_empty() || comment_prefix(translated, rule).is_some() {
+            continue;
+        }
+        repairs.push((idx + 1, format!("{prefix}{text}")));
+    }
+    repairs
+}
+
+/// Find the comment lines of the code block in `msgid` whose
+/// translation in `msgstr` lost its comment marker.
+///
+/// The code blocks are compared line by line, so they must have the
+/// same number of lines. Returns the lines of the code, counted from
+/// 1, without the fences.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::code_blocks::{default_rules, missing_comment_markers};
+///
+/// let msgid = "```rust\n// Add one\nx += 1;\n```";
+/// let msgstr = "```rust\nLæg en til\nx += 1;\n```";
+/// assert_eq!(missing_comment_markers(msgid, msgstr, &default_rules()), vec![1]);
+/// ```
+pub fn missing_comment_markers(
+    msgid: &str,
+    msgstr: &str,
+    rules: &BTreeMap<String, CodeRule>,
+) -> Vec<usize> {
+    marker_repairs(msgid, msgstr, rules)
+        .into_iter()
+        .map(|(lineno, _)| lineno)
+        .collect()
+}
+
+/// Put back the comment markers which the translation `msgstr` of the
+/// code block in `msgid` lost, see [`missing_comment_markers`].
+///
+/// The indentation and marker are copied from the line in `msgid`.
+/// Returns `None` if nothing needs to be repaired.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::code_blocks::{default_rules, repair_comment_markers};
+///
+/// let msgid = "```rust\nfn f() {\n    /// Add one\n}\n```";
+/// let msgstr = "```rust\nfn f() {\nLæg en til\n}\n```";
+/// assert_eq!(
+///     repair_comment_markers(msgid, msgstr, &default_rules()).as_deref(),
+///     Some("```rust\nfn f() {\n    /// Læg en til\n}\n```")
+/// );
+/// ```
+pub fn repair_comment_markers(
+    msgid: &str,
+    msgstr: &str,
+    rules: &BTreeMap<String, CodeRule>,
+) -> Option<String> {
+    let repairs = marker_repairs(msgid, msgstr, rules);
+    if repairs.is_empty() {
+        return None;
+    }
+    let mut lines = msgstr.split('\n').map(String::from).collect::<Vec<_>>();
+    for (lineno, line) in repairs {
+        // The first line is the opening fence.
+        lines[lineno] = line;
+    }
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn texts<'a>(code: &'a str, rule: &CodeRule) -> Vec<&'a str> {
+        code_segments(code, rule)
+            .into_iter()
+            .map(|range| &code[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_code_segments_rust() {
+        let rules = default_rules();
+        let code = "/// Add two numbers.\n\
+                    fn add(a: i32, b: i32) -> i32 {\n    \
+                        println!(\"Adding \\\"{a}\\\" and {b}\"); // Debug output\n    \
+                        a + b // 42\n\
+                    }\n";
+        assert_eq!(
+            texts(code, &rules["rust"]),
+            vec![
+                "Add two numbers.",
+                "Adding \\\"{a}\\\" and {b}",
+                "Debug output"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_code_segments_sql_and_yaml() {
+        let rules = default_rules();
+        assert_eq!(
+            texts(
+                "SELECT 'Hello' -- Find the rows\nFROM t; -- 1 / 2\n",
+                &rules["sql"]
+            ),
+            vec!["Hello", "Find the rows"]
+        );
+        // The comment marker of YAML is not part of the URL, and there
+        // are no string rules to misfire on quotes.
+        assert_eq!(
+            texts(
+                "url: \"https://example.com\" # The homepage\nname: Bob's\n",
+                &rules["yaml"]
+            ),
+            vec!["The homepage"]
+        );
+    }
+
+    #[test]
+    fn test_code_segments_shebang() {
+        let rules = default_rules();
+        assert_eq!(
+            texts(
+                "#!/bin/sh\n# Print the date\n#!not a shebang\n",
+                &rules["sh"]
+            ),
+            vec!["Print the date", "not a shebang"]
+        );
+    }
+
+    #[test]
+    fn test_code_segments_unterminated_string() {
+        let rules = default_rules();
+        assert_eq!(
+            texts("let s = \"open // not a comment\n", &rules["rust"]),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn test_detect_language_shebang() {
+        assert_eq!(detect_language("#!/bin/bash\nls\n"), Some("bash"));
+        assert_eq!(detect_language("#!/bin/sh\n"), Some("sh"));
+        assert_eq!(
+            detect_language("#!/usr/bin/env -S python3.12 -u\n"),
+            Some("python")
+        );
+        assert_eq!(detect_language("#!/usr/bin/env node\n"), Some("js"));
+        assert_eq!(detect_language("#!/usr/bin/perl\n"), None);
+        // A shebang line only counts on the first line.
+        assert_eq!(detect_language("\n#!/bin/bash\n"), None);
+    }
+
+    #[test]
+    fn test_detect_language_keywords() {
+        assert_eq!(
+            detect_language("import os\n\ndef main():\n    print(os.getcwd())\n"),
+            Some("python")
+        );
+        assert_eq!(detect_language("$ cargo build\n$ cargo test\n"), Some("sh"));
+        assert_eq!(
+            detect_language("cd src\nexport FOO=1 # Set the flag\n"),
+            Some("sh")
+        );
+        assert_eq!(
+            detect_language("SELECT name\nFROM users\nWHERE id = 1;\n"),
+            Some("sql")
+        );
+        // One matching line is not enough.
+        assert_eq!(detect_language("let x = 1;\n"), None);
+        // `let` and `const` could be Rust or JavaScript.
+        assert_eq!(detect_language("let x = 1;\nlet y = 2;\n"), None);
+        assert_eq!(detect_language(""), None);
+    }
+
+    #[test]
+    fn test_translate_code_keeps_comment_columns() {
+        let rules = default_rules();
+        let code = "    let a = \"Hi\";   // First\n\
+                    \x20   let b = \"Hello\"; // Second\n\
+                    \x20   // Third\n\
+                    \x20   let c = \"Yo\";//Fourth\n";
+        let translated = translate_code(code, &rules["rust"], |msgid| match msgid {
+            "Hi" => Some("Hej"),
+            "Hello" => Some("Goddag allesammen"),
+            "First" => Some("Første"),
+            "Third" => Some("Tredje"),
+            "Yo" => Some("Hallo"),
+            "Fourth" => Some("Fjerde\ntil sidst"),
+            _ => None,
+        });
+        assert_eq!(
+            translated,
+            "    let a = \"Hej\";  // Første\n\
+             \x20   let b = \"Goddag allesammen\"; // Second\n\
+             \x20   // Tredje\n\
+             \x20   let c = \"Hallo\";//Fourth\n"
+        );
+    }
+
+    #[test]
+    fn test_missing_comment_markers() {
+        let rules = default_rules();
+        let msgid = "```python\n\
+                     # Print a greeting\n\
+                     print('Hi')  # inline\n\
+                     \n\
+                     # Done\n\
+                     ```";
+        let msgstr = "```python\n\
+                      Udskriv en hilsen\n\
+                      print('Hej')\n\
+                      \n\
+                      # Færdig\n\
+                      ```";
+        assert_eq!(missing_comment_markers(msgid, msgstr, &rules), vec![1]);
+        assert_eq!(
+            repair_comment_markers(msgid, msgstr, &rules).as_deref(),
+            Some("```python\n# Udskriv en hilsen\nprint('Hej')\n\n# Færdig\n```")
+        );
+        // An empty line is not a comment without a marker.
+        assert_eq!(
+            missing_comment_markers("```sh\n# Note\n```", "```sh\n\n```", &rules),
+            Vec::<usize>::new()
+        );
+        // Code blocks with a different number of lines are not compared.
+        assert_eq!(
+            missing_comment_markers(msgid, "```python\nUdskriv\n```", &rules),
+            Vec::<usize>::new()
+        );
+        // Neither are code blocks without a rule, nor other messages.
+        assert_eq!(
+            missing_comment_markers("```text\n# Note\n```", "```text\nNote\n```", &rules),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            missing_comment_markers("# Heading", "Overskrift", &rules),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let value: toml::Value = toml::from_str(
+            r##"
+            sql = { comments = ["--", "#"], strings = ["'"] }
+            nix = { comments = ["#"] }
+            "##,
+        )
+        .unwrap();
+        let rules = parse_rules(&value).unwrap();
+        assert_eq!(rules["sql"].comments, vec!["--", "#"]);
+        assert_eq!(rules["nix"].strings, Vec::<String>::new());
+        assert_eq!(rules["rust"], default_rules()["rust"]);
+
+        let value: toml::Value = toml::from_str(r#"sql = { comments = [""] }"#).unwrap();
+        assert!(parse_rules(&value).is_err());
+    }
+
+    #[test]
+    fn test_is_valid_translation() {
+        let rules = default_rules();
+        let code = "let x = \"Hello\";";
+        let range = 9..14;
+        assert!(is_valid_translation(code, &range, "Hej", &rules["rust"]));
+        assert!(is_valid_translation(
+            code,
+            &range,
+            "Sag \\\"Hej\\\"",
+            &rules["rust"]
+        ));
+        assert!(!is_valid_translation(
+            code,
+            &range,
+            "Sag \"Hej\"",
+            &rules["rust"]
+        ));
+        assert!(!is_valid_translation(
+            code,
+            &range,
+            "Hej\nverden",
+            &rules["rust"]
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Translatable strings in `book.toml`.
+//!
+//! Renderers and other preprocessors read user-facing strings from
+//! `book.toml`, such as labels and default titles. The keys listed in
+//! `output.xgettext.extra-config-keys` are extracted as messages.
+//!
+//! A preprocessor cannot change the configuration seen by the
+//! renderers. The translations are therefore passed to `mdbook` as
+//! `MDBOOK_*` environment variables, see [`env_var_name`].
+
+use anyhow::{anyhow, bail};
+use polib::catalog::Catalog;
+
+/// Read the `output.xgettext.extra-config-keys` setting.
+pub fn extra_config_keys(config: &mdbook::Config) -> anyhow::Result<Vec<String>> {
+    let Some(value) = config.get("output.xgettext.extra-config-keys") else {
+        return Ok(Vec::new());
+    };
+    let error = || anyhow!("Expected a list of strings for output.xgettext.extra-config-keys");
+    value
+        .as_array()
+        .ok_or_else(error)?
+        .iter()
+        .map(|key| key.as_str().map(String::from).ok_or_else(error))
+        .collect()
+}
+
+/// Find the values of the extra config keys in `config`.
+///
+/// Returns pairs of keys and values. Keys which are not set are left
+/// out.
+pub fn config_messages(config: &mdbook::Config) -> anyhow::Result<Vec<(String, String)>> {
+    let mut messages = Vec::new();
+    for key in extra_config_keys(config)? {
+        match config.get(&key) {
+            None => {}
+            Some(toml::Value::String(value)) => messages.push((key, value.clone())),
+            Some(_) => bail!("Expected a string for {key}, which is in extra-config-keys"),
+        }
+    }
+    Ok(messages)
+}
+
+/// Find the translations of the extra config keys in `config`.
+///
+/// Returns pairs of keys and translations. Keys without a translation
+/// in `catalog` are left out.
+pub fn translate_config(
+    config: &mdbook::Config,
+    catalog: &Catalog,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let mut translations = Vec::new();
+    for (key, value) in config_messages(config)? {
+        let msgstr = catalog
+            .find_message(None, &value, None)
+            .filter(|message| !message.is_fuzzy())
+            .and_then(|message| message.msgstr().ok())
+            .filter(|msgstr| !msgstr.is_empty());
+        if let Some(msgstr) = msgstr {
+            translations.push((key, String::from(msgstr)));
+        }
+    }
+    Ok(translations)
+}
+
+/// The environment variable which overrides `key` in `mdbook`.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::config_keys::env_var_name;
+///
+/// assert_eq!(
+///     env_var_name("output.html.edit-url-template"),
+///     "MDBOOK_OUTPUT__HTML__EDIT_URL_TEMPLATE"
+/// );
+/// ```
+pub fn env_var_name(key: &str) -> String {
+    format!(
+        "MDBOOK_{}",
+        key.to_ascii_uppercase()
+            .replace('.', "__")
+            .replace('-', "_")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    fn create_config(toml: &str) -> mdbook::Config {
+        toml.parse().unwrap()
+    }
+
+    #[test]
+    fn test_config_messages() {
+        let config = create_config(
+            "[output.xgettext]\n\
+             extra-config-keys = [\"output.html.label\", \"output.html.missing\"]\n\
+             [output.html]\n\
+             label = \"Edit this page\"\n",
+        );
+        assert_eq!(
+            config_messages(&config).unwrap(),
+            vec![(
+                String::from("output.html.label"),
+                String::from("Edit this page")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_config_messages_not_string() {
+        let config = create_config(
+            "[output.xgettext]\n\
+             extra-config-keys = [\"output.html.fold\"]\n\
+             [output.html]\n\
+             fold = true\n",
+        );
+        assert!(config_messages(&config).is_err());
+    }
+
+    #[test]
+    fn test_translate_config() {
+        let config = create_config(
+            "[output.xgettext]\n\
+             extra-config-keys = [\"output.html.label\", \"output.html.other\"]\n\
+             [output.html]\n\
+             label = \"Edit this page\"\n\
+             other = \"Untranslated\"\n",
+        );
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_msgid(String::from("Edit this page"))
+                .with_msgstr(String::from("Rediger denne side"))
+                .done(),
+        );
+        assert_eq!(
+            translate_config(&config, &catalog).unwrap(),
+            vec![(
+                String::from("output.html.label"),
+                String::from("Rediger denne side")
+            )]
+        );
+    }
+}
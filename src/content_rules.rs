@@ -0,0 +1,273 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Project specific rules for the content of translations.
+//!
+//! The rules are read from a TOML file with a `[[rules]]` table for
+//! each rule:
+//!
+//! ```toml
+//! [[rules]]
+//! pattern = "Acme Cloud"
+//! message = "Acme Cloud is now called Acme Platform"
+//!
+//! [[rules]]
+//! pattern = "Acme™"
+//! when = "Acme"
+//! require = "first-use"
+//! severity = "warning"
+//! message = "Use the trademark symbol the first time Acme is mentioned"
+//! languages = ["de", "fr"]
+//! ```
+//!
+//! By default, a translation must not match the `pattern`. With
+//! `require`, a translation must match it if the `msgid` matches
+//! `when`, either always or only for the first such message.
+
+use crate::language::same_language;
+use anyhow::Context;
+use polib::catalog::Catalog;
+use regex::Regex;
+use serde::Deserialize;
+
+/// How serious a rule violation is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// The violation is reported, but not treated as an error.
+    Warning,
+    /// The violation is an error.
+    #[default]
+    Error,
+}
+
+/// When a translation is required to match the pattern of a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Requirement {
+    /// Every matching message must have a matching translation.
+    Always,
+    /// Only the first matching message in the catalog.
+    FirstUse,
+}
+
+/// A rule as written in the rules file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    pattern: String,
+    message: String,
+    #[serde(default)]
+    severity: Severity,
+    when: Option<String>,
+    require: Option<Requirement>,
+    #[serde(default)]
+    languages: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<RawRule>,
+}
+
+/// A rule for the content of translations.
+#[derive(Debug, Clone)]
+pub struct ContentRule {
+    /// The pattern matched against the translation.
+    pub pattern: Regex,
+    /// The pattern matched against the `msgid` for required content.
+    pub when: Option<Regex>,
+    /// Whether the translation must match the pattern instead of not
+    /// matching it.
+    pub require: Option<Requirement>,
+    /// The explanation shown when the rule is violated.
+    pub message: String,
+    /// How serious a violation is.
+    pub severity: Severity,
+    /// The languages the rule applies to, all languages if empty.
+    pub languages: Vec<String>,
+}
+
+impl ContentRule {
+    /// Check if the rule applies to translations into `language`.
+    ///
+    /// A rule for `de` also applies to regional variants such as
+    /// `de-AT`.
+    pub fn applies_to(&self, language: Option<&str>) -> bool {
+        let Some(language) = language else {
+            return self.languages.is_empty();
+        };
+        let general = language.split_once(['-', '_']).map(|(general, _)| general);
+        self.languages.is_empty()
+            || self.languages.iter().any(|candidate| {
+                same_language(candidate, language)
+                    || general.is_some_and(|general| same_language(candidate, general))
+            })
+    }
+}
+
+/// A translation which violates a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleViolation {
+    /// The untranslated message.
+    pub msgid: String,
+    /// The explanation of the rule.
+    pub message: String,
+    /// How serious the violation is.
+    pub severity: Severity,
+}
+
+/// Parse the rules file in `content`.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::content_rules::parse_rules;
+///
+/// let rules = parse_rules(
+///     "[[rules]]\n\
+///      pattern = \"Acme Cloud\"\n\
+///      message = \"Use the new product name\"\n",
+/// )
+/// .unwrap();
+/// assert!(rules[0].pattern.is_match("Welcome to Acme Cloud"));
+/// ```
+pub fn parse_rules(content: &str) -> anyhow::Result<Vec<ContentRule>> {
+    let file: RulesFile = toml::from_str(content).context("Could not parse the rules")?;
+    file.rules
+        .into_iter()
+        .map(|rule| {
+            let pattern = Regex::new(&rule.pattern)
+                .with_context(|| format!("Invalid pattern {:?}", rule.pattern))?;
+            let when = match &rule.when {
+                Some(when) => {
+                    Some(Regex::new(when).with_context(|| format!("Invalid pattern {when:?}"))?)
+                }
+                None => None,
+            };
+            Ok(ContentRule {
+                pattern,
+                when,
+                require: rule.require,
+                message: rule.message,
+                severity: rule.severity,
+                languages: rule.languages,
+            })
+        })
+        .collect()
+}
+
+/// Check the translations in `catalog` against `rules`.
+///
+/// Only rules for `language` are used. Fuzzy and untranslated messages
+/// are skipped. A rule with `require` and no `when` pattern uses its
+/// `pattern` for the `msgid`.
+pub fn check_rules(
+    catalog: &Catalog,
+    language: Option<&str>,
+    rules: &[ContentRule],
+) -> Vec<RuleViolation> {
+    let mut violations = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.applies_to(language)) {
+        let when = rule.when.as_ref().unwrap_or(&rule.pattern);
+        let mut seen = false;
+        for msg in catalog.messages().filter(|msg| !msg.is_fuzzy()) {
+            let Some(msgstr) = msg.msgstr().ok().filter(|msgstr| !msgstr.is_empty()) else {
+                continue;
+            };
+            let violated = match rule.require {
+                None => rule.pattern.is_match(msgstr),
+                Some(requirement) => {
+                    if !when.is_match(msg.msgid()) || (requirement == Requirement::FirstUse && seen)
+                    {
+                        continue;
+                    }
+                    seen = true;
+                    !rule.pattern.is_match(msgstr)
+                }
+            };
+            if violated {
+                violations.push(RuleViolation {
+                    msgid: String::from(msg.msgid()),
+                    message: rule.message.clone(),
+                    severity: rule.severity,
+                });
+            }
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_catalog;
+    use pretty_assertions::assert_eq;
+
+    fn msgids(violations: &[RuleViolation]) -> Vec<&str> {
+        violations
+            .iter()
+            .map(|violation| violation.msgid.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_forbidden_content() {
+        let rules = parse_rules(
+            "[[rules]]\n\
+             pattern = '`[^`]*[“”][^`]*`'\n\
+             message = \"Use straight quotes in code\"\n\
+             severity = \"warning\"\n",
+        )
+        .unwrap();
+        let catalog = create_catalog(&[
+            ("", "Run `echo \"hi\"`", "Kør `echo “hi”`"),
+            ("", "Say \"hi\"", "Sig „hi“"),
+        ]);
+        let violations = check_rules(&catalog, Some("da"), &rules);
+        assert_eq!(msgids(&violations), vec!["Run `echo \"hi\"`"]);
+        assert_eq!(violations[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_required_content_first_use() {
+        let rules = parse_rules(
+            "[[rules]]\n\
+             pattern = \"Acme™\"\n\
+             when = \"Acme\"\n\
+             require = \"first-use\"\n\
+             message = \"Use the trademark symbol\"\n\
+             languages = [\"de\"]\n",
+        )
+        .unwrap();
+        let catalog = create_catalog(&[
+            ("", "Welcome to Acme™", "Willkommen bei Acme"),
+            ("", "Acme is fast", "Acme ist schnell"),
+        ]);
+        assert_eq!(
+            msgids(&check_rules(&catalog, Some("de-AT"), &rules)),
+            vec!["Welcome to Acme™"]
+        );
+        assert_eq!(check_rules(&catalog, Some("da"), &rules), Vec::new());
+        assert_eq!(check_rules(&catalog, None, &rules), Vec::new());
+    }
+
+    #[test]
+    fn test_parse_rules_invalid() {
+        assert!(parse_rules("[[rules]]\npattern = \"(\"\nmessage = \"\"\n").is_err());
+        assert!(parse_rules("[[rules]]\npatern = \"a\"\nmessage = \"\"\n").is_err());
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Values in YAML front matter.
+//!
+//! This is not a YAML parser: it only understands top-level `key:
+//! value` lines where the value is a scalar on the same line. This
+//! covers keys such as `title` and `description`. Other values, such
+//! as nested mappings, lists, and multi-line strings, are ignored.
+
+use std::ops::Range;
+
+/// A value found in the front matter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontMatterValue {
+    /// Zero-based line number of the value.
+    pub line: usize,
+    /// Byte range of the value as written, including quotes.
+    pub range: Range<usize>,
+    /// The value without quotes and escapes.
+    pub text: String,
+}
+
+/// Unquote a double-quoted scalar which starts at the beginning of
+/// `value`.
+///
+/// Returns the length of the quoted scalar and the unquoted text.
+fn parse_double_quoted(value: &str) -> Option<(usize, String)> {
+    let mut text = String::new();
+    let mut chars = value.char_indices().skip(1);
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Some((idx + 1, text)),
+            '\\' => match chars.next()?.1 {
+                'n' => text.push('\n'),
+                't' => text.push('\t'),
+                c @ ('"' | '\\' | '/') => text.push(c),
+                // Other escapes are rare in front matter.
+                _ => return None,
+            },
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+/// Unquote a single-quoted scalar which starts at the beginning of
+/// `value`.
+///
+/// Returns the length of the quoted scalar and the unquoted text.
+fn parse_single_quoted(value: &str) -> Option<(usize, String)> {
+    let mut text = String::new();
+    let mut chars = value.char_indices().skip(1).peekable();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\'' if matches!(chars.peek(), Some((_, '\''))) => {
+                chars.next();
+                text.push('\'');
+            }
+            '\'' => return Some((idx + 1, text)),
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+/// Parse the scalar at the start of `value`.
+///
+/// Returns the length of the scalar as written and the unquoted text.
+fn parse_scalar(value: &str) -> Option<(usize, String)> {
+    match value.chars().next()? {
+        '"' => parse_double_quoted(value),
+        '\'' => parse_single_quoted(value),
+        // Block scalars, flow collections, anchors, aliases, tags,
+        // and comments.
+        '|' | '>' | '[' | '{' | '&' | '*' | '!' | '#' => None,
+        _ => {
+            let end = value.find(" #").unwrap_or(value.len());
+            let plain = value[..end].trim_end();
+            Some((plain.len(), String::from(plain)))
+        }
+    }
+}
+
+/// Find the values of `keys` in `yaml`.
+///
+/// Values which are empty or not on the same line as their key are
+/// skipped.
+pub fn find_values(yaml: &str, keys: &[String]) -> Vec<FrontMatterValue> {
+    let mut values = Vec::new();
+    let mut offset = 0;
+    for (line, content) in yaml.split_inclusive('\n').enumerate() {
+        let line_offset = offset;
+        offset += content.len();
+        let content = content.trim_end_matches(['\n', '\r']);
+        let Some((key, rest)) = content.split_once(':') else {
+            continue;
+        };
+        if !keys.iter().any(|k| k == key) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+            continue;
+        }
+        let start = line_offset + key.len() + 1 + (rest.len() - rest.trim_start().len());
+        let Some((len, text)) = parse_scalar(&yaml[start..line_offset + content.len()]) else {
+            continue;
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        values.push(FrontMatterValue {
+            line,
+            range: start..start + len,
+            text,
+        });
+    }
+    values
+}
+
+/// Check if `text` can be written as a plain scalar.
+fn is_plain_safe(text: &str) -> bool {
+    !text.is_empty()
+        && text.trim() == text
+        && !text.contains(['\n', '\t'])
+        && !text.contains(": ")
+        && !text.contains(" #")
+        && !text.ends_with(':')
+        && !text.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`',
+        ])
+}
+
+/// Quote `text` for use in place of the scalar `original`.
+///
+/// The quoting style of `original` is kept when possible.
+fn quote_like(original: &str, text: &str) -> String {
+    match original.chars().next() {
+        Some('\'') if !text.contains('\n') => format!("'{}'", text.replace('\'', "''")),
+        Some('\'' | '"') => format!(
+            "\"{}\"",
+            text.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\t', "\\t")
+        ),
+        _ if is_plain_safe(text) => String::from(text),
+        _ => quote_like("\"", text),
+    }
+}
+
+/// Replace the values of `keys` in `yaml` using `translate`.
+///
+/// Values for which `translate` returns `None` are left unchanged, as
+/// is the rest of `yaml`.
+pub fn replace_values<'a>(
+    yaml: &str,
+    keys: &[String],
+    translate: impl Fn(&str) -> Option<&'a str>,
+) -> String {
+    let mut replaced = String::new();
+    let mut pos = 0;
+    for value in find_values(yaml, keys) {
+        if let Some(translation) = translate(&value.text) {
+            replaced.push_str(&yaml[pos..value.range.start]);
+            replaced.push_str(&quote_like(&yaml[value.range.clone()], translation));
+            pos = value.range.end;
+        }
+    }
+    replaced.push_str(&yaml[pos..]);
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn keys() -> Vec<String> {
+        vec![String::from("title"), String::from("description")]
+    }
+
+    fn texts(yaml: &str) -> Vec<(usize, String)> {
+        find_values(yaml, &keys())
+            .into_iter()
+            .map(|value| (value.line, value.text))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_values() {
+        assert_eq!(
+            texts(
+                "title: Hello world # comment\n\
+                 author: Someone\n\
+                 description: \"A \\\"quoted\\\" value\"\n"
+            ),
+            vec![
+                (0, String::from("Hello world")),
+                (2, String::from("A \"quoted\" value")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_values_single_quoted() {
+        assert_eq!(
+            texts("title: 'It''s here'\n"),
+            vec![(0, String::from("It's here"))]
+        );
+    }
+
+    #[test]
+    fn test_find_values_skipped() {
+        assert_eq!(
+            texts(
+                "title: |\n  Block\n\
+                 description:\n\
+                 \x20 title: Nested\n\
+                 titles: Other key\n"
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_replace_values() {
+        let yaml = "title: Hello\nauthor: Someone\ndescription: 'A value'\n";
+        let replaced = replace_values(yaml, &keys(), |text| match text {
+            "Hello" => Some("Hej: verden"),
+            "A value" => Some("En 'værdi'"),
+            _ => None,
+        });
+        assert_eq!(
+            replaced,
+            "title: \"Hej: verden\"\nauthor: Someone\ndescription: 'En ''værdi'''\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_values_round_trip() {
+        let yaml = "title: \"Say \\\"hi\\\"\"\n";
+        let replaced = replace_values(yaml, &keys(), |_| Some("Sig \"hej\"\\"));
+        assert_eq!(texts(&replaced), vec![(0, String::from("Sig \"hej\"\\"))]);
+    }
+}
@@ -0,0 +1,349 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Adapt the capitalization of translated headings.
+//!
+//! English headings are often written in Title Case, while many
+//! languages use sentence case. Translators usually adapt this, but
+//! headings which are copied verbatim, such as product names or
+//! headings left in English on purpose, keep the English style. The
+//! functions here change the case of such headings.
+
+use crate::language::same_language;
+use crate::{extract_messages_with_comments, MarkdownConfig};
+use anyhow::{anyhow, bail};
+use polib::catalog::Catalog;
+use polib::message::{MessageMutView, MessageView};
+use pulldown_cmark::Event;
+use std::collections::HashSet;
+
+/// A rule for the capitalization of headings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadingCase {
+    /// Only the first word is capitalized: "Getting started".
+    Sentence,
+    /// No word is capitalized: "getting started".
+    Lower,
+}
+
+impl HeadingCase {
+    /// Parse a rule name, such as `sentence`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sentence" => Some(HeadingCase::Sentence),
+            "lower" => Some(HeadingCase::Lower),
+            _ => None,
+        }
+    }
+
+    /// Change the case of `word`, the `index`-th word of a heading.
+    ///
+    /// Only capitalized words such as "Started" are changed. Words with
+    /// other capital letters, such as "API" or "GitHub", are left
+    /// alone, as are words of a single letter.
+    fn apply(&self, word: &str, index: usize) -> Option<String> {
+        if *self == HeadingCase::Sentence && index == 0 {
+            return None;
+        }
+        let mut chars = word.chars();
+        let first = chars.next()?;
+        let rest = chars.as_str();
+        if !first.is_uppercase() || rest.is_empty() || rest.chars().any(char::is_uppercase) {
+            return None;
+        }
+        Some(first.to_lowercase().chain(rest.chars()).collect())
+    }
+}
+
+/// The heading settings for a language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadingCaseConfig {
+    /// The capitalization rule.
+    pub case: HeadingCase,
+    /// Change all translated headings, not only the ones which are the
+    /// same as the untranslated heading.
+    pub always: bool,
+    /// Words which keep their case, such as product names.
+    pub keep: Vec<String>,
+}
+
+impl HeadingCaseConfig {
+    /// Read the settings for `language` from
+    /// `preprocessor.gettext.heading-case`.
+    ///
+    /// The table has a table for each language, with a `case`, such as
+    /// `"sentence"`, an optional `always` boolean, and an optional list
+    /// of words to `keep`. Regional variants, such as `fr-CA`, fall back
+    /// to the general language. Returns `None` if there are no settings
+    /// for the language.
+    pub fn from_book_config(
+        config: &mdbook::Config,
+        language: &str,
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(value) = config.get("preprocessor.gettext.heading-case") else {
+            return Ok(None);
+        };
+        let table = value
+            .as_table()
+            .ok_or_else(|| anyhow!("Expected a table for preprocessor.gettext.heading-case"))?;
+        let general = language.split_once(['-', '_']).map(|(general, _)| general);
+        let Some((key, settings)) = table
+            .iter()
+            .find(|(key, _)| same_language(key, language))
+            .or_else(|| {
+                let general = general?;
+                table.iter().find(|(key, _)| same_language(key, general))
+            })
+        else {
+            return Ok(None);
+        };
+        let prefix = format!("preprocessor.gettext.heading-case.{key}");
+        let case = settings
+            .get("case")
+            .and_then(|case| case.as_str())
+            .ok_or_else(|| anyhow!("Expected a string for {prefix}.case"))?;
+        let case = HeadingCase::from_name(case).ok_or_else(|| {
+            anyhow!("Unknown case {case:?} for {prefix}.case, expected \"sentence\" or \"lower\"")
+        })?;
+        let always = match settings.get("always") {
+            None => false,
+            Some(value) => value
+                .as_bool()
+                .ok_or_else(|| anyhow!("Expected a boolean for {prefix}.always"))?,
+        };
+        let keep = match settings.get("keep") {
+            None => Vec::new(),
+            Some(value) => {
+                let Some(words) = value.as_array() else {
+                    bail!("Expected a list of strings for {prefix}.keep");
+                };
+                words
+                    .iter()
+                    .map(|word| {
+                        word.as_str()
+                            .map(String::from)
+                            .ok_or_else(|| anyhow!("Expected a list of strings for {prefix}.keep"))
+                    })
+                    .collect::<anyhow::Result<_>>()?
+            }
+        };
+        Ok(Some(HeadingCaseConfig { case, always, keep }))
+    }
+}
+
+/// Change the capitalization of the Markdown `heading`.
+///
+/// Only plain text is changed: code spans, link destinations, and HTML
+/// are left alone, as are the words in `keep`.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::heading_case::{change_heading_case, HeadingCase};
+///
+/// assert_eq!(
+///     change_heading_case("Using `Cargo` With GitHub Actions", HeadingCase::Sentence, &[]),
+///     "Using `Cargo` with GitHub actions"
+/// );
+/// ```
+pub fn change_heading_case(heading: &str, case: HeadingCase, keep: &[String]) -> String {
+    let mut result = String::with_capacity(heading.len());
+    let mut last_end = 0;
+    let mut index = 0;
+    for (event, range) in MarkdownConfig::default().parser(heading).into_offset_iter() {
+        let text = match event {
+            Event::Text(text) => text,
+            // A code span counts as a word.
+            Event::Code(_) => {
+                index += 1;
+                continue;
+            }
+            _ => continue,
+        };
+        // Text with escapes or entities doesn't match the source.
+        if heading[range.clone()] != *text {
+            continue;
+        }
+        result.push_str(&heading[last_end..range.start]);
+        let mut rest = &heading[range.clone()];
+        while let Some(start) = rest.find(char::is_alphabetic) {
+            let end = rest[start..]
+                .find(|c: char| !c.is_alphabetic())
+                .map_or(rest.len(), |end| start + end);
+            let word = &rest[start..end];
+            result.push_str(&rest[..start]);
+            match case.apply(word, index) {
+                Some(changed) if !keep.iter().any(|kept| kept == word) => result.push_str(&changed),
+                _ => result.push_str(word),
+            }
+            index += 1;
+            rest = &rest[end..];
+        }
+        result.push_str(rest);
+        last_end = range.end;
+    }
+    result.push_str(&heading[last_end..]);
+    result
+}
+
+/// Find the messages of the headings in `document`.
+pub fn heading_msgids(document: &str, config: &MarkdownConfig) -> Vec<String> {
+    let config = MarkdownConfig {
+        kind_comments: true,
+        ..config.clone()
+    };
+    extract_messages_with_comments(document, &config)
+        .into_iter()
+        .filter(|message| message.comment.lines().next() == Some("heading"))
+        .map(|message| message.msgid)
+        .collect()
+}
+
+/// Change the case of the translated `headings` in `catalog`.
+///
+/// Only translations which are the same as their `msgid` are changed,
+/// unless `config.always` is set. Fuzzy and untranslated messages are
+/// skipped. Returns the messages which were changed.
+pub fn adapt_heading_case(
+    catalog: &mut Catalog,
+    headings: &HashSet<String>,
+    config: &HeadingCaseConfig,
+) -> Vec<String> {
+    let mut changed = Vec::new();
+    for mut message in catalog.messages_mut() {
+        if message.is_fuzzy() || !headings.contains(message.msgid()) {
+            continue;
+        }
+        let Ok(msgstr) = message.msgstr() else {
+            continue;
+        };
+        if msgstr.is_empty() || (!config.always && msgstr != message.msgid()) {
+            continue;
+        }
+        let adapted = change_heading_case(msgstr, config.case, &config.keep);
+        if adapted != msgstr {
+            changed.push(String::from(message.msgid()));
+            // Singular messages always have a msgstr.
+            let _ = message.set_msgstr(adapted);
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_change_heading_case() {
+        let keep = vec![String::from("Rust")];
+        assert_eq!(
+            change_heading_case("Why Rust Is [Fast](fast.md)", HeadingCase::Sentence, &keep),
+            "Why Rust is [fast](fast.md)"
+        );
+        assert_eq!(
+            change_heading_case("Structs & Enums in API Design", HeadingCase::Lower, &[]),
+            "structs & enums in API design"
+        );
+        assert_eq!(
+            change_heading_case("Introduction", HeadingCase::Sentence, &[]),
+            "Introduction"
+        );
+        assert_eq!(
+            change_heading_case("`cargo` Basics", HeadingCase::Sentence, &[]),
+            "`cargo` basics"
+        );
+    }
+
+    #[test]
+    fn test_adapt_heading_case() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (msgid, msgstr) in [
+            ("Getting Started", "Getting Started"),
+            ("Error Handling", "Gestion Des Erreurs"),
+            ("Hello World", "Hello World"),
+        ] {
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let headings = heading_msgids(
+            "# Getting Started\n\n## Error Handling\n\nHello World\n",
+            &MarkdownConfig::default(),
+        )
+        .into_iter()
+        .collect::<HashSet<_>>();
+        let mut config = HeadingCaseConfig {
+            case: HeadingCase::Sentence,
+            always: false,
+            keep: Vec::new(),
+        };
+        assert_eq!(
+            adapt_heading_case(&mut catalog, &headings, &config),
+            vec!["Getting Started"]
+        );
+        config.always = true;
+        assert_eq!(
+            adapt_heading_case(&mut catalog, &headings, &config),
+            vec!["Error Handling"]
+        );
+        let msgstr = |msgid| {
+            catalog
+                .find_message(None, msgid, None)
+                .and_then(|message| message.msgstr().ok())
+                .map(String::from)
+        };
+        assert_eq!(
+            msgstr("Getting Started").as_deref(),
+            Some("Getting started")
+        );
+        assert_eq!(
+            msgstr("Error Handling").as_deref(),
+            Some("Gestion des erreurs")
+        );
+        assert_eq!(msgstr("Hello World").as_deref(), Some("Hello World"));
+    }
+
+    #[test]
+    fn test_heading_case_config() {
+        let config: mdbook::Config = "[preprocessor.gettext.heading-case.fr]\n\
+                                      case = \"sentence\"\n\
+                                      keep = [\"Rust\"]\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            HeadingCaseConfig::from_book_config(&config, "fr-CA").unwrap(),
+            Some(HeadingCaseConfig {
+                case: HeadingCase::Sentence,
+                always: false,
+                keep: vec![String::from("Rust")],
+            })
+        );
+        assert_eq!(
+            HeadingCaseConfig::from_book_config(&config, "de").unwrap(),
+            None
+        );
+        let config: mdbook::Config = "[preprocessor.gettext.heading-case.fr]\n\
+                                      case = \"title\"\n"
+            .parse()
+            .unwrap();
+        assert!(HeadingCaseConfig::from_book_config(&config, "fr").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Text content of raw HTML.
+//!
+//! This is a small HTML tokenizer which only knows enough about HTML
+//! to find the text between block-level tags. It doesn't build a
+//! document tree and it doesn't decode character references: the
+//! text is returned exactly as written in the source.
+
+use std::ops::Range;
+
+/// Elements with content which is not shown as text.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Elements which are kept as part of the surrounding text.
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "code", "data", "del", "dfn", "em", "i", "img",
+    "ins", "kbd", "mark", "q", "s", "samp", "small", "span", "strong", "sub", "sup", "time", "u",
+    "var", "wbr",
+];
+
+/// Inline elements without content.
+const VOID_ELEMENTS: &[&str] = &["br", "img", "wbr"];
+
+/// Find the end of the tag starting at `start`.
+///
+/// Returns the position after the closing `>`, or the end of `html`
+/// if the tag is unterminated. Quoted attribute values can contain
+/// `>` without ending the tag.
+fn tag_end(html: &str, start: usize) -> usize {
+    let mut quote = None;
+    for (idx, c) in html[start..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return start + idx + 1,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+/// Extract the lowercase element name from a tag such as `<td
+/// class="x">` or `</td>`.
+fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('<')
+        .trim_start_matches('/')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Check if `html` has a tag starting at `idx`.
+fn is_tag_start(html: &str, idx: usize) -> bool {
+    let mut chars = html[idx..].chars();
+    chars.next() == Some('<')
+        && matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '/' || c == '!' || c == '?')
+}
+
+/// Trim whitespace and void elements from both ends of `range`.
+///
+/// This keeps elements such as an `<img>` next to the text out of the
+/// messages, so that a translation cannot change them.
+fn trim_run(html: &str, range: Range<usize>) -> Range<usize> {
+    let (mut start, mut end) = (range.start, range.end);
+    loop {
+        let text = &html[start..end];
+        start += text.len() - text.trim_start().len();
+        end -= text.len() - text.trim_end().len();
+        if start < end && is_tag_start(html, start) {
+            let tag_end = tag_end(html, start);
+            if tag_end <= end && VOID_ELEMENTS.contains(&tag_name(&html[start..tag_end]).as_str()) {
+                start = tag_end;
+                continue;
+            }
+        }
+        if let Some(tag_start) = html[start..end].rfind('<').map(|idx| start + idx) {
+            if is_tag_start(html, tag_start)
+                && tag_end(html, tag_start) == end
+                && VOID_ELEMENTS.contains(&tag_name(&html[tag_start..end]).as_str())
+            {
+                end = tag_start;
+                continue;
+            }
+        }
+        return start..end;
+    }
+}
+
+/// Find the runs of text in `html`.
+///
+/// A run of text is the content between two block-level tags, such
+/// as `<td>` or `<p>`. Inline elements, such as `<b>` or `<a>`, are
+/// part of the run. The returned ranges exclude leading and trailing
+/// whitespace and void elements, such as `<img>`. Runs without any
+/// text outside of tags are skipped, as
+/// is the content of comments and of `<script>` and `<style>`
+/// elements.
+pub fn text_runs(html: &str) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut run_start = 0;
+    let mut has_text = false;
+    let mut pos = 0;
+
+    let mut push_run = |start: usize, end: usize, has_text: bool| {
+        if has_text {
+            runs.push(trim_run(html, start..end));
+        }
+    };
+
+    while let Some(idx) = html[pos..].find('<').map(|idx| pos + idx) {
+        has_text |= !html[pos..idx].trim().is_empty();
+        if !is_tag_start(html, idx) {
+            has_text = true;
+            pos = idx + 1;
+            continue;
+        }
+
+        let end = if html[idx..].starts_with("<!--") {
+            html[idx + 4..]
+                .find("-->")
+                .map_or(html.len(), |end| idx + 4 + end + 3)
+        } else {
+            tag_end(html, idx)
+        };
+        let name = tag_name(&html[idx..end]);
+        if INLINE_ELEMENTS.contains(&name.as_str()) {
+            pos = end;
+            continue;
+        }
+
+        push_run(run_start, idx, has_text);
+        has_text = false;
+        pos = if !html[idx..].starts_with("</") && RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+            let closing = format!("</{name}");
+            html[end..]
+                .to_ascii_lowercase()
+                .find(&closing)
+                .map_or(html.len(), |close| tag_end(html, end + close))
+        } else {
+            end
+        };
+        run_start = pos;
+    }
+    has_text |= !html[pos..].trim().is_empty();
+    push_run(run_start, html.len(), has_text);
+
+    runs
+}
+
+/// Collapse runs of whitespace in `text` into single spaces.
+pub fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn texts(html: &str) -> Vec<&str> {
+        text_runs(html)
+            .into_iter()
+            .map(|range| &html[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_text_runs_table() {
+        assert_eq!(
+            texts("<table>\n  <tr><th>Name</th><td>A <b>bold</b> value</td></tr>\n</table>\n"),
+            vec!["Name", "A <b>bold</b> value"]
+        );
+    }
+
+    #[test]
+    fn test_text_runs_attributes() {
+        assert_eq!(
+            texts(r#"<p><a title="a > b" href='x'>Link</a></p>"#),
+            vec![r#"<a title="a > b" href='x'>Link</a>"#]
+        );
+    }
+
+    #[test]
+    fn test_text_runs_comments_and_scripts() {
+        assert_eq!(
+            texts(
+                "<!-- <p>Hidden</p> --><p>Shown</p><script>if (a<b) {}</script><STYLE>p {}</style>"
+            ),
+            vec!["Shown"]
+        );
+    }
+
+    #[test]
+    fn test_text_runs_less_than() {
+        assert_eq!(texts("<p>1 < 2</p>"), vec!["1 < 2"]);
+    }
+
+    #[test]
+    fn test_text_runs_without_text() {
+        assert_eq!(
+            texts("<div>\n  <img src=\"x.png\">\n</div>"),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn test_normalize_whitespace() {
+        assert_eq!(normalize_whitespace("  Foo\n    bar  baz "), "Foo bar baz");
+    }
+
+    #[test]
+    fn test_text_runs_void_elements() {
+        assert_eq!(
+            texts("<td><img src=\"a.png\"\n     height=\"20\"> Label <br> text<br/></td>"),
+            vec!["Label <br> text"]
+        );
+    }
+}
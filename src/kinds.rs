@@ -0,0 +1,231 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Describe where in the document a message comes from.
+//!
+//! Translators treat a heading differently from a table cell or the
+//! alt text of an image, for example when deciding on capitalization
+//! or length. The kind of each message can therefore be added as an
+//! extracted comment, and it is part of each
+//! [`ExtractedMessage`](crate::ExtractedMessage).
+
+use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+
+/// The kind of block a message comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockKind {
+    /// A paragraph, or text which is not in any other kind of block.
+    Paragraph,
+    /// A heading.
+    Heading,
+    /// A cell in the first row of a table.
+    TableHeader,
+    /// A cell in the other rows of a table.
+    TableCell,
+    /// An item of a list.
+    ListItem,
+    /// The definition of a footnote.
+    Footnote,
+    /// The term of a definition list.
+    DefinitionTerm,
+    /// The definition of a term in a definition list.
+    Definition,
+    /// A block quote, including GitHub-style alerts.
+    BlockQuote,
+    /// An image which is the only content of its block.
+    ImageAltText,
+    /// A code block, or a comment or string in a code block.
+    CodeBlock,
+    /// Raw HTML, see [`MarkdownConfig::extract_html`](crate::MarkdownConfig::extract_html).
+    Html,
+    /// A value in the front matter, see
+    /// [`MarkdownConfig::frontmatter_keys`](crate::MarkdownConfig::frontmatter_keys).
+    FrontMatter,
+}
+
+impl BlockKind {
+    /// The name of the kind, such as `table cell`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::kinds::BlockKind;
+    ///
+    /// assert_eq!(BlockKind::ImageAltText.name(), "image alt text");
+    /// ```
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockKind::Paragraph => "paragraph",
+            BlockKind::Heading => "heading",
+            BlockKind::TableHeader => "table header",
+            BlockKind::TableCell => "table cell",
+            BlockKind::ListItem => "list item",
+            BlockKind::Footnote => "footnote",
+            BlockKind::DefinitionTerm => "definition term",
+            BlockKind::Definition => "definition",
+            BlockKind::BlockQuote => "block quote",
+            BlockKind::ImageAltText => "image alt text",
+            BlockKind::CodeBlock => "code block",
+            BlockKind::Html => "HTML",
+            BlockKind::FrontMatter => "front matter",
+        }
+    }
+}
+
+/// The blocks which enclose the current position in a document.
+#[derive(Debug, Default)]
+pub(crate) struct BlockStack<'a> {
+    tags: Vec<Tag<'a>>,
+}
+
+impl<'a> BlockStack<'a> {
+    /// Move past `events`.
+    pub fn update(&mut self, events: &[(usize, Event<'a>)]) {
+        for (_, event) in events {
+            match event {
+                Event::Start(tag) => self.tags.push(tag.clone()),
+                Event::End(_) => {
+                    self.tags.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The level of the heading the current position is in, if any.
+    pub fn heading_level(&self) -> Option<usize> {
+        self.tags.iter().rev().find_map(|tag| match tag {
+            Tag::Heading { level, .. } => Some(*level as usize),
+            _ => None,
+        })
+    }
+
+    /// Find the kind of block of the message made from `events`.
+    pub fn block_kind(&self, events: &[(usize, Event)]) -> BlockKind {
+        if let Some((_, Event::Start(Tag::CodeBlock(_)))) = events.first() {
+            return BlockKind::CodeBlock;
+        }
+        if is_image_only(events) {
+            return BlockKind::ImageAltText;
+        }
+        let in_table_head = self.tags.iter().any(|tag| matches!(tag, Tag::TableHead));
+        let kind = self.tags.iter().rev().find_map(|tag| match tag {
+            Tag::Heading { .. } => Some(BlockKind::Heading),
+            Tag::TableCell if in_table_head => Some(BlockKind::TableHeader),
+            Tag::TableCell => Some(BlockKind::TableCell),
+            Tag::Item => Some(BlockKind::ListItem),
+            Tag::FootnoteDefinition(_) => Some(BlockKind::Footnote),
+            Tag::DefinitionListTitle => Some(BlockKind::DefinitionTerm),
+            Tag::DefinitionListDefinition => Some(BlockKind::Definition),
+            Tag::BlockQuote(_) => Some(BlockKind::BlockQuote),
+            _ => None,
+        });
+        kind.unwrap_or(BlockKind::Paragraph)
+    }
+
+    /// Describe the message made from `events`, such as `heading` or
+    /// `table cell`. Code blocks include their language, such as
+    /// `code block (rust)`.
+    pub fn message_kind(&self, events: &[(usize, Event)]) -> String {
+        if let Some((_, Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))))) = events.first()
+        {
+            if let Some(lang) = info
+                .split([' ', ','])
+                .next()
+                .filter(|lang| !lang.is_empty())
+            {
+                return format!("code block ({lang})");
+            }
+        }
+        String::from(self.block_kind(events).name())
+    }
+}
+
+/// Check if `events` are a single image, possibly in a paragraph.
+fn is_image_only(events: &[(usize, Event)]) -> bool {
+    let events = match events {
+        [(_, Event::Start(Tag::Paragraph)), inner @ .., (_, Event::End(TagEnd::Paragraph))] => {
+            inner
+        }
+        _ => events,
+    };
+    matches!(
+        events,
+        [
+            (_, Event::Start(Tag::Image { .. })),
+            ..,
+            (_, Event::End(TagEnd::Image))
+        ]
+    ) && events
+        .iter()
+        .filter(|(_, event)| matches!(event, Event::Start(Tag::Image { .. })))
+        .count()
+        == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{extract_events, group_events, Group};
+    use pretty_assertions::assert_eq;
+
+    /// Find the kinds of the messages in `document`.
+    fn message_kinds(document: &str) -> Vec<String> {
+        let events = extract_events(document, None);
+        let mut stack = BlockStack::default();
+        let mut kinds = Vec::new();
+        for group in group_events(&events) {
+            if let Group::Translate(events) = group {
+                kinds.push(stack.message_kind(events));
+            }
+            stack.update(group.events());
+        }
+        kinds
+    }
+
+    #[test]
+    fn test_message_kinds() {
+        assert_eq!(
+            message_kinds(
+                "# Title\n\
+                 \n\
+                 Text with ![an image](foo.png).\n\
+                 \n\
+                 ![Alt text](foo.png)\n\
+                 \n\
+                 - Item\n\
+                 \n\
+                 > Quote\n\
+                 \n\
+                 | Name |\n\
+                 |------|\n\
+                 | Foo  |\n\
+                 \n\
+                 ```rust,editable\n\
+                 fn main() {}\n\
+                 ```\n"
+            ),
+            vec![
+                "heading",
+                "paragraph",
+                "image alt text",
+                "list item",
+                "block quote",
+                "table header",
+                "table cell",
+                "code block (rust)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,400 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finding the PO file for a language.
+//!
+//! PO files are normally named after their language, such as
+//! `po/ko.po`. Files delivered by translation vendors often have
+//! other names, such as `final_ko_v2.po`. The `Language` header in
+//! the PO file itself says which language it contains, so this is
+//! used to find such files and to warn about files named after the
+//! wrong language.
+//!
+//! Regional variants fall back to more general PO files: when there
+//! is no PO file for `pt-BR`, the one for `pt` is used.
+
+use crate::catalog_io::read_catalog;
+use anyhow::{anyhow, Context};
+use polib::catalog::Catalog;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A PO file found for a language.
+pub struct FoundCatalog {
+    /// The path of the PO file.
+    pub path: PathBuf,
+    /// The parsed PO file.
+    pub catalog: Catalog,
+    /// Problems noticed while looking for the PO file, such as a
+    /// `Language` header which doesn't match the file name.
+    pub warnings: Vec<String>,
+}
+
+/// The language in the `Language` header of `catalog`, if any.
+pub fn catalog_language(catalog: &Catalog) -> Option<&str> {
+    Some(catalog.metadata.language.trim()).filter(|language| !language.is_empty())
+}
+
+/// Check if `a` and `b` are the same language.
+///
+/// Case is ignored, as is the difference between `-` and `_`, so
+/// `pt-BR` is the same as `pt_br`.
+pub fn same_language(a: &str, b: &str) -> bool {
+    let normalize = |language: &str| language.trim().to_ascii_lowercase().replace('-', "_");
+    normalize(a) == normalize(b)
+}
+
+/// Languages which are written from right to left.
+const RTL_LANGUAGES: &[&str] = &[
+    "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ps", "sd", "syr", "ug", "ur", "yi",
+];
+
+/// Scripts which are written from right to left.
+const RTL_SCRIPTS: &[&str] = &["arab", "hebr", "syrc", "thaa", "nkoo", "adlm"];
+
+/// Check if `language` is written from right to left.
+///
+/// A script subtag takes precedence over the language, so `pa-Arab`
+/// is written from right to left and `ku-Latn` is not.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::language::is_rtl;
+///
+/// assert!(is_rtl("ar"));
+/// assert!(is_rtl("he-IL"));
+/// assert!(is_rtl("pa-Arab"));
+/// assert!(!is_rtl("en"));
+/// assert!(!is_rtl("fa-Latn"));
+/// ```
+pub fn is_rtl(language: &str) -> bool {
+    let subtags = language
+        .split(['-', '_'])
+        .map(|subtag| subtag.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    let script = subtags
+        .iter()
+        .skip(1)
+        .find(|subtag| subtag.len() == 4 && subtag.chars().all(|c| c.is_ascii_alphabetic()));
+    match script {
+        Some(script) => RTL_SCRIPTS.contains(&script.as_str()),
+        None => RTL_LANGUAGES.contains(&subtags[0].as_str()),
+    }
+}
+
+/// Parse the PO file at `path`.
+///
+/// PO files in ISO-8859-1 are decoded, see [`read_catalog`].
+fn parse_catalog(path: &Path) -> anyhow::Result<Catalog> {
+    read_catalog(path)
+}
+
+/// Describe a `Language` header which doesn't match `language`.
+fn mismatch_warning(path: &Path, header: &str, language: &str) -> String {
+    format!(
+        "{} has a Language header of {header:?}, expected {language:?}",
+        path.display()
+    )
+}
+
+/// How to find the PO file for a language.
+///
+/// This is read from the `preprocessor.gettext` table in `book.toml`,
+/// see [`LookupConfig::from_book_config`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LookupConfig {
+    /// Find the PO file using its `Language` header instead of its
+    /// name.
+    pub detect_language: bool,
+
+    /// Alternative language tags to try, such as `zh-TW` for
+    /// `zh-Hant`.
+    pub aliases: HashMap<String, String>,
+}
+
+impl LookupConfig {
+    /// Read the configuration from the `preprocessor.gettext` table.
+    ///
+    /// The `detect-language` key is a boolean and `language-aliases`
+    /// is a table which maps language tags to other language tags.
+    pub fn from_book_config(config: &mdbook::Config) -> anyhow::Result<Self> {
+        let mut lookup_config = LookupConfig::default();
+        if let Some(value) = config.get("preprocessor.gettext.detect-language") {
+            lookup_config.detect_language = value.as_bool().ok_or_else(|| {
+                anyhow!("Could not parse preprocessor.gettext.detect-language as a boolean")
+            })?;
+        }
+        if let Some(value) = config.get("preprocessor.gettext.language-aliases") {
+            let table = value.as_table().ok_or_else(|| {
+                anyhow!("Could not parse preprocessor.gettext.language-aliases as a table")
+            })?;
+            for (language, alias) in table {
+                let alias = alias.as_str().ok_or_else(|| {
+                    anyhow!("Expected a string for {language:?} in preprocessor.gettext.language-aliases")
+                })?;
+                lookup_config
+                    .aliases
+                    .insert(language.clone(), String::from(alias));
+            }
+        }
+        Ok(lookup_config)
+    }
+
+    /// Find the alias of `language`, ignoring case.
+    fn alias(&self, language: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(tag, _)| same_language(tag, language))
+            .map(|(_, alias)| alias.as_str())
+    }
+}
+
+/// The language tags to try for `language`, most specific first.
+///
+/// A BCP 47 tag such as `zh-Hant-TW` falls back to the language and
+/// script, `zh-Hant`, and then to the language, `zh`. The alias of each
+/// tag is tried right after the tag itself.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::language::{language_candidates, LookupConfig};
+///
+/// let mut config = LookupConfig::default();
+/// config.aliases.insert("zh-Hant".into(), "zh-TW".into());
+/// assert_eq!(
+///     language_candidates("zh-Hant-HK", &config),
+///     vec!["zh-Hant-HK", "zh-Hant", "zh-TW", "zh"]
+/// );
+/// assert_eq!(language_candidates("pt_BR", &config), vec!["pt_BR", "pt"]);
+/// ```
+pub fn language_candidates(language: &str, config: &LookupConfig) -> Vec<String> {
+    let subtags = language.split(['-', '_']).collect::<Vec<_>>();
+    let mut tags = vec![String::from(language)];
+    if subtags.len() > 2 && subtags[1].len() == 4 && subtags[1].chars().all(char::is_alphabetic) {
+        tags.push(format!("{}-{}", subtags[0], subtags[1]));
+    }
+    if subtags.len() > 1 {
+        tags.push(String::from(subtags[0]));
+    }
+
+    let mut candidates: Vec<String> = Vec::new();
+    for tag in tags {
+        let alias = config.alias(&tag).map(String::from);
+        for candidate in std::iter::once(tag).chain(alias) {
+            if !candidates.iter().any(|c| same_language(c, &candidate)) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates
+}
+
+/// Read the PO files in `dir`, sorted by name.
+fn read_catalogs(dir: &Path) -> anyhow::Result<Vec<(PathBuf, Catalog)>> {
+    let mut paths = Vec::new();
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir).with_context(|| format!("Could not read {:?}", dir))? {
+            let path = entry
+                .with_context(|| format!("Could not read {:?}", dir))?
+                .path();
+            if path.extension().is_some_and(|ext| ext == "po") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| parse_catalog(&path).map(|catalog| (path, catalog)))
+        .collect()
+}
+
+/// Check if `path` is named after `language`.
+///
+/// Both `pt-BR.po` and `pt_BR.po` are named after `pt-BR`.
+fn is_named_after(path: &Path, language: &str) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| same_language(stem, language))
+}
+
+/// Find the PO file for `language` in `dir`.
+///
+/// The [`language_candidates`] are tried in order, so `pt-BR` falls
+/// back to `pt` if there is no PO file for `pt-BR`.
+///
+/// Without `config.detect_language`, the PO file is found by its
+/// name, such as `pt-BR.po` or `pt_BR.po`. A warning is added if its
+/// `Language` header names another language.
+///
+/// With `config.detect_language`, all PO files in `dir` are read and
+/// the first one whose `Language` header matches is used, regardless
+/// of its name. A file named after the language is only used without
+/// such a header. Files named after the language with a different
+/// header are skipped with a warning.
+///
+/// Returns `None` if no PO file is found.
+pub fn find_catalog(
+    dir: &Path,
+    language: &str,
+    config: &LookupConfig,
+) -> anyhow::Result<Option<FoundCatalog>> {
+    let candidates = language_candidates(language, config);
+    if !config.detect_language {
+        for candidate in &candidates {
+            let mut names = vec![format!("{candidate}.po")];
+            names.push(names[0].replace('-', "_"));
+            let Some(path) = names
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.exists())
+            else {
+                continue;
+            };
+            let catalog = parse_catalog(&path)?;
+            let warnings = match catalog_language(&catalog) {
+                Some(header) if !same_language(header, candidate) => {
+                    vec![mismatch_warning(&path, header, candidate)]
+                }
+                _ => Vec::new(),
+            };
+            return Ok(Some(FoundCatalog {
+                path,
+                catalog,
+                warnings,
+            }));
+        }
+        return Ok(None);
+    }
+
+    let mut catalogs = read_catalogs(dir)?;
+    let mut warnings = Vec::new();
+    for candidate in &candidates {
+        let mut matching = Vec::new();
+        let mut unlabeled = None;
+        for (idx, (path, catalog)) in catalogs.iter().enumerate() {
+            match catalog_language(catalog) {
+                Some(header) if same_language(header, candidate) => matching.push(idx),
+                Some(header) if is_named_after(path, candidate) => {
+                    warnings.push(mismatch_warning(path, header, candidate));
+                }
+                None if is_named_after(path, candidate) => unlabeled = Some(idx),
+                _ => {}
+            }
+        }
+
+        if matching.len() > 1 {
+            let names = matching
+                .iter()
+                .map(|&idx| catalogs[idx].0.display().to_string())
+                .collect::<Vec<_>>();
+            warnings.push(format!(
+                "Found several PO files for {candidate:?}: {}, using the first",
+                names.join(", ")
+            ));
+        }
+        if let Some(idx) = matching.first().copied().or(unlabeled) {
+            let (path, catalog) = catalogs.swap_remove(idx);
+            return Ok(Some(FoundCatalog {
+                path,
+                catalog,
+                warnings,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::metadata::CatalogMetadata;
+    use polib::po_file;
+    use pretty_assertions::assert_eq;
+
+    /// Write a PO file with the `language` header to `path`.
+    fn write_po(path: &Path, language: &str) -> anyhow::Result<()> {
+        let mut metadata = CatalogMetadata::new();
+        metadata.language = String::from(language);
+        po_file::write(&Catalog::new(metadata), path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_language() {
+        assert!(same_language("pt-BR", "pt_br"));
+        assert!(!same_language("pt", "pt-BR"));
+    }
+
+    #[test]
+    fn test_find_catalog_by_name() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        write_po(&tmpdir.path().join("ko.po"), "ja")?;
+        let found = find_catalog(tmpdir.path(), "ko", &LookupConfig::default())?.unwrap();
+        assert_eq!(found.path, tmpdir.path().join("ko.po"));
+        assert_eq!(found.warnings.len(), 1);
+        assert!(find_catalog(tmpdir.path(), "da", &LookupConfig::default())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_catalog_detect() -> anyhow::Result<()> {
+        let detect = LookupConfig {
+            detect_language: true,
+            ..LookupConfig::default()
+        };
+        let tmpdir = tempfile::tempdir()?;
+        write_po(&tmpdir.path().join("ko.po"), "ja")?;
+        write_po(&tmpdir.path().join("final_ko_v2.po"), "ko")?;
+        write_po(&tmpdir.path().join("da.po"), "")?;
+        let found = find_catalog(tmpdir.path(), "ko", &detect)?.unwrap();
+        assert_eq!(found.path, tmpdir.path().join("final_ko_v2.po"));
+        assert_eq!(found.warnings.len(), 1);
+
+        // Files without a header are found by their name.
+        let found = find_catalog(tmpdir.path(), "da", &detect)?.unwrap();
+        assert_eq!(found.path, tmpdir.path().join("da.po"));
+        assert!(found.warnings.is_empty());
+
+        assert!(find_catalog(tmpdir.path(), "ja", &detect)?.is_some());
+        assert!(find_catalog(tmpdir.path(), "fr", &detect)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_catalog_fallback() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        write_po(&tmpdir.path().join("pt.po"), "pt")?;
+        write_po(&tmpdir.path().join("zh_TW.po"), "zh_TW")?;
+        let mut config = LookupConfig::default();
+        config
+            .aliases
+            .insert(String::from("zh-Hant"), String::from("zh-TW"));
+
+        let found = find_catalog(tmpdir.path(), "pt-BR", &config)?.unwrap();
+        assert_eq!(found.path, tmpdir.path().join("pt.po"));
+        let found = find_catalog(tmpdir.path(), "zh-Hant", &config)?.unwrap();
+        assert_eq!(found.path, tmpdir.path().join("zh_TW.po"));
+        assert!(found.warnings.is_empty());
+        assert!(find_catalog(tmpdir.path(), "zh-Hans", &config)?.is_none());
+
+        config.detect_language = true;
+        let found = find_catalog(tmpdir.path(), "zh-Hant", &config)?.unwrap();
+        assert_eq!(found.path, tmpdir.path().join("zh_TW.po"));
+        Ok(())
+    }
+}
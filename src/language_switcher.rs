@@ -0,0 +1,563 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Links between the translations of a book.
+//!
+//! The languages are listed in `preprocessor.gettext.languages`
+//! together with the URL where each translation is served. Scripts
+//! added to each chapter can then put a language menu in the menu bar
+//! of the default `mdbook` theme, see [`switcher_script`], and add
+//! canonical and alternate links for search engines, see
+//! [`canonical_script`].
+//!
+//! Renderers based on templates can build the same links themselves
+//! from the context returned by [`template_context`]. Search engines
+//! can also find the translations through a sitemap, see [`sitemap`].
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A translation of the book.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BookLanguage {
+    /// The language tag, such as `da`.
+    pub language: String,
+    /// The name shown in the menu, such as `Dansk`.
+    pub name: String,
+    /// The URL of the root of the translation, such as `/da/`.
+    pub url: String,
+}
+
+/// Read the `preprocessor.gettext.languages` setting.
+///
+/// The setting is a list of tables with `language`, `name`, and `url`
+/// keys. An empty list is returned if it is not set.
+pub fn book_languages(config: &mdbook::Config) -> anyhow::Result<Vec<BookLanguage>> {
+    let Some(value) = config.get("preprocessor.gettext.languages") else {
+        return Ok(Vec::new());
+    };
+    let languages: Vec<BookLanguage> = value
+        .clone()
+        .try_into()
+        .context("Could not parse preprocessor.gettext.languages")?;
+    if let Some(language) = languages.iter().find(|language| language.url.is_empty()) {
+        return Err(anyhow!(
+            "Missing URL for {:?} in preprocessor.gettext.languages",
+            language.language
+        ));
+    }
+    Ok(languages)
+}
+
+#[derive(Serialize)]
+struct SwitcherLink<'a> {
+    language: &'a str,
+    name: &'a str,
+    href: String,
+}
+
+/// The output path of the chapter at `path`, with `/` as separator.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::language_switcher::html_path;
+/// use std::path::Path;
+///
+/// assert_eq!(html_path(Path::new("guide/README.md")), "guide/index.html");
+/// assert_eq!(html_path(Path::new("guide/setup.md")), "guide/setup.html");
+/// ```
+pub fn html_path(path: &Path) -> String {
+    let path = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    match path.strip_suffix("README.md") {
+        Some(dir) => format!("{dir}index.html"),
+        None => path
+            .strip_suffix(".md")
+            .map_or(path.clone(), |p| format!("{p}.html")),
+    }
+}
+
+/// The URL of the chapter at `chapter_path` in `language`.
+fn chapter_url(language: &BookLanguage, chapter_path: &Path) -> String {
+    format!(
+        "{}/{}",
+        language.url.trim_end_matches('/'),
+        html_path(chapter_path)
+    )
+}
+
+/// Create a `<script>` element which adds the language switcher to the
+/// menu bar.
+///
+/// The links point to the chapter at `chapter_path` in each of the
+/// `languages`. The `current` language is marked in the menu.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::language_switcher::{switcher_script, BookLanguage};
+/// use std::path::Path;
+///
+/// let languages = vec![BookLanguage {
+///     language: String::from("da"),
+///     name: String::from("Dansk"),
+///     url: String::from("/da/"),
+/// }];
+/// let script = switcher_script(&languages, Some("da"), Path::new("guide/setup.md"));
+/// assert!(script.contains(r#""href":"/da/guide/setup.html""#));
+/// ```
+pub fn switcher_script(
+    languages: &[BookLanguage],
+    current: Option<&str>,
+    chapter_path: &Path,
+) -> String {
+    let links = languages
+        .iter()
+        .map(|language| SwitcherLink {
+            language: &language.language,
+            name: &language.name,
+            href: chapter_url(language, chapter_path),
+        })
+        .collect::<Vec<_>>();
+    // Escape "</" so a name cannot end the script element.
+    let links = serde_json::to_string(&links)
+        .expect("the links can be serialized")
+        .replace("</", "<\\/");
+    let current = serde_json::to_string(&current).expect("a string can be serialized");
+    format!(
+        r##"<script>
+document.addEventListener("DOMContentLoaded", () => {{
+  const links = {links};
+  const current = {current};
+  const buttons = document.querySelector(".right-buttons");
+  if (!buttons || document.getElementById("language-toggle")) return;
+  const toggle = document.createElement("button");
+  toggle.id = "language-toggle";
+  toggle.className = "icon-button";
+  toggle.type = "button";
+  toggle.title = "Change language";
+  toggle.setAttribute("aria-label", "Change language");
+  toggle.setAttribute("aria-haspopup", "true");
+  toggle.setAttribute("aria-expanded", "false");
+  toggle.innerHTML = '<i class="fa fa-globe"></i>';
+  const list = document.createElement("ul");
+  list.id = "language-list";
+  list.className = "theme-popup";
+  list.setAttribute("role", "menu");
+  list.setAttribute("aria-label", "Languages");
+  list.style.display = "none";
+  list.style.left = "auto";
+  list.style.right = "0";
+  for (const link of links) {{
+    const item = document.createElement("li");
+    item.setAttribute("role", "none");
+    const anchor = document.createElement("a");
+    anchor.className = "theme";
+    anchor.setAttribute("role", "menuitem");
+    anchor.href = link.href;
+    anchor.lang = link.language;
+    anchor.textContent = link.name;
+    if (link.language === current) anchor.setAttribute("aria-current", "true");
+    item.append(anchor);
+    list.append(item);
+  }}
+  toggle.addEventListener("click", () => {{
+    const open = list.style.display === "none";
+    list.style.display = open ? "block" : "none";
+    toggle.setAttribute("aria-expanded", String(open));
+  }});
+  const wrapper = document.createElement("div");
+  wrapper.style.display = "inline-block";
+  wrapper.style.position = "relative";
+  wrapper.append(toggle, list);
+  buttons.prepend(wrapper);
+}});
+</script>
+"##
+    )
+}
+
+/// Create a `<script>` element which adds canonical and alternate
+/// links to the `<head>` of the page.
+///
+/// The canonical link points to the chapter at `chapter_path` in the
+/// `current` language. An alternate link is added for each of the
+/// `languages`, and the first language is used for `x-default`.
+/// Nothing is added if `current` is not one of the `languages`.
+///
+/// The links are added while the page is loading, since a
+/// preprocessor cannot change the `<head>` written by the theme.
+pub fn canonical_script(
+    languages: &[BookLanguage],
+    current: Option<&str>,
+    chapter_path: &Path,
+) -> String {
+    let Some(current) = languages
+        .iter()
+        .find(|language| Some(language.language.as_str()) == current)
+    else {
+        return String::new();
+    };
+    let mut links = vec![(
+        String::from("canonical"),
+        None,
+        chapter_url(current, chapter_path),
+    )];
+    for language in languages {
+        links.push((
+            String::from("alternate"),
+            Some(language.language.clone()),
+            chapter_url(language, chapter_path),
+        ));
+    }
+    if let Some(default) = languages.first() {
+        links.push((
+            String::from("alternate"),
+            Some(String::from("x-default")),
+            chapter_url(default, chapter_path),
+        ));
+    }
+    // Escape "</" so a URL cannot end the script element.
+    let links = serde_json::to_string(&links)
+        .expect("the links can be serialized")
+        .replace("</", "<\\/");
+    format!(
+        r##"<script>
+if (!document.querySelector('link[rel="canonical"]')) {{
+  for (const [rel, hreflang, href] of {links}) {{
+    const link = document.createElement("link");
+    link.rel = rel;
+    if (hreflang) link.hreflang = hreflang;
+    link.href = href;
+    document.head.append(link);
+  }}
+}}
+</script>
+"##
+    )
+}
+
+/// A translation of the book, as seen from a page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateLanguage {
+    /// The language tag, such as `da`.
+    pub language: String,
+    /// The name shown in the menu, such as `Dansk`.
+    pub name: String,
+    /// The URL of the page in this language.
+    pub url: String,
+    /// The URL of the page in this language, relative to the page in
+    /// the current language. This is the same as `url` if the two are
+    /// served from different hosts.
+    pub relative_url: String,
+}
+
+/// The languages of the book, for use in templates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateContext {
+    /// All translations of the book, in the order from `book.toml`.
+    pub languages: Vec<TemplateLanguage>,
+    /// The language of the page, such as `da`.
+    pub current_language: Option<String>,
+}
+
+/// Split `url` into the scheme and host, and the path.
+fn split_origin(url: &str) -> (&str, &str) {
+    let Some(start) = url.find("://").map(|idx| idx + 3) else {
+        return ("", url);
+    };
+    let end = url[start..].find('/').map_or(url.len(), |idx| start + idx);
+    url.split_at(end)
+}
+
+/// Make the URL `to` relative to the page at `from`.
+fn relative_url(from: &str, to: &str) -> String {
+    let (from_origin, from_path) = split_origin(from);
+    let (to_origin, to_path) = split_origin(to);
+    if from_origin != to_origin || !from_path.starts_with('/') || !to_path.starts_with('/') {
+        return String::from(to);
+    }
+    let from_segments = from_path[1..].split('/').collect::<Vec<_>>();
+    let to_segments = to_path[1..].split('/').collect::<Vec<_>>();
+    let from_dirs = &from_segments[..from_segments.len() - 1];
+    let common = from_dirs
+        .iter()
+        .zip(&to_segments[..to_segments.len() - 1])
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut url = "../".repeat(from_dirs.len() - common);
+    url.push_str(&to_segments[common..].join("/"));
+    url
+}
+
+/// Describe the `languages` for the page of the chapter at
+/// `chapter_path` in the `current` language.
+///
+/// Template-based renderers can add this to the context of each page
+/// to build a language switcher and `hreflang` links.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::language_switcher::{template_context, BookLanguage};
+/// use std::path::Path;
+///
+/// let languages = ["en", "da"]
+///     .iter()
+///     .map(|language| BookLanguage {
+///         language: String::from(*language),
+///         name: String::from(*language),
+///         url: format!("/{language}/"),
+///     })
+///     .collect::<Vec<_>>();
+/// let context = template_context(&languages, Some("en"), Path::new("guide/setup.md"));
+/// assert_eq!(context.languages[1].url, "/da/guide/setup.html");
+/// assert_eq!(context.languages[1].relative_url, "../../da/guide/setup.html");
+/// ```
+pub fn template_context(
+    languages: &[BookLanguage],
+    current: Option<&str>,
+    chapter_path: &Path,
+) -> TemplateContext {
+    let current_url = languages
+        .iter()
+        .find(|language| Some(language.language.as_str()) == current)
+        .map(|language| chapter_url(language, chapter_path));
+    let languages = languages
+        .iter()
+        .map(|language| {
+            let url = chapter_url(language, chapter_path);
+            TemplateLanguage {
+                language: language.language.clone(),
+                name: language.name.clone(),
+                relative_url: match &current_url {
+                    Some(current_url) => relative_url(current_url, &url),
+                    None => url.clone(),
+                },
+                url,
+            }
+        })
+        .collect();
+    TemplateContext {
+        languages,
+        current_language: current.map(String::from),
+    }
+}
+
+/// Escape `text` for use in XML.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Create a `sitemap.xml` file with the chapters at `chapter_paths`.
+///
+/// The sitemap lists the chapters of each of the `languages`, or only
+/// the chapters of `current` if it is set, so each translation can
+/// have its own sitemap. Every entry links to the same chapter in the
+/// other languages with `hreflang`, and the first language is used
+/// for `x-default`. Search engines require absolute URLs in sitemaps.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::language_switcher::{sitemap, BookLanguage};
+/// use std::path::Path;
+///
+/// let languages = vec![BookLanguage {
+///     language: String::from("da"),
+///     name: String::from("Dansk"),
+///     url: String::from("https://example.com/da/"),
+/// }];
+/// let sitemap = sitemap(&languages, None, [Path::new("guide/setup.md")]);
+/// assert!(sitemap.contains("<loc>https://example.com/da/guide/setup.html</loc>"));
+/// ```
+pub fn sitemap<'a>(
+    languages: &[BookLanguage],
+    current: Option<&str>,
+    chapter_paths: impl IntoIterator<Item = &'a Path>,
+) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" \
+         xmlns:xhtml=\"http://www.w3.org/1999/xhtml\">\n",
+    );
+    for chapter_path in chapter_paths {
+        let mut alternates = languages
+            .iter()
+            .map(|language| {
+                (
+                    language.language.as_str(),
+                    chapter_url(language, chapter_path),
+                )
+            })
+            .collect::<Vec<_>>();
+        if let Some(default) = languages.first() {
+            alternates.push(("x-default", chapter_url(default, chapter_path)));
+        }
+        for language in languages {
+            if current.is_some_and(|current| current != language.language) {
+                continue;
+            }
+            xml.push_str("  <url>\n");
+            xml.push_str(&format!(
+                "    <loc>{}</loc>\n",
+                escape_xml(&chapter_url(language, chapter_path))
+            ));
+            for (hreflang, href) in &alternates {
+                xml.push_str(&format!(
+                    "    <xhtml:link rel=\"alternate\" hreflang=\"{}\" href=\"{}\"/>\n",
+                    escape_xml(hreflang),
+                    escape_xml(href)
+                ));
+            }
+            xml.push_str("  </url>\n");
+        }
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_book_languages() {
+        let config: mdbook::Config = "[[preprocessor.gettext.languages]]\n\
+                                      language = \"en\"\n\
+                                      name = \"English\"\n\
+                                      url = \"/\"\n\
+                                      [[preprocessor.gettext.languages]]\n\
+                                      language = \"da\"\n\
+                                      name = \"Dansk\"\n\
+                                      url = \"https://example.com/da\"\n"
+            .parse()
+            .unwrap();
+        let languages = book_languages(&config).unwrap();
+        assert_eq!(
+            languages
+                .iter()
+                .map(|l| l.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["English", "Dansk"]
+        );
+        let script = switcher_script(&languages, None, Path::new("README.md"));
+        assert!(script.contains(r#""href":"/index.html""#));
+        assert!(script.contains(r#""href":"https://example.com/da/index.html""#));
+        assert!(script.contains("const current = null;"));
+    }
+
+    #[test]
+    fn test_book_languages_invalid() {
+        let config: mdbook::Config = "[preprocessor.gettext]\n\
+                                      languages = [{ language = \"da\" }]\n"
+            .parse()
+            .unwrap();
+        assert!(book_languages(&config).is_err());
+    }
+
+    #[test]
+    fn test_canonical_script() {
+        let languages = ["en", "da"]
+            .iter()
+            .map(|language| BookLanguage {
+                language: String::from(*language),
+                name: String::from(*language),
+                url: format!("https://example.com/{language}/"),
+            })
+            .collect::<Vec<_>>();
+        let script = canonical_script(&languages, Some("da"), Path::new("foo.md"));
+        assert!(script.contains(
+            r#"[["canonical",null,"https://example.com/da/foo.html"],["alternate","en","https://example.com/en/foo.html"],["alternate","da","https://example.com/da/foo.html"],["alternate","x-default","https://example.com/en/foo.html"]]"#
+        ));
+        assert_eq!(
+            canonical_script(&languages, Some("fr"), Path::new("foo.md")),
+            ""
+        );
+        assert_eq!(canonical_script(&languages, None, Path::new("foo.md")), "");
+    }
+
+    #[test]
+    fn test_relative_url() {
+        assert_eq!(relative_url("/foo.html", "/da/foo.html"), "da/foo.html");
+        assert_eq!(relative_url("/da/a/b.html", "/a/b.html"), "../../a/b.html");
+        assert_eq!(relative_url("/da/a/b.html", "/da/a/c.html"), "c.html");
+        assert_eq!(
+            relative_url(
+                "https://example.com/da/foo.html",
+                "https://example.com/foo.html"
+            ),
+            "../foo.html"
+        );
+        assert_eq!(
+            relative_url(
+                "https://example.com/foo.html",
+                "https://da.example.com/foo.html"
+            ),
+            "https://da.example.com/foo.html"
+        );
+    }
+
+    #[test]
+    fn test_html_path() {
+        assert_eq!(html_path(Path::new("foo.md")), "foo.html");
+        assert_eq!(html_path(Path::new("guide/README.md")), "guide/index.html");
+        assert_eq!(html_path(Path::new("guide/index.md")), "guide/index.html");
+    }
+
+    #[test]
+    fn test_sitemap() {
+        let languages = ["en", "da"]
+            .iter()
+            .map(|language| BookLanguage {
+                language: String::from(*language),
+                name: String::from(*language),
+                url: format!("https://example.com/{language}/"),
+            })
+            .collect::<Vec<_>>();
+        let chapters = [Path::new("README.md"), Path::new("a&b.md")];
+        assert_eq!(
+            sitemap(&languages, Some("da"), chapters),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" \
+             xmlns:xhtml=\"http://www.w3.org/1999/xhtml\">\n\
+             \x20 <url>\n\
+             \x20   <loc>https://example.com/da/index.html</loc>\n\
+             \x20   <xhtml:link rel=\"alternate\" hreflang=\"en\" href=\"https://example.com/en/index.html\"/>\n\
+             \x20   <xhtml:link rel=\"alternate\" hreflang=\"da\" href=\"https://example.com/da/index.html\"/>\n\
+             \x20   <xhtml:link rel=\"alternate\" hreflang=\"x-default\" href=\"https://example.com/en/index.html\"/>\n\
+             \x20 </url>\n\
+             \x20 <url>\n\
+             \x20   <loc>https://example.com/da/a&amp;b.html</loc>\n\
+             \x20   <xhtml:link rel=\"alternate\" hreflang=\"en\" href=\"https://example.com/en/a&amp;b.html\"/>\n\
+             \x20   <xhtml:link rel=\"alternate\" hreflang=\"da\" href=\"https://example.com/da/a&amp;b.html\"/>\n\
+             \x20   <xhtml:link rel=\"alternate\" hreflang=\"x-default\" href=\"https://example.com/en/a&amp;b.html\"/>\n\
+             \x20 </url>\n\
+             </urlset>\n"
+        );
+        assert_eq!(
+            sitemap(&languages, None, chapters).matches("<url>").count(),
+            4
+        );
+    }
+}
@@ -22,11 +22,908 @@
 //!
 //! See <https://github.com/google/mdbook-i18n-helpers> for details on
 //! how to use the supplied `mdbook` plugins.
+//!
+//! # Stability
+//!
+//! The functions in [`standalone`] follow semantic versioning. The
+//! lower-level functions here expose the events of the Markdown
+//! parser, so they change when the parser changes. When such a change
+//! affects the public API, the release notes in `CHANGELOG.md`
+//! describe how to migrate and the old API is kept with a deprecation
+//! warning for at least one release where possible. Use the accessors
+//! on [`Group`] instead of matching on its variants to be less
+//! affected by these changes.
+
+pub mod alignment;
+pub mod anchors;
+pub mod backdate;
+pub mod catalog_io;
+pub mod chapter_status;
+pub mod code_blocks;
+pub mod config_keys;
+pub mod content_rules;
+mod frontmatter;
+pub mod heading_case;
+mod html;
+pub mod kinds;
+pub mod language;
+pub mod language_switcher;
+pub mod markup;
+pub mod numbering;
+pub mod obsolete;
+mod placeholders;
+pub mod segmentation;
+pub mod sentences;
+pub mod source_hash;
+pub mod sources;
+pub mod standalone;
+pub mod state;
+pub mod symbols;
+pub mod template_functions;
+#[cfg(test)]
+mod test_utils;
+pub mod theme_strings;
+pub mod translator;
 
-use mdbook::utils::new_cmark_parser;
+use anyhow::{anyhow, Context};
 use polib::catalog::Catalog;
-use pulldown_cmark::{Event, Tag};
+use pulldown_cmark::{
+    BrokenLink, BrokenLinkCallback, CodeBlockKind, Event, MetadataBlockKind, Parser, Tag, TagEnd,
+};
 use pulldown_cmark_to_cmark::{cmark_resume_with_options, Options, State};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+/// Configuration for how Markdown is turned into messages.
+///
+/// The same configuration must be used when extracting the messages
+/// and when translating them, otherwise the messages won't match. The
+/// `mdbook-xgettext` renderer and the `mdbook-gettext` preprocessor
+/// both read it from the `output.xgettext` table in `book.toml`, see
+/// [`MarkdownConfig::from_book_config`].
+///
+/// It also decides which Markdown syntax is supported, see
+/// [`MarkdownConfig::parser_options`]. Tools which parse the Markdown
+/// themselves should use [`MarkdownConfig::parser`] so they agree with
+/// the extraction.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MarkdownConfig {
+    /// Opening and closing delimiters of math, such as `("\\(", "\\)")`.
+    ///
+    /// Math written as `$...$` or `$$...$$` is always recognized by
+    /// the parser, this is for the other delimiters supported by
+    /// MathJax.
+    ///
+    /// Math is kept verbatim: special Markdown characters between the
+    /// delimiters are not escaped when the Markdown is reconstructed.
+    /// Math in a paragraph stays part of the surrounding message.
+    pub math_delimiters: Vec<(String, String)>,
+
+    /// Extract the text of raw HTML blocks.
+    ///
+    /// Raw HTML is normally skipped. With this setting, each run of
+    /// text between block-level tags, such as the cells of a
+    /// `<table>`, becomes a message. Inline tags, such as `<b>`, are
+    /// kept in the message.
+    pub extract_html: bool,
+
+    /// Skip messages which only consist of math.
+    ///
+    /// Math is normally part of the messages, just like other inline
+    /// Markdown. With this setting, paragraphs with nothing but math,
+    /// such as display math on its own, are neither extracted nor
+    /// translated.
+    pub skip_math: bool,
+
+    /// Keys of the YAML front matter to translate, such as `title`.
+    ///
+    /// Front matter is a block delimited by `---` lines. It is only
+    /// recognized when this list is non-empty, otherwise the lines are
+    /// parsed as normal Markdown. The values of the listed top-level
+    /// keys become messages, the rest of the front matter is kept
+    /// unchanged. Only values written on the same line as the key are
+    /// supported.
+    pub frontmatter_keys: Vec<String>,
+
+    /// Keep reference links, such as `[foo][1]`, in the messages.
+    ///
+    /// Reference links are normally turned into inline links, which
+    /// puts the URL into the message. With this setting, the messages
+    /// only have the label of the link, so they don't change when the
+    /// URL changes. Collapsed and shortcut references, such as
+    /// `[foo][]` and `[foo]`, are written as `[foo][foo]` so that the
+    /// link text can be translated. Use [`reconstruct_document`] to
+    /// write the link reference definitions with the translation.
+    pub keep_reference_links: bool,
+
+    /// Replace inline markup with numbered placeholders.
+    ///
+    /// Inline code and inline HTML become placeholders such as `{1}`
+    /// in the messages, and links become `{1}` and `{/1}` around the
+    /// link text. The markup is described in an extracted comment,
+    /// see [`extract_messages_with_comments`]. This keeps URLs and
+    /// attributes from being changed by mistake in the translations.
+    pub placeholders: bool,
+
+    /// Describe the kind of each message in an extracted comment.
+    ///
+    /// The comment says where the message comes from, such as
+    /// `heading`, `list item`, `table cell`, `image alt text`, or
+    /// `code block (rust)`. This helps translators decide on the
+    /// capitalization and length of the translation. It only affects
+    /// the comments, so the setting doesn't have to match between
+    /// extraction and translation.
+    pub kind_comments: bool,
+
+    /// Add an extracted comment with a hash of each message and its
+    /// neighbors.
+    ///
+    /// The comment looks like `source-hash: 3f2a9c1e`, see
+    /// [`source_hash`]. Like [`MarkdownConfig::kind_comments`], it
+    /// only affects the comments.
+    pub source_hashes: bool,
+
+    /// Split code blocks into their comments and strings, using the
+    /// rule for the fence token of each code block.
+    ///
+    /// Code blocks are normally extracted as a whole. With rules, each
+    /// comment and string literal of a code block with a rule becomes
+    /// a message, and the rest of the code is kept as it is. Code
+    /// blocks without a rule are still extracted as a whole. See
+    /// [`code_blocks`] for the default rules.
+    pub code_rules: Option<BTreeMap<String, code_blocks::CodeRule>>,
+
+    /// Guess the language of code blocks without a fence token.
+    ///
+    /// The rule for the guessed language in
+    /// [`MarkdownConfig::code_rules`] is then used to split the code
+    /// block, see [`code_blocks::detect_language`]. Code blocks whose
+    /// language can't be guessed are extracted as a whole. This has
+    /// no effect without `code_rules`.
+    pub detect_code_languages: bool,
+
+    /// Parse superscript and subscript, such as `^2^` and `~2~`.
+    ///
+    /// This is for books whose renderer supports them. The markup is
+    /// kept in the messages either way, but with this set, a single
+    /// `~` is no longer read as strikethrough.
+    pub superscript_subscript: bool,
+}
+
+impl MarkdownConfig {
+    /// Read the configuration from the `output.xgettext` table.
+    ///
+    /// The `math-delimiters` key is a list of `[open, close]` pairs
+    /// while `extract-html`, `skip-math`, `keep-reference-links`,
+    /// `placeholders`, `kind-comments`, `source-hashes`, and
+    /// `split-code-blocks` are booleans. The `frontmatter-keys` key is
+    /// a list of strings. The `code-rules` key is a table of rules
+    /// which are added to the default rules. Both `code-rules` and the
+    /// boolean `detect-code-languages` imply `split-code-blocks`.
+    pub fn from_book_config(config: &mdbook::Config) -> anyhow::Result<Self> {
+        let mut markdown_config = MarkdownConfig::default();
+        if let Some(value) = config.get("output.xgettext.math-delimiters") {
+            markdown_config.math_delimiters = parse_delimiters(value)
+                .context("Could not parse output.xgettext.math-delimiters")?;
+        }
+        if let Some(value) = config.get("output.xgettext.extract-html") {
+            markdown_config.extract_html =
+                parse_bool(value).context("Could not parse output.xgettext.extract-html")?;
+        }
+        if let Some(value) = config.get("output.xgettext.skip-math") {
+            markdown_config.skip_math =
+                parse_bool(value).context("Could not parse output.xgettext.skip-math")?;
+        }
+        if let Some(value) = config.get("output.xgettext.keep-reference-links") {
+            markdown_config.keep_reference_links = parse_bool(value)
+                .context("Could not parse output.xgettext.keep-reference-links")?;
+        }
+        if let Some(value) = config.get("output.xgettext.kind-comments") {
+            markdown_config.kind_comments =
+                parse_bool(value).context("Could not parse output.xgettext.kind-comments")?;
+        }
+        if let Some(value) = config.get("output.xgettext.source-hashes") {
+            markdown_config.source_hashes =
+                parse_bool(value).context("Could not parse output.xgettext.source-hashes")?;
+        }
+        if let Some(value) = config.get("output.xgettext.placeholders") {
+            markdown_config.placeholders =
+                parse_bool(value).context("Could not parse output.xgettext.placeholders")?;
+        }
+        if let Some(value) = config.get("output.xgettext.frontmatter-keys") {
+            markdown_config.frontmatter_keys =
+                parse_strings(value).context("Could not parse output.xgettext.frontmatter-keys")?;
+        }
+        if let Some(value) = config.get("output.xgettext.split-code-blocks") {
+            if parse_bool(value).context("Could not parse output.xgettext.split-code-blocks")? {
+                markdown_config.code_rules = Some(code_blocks::default_rules());
+            }
+        }
+        if let Some(value) = config.get("output.xgettext.code-rules") {
+            markdown_config.code_rules = Some(
+                code_blocks::parse_rules(value)
+                    .context("Could not parse output.xgettext.code-rules")?,
+            );
+        }
+        if let Some(value) = config.get("output.xgettext.superscript-subscript") {
+            markdown_config.superscript_subscript = parse_bool(value)
+                .context("Could not parse output.xgettext.superscript-subscript")?;
+        }
+        if let Some(value) = config.get("output.xgettext.detect-code-languages") {
+            markdown_config.detect_code_languages = parse_bool(value)
+                .context("Could not parse output.xgettext.detect-code-languages")?;
+            if markdown_config.detect_code_languages && markdown_config.code_rules.is_none() {
+                markdown_config.code_rules = Some(code_blocks::default_rules());
+            }
+        }
+        Ok(markdown_config)
+    }
+
+    /// The Markdown extensions to parse.
+    ///
+    /// This enables the same Markdown extensions as `mdbook`. We use
+    /// the old footnote syntax since it allows references to footnotes
+    /// defined outside of the text, as is the case when a message is
+    /// translated on its own. Math written as `$...$` or `$$...$$` is
+    /// parsed into `InlineMath` and `DisplayMath` events as well, and
+    /// the kind of GitHub-style alerts such as `> [!NOTE]` is stored in
+    /// the `BlockQuote` tag. Definition lists are parsed too.
+    /// Superscript and subscript are parsed when
+    /// [`MarkdownConfig::superscript_subscript`] is set.
+    ///
+    /// YAML front matter is only parsed when there are
+    /// [`MarkdownConfig::frontmatter_keys`] to translate, since
+    /// `mdbook` itself doesn't support it.
+    pub fn parser_options(&self) -> pulldown_cmark::Options {
+        let mut options = pulldown_cmark::Options::empty();
+        options.insert(pulldown_cmark::Options::ENABLE_TABLES);
+        options.insert(pulldown_cmark::Options::ENABLE_OLD_FOOTNOTES);
+        options.insert(pulldown_cmark::Options::ENABLE_STRIKETHROUGH);
+        options.insert(pulldown_cmark::Options::ENABLE_TASKLISTS);
+        options.insert(pulldown_cmark::Options::ENABLE_HEADING_ATTRIBUTES);
+        options.insert(pulldown_cmark::Options::ENABLE_MATH);
+        options.insert(pulldown_cmark::Options::ENABLE_GFM);
+        options.insert(pulldown_cmark::Options::ENABLE_DEFINITION_LIST);
+        if !self.frontmatter_keys.is_empty() {
+            options.insert(pulldown_cmark::Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+        }
+        if self.superscript_subscript {
+            options.insert(pulldown_cmark::Options::ENABLE_SUPERSCRIPT);
+            options.insert(pulldown_cmark::Options::ENABLE_SUBSCRIPT);
+        }
+        options
+    }
+
+    /// Create a parser for `text` with the same extensions as used for
+    /// extracting and translating messages.
+    ///
+    /// Use this when looking at the Markdown outside of the messages,
+    /// so all tools agree on the supported syntax. Unlike the
+    /// extraction, reference links are left as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::MarkdownConfig;
+    /// use pulldown_cmark::{Event, Tag};
+    ///
+    /// let config = MarkdownConfig::default();
+    /// let mut events = config.parser("| A |\n|---|\n| B |\n");
+    /// assert!(matches!(events.next(), Some(Event::Start(Tag::Table(_)))));
+    /// ```
+    pub fn parser<'a>(&self, text: &'a str) -> Parser<'a> {
+        Parser::new_ext(text, self.parser_options())
+    }
+}
+
+/// Parse a boolean.
+fn parse_bool(value: &toml::Value) -> anyhow::Result<bool> {
+    value
+        .as_bool()
+        .ok_or_else(|| anyhow!("Expected a boolean, got {value}"))
+}
+
+/// Parse a list of strings.
+fn parse_strings(value: &toml::Value) -> anyhow::Result<Vec<String>> {
+    let values = value
+        .as_array()
+        .ok_or_else(|| anyhow!("Expected a list of strings"))?;
+    values
+        .iter()
+        .map(|value| {
+            value
+                .as_str()
+                .map(String::from)
+                .ok_or_else(|| anyhow!("Expected a string, got {value}"))
+        })
+        .collect()
+}
+
+/// Parse a list of `[open, close]` string pairs.
+fn parse_delimiters(value: &toml::Value) -> anyhow::Result<Vec<(String, String)>> {
+    let pairs = value
+        .as_array()
+        .ok_or_else(|| anyhow!("Expected a list of [open, close] pairs"))?;
+    pairs
+        .iter()
+        .map(|pair| match pair.as_array().map(Vec::as_slice) {
+            Some([open, close]) => match (open.as_str(), close.as_str()) {
+                (Some(open), Some(close)) if !open.is_empty() && !close.is_empty() => {
+                    Ok((String::from(open), String::from(close)))
+                }
+                _ => Err(anyhow!("Expected non-empty strings in {pair}")),
+            },
+            _ => Err(anyhow!("Expected an [open, close] pair, got {pair}")),
+        })
+        .collect()
+}
+
+/// Find the math spans in `text`.
+///
+/// A span starts with one of the opening delimiters and ends with
+/// the matching closing delimiter. Delimiters escaped with a
+/// backslash are ignored.
+fn find_math_spans(text: &str, delimiters: &[(String, String)]) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    loop {
+        let next_open = delimiters
+            .iter()
+            .filter_map(|(open, close)| {
+                text[pos..]
+                    .match_indices(open.as_str())
+                    .map(|(idx, _)| pos + idx)
+                    .find(|&idx| !text[..idx].ends_with('\\'))
+                    .map(|idx| (idx, open, close))
+            })
+            .min_by_key(|(idx, _, _)| *idx);
+        let Some((start, open, close)) = next_open else {
+            break;
+        };
+        let content_start = start + open.len();
+        let Some(end) = text[content_start..]
+            .find(close.as_str())
+            .map(|idx| content_start + idx + close.len())
+        else {
+            break;
+        };
+        spans.push(start..end);
+        pos = end;
+    }
+    spans
+}
+
+/// Replace math in a run of text events with verbatim inline HTML
+/// events.
+///
+/// The events in `run` must be `Text` or `SoftBreak` events. The raw
+/// text of the run is reconstructed from the byte ranges in the
+/// source `text`: this brings back the backslashes which the parser
+/// removed from escaped characters, such as `\(`. Math is then
+/// emitted as an `InlineHtml` event since these are not escaped when
+/// the Markdown is reconstructed.
+fn protect_math_run<'a>(
+    text: &'a str,
+    run: &[(Event<'a>, Range<usize>)],
+    delimiters: &[(String, String)],
+) -> Vec<(Event<'a>, Range<usize>)> {
+    // The raw text of the run and the position of each event in it.
+    let mut raw = String::new();
+    let mut atoms = Vec::new();
+    let mut prev_end = None;
+    for (event, range) in run {
+        let start = raw.len();
+        match event {
+            Event::SoftBreak => {
+                // Skip the line prefix after a line break: it can
+                // contain indentation and block quote markers.
+                raw.push('\n');
+                prev_end = None;
+            }
+            _ => {
+                match prev_end {
+                    Some(prev_end) => raw.push_str(&text[prev_end..range.start]),
+                    // The range of an escaped character at the start
+                    // of a line excludes the backslash.
+                    None if text[..range.start].ends_with('\\') => raw.push('\\'),
+                    None => {}
+                }
+                raw.push_str(&text[range.clone()]);
+                prev_end = Some(range.end);
+            }
+        }
+        atoms.push(start..raw.len());
+    }
+
+    let spans = find_math_spans(&raw, delimiters);
+    if spans.is_empty() {
+        return run.to_vec();
+    }
+
+    // Split the raw text into alternating segments of text and math.
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    for span in spans {
+        if pos < span.start {
+            segments.push((false, pos..span.start));
+        }
+        pos = span.end;
+        segments.push((true, span));
+    }
+    if pos < raw.len() {
+        segments.push((false, pos..raw.len()));
+    }
+
+    let mut events = Vec::new();
+    for (is_math, segment) in segments {
+        let overlapping = run
+            .iter()
+            .zip(&atoms)
+            .filter(|(_, atom)| atom.start < segment.end && segment.start < atom.end);
+        if is_math {
+            let range = overlapping
+                .map(|((_, range), _)| range.clone())
+                .next()
+                .unwrap_or_default();
+            let math = String::from(&raw[segment]);
+            events.push((Event::InlineHtml(math.into()), range));
+            continue;
+        }
+        for ((event, range), atom) in overlapping {
+            if segment.start <= atom.start && atom.end <= segment.end {
+                events.push((event.clone(), range.clone()));
+            } else {
+                let start = std::cmp::max(segment.start, atom.start);
+                let end = std::cmp::min(segment.end, atom.end);
+                let text = raw[start..end].replace('\n', " ");
+                events.push((Event::Text(text.into()), range.clone()));
+            }
+        }
+    }
+    events
+}
+
+/// Replace math in `events` with verbatim inline HTML events.
+///
+/// See [`MarkdownConfig::math_delimiters`] for details.
+fn protect_math<'a>(
+    text: &'a str,
+    events: Vec<(Event<'a>, Range<usize>)>,
+    delimiters: &[(String, String)],
+) -> Vec<(Event<'a>, Range<usize>)> {
+    let mut protected = Vec::with_capacity(events.len());
+    let mut run = Vec::new();
+    for (event, range) in events {
+        if let Event::Text(_) | Event::SoftBreak = event {
+            run.push((event, range));
+            continue;
+        }
+        protected.extend(protect_math_run(text, &run, delimiters));
+        run.clear();
+        protected.push((event, range));
+    }
+    protected.extend(protect_math_run(text, &run, delimiters));
+    protected
+}
+
+/// Check if `event` is verbatim math.
+///
+/// The math is found by [`protect_math`]. It is represented by an
+/// `InlineHtml` event, but unlike HTML from the parser, it doesn't
+/// start with `<`.
+fn is_verbatim_math(event: &Event) -> bool {
+    matches!(event, Event::InlineHtml(html) if !html.starts_with('<'))
+}
+
+/// Check if `events` only contain math and whitespace.
+fn is_math_only(events: &[(usize, Event)]) -> bool {
+    let mut has_math = false;
+    for (_, event) in events {
+        match event {
+            Event::InlineMath(_) | Event::DisplayMath(_) => has_math = true,
+            event if is_verbatim_math(event) => has_math = true,
+            Event::Start(Tag::Paragraph)
+            | Event::End(TagEnd::Paragraph)
+            | Event::SoftBreak
+            | Event::HardBreak => {}
+            Event::Text(text) if text.trim().is_empty() => {}
+            _ => return false,
+        }
+    }
+    has_math
+}
+
+/// Find the runs of consecutive `Html` events in `events`.
+fn html_event_runs(events: &[(usize, Event)]) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (idx, (_, event)) in events.iter().enumerate() {
+        if !matches!(event, Event::Html(_)) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.end == idx => run.end = idx + 1,
+            _ => runs.push(idx..idx + 1),
+        }
+    }
+    runs
+}
+
+/// Join the HTML in `events` and return the line number and offset
+/// of each event in the joined string.
+fn join_html(events: &[(usize, Event)]) -> (String, Vec<(usize, usize)>) {
+    let mut html = String::new();
+    let mut positions = Vec::new();
+    for (lineno, event) in events {
+        if let Event::Html(text) = event {
+            positions.push((*lineno, html.len()));
+            html.push_str(text);
+        }
+    }
+    (html, positions)
+}
+
+/// Extract messages from the raw HTML in `events`.
+fn extract_html_messages(events: &[(usize, Event)]) -> Vec<(usize, String)> {
+    let mut messages = Vec::new();
+    for run in html_event_runs(events) {
+        let (html, positions) = join_html(&events[run]);
+        for range in html::text_runs(&html) {
+            let idx = positions.partition_point(|&(_, offset)| offset <= range.start) - 1;
+            let (lineno, offset) = positions[idx];
+            let lineno = lineno + html[offset..range.start].matches('\n').count();
+            messages.push((lineno, html::normalize_whitespace(&html[range])));
+        }
+    }
+    messages
+}
+
+/// Translate the raw HTML in `events` using `catalog`.
+///
+/// Each run of `Html` events with a translation is replaced by a
+/// single `Html` event. Other events are returned unchanged.
+fn translate_html_events<'a>(
+    events: &[(usize, Event<'a>)],
+    catalog: &Catalog,
+) -> Vec<(usize, Event<'a>)> {
+    let mut translated_events = Vec::new();
+    let mut pos = 0;
+    for run in html_event_runs(events) {
+        translated_events.extend_from_slice(&events[pos..run.start]);
+        pos = run.end;
+        let (html, _) = join_html(&events[run.clone()]);
+        let mut translated = String::new();
+        let mut text_end = 0;
+        for range in html::text_runs(&html) {
+            let msgid = html::normalize_whitespace(&html[range.clone()]);
+            if let Some(msgstr) = find_translation(catalog, &msgid) {
+                translated.push_str(&html[text_end..range.start]);
+                translated.push_str(msgstr);
+                text_end = range.end;
+            }
+        }
+        if text_end == 0 {
+            translated_events.extend_from_slice(&events[run]);
+        } else {
+            translated.push_str(&html[text_end..]);
+            translated_events.push((events[run.start].0, Event::Html(translated.into())));
+        }
+    }
+    translated_events.extend_from_slice(&events[pos..]);
+    translated_events
+}
+
+/// Find the YAML front matter in `events`.
+///
+/// Returns the index and line number of each `Text` event in a YAML
+/// metadata block, together with its text.
+fn frontmatter_events<'b>(events: &'b [(usize, Event)]) -> Vec<(usize, usize, &'b str)> {
+    let mut found = Vec::new();
+    for (idx, pair) in events.windows(2).enumerate() {
+        if let [(_, Event::Start(Tag::MetadataBlock(MetadataBlockKind::YamlStyle))), (lineno, Event::Text(yaml))] =
+            pair
+        {
+            found.push((idx + 1, *lineno, yaml.as_ref()));
+        }
+    }
+    found
+}
+
+/// Extract messages from the YAML front matter in `events`.
+fn extract_frontmatter_messages(
+    events: &[(usize, Event)],
+    keys: &[String],
+) -> Vec<(usize, String)> {
+    let mut messages = Vec::new();
+    for (_, lineno, yaml) in frontmatter_events(events) {
+        for value in frontmatter::find_values(yaml, keys) {
+            messages.push((lineno + value.line, value.text));
+        }
+    }
+    messages
+}
+
+/// Translate the YAML front matter in `events` using `catalog`.
+///
+/// The values of `keys` are replaced, other events are returned
+/// unchanged.
+fn translate_frontmatter_events<'a>(
+    events: &[(usize, Event<'a>)],
+    catalog: &Catalog,
+    keys: &[String],
+) -> Vec<(usize, Event<'a>)> {
+    let mut translated_events = events.to_vec();
+    for (idx, lineno, yaml) in frontmatter_events(events) {
+        let translated =
+            frontmatter::replace_values(yaml, keys, |msgid| find_translation(catalog, msgid));
+        translated_events[idx] = (lineno, Event::Text(translated.into()));
+    }
+    translated_events
+}
+
+/// Find the rule, line, and code of `events` if they are a code block
+/// which is split into comments and strings.
+///
+/// See [`MarkdownConfig::code_rules`] and
+/// [`MarkdownConfig::detect_code_languages`].
+fn split_code_block<'c>(
+    events: &[(usize, Event)],
+    config: &'c MarkdownConfig,
+) -> Option<(&'c code_blocks::CodeRule, usize, String)> {
+    let rules = config.code_rules.as_ref()?;
+    let Some((start, Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))))) = events.first()
+    else {
+        return None;
+    };
+    let mut lineno = None;
+    let mut code = String::new();
+    for (event_lineno, event) in events {
+        if let Event::Text(text) = event {
+            lineno.get_or_insert(*event_lineno);
+            code.push_str(text);
+        }
+    }
+    let rule = match info.split([' ', ',']).next() {
+        Some(token) if !token.is_empty() => rules.get(token)?,
+        _ if config.detect_code_languages => rules.get(code_blocks::detect_language(&code)?)?,
+        _ => return None,
+    };
+    Some((rule, lineno.unwrap_or(start + 1), code))
+}
+
+/// Extract the comments and strings of `code`, which starts at
+/// `lineno`.
+///
+/// Returns the line and the byte range in `code` of each message.
+fn extract_code_messages(
+    lineno: usize,
+    code: &str,
+    rule: &code_blocks::CodeRule,
+) -> Vec<(usize, Range<usize>, String)> {
+    code_blocks::code_segments(code, rule)
+        .into_iter()
+        .map(|range| {
+            let lineno = lineno + code[..range.start].matches('\n').count();
+            (lineno, range.clone(), String::from(&code[range]))
+        })
+        .collect()
+}
+
+/// Find the byte offset in the document of the byte `offset` in the
+/// code of a code block.
+///
+/// The code is the text of the `events` of the code block, which are
+/// at the byte `ranges` of the document.
+fn code_offset(events: &[(usize, Event)], ranges: &[Range<usize>], offset: usize) -> usize {
+    let mut code_len = 0;
+    for ((_, event), range) in events.iter().zip(ranges) {
+        let Event::Text(text) = event else {
+            continue;
+        };
+        if offset < code_len + text.len() {
+            // The text of indented lines can be shorter than its range.
+            let delta = offset - code_len;
+            return range.start + delta.min(range.len());
+        }
+        code_len += text.len();
+    }
+    ranges.last().map_or(0, |range| range.end)
+}
+
+/// Translate the comments and strings in the code block `events`,
+/// which has the given `code`.
+///
+/// Translations which would break the code, such as a translation
+/// which ends a string early, are not used. See
+/// [`code_blocks::translate_code`].
+fn translate_code_events<'a>(
+    events: &[(usize, Event<'a>)],
+    lineno: usize,
+    code: &str,
+    rule: &code_blocks::CodeRule,
+    catalog: &Catalog,
+) -> Vec<(usize, Event<'a>)> {
+    let translated =
+        code_blocks::translate_code(code, rule, |msgid| find_translation(catalog, msgid));
+    let mut translated_events = events[..1].to_vec();
+    if !translated.is_empty() {
+        translated_events.push((lineno, Event::Text(translated.into())));
+    }
+    translated_events.extend_from_slice(&events[events.len() - 1..]);
+    translated_events
+}
+
+/// Repair the translation `msgstr` of a code block in `msgid` if it
+/// lost the markers of its comment lines.
+///
+/// The rules are [`MarkdownConfig::code_rules`] or the default rules,
+/// see [`code_blocks::repair_comment_markers`].
+fn repair_code_comments(msgid: &str, msgstr: &str, config: &MarkdownConfig) -> Option<String> {
+    if !msgid.starts_with("```") && !msgid.starts_with("~~~") {
+        return None;
+    }
+    match &config.code_rules {
+        Some(rules) => code_blocks::repair_comment_markers(msgid, msgstr, rules),
+        None => code_blocks::repair_comment_markers(msgid, msgstr, &code_blocks::default_rules()),
+    }
+}
+
+/// Create a new Markdown parser for `text`.
+///
+/// The parser uses [`MarkdownConfig::parser_options`]. References to
+/// links which are not defined in `text` are resolved using `links`,
+/// see [`link_definitions`].
+fn new_cmark_parser<'a, 'links>(
+    text: &'a str,
+    config: &MarkdownConfig,
+    links: &'links LinkDefinitions,
+) -> Parser<'a, impl BrokenLinkCallback<'a> + 'links> {
+    let callback = move |link: BrokenLink| {
+        links
+            .get(&normalize_label(&link.reference))
+            .map(|(dest_url, title)| (dest_url.clone().into(), title.clone().into()))
+    };
+    Parser::new_with_broken_link_callback(text, config.parser_options(), Some(callback))
+}
+
+/// The destination and title of links by their normalized label.
+type LinkDefinitions = HashMap<String, (String, String)>;
+
+/// Normalize a link label for matching.
+///
+/// Like in CommonMark, labels are matched case-insensitively and with
+/// runs of whitespace collapsed.
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Collect the definitions of the reference links in `events`.
+///
+/// The link reference definitions themselves are not part of the
+/// events, but each reference link has the destination and title
+/// from its definition.
+fn link_definitions(events: &[(usize, Event)]) -> LinkDefinitions {
+    use pulldown_cmark::LinkType;
+    let mut links = LinkDefinitions::new();
+    for (_, event) in events {
+        if let Event::Start(
+            Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }
+            | Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            },
+        ) = event
+        {
+            if matches!(
+                link_type,
+                LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut
+            ) {
+                links.insert(
+                    normalize_label(id),
+                    (dest_url.to_string(), title.to_string()),
+                );
+            }
+        }
+    }
+    links
+}
+
+/// Turn all reference links and images into full reference links
+/// and images.
+///
+/// Collapsed and shortcut references use the link text as the label,
+/// which breaks when the text is translated. See
+/// [`MarkdownConfig::keep_reference_links`].
+fn keep_reference(event: Event) -> Event {
+    use pulldown_cmark::LinkType;
+    let reference_type = |link_type| match link_type {
+        LinkType::ReferenceUnknown
+        | LinkType::Collapsed
+        | LinkType::CollapsedUnknown
+        | LinkType::Shortcut
+        | LinkType::ShortcutUnknown => LinkType::Reference,
+        _ => link_type,
+    };
+    match event {
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type: reference_type(link_type),
+            dest_url,
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Image {
+            link_type: reference_type(link_type),
+            dest_url,
+            title,
+            id,
+        }),
+        _ => event,
+    }
+}
+
+/// Turn reference links and images into inline links and images.
+///
+/// The link reference definitions are not part of the events, so the
+/// references would be dangling once a message is translated on its
+/// own.
+fn expand_reference(event: Event) -> Event {
+    use pulldown_cmark::LinkType;
+    let inline_type = |link_type| match link_type {
+        LinkType::Reference
+        | LinkType::ReferenceUnknown
+        | LinkType::Collapsed
+        | LinkType::CollapsedUnknown
+        | LinkType::Shortcut
+        | LinkType::ShortcutUnknown => LinkType::Inline,
+        _ => link_type,
+    };
+    match event {
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type: inline_type(link_type),
+            dest_url,
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Image {
+            link_type: inline_type(link_type),
+            dest_url,
+            title,
+            id,
+        }),
+        _ => event,
+    }
+}
 
 /// Extract Markdown events from `text`.
 ///
@@ -41,7 +938,7 @@ use pulldown_cmark_to_cmark::{cmark_resume_with_options, Options, State};
 ///
 /// ```
 /// use mdbook_i18n_helpers::extract_events;
-/// use pulldown_cmark::{Event, Tag};
+/// use pulldown_cmark::{Event, Tag, TagEnd};
 ///
 /// assert_eq!(
 ///     extract_events("Hello,\nworld!", None),
@@ -50,42 +947,122 @@ use pulldown_cmark_to_cmark::{cmark_resume_with_options, Options, State};
 ///         (1, Event::Text("Hello,".into())),
 ///         (1, Event::Text(" ".into())),
 ///         (2, Event::Text("world!".into())),
-///         (1, Event::End(Tag::Paragraph)),
+///         (1, Event::End(TagEnd::Paragraph)),
 ///     ]
 /// );
 /// ```
-pub fn extract_events<'a>(text: &'a str, state: Option<State<'static>>) -> Vec<(usize, Event<'a>)> {
-    // Offsets of each newline in the input, used to calculate line
-    // numbers from byte offsets.
-    let offsets = text
-        .match_indices('\n')
-        .map(|(offset, _)| offset)
-        .collect::<Vec<_>>();
+pub fn extract_events<'a>(text: &'a str, state: Option<State<'_>>) -> Vec<(usize, Event<'a>)> {
+    extract_events_with_config(text, state, &MarkdownConfig::default())
+}
+
+/// Extract Markdown events from `text` using `config`.
+///
+/// This is like [`extract_events`], but it takes the settings in
+/// `config` into account.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::{extract_events_with_config, MarkdownConfig};
+/// use pulldown_cmark::{Event, Tag, TagEnd};
+///
+/// let config = MarkdownConfig {
+///     math_delimiters: vec![("\\[".into(), "\\]".into())],
+///     ..MarkdownConfig::default()
+/// };
+/// assert_eq!(
+///     extract_events_with_config("Sum: \\[\\sum_{n=1}^{\\infty}\\]", None, &config),
+///     vec![
+///         (1, Event::Start(Tag::Paragraph)),
+///         (1, Event::Text("Sum: ".into())),
+///         (1, Event::InlineHtml("\\[\\sum_{n=1}^{\\infty}\\]".into())),
+///         (1, Event::End(TagEnd::Paragraph)),
+///     ]
+/// );
+/// ```
+pub fn extract_events_with_config<'a>(
+    text: &'a str,
+    state: Option<State<'_>>,
+    config: &MarkdownConfig,
+) -> Vec<(usize, Event<'a>)> {
+    extract_events_with_links(text, state, config, &LinkDefinitions::new())
+}
 
+/// Extract Markdown events from `text`, resolving references to
+/// links defined elsewhere using `links`.
+fn extract_events_with_links<'a>(
+    text: &'a str,
+    state: Option<State<'_>>,
+    config: &MarkdownConfig,
+    links: &LinkDefinitions,
+) -> Vec<(usize, Event<'a>)> {
     match state {
         // If we're in a code block, we disable the normal parsing and
         // return lines of text. This matches the behavior of the
         // parser in this case.
-        Some(state) if state.is_in_code_block => text
+        Some(state) if state.is_in_code_block() => text
             .split_inclusive('\n')
             .enumerate()
             .map(|(idx, line)| (idx + 1, Event::Text(line.into())))
             .collect(),
         // Otherwise, we parse the text line normally.
-        _ => new_cmark_parser(text, false)
-            .into_offset_iter()
-            .map(|(event, range)| {
-                let lineno = offsets.partition_point(|&o| o < range.start) + 1;
-                let event = match event {
-                    Event::SoftBreak => Event::Text(" ".into()),
-                    _ => event,
-                };
-                (lineno, event)
-            })
-            .collect(),
+        _ => {
+            let newlines = newline_offsets(text);
+            parse_events(text, config, links)
+                .into_iter()
+                .map(|(range, event)| (line_number(&newlines, range.start), event))
+                .collect()
+        }
     }
 }
 
+/// The byte offsets of the newlines in `text`, used to calculate line
+/// numbers from byte offsets.
+fn newline_offsets(text: &str) -> Vec<usize> {
+    text.match_indices('\n').map(|(offset, _)| offset).collect()
+}
+
+/// The line number of the byte `offset`, counting from 1.
+fn line_number(newlines: &[usize], offset: usize) -> usize {
+    newlines.partition_point(|&newline| newline < offset) + 1
+}
+
+/// The byte range of the lines from `lineno` to `end_lineno` in a
+/// text of `len` bytes, without the final newline.
+fn line_span(newlines: &[usize], len: usize, lineno: usize, end_lineno: usize) -> Range<usize> {
+    let start = match lineno {
+        0 | 1 => 0,
+        _ => newlines.get(lineno - 2).map_or(len, |newline| newline + 1),
+    };
+    let end = newlines.get(end_lineno.max(1) - 1).copied().unwrap_or(len);
+    start..end.max(start)
+}
+
+/// Parse `text` into Markdown events with their byte ranges.
+fn parse_events<'a>(
+    text: &'a str,
+    config: &MarkdownConfig,
+    links: &LinkDefinitions,
+) -> Vec<(Range<usize>, Event<'a>)> {
+    let mut events = new_cmark_parser(text, config, links)
+        .into_offset_iter()
+        .collect::<Vec<_>>();
+    if !config.math_delimiters.is_empty() {
+        events = protect_math(text, events, &config.math_delimiters);
+    }
+    events
+        .into_iter()
+        .map(|(event, range)| {
+            let event = match event {
+                Event::SoftBreak => Event::Text(" ".into()),
+                _ if config.keep_reference_links => keep_reference(event),
+                _ => expand_reference(event),
+            };
+            (range, event)
+        })
+        .collect()
+}
+
 /// Markdown events grouped by type.
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Group<'a> {
@@ -102,6 +1079,49 @@ pub enum Group<'a> {
     Skip(&'a [(usize, Event<'a>)]),
 }
 
+impl<'a> Group<'a> {
+    /// The events in the group.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::{extract_events, group_events};
+    ///
+    /// let events = extract_events("- A list item.", None);
+    /// let groups = group_events(&events);
+    /// assert_eq!(groups[1].events(), &events[2..3]);
+    /// ```
+    pub fn events(&self) -> &'a [(usize, Event<'a>)] {
+        match self {
+            Group::Translate(events) | Group::Skip(events) => events,
+        }
+    }
+
+    /// The events of a translatable group, `None` for a skipped group.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::{extract_events, group_events};
+    ///
+    /// let events = extract_events("- A list item.", None);
+    /// let groups = group_events(&events);
+    /// assert_eq!(groups[0].translate_events(), None);
+    /// assert_eq!(groups[1].translate_events(), Some(&events[2..3]));
+    /// ```
+    pub fn translate_events(&self) -> Option<&'a [(usize, Event<'a>)]> {
+        match self {
+            Group::Translate(events) => Some(events),
+            Group::Skip(_) => None,
+        }
+    }
+
+    /// Iterate over the events in the group.
+    pub fn iter(&self) -> std::slice::Iter<'a, (usize, Event<'a>)> {
+        self.events().iter()
+    }
+}
+
 /// Group Markdown events into translatable and skipped events.
 ///
 /// This function will partition the input events into groups of
@@ -112,7 +1132,7 @@ pub enum Group<'a> {
 ///
 /// ```
 /// use mdbook_i18n_helpers::{extract_events, group_events, Group};
-/// use pulldown_cmark::{Event, Tag};
+/// use pulldown_cmark::{Event, Tag, TagEnd};
 ///
 /// let events = extract_events("- A list item.", None);
 /// assert_eq!(
@@ -121,8 +1141,8 @@ pub enum Group<'a> {
 ///         (1, Event::Start(Tag::List(None))),
 ///         (1, Event::Start(Tag::Item)),
 ///         (1, Event::Text("A list item.".into())),
-///         (1, Event::End(Tag::Item)),
-///         (1, Event::End(Tag::List(None))),
+///         (1, Event::End(TagEnd::Item)),
+///         (1, Event::End(TagEnd::List(false))),
 ///     ],
 /// );
 ///
@@ -138,86 +1158,293 @@ pub enum Group<'a> {
 ///             (1, Event::Text("A list item.".into())),
 ///         ]),
 ///         Group::Skip(&[
-///             (1, Event::End(Tag::Item)),
-///             (1, Event::End(Tag::List(None))),
+///             (1, Event::End(TagEnd::Item)),
+///             (1, Event::End(TagEnd::List(false))),
 ///         ]),
 ///     ]
 /// );
 /// ```
 pub fn group_events<'a>(events: &'a [(usize, Event<'a>)]) -> Vec<Group<'a>> {
-    let mut groups = Vec::new();
-
-    #[derive(Debug)]
-    enum State {
-        Translate(usize),
-        Skip(usize),
-    }
+    iter_groups(events).collect()
+}
 
-    impl State {
-        fn into_group<'a>(self, idx: usize, events: &'a [(usize, Event<'a>)]) -> Group<'a> {
-            match self {
-                State::Translate(start) => Group::Translate(&events[start..idx]),
-                State::Skip(start) => Group::Skip(&events[start..idx]),
-            }
-        }
+/// Iterate over the groups of Markdown events.
+///
+/// This gives the same groups as [`group_events`], but finds each
+/// group when it is needed instead of collecting them in a vector
+/// first.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::{extract_events, group_events, iter_groups};
+///
+/// let events = extract_events("# Title\n\nText", None);
+/// let groups = iter_groups(&events).collect::<Vec<_>>();
+/// assert_eq!(groups, group_events(&events));
+/// ```
+pub fn iter_groups<'a>(events: &'a [(usize, Event<'a>)]) -> Groups<'a> {
+    Groups {
+        events,
+        idx: 0,
+        state: Some(GroupState::Skip(0)),
+        in_metadata_block: false,
     }
+}
 
-    let mut state = State::Skip(0);
+/// An iterator over the groups of Markdown events, see
+/// [`iter_groups`].
+#[derive(Debug, Clone)]
+pub struct Groups<'a> {
+    events: &'a [(usize, Event<'a>)],
+    /// The index of the next event to look at.
+    idx: usize,
+    /// The kind and start of the current group, `None` when all
+    /// groups have been returned.
+    state: Option<GroupState>,
+    /// The text of front matter is handled separately.
+    in_metadata_block: bool,
+}
 
-    for (idx, (_, event)) in events.iter().enumerate() {
-        match event {
-            // These block-level events force new groups. We do this
-            // because we want to include these events in the group to
-            // make the group self-contained.
-            Event::Start(Tag::Paragraph | Tag::CodeBlock(..)) => {
-                // A translatable group starts here.
-                groups.push(state.into_group(idx, events));
-                state = State::Translate(idx);
-            }
-            Event::End(Tag::Paragraph | Tag::CodeBlock(..)) => {
-                // A translatable group ends after `idx`.
-                let idx = idx + 1;
-                groups.push(state.into_group(idx, events));
-                state = State::Skip(idx);
+#[derive(Debug, Clone, Copy)]
+enum GroupState {
+    Translate(usize),
+    Skip(usize),
+}
+
+impl GroupState {
+    fn into_group<'a>(self, idx: usize, events: &'a [(usize, Event<'a>)]) -> Group<'a> {
+        match self {
+            GroupState::Translate(start) => Group::Translate(&events[start..idx]),
+            GroupState::Skip(start) => Group::Skip(&events[start..idx]),
+        }
+    }
+}
+
+impl<'a> Iterator for Groups<'a> {
+    type Item = Group<'a>;
+
+    fn next(&mut self) -> Option<Group<'a>> {
+        let events = self.events;
+        let state = self.state?;
+        while let Some((_, event)) = events.get(self.idx) {
+            let idx = self.idx;
+            self.idx += 1;
+            match event {
+                Event::Start(Tag::MetadataBlock(_)) => self.in_metadata_block = true,
+                Event::End(TagEnd::MetadataBlock(_)) => self.in_metadata_block = false,
+                _ => {}
             }
+            match event {
+                // These block-level events force new groups. We do this
+                // because we want to include these events in the group to
+                // make the group self-contained.
+                Event::Start(Tag::Paragraph | Tag::CodeBlock(..)) => {
+                    // A translatable group starts here.
+                    self.state = Some(GroupState::Translate(idx));
+                    return Some(state.into_group(idx, events));
+                }
+                Event::End(TagEnd::Paragraph | TagEnd::CodeBlock) => {
+                    // A translatable group ends after `idx`.
+                    self.state = Some(GroupState::Skip(idx + 1));
+                    return Some(state.into_group(idx + 1, events));
+                }
 
-            // Inline events start or continue a translating group.
-            Event::Start(
-                Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..),
-            )
-            | Event::End(
-                Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..),
-            )
-            | Event::Text(_)
-            | Event::Code(_)
-            | Event::FootnoteReference(_)
-            | Event::SoftBreak
-            | Event::HardBreak => {
-                // If we're currently skipping, then a new
-                // translatable group starts here.
-                if let State::Skip(start) = state {
-                    groups.push(Group::Skip(&events[start..idx]));
-                    state = State::Translate(idx);
+                // Inline events start or continue a translating group.
+                Event::Start(
+                    Tag::Emphasis
+                    | Tag::Strong
+                    | Tag::Strikethrough
+                    | Tag::Superscript
+                    | Tag::Subscript
+                    | Tag::Link { .. }
+                    | Tag::Image { .. },
+                )
+                | Event::End(
+                    TagEnd::Emphasis
+                    | TagEnd::Strong
+                    | TagEnd::Strikethrough
+                    | TagEnd::Superscript
+                    | TagEnd::Subscript
+                    | TagEnd::Link
+                    | TagEnd::Image,
+                )
+                | Event::Text(_)
+                | Event::Code(_)
+                | Event::InlineMath(_)
+                | Event::DisplayMath(_)
+                | Event::FootnoteReference(_)
+                | Event::SoftBreak
+                | Event::HardBreak
+                    if !self.in_metadata_block =>
+                {
+                    // If we're currently skipping, then a new
+                    // translatable group starts here.
+                    if let GroupState::Skip(_) = state {
+                        self.state = Some(GroupState::Translate(idx));
+                        return Some(state.into_group(idx, events));
+                    }
                 }
-            }
 
-            // All other block-level events start or continue a
-            // skipping group.
-            _ => {
-                if let State::Translate(start) = state {
-                    groups.push(Group::Translate(&events[start..idx]));
-                    state = State::Skip(idx);
+                // Verbatim math is inline and behaves like text.
+                event if is_verbatim_math(event) => {
+                    if let GroupState::Skip(_) = state {
+                        self.state = Some(GroupState::Translate(idx));
+                        return Some(state.into_group(idx, events));
+                    }
+                }
+
+                // All other block-level events start or continue a
+                // skipping group.
+                _ => {
+                    if let GroupState::Translate(_) = state {
+                        self.state = Some(GroupState::Skip(idx));
+                        return Some(state.into_group(idx, events));
+                    }
                 }
             }
         }
+
+        // The last group runs to the end of the events.
+        self.state = None;
+        Some(state.into_group(events.len(), events))
     }
+}
 
-    match state {
-        State::Translate(start) => groups.push(Group::Translate(&events[start..])),
-        State::Skip(start) => groups.push(Group::Skip(&events[start..])),
+/// How to write Markdown syntax which has several equivalent forms.
+///
+/// The Markdown events don't record which form the source text used,
+/// so the default is a fixed style. Use
+/// [`ReconstructOptions::from_source`] to follow the style of a
+/// source text instead, which keeps the diffs small when the result
+/// is compared with the source. Table alignment is part of the events
+/// and is always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconstructOptions {
+    /// Token for emphasis, `_` or `*`.
+    pub emphasis_token: char,
+    /// Token for strong emphasis, `**` or `__`.
+    pub strong_token: &'static str,
+    /// Marker for the items of bullet lists: `-`, `*`, or `+`.
+    pub list_token: char,
+    /// Delimiter after the numbers of ordered lists, `.` or `)`.
+    pub ordered_list_token: char,
+    /// Number the items of ordered lists `1.`, `2.`, `3.`, instead of
+    /// repeating the number of the first item.
+    pub increment_ordered_list_bullets: bool,
+}
+
+impl Default for ReconstructOptions {
+    /// The style of the [Google developer documentation style
+    /// guide](https://developers.google.com/style/text-formatting).
+    fn default() -> Self {
+        Self {
+            emphasis_token: '_',
+            strong_token: "**",
+            list_token: '-',
+            ordered_list_token: '.',
+            increment_ordered_list_bullets: false,
+        }
+    }
+}
+
+impl ReconstructOptions {
+    /// Find the style used in the Markdown `text`.
+    ///
+    /// The first use of each kind of syntax decides the style. Syntax
+    /// which doesn't occur in `text` gets the default style. The
+    /// `text` is parsed with the extensions enabled in `config`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::{MarkdownConfig, ReconstructOptions};
+    ///
+    /// let options =
+    ///     ReconstructOptions::from_source("* Some *emphasis*\n", &MarkdownConfig::default());
+    /// assert_eq!(options.list_token, '*');
+    /// assert_eq!(options.emphasis_token, '*');
+    /// assert_eq!(options.strong_token, "**");
+    /// ```
+    pub fn from_source(text: &str, config: &MarkdownConfig) -> Self {
+        let mut options = Self::default();
+        let (mut emphasis, mut strong, mut bullet, mut ordered) = (false, false, false, false);
+        // Item numbers of the first ordered list, and the nesting
+        // depth of that list while we are inside it.
+        let mut item_numbers = Vec::new();
+        let mut ordered_depth = None;
+        let mut depth = 0;
+        let links = LinkDefinitions::new();
+        let parser = new_cmark_parser(text, config, &links);
+        for (event, range) in parser.into_offset_iter() {
+            let source = &text[range];
+            match event {
+                Event::Start(Tag::Emphasis) if !emphasis => {
+                    emphasis = true;
+                    if let Some(token @ ('*' | '_')) = source.chars().next() {
+                        options.emphasis_token = token;
+                    }
+                }
+                Event::Start(Tag::Strong) if !strong => {
+                    strong = true;
+                    if source.starts_with("__") {
+                        options.strong_token = "__";
+                    }
+                }
+                Event::Start(Tag::List(start)) => {
+                    depth += 1;
+                    let marker = source
+                        .trim_start()
+                        .trim_start_matches(|c: char| c.is_ascii_digit());
+                    match (start, marker.chars().next()) {
+                        (None, Some(token @ ('-' | '*' | '+'))) if !bullet => {
+                            bullet = true;
+                            options.list_token = token;
+                        }
+                        (Some(_), Some(token @ ('.' | ')'))) if !ordered => {
+                            ordered = true;
+                            ordered_depth = Some(depth);
+                            options.ordered_list_token = token;
+                        }
+                        _ => {}
+                    }
+                }
+                Event::End(TagEnd::List(_)) => {
+                    if ordered_depth == Some(depth) {
+                        ordered_depth = None;
+                    }
+                    depth -= 1;
+                }
+                Event::Start(Tag::Item) if ordered_depth == Some(depth) => {
+                    let source = source.trim_start();
+                    let digits = source.len()
+                        - source
+                            .trim_start_matches(|c: char| c.is_ascii_digit())
+                            .len();
+                    if let Ok(number) = source[..digits].parse::<u64>() {
+                        item_numbers.push(number);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let [first, second, ..] = item_numbers[..] {
+            options.increment_ordered_list_bullets = first != second;
+        }
+        options
     }
 
-    groups
+    fn cmark_options(&self) -> Options<'static> {
+        Options {
+            code_block_token_count: 3,
+            list_token: self.list_token,
+            ordered_list_token: self.ordered_list_token,
+            increment_ordered_list_bullets: self.increment_ordered_list_bullets,
+            emphasis_token: self.emphasis_token,
+            strong_token: self.strong_token,
+            ..Options::default()
+        }
+    }
 }
 
 /// Render a slice of Markdown events back to Markdown.
@@ -236,39 +1463,56 @@ pub fn group_events<'a>(events: &'a [(usize, Event<'a>)]) -> Vec<Group<'a>> {
 /// Notice how this will normalize the Markdown to use `_` for
 /// emphasis and `**` for strong emphasis. The style is chosen to
 /// match the [Google developer documentation style
-/// guide](https://developers.google.com/style/text-formatting).
-pub fn reconstruct_markdown(
-    group: &[(usize, Event)],
-    state: Option<State<'static>>,
-) -> (String, State<'static>) {
-    let events = group.iter().map(|(_, event)| event);
+/// guide](https://developers.google.com/style/text-formatting). Use
+/// [`reconstruct_markdown_with_options`] for another style.
+pub fn reconstruct_markdown<'a>(
+    group: &[(usize, Event<'a>)],
+    state: Option<State<'a>>,
+) -> (String, State<'a>) {
+    reconstruct_markdown_with_options(group, state, &ReconstructOptions::default())
+}
+
+/// Render a slice of Markdown events back to Markdown using the style
+/// in `options`.
+///
+/// The messages in the PO files are always rendered in the default
+/// style, so only use this for Markdown which is not used as a
+/// message, such as a translated document.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::{
+///     extract_events, reconstruct_markdown_with_options, MarkdownConfig, ReconstructOptions,
+/// };
+///
+/// let source = "Hello *world!*";
+/// let options = ReconstructOptions::from_source(source, &MarkdownConfig::default());
+/// let (reconstructed, _) =
+///     reconstruct_markdown_with_options(&extract_events(source, None), None, &options);
+/// assert_eq!(reconstructed, "Hello *world!*");
+/// ```
+pub fn reconstruct_markdown_with_options<'a>(
+    group: &[(usize, Event<'a>)],
+    state: Option<State<'a>>,
+    options: &ReconstructOptions,
+) -> (String, State<'a>) {
+    let events = group.iter().map(|(_, event)| cmark_event(event));
     let mut markdown = String::new();
-    let options = Options {
-        code_block_token_count: 3,
-        list_token: '-',
-        emphasis_token: '_',
-        strong_token: "**",
-        ..Options::default()
-    };
+    let options = options.cmark_options();
     // Advance the true state, but throw away the rendered Markdown
     // since it can contain unwanted padding.
-    let new_state = cmark_resume_with_options(
-        events.clone(),
-        String::new(),
-        state.clone(),
-        options.clone(),
-    )
-    .unwrap();
+    let new_state = advance_state_with_options(group, state.clone(), options.clone());
 
     // Block quotes and lists add padding to the state, which is
     // reflected in the rendered Markdown. We want to capture the
     // Markdown without the padding to remove the effect of these
     // structural elements. Similarly, we don't want extra newlines at
     // the start.
-    let simplified_state = state.map(|state| State {
-        newlines_before_start: 0,
-        padding: Vec::new(),
-        ..state
+    let simplified_state = state.map(|mut state| {
+        state.newlines_before_start = 0;
+        state.padding = Vec::new();
+        state
     });
     cmark_resume_with_options(events, &mut markdown, simplified_state, options).unwrap();
     // Even with `newlines_before_start` set to zero, we get a leading
@@ -278,6 +1522,261 @@ pub fn reconstruct_markdown(
     (String::from(markdown.trim_matches('\n')), new_state)
 }
 
+/// A writer which throws away everything written to it.
+struct Discard;
+
+impl std::fmt::Write for Discard {
+    fn write_str(&mut self, _: &str) -> std::fmt::Result {
+        Ok(())
+    }
+}
+
+/// Advance `state` past `group` without keeping the Markdown.
+///
+/// This gives the same state as [`reconstruct_markdown_with_options`],
+/// but renders the events once instead of twice and does not build a
+/// string.
+fn advance_state_with_options<'a>(
+    group: &[(usize, Event<'a>)],
+    state: Option<State<'a>>,
+    options: Options,
+) -> State<'a> {
+    let events = group.iter().map(|(_, event)| cmark_event(event));
+    cmark_resume_with_options(events, Discard, state, options).unwrap()
+}
+
+/// Prepare `event` for rendering.
+///
+/// Superscript and subscript would be written as `<sup>` and `<sub>`
+/// tags. They are written with `^` and `~` instead, so the messages
+/// look like the Markdown they come from.
+fn cmark_event<'b, 'a>(event: &'b Event<'a>) -> Cow<'b, Event<'a>> {
+    match event {
+        Event::Start(Tag::Superscript) | Event::End(TagEnd::Superscript) => {
+            Cow::Owned(Event::InlineHtml("^".into()))
+        }
+        Event::Start(Tag::Subscript) | Event::End(TagEnd::Subscript) => {
+            Cow::Owned(Event::InlineHtml("~".into()))
+        }
+        _ => Cow::Borrowed(event),
+    }
+}
+
+/// Renders the groups of a document one after the other.
+///
+/// Markdown such as block quotes and lists affects how the text inside
+/// it is written, which [`reconstruct_markdown`] tracks in a [`State`].
+/// The state after one group must be passed on when rendering the next
+/// group. A session owns this state, so tools which walk the groups of
+/// a document don't have to thread it through by hand.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::{extract_events, iter_groups, Group, RenderSession};
+///
+/// let events = extract_events("> Hello *world!*\n>\n> 1. Bye", None);
+/// let mut session = RenderSession::new();
+/// let mut messages = Vec::new();
+/// for group in iter_groups(&events) {
+///     match group {
+///         Group::Translate(events) => messages.push(session.render_group(events)),
+///         Group::Skip(events) => session.advance(events),
+///     }
+/// }
+/// assert_eq!(messages, vec!["Hello _world!_", "Bye"]);
+/// assert_eq!(session.finish(), "");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RenderSession<'a> {
+    state: Option<State<'a>>,
+    options: ReconstructOptions,
+}
+
+impl<'a> RenderSession<'a> {
+    /// Start a session at the beginning of a document, rendering in
+    /// the default style.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a session which renders in the style of `options`.
+    pub fn with_options(options: ReconstructOptions) -> Self {
+        Self {
+            state: None,
+            options,
+        }
+    }
+
+    /// The state after the groups rendered so far, `None` at the start
+    /// of the document.
+    ///
+    /// This can be passed to [`extract_events`] to parse text, such as
+    /// a translation, which belongs at the current position.
+    pub fn state(&self) -> Option<&State<'a>> {
+        self.state.as_ref()
+    }
+
+    /// Render `group` to Markdown and move past it.
+    ///
+    /// The Markdown is written without the padding of the enclosing
+    /// block quotes and lists, like [`reconstruct_markdown`].
+    pub fn render_group(&mut self, group: &[(usize, Event<'a>)]) -> String {
+        let (markdown, state) =
+            reconstruct_markdown_with_options(group, self.state.take(), &self.options);
+        self.state = Some(state);
+        markdown
+    }
+
+    /// Move past `group` without rendering it.
+    ///
+    /// This is faster than [`RenderSession::render_group`] and should
+    /// be used for groups where the Markdown is not needed, such as
+    /// skipped groups.
+    pub fn advance(&mut self, group: &[(usize, Event<'a>)]) {
+        let options = self.options.cmark_options();
+        self.state = Some(advance_state_with_options(
+            group,
+            self.state.take(),
+            options,
+        ));
+    }
+
+    /// End the session and return the Markdown which belongs at the
+    /// end of the document.
+    ///
+    /// This is the definitions of the reference links rendered in the
+    /// session, see [`reconstruct_document`]. It is empty if there are
+    /// none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::{extract_events_with_config, MarkdownConfig, RenderSession};
+    ///
+    /// let config = MarkdownConfig {
+    ///     keep_reference_links: true,
+    ///     ..MarkdownConfig::default()
+    /// };
+    /// let events = extract_events_with_config("See [here][1].\n\n[1]: https://example.com", None, &config);
+    /// let mut session = RenderSession::new();
+    /// assert_eq!(session.render_group(&events), "See [here][1].");
+    /// assert_eq!(session.finish(), "\n\n[1]: https://example.com");
+    /// ```
+    pub fn finish(self) -> String {
+        let mut markdown = String::new();
+        if let Some(state) = self.state {
+            state.finalize(&mut markdown).unwrap();
+        }
+        markdown
+    }
+}
+
+/// Render a complete document back to Markdown.
+///
+/// This is like [`reconstruct_markdown`], but it also writes the
+/// definitions of the reference links at the end of the document.
+/// These are needed for the links kept with
+/// [`MarkdownConfig::keep_reference_links`].
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::{extract_events_with_config, reconstruct_document, MarkdownConfig};
+///
+/// let config = MarkdownConfig {
+///     keep_reference_links: true,
+///     ..MarkdownConfig::default()
+/// };
+/// let events = extract_events_with_config("See [here][1].\n\n[1]: https://example.com", None, &config);
+/// assert_eq!(
+///     reconstruct_document(&events),
+///     "See [here][1].\n\n[1]: https://example.com"
+/// );
+/// ```
+pub fn reconstruct_document(events: &[(usize, Event)]) -> String {
+    reconstruct_document_with_options(events, &ReconstructOptions::default())
+}
+
+/// Render a complete document back to Markdown using the style in
+/// `options`.
+///
+/// See [`reconstruct_document`] and [`ReconstructOptions`].
+pub fn reconstruct_document_with_options(
+    events: &[(usize, Event)],
+    options: &ReconstructOptions,
+) -> String {
+    let (mut markdown, state) = reconstruct_markdown_with_options(events, None, options);
+    state.finalize(&mut markdown).unwrap();
+    markdown
+}
+
+/// Find the delimiter rows of the tables in `text`.
+///
+/// Returns the byte range of each row, without the indentation or
+/// block quote markers in front of it.
+fn table_delimiter_rows(text: &str, config: &MarkdownConfig) -> Vec<Range<usize>> {
+    let links = LinkDefinitions::new();
+    new_cmark_parser(text, config, &links)
+        .into_offset_iter()
+        .filter(|(event, _)| matches!(event, Event::Start(Tag::Table(_))))
+        .filter_map(|(_, range)| {
+            let table = &text[range.clone()];
+            let start = range.start + table.find('\n')? + 1;
+            let line = text[start..].lines().next()?;
+            let content = line.trim_start_matches([' ', '\t', '>']);
+            let content_start = start + line.len() - content.len();
+            Some(content_start..content_start + content.trim_end().len())
+        })
+        .collect()
+}
+
+/// Copy the table delimiter rows of `source` into `translated`.
+///
+/// Reconstructing a table writes a new delimiter row, such as
+/// `|:-|-:|`. The alignment is kept, but the width of the columns is
+/// not. Use this to restore the delimiter rows as written in the
+/// source, so that translating a table only changes its cells. The
+/// `translated` text is returned unchanged if it doesn't have the
+/// same number of tables as `source`. Both texts are parsed with the
+/// extensions enabled in `config`.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::{
+///     extract_events, reconstruct_document, restore_table_delimiter_rows, MarkdownConfig,
+/// };
+///
+/// let source = "| Name | Size |\n|:-----|-----:|\n| Foo  |   10 |";
+/// let reconstructed = reconstruct_document(&extract_events(source, None));
+/// assert_eq!(reconstructed, "|Name|Size|\n|:---|---:|\n|Foo|10|");
+/// assert_eq!(
+///     restore_table_delimiter_rows(source, &reconstructed, &MarkdownConfig::default()),
+///     "|Name|Size|\n|:-----|-----:|\n|Foo|10|"
+/// );
+/// ```
+pub fn restore_table_delimiter_rows(
+    source: &str,
+    translated: &str,
+    config: &MarkdownConfig,
+) -> String {
+    let source_rows = table_delimiter_rows(source, config);
+    let translated_rows = table_delimiter_rows(translated, config);
+    if source_rows.len() != translated_rows.len() {
+        return String::from(translated);
+    }
+    let mut restored = String::with_capacity(translated.len());
+    let mut pos = 0;
+    for (source_row, translated_row) in source_rows.into_iter().zip(translated_rows) {
+        restored.push_str(&translated[pos..translated_row.start]);
+        restored.push_str(&source[source_row]);
+        pos = translated_row.end;
+    }
+    restored.push_str(&translated[pos..]);
+    restored
+}
+
 /// Extract translatable strings from `document`.
 ///
 /// # Examples
@@ -326,25 +1825,282 @@ pub fn reconstruct_markdown(
 /// );
 /// ```
 pub fn extract_messages(document: &str) -> Vec<(usize, String)> {
-    let events = extract_events(document, None);
+    extract_messages_with_config(document, &MarkdownConfig::default())
+}
+
+/// Extract translatable strings from `document` using `config`.
+///
+/// This is like [`extract_messages`], but it takes the settings in
+/// `config` into account.
+///
+/// # Examples
+///
+/// Math can be kept verbatim in the messages:
+///
+/// ```
+/// use mdbook_i18n_helpers::{extract_messages_with_config, MarkdownConfig};
+///
+/// let config = MarkdownConfig {
+///     math_delimiters: vec![("\\(".into(), "\\)".into())],
+///     ..MarkdownConfig::default()
+/// };
+/// assert_eq!(
+///     extract_messages_with_config("The area is \\(\\pi r^2\\).", &config),
+///     vec![(1, "The area is \\(\\pi r^2\\).".into())],
+/// );
+/// ```
+pub fn extract_messages_with_config(
+    document: &str,
+    config: &MarkdownConfig,
+) -> Vec<(usize, String)> {
+    extract_messages_with_comments(document, config)
+        .into_iter()
+        .map(|message| (message.lineno, message.msgid))
+        .collect()
+}
+
+/// A message extracted from a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedMessage {
+    /// The line number where the message starts.
+    pub lineno: usize,
+    /// The line number where the message ends.
+    pub end_lineno: usize,
+    /// The text of the message.
+    pub msgid: String,
+    /// A comment for the translators, empty if there is nothing to
+    /// add.
+    pub comment: String,
+    /// The message is from a code block, so its whitespace matters.
+    ///
+    /// Such messages should get the `no-wrap` flag in PO files, which
+    /// tells tools such as `msgmerge` not to wrap the lines.
+    pub no_wrap: bool,
+    /// The byte range of the message in the document.
+    ///
+    /// The range covers the content of the block, so it leaves out
+    /// markup such as `#` before a heading or `-` before a list item.
+    /// For a message from a comment or string in a code block, see
+    /// [`MarkdownConfig::code_rules`], this is the range of the comment
+    /// or string. Messages from raw HTML and front matter span the
+    /// lines they are on.
+    pub span: Range<usize>,
+    /// The kind of block the message comes from.
+    pub kind: kinds::BlockKind,
+    /// The headings above the message, from the top level down.
+    ///
+    /// Each heading is given as its message, so it is written like the
+    /// `msgid` of the heading. The heading of a section is included
+    /// for the messages of the section, but not for the heading itself.
+    pub headings: Vec<String>,
+}
+
+/// Turn `messages` into [`ExtractedMessage`] values without comments.
+///
+/// The messages are of the given `kind` and below `headings`. The
+/// span of each message covers its lines in `document`, which has
+/// newlines at `newlines`.
+fn without_comments(
+    messages: Vec<(usize, String)>,
+    kind: kinds::BlockKind,
+    headings: &[String],
+    document: &str,
+    newlines: &[usize],
+) -> Vec<ExtractedMessage> {
+    messages
+        .into_iter()
+        .map(|(lineno, msgid)| {
+            let end_lineno = lineno + msgid.matches('\n').count();
+            ExtractedMessage {
+                lineno,
+                end_lineno,
+                comment: String::new(),
+                no_wrap: false,
+                span: line_span(newlines, document.len(), lineno, end_lineno),
+                kind,
+                headings: headings.to_vec(),
+                msgid,
+            }
+        })
+        .collect()
+}
+
+/// Shrink `span` to leave out the trailing whitespace in `document`.
+fn trim_span(document: &str, span: Range<usize>) -> Range<usize> {
+    let trimmed = document[span.clone()].trim_end();
+    span.start..span.start + trimmed.len()
+}
+
+/// Keep the headings of the sections which contain a heading at
+/// `level`, and add the heading `msgid`.
+fn enter_section(headings: &mut Vec<(usize, String)>, level: usize, msgid: &str) {
+    headings.retain(|(heading_level, _)| *heading_level < level);
+    headings.push((level, String::from(msgid)));
+}
+
+/// Extract translatable strings and comments from `document` using
+/// `config`.
+///
+/// This is like [`extract_messages_with_config`], but each message
+/// comes with a comment. The comment explains the placeholders when
+/// [`MarkdownConfig::placeholders`] is set, describes the kind of
+/// message when [`MarkdownConfig::kind_comments`] is set, and has a
+/// hash of the message and its neighbors when
+/// [`MarkdownConfig::source_hashes`] is set.
+///
+/// Each message also has its byte range in `document`, the kind of
+/// block it comes from, and the headings above it, which is useful
+/// for tools such as editor plugins.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::{extract_messages_with_comments, MarkdownConfig};
+///
+/// let config = MarkdownConfig {
+///     placeholders: true,
+///     ..MarkdownConfig::default()
+/// };
+/// let messages = extract_messages_with_comments("Run `cargo`.", &config);
+/// assert_eq!(messages[0].msgid, "Run {1}.");
+/// assert_eq!(messages[0].comment, "{1}: `cargo`");
+/// ```
+///
+/// ```
+/// use mdbook_i18n_helpers::kinds::BlockKind;
+/// use mdbook_i18n_helpers::{extract_messages_with_comments, MarkdownConfig};
+///
+/// let document = "# Setup\n\n| Tool  |\n|-------|\n| cargo |\n";
+/// let messages = extract_messages_with_comments(document, &MarkdownConfig::default());
+/// let cell = &messages[2];
+/// assert_eq!(cell.msgid, "cargo");
+/// assert_eq!(&document[cell.span.clone()], "cargo");
+/// assert_eq!(cell.kind, BlockKind::TableCell);
+/// assert_eq!(cell.headings, vec!["Setup"]);
+/// ```
+pub fn extract_messages_with_comments(
+    document: &str,
+    config: &MarkdownConfig,
+) -> Vec<ExtractedMessage> {
+    let newlines = newline_offsets(document);
+    let (ranges, events): (Vec<_>, Vec<_>) =
+        parse_events(document, config, &LinkDefinitions::new())
+            .into_iter()
+            .map(|(range, event)| (range.clone(), (line_number(&newlines, range.start), event)))
+            .unzip();
     let mut messages = Vec::new();
-    let mut state = None;
-    for group in group_events(&events) {
+    let mut session = RenderSession::new();
+    let mut blocks = kinds::BlockStack::default();
+    let mut sections = Vec::new();
+    let mut group_start = 0;
+    for group in iter_groups(&events) {
+        let ranges = &ranges[group_start..group_start + group.events().len()];
+        group_start += group.events().len();
+        let headings = sections
+            .iter()
+            .map(|(_, heading): &(usize, String)| heading.clone())
+            .collect::<Vec<_>>();
         match group {
             Group::Translate(events) => {
-                if let Some((lineno, _)) = events.first() {
-                    let (text, new_state) = reconstruct_markdown(events, state);
-                    messages.push((*lineno, text));
-                    state = Some(new_state);
+                if let Some((rule, lineno, code)) = split_code_block(events, config) {
+                    let kind = blocks.message_kind(events);
+                    for (lineno, range, msgid) in extract_code_messages(lineno, &code, rule) {
+                        messages.push(ExtractedMessage {
+                            lineno,
+                            end_lineno: lineno,
+                            msgid,
+                            comment: match config.kind_comments {
+                                true => kind.clone(),
+                                false => String::new(),
+                            },
+                            no_wrap: true,
+                            span: code_offset(events, ranges, range.start)
+                                ..code_offset(events, ranges, range.end),
+                            kind: kinds::BlockKind::CodeBlock,
+                            headings: headings.clone(),
+                        });
+                    }
+                    session.advance(events);
+                } else if let Some((lineno, _)) = events.first() {
+                    let block_kind = blocks.block_kind(events);
+                    let heading_level = blocks.heading_level();
+                    let kind = blocks.message_kind(events);
+                    let end_lineno = events.iter().map(|(lineno, _)| *lineno).max();
+                    let (events, originals) = if config.placeholders {
+                        let (msgid_events, originals) = placeholders::insert_placeholders(events);
+                        (Cow::Owned(msgid_events), originals)
+                    } else {
+                        (Cow::Borrowed(events), Vec::new())
+                    };
+                    let text = session.render_group(&events);
+                    if !(config.skip_math && is_math_only(&events)) {
+                        let mut comment = placeholders::placeholder_comment(&originals);
+                        if config.kind_comments {
+                            comment = if comment.is_empty() {
+                                kind
+                            } else {
+                                format!("{kind}\n{comment}")
+                            };
+                        }
+                        let headings = match heading_level {
+                            Some(level) => {
+                                let parents = sections
+                                    .iter()
+                                    .filter(|(heading_level, _)| *heading_level < level)
+                                    .map(|(_, heading)| heading.clone())
+                                    .collect();
+                                enter_section(&mut sections, level, &text);
+                                parents
+                            }
+                            None => headings,
+                        };
+                        let start = ranges.iter().map(|range| range.start).min();
+                        let end = ranges.iter().map(|range| range.end).max();
+                        let span = trim_span(document, start.unwrap_or(0)..end.unwrap_or(0));
+                        messages.push(ExtractedMessage {
+                            lineno: *lineno,
+                            end_lineno: end_lineno.unwrap_or(*lineno),
+                            msgid: text,
+                            comment,
+                            no_wrap: matches!(
+                                events.first(),
+                                Some((_, Event::Start(Tag::CodeBlock(_))))
+                            ),
+                            span,
+                            kind: block_kind,
+                            headings,
+                        });
+                    }
                 }
             }
             Group::Skip(events) => {
-                let (_, new_state) = reconstruct_markdown(events, state);
-                state = Some(new_state);
+                if config.extract_html {
+                    messages.extend(without_comments(
+                        extract_html_messages(events),
+                        kinds::BlockKind::Html,
+                        &headings,
+                        document,
+                        &newlines,
+                    ));
+                }
+                if !config.frontmatter_keys.is_empty() {
+                    messages.extend(without_comments(
+                        extract_frontmatter_messages(events, &config.frontmatter_keys),
+                        kinds::BlockKind::FrontMatter,
+                        &headings,
+                        document,
+                        &newlines,
+                    ));
+                }
+                session.advance(events);
             }
         }
+        blocks.update(group.events());
     }
 
+    if config.source_hashes {
+        source_hash::add_source_hashes(&mut messages);
+    }
     messages
 }
 
@@ -355,7 +2111,7 @@ pub fn extract_messages(document: &str) -> Vec<(usize, String)> {
 /// has been wrapped in a paragraph:
 ///
 /// ```
-/// use pulldown_cmark::{Event, Tag};
+/// use pulldown_cmark::{Event, Tag, TagEnd};
 /// use mdbook_i18n_helpers::{extract_events, reconstruct_markdown, trim_paragraph};
 ///
 /// let old_events = vec![(1, Event::Text("A line of text".into()))];
@@ -367,7 +2123,7 @@ pub fn extract_messages(document: &str) -> Vec<(usize, String)> {
 ///     &[
 ///         (1, Event::Start(Tag::Paragraph)),
 ///         (1, Event::Text("A line of text".into())),
-///         (1, Event::End(Tag::Paragraph)),
+///         (1, Event::End(TagEnd::Paragraph)),
 ///     ],
 /// );
 ///
@@ -381,54 +2137,249 @@ pub fn trim_paragraph<'a, 'event>(
     old_events: &'a [(usize, Event<'event>)],
 ) -> &'a [(usize, Event<'event>)] {
     use pulldown_cmark::Event::{End, Start};
-    use pulldown_cmark::Tag::Paragraph;
     match new_events {
-        [(_, Start(Paragraph)), inner @ .., (_, End(Paragraph))] => match old_events {
-            [(_, Start(Paragraph)), .., (_, End(Paragraph))] => new_events,
+        [(_, Start(Tag::Paragraph)), inner @ .., (_, End(TagEnd::Paragraph))] => match old_events {
+            [(_, Start(Tag::Paragraph)), .., (_, End(TagEnd::Paragraph))] => new_events,
             [..] => inner,
         },
         [..] => new_events,
     }
 }
 
+/// Find the translation of `msgid` in `catalog`.
+///
+/// Fuzzy and empty translations are ignored.
+fn find_translation<'a>(catalog: &'a Catalog, msgid: &str) -> Option<&'a str> {
+    catalog
+        .find_message(None, msgid, None)
+        .filter(|msg| !msg.flags().is_fuzzy())
+        .and_then(|msg| msg.msgstr().ok())
+        .filter(|msgstr| !msgstr.is_empty())
+}
+
 /// Translate `events` using `catalog`.
 pub fn translate_events<'a>(
     events: &'a [(usize, Event<'a>)],
     catalog: &'a Catalog,
+) -> Vec<(usize, Event<'a>)> {
+    translate_events_with_config(events, catalog, &MarkdownConfig::default())
+}
+
+/// Translate `events` using `catalog` and `config`.
+///
+/// The `config` must be the same as the one used when extracting the
+/// messages in `catalog`.
+pub fn translate_events_with_config<'a>(
+    events: &'a [(usize, Event<'a>)],
+    catalog: &'a Catalog,
+    config: &MarkdownConfig,
+) -> Vec<(usize, Event<'a>)> {
+    translate_events_impl(events, catalog, config, None)
+}
+
+/// Translate `events` and mark where each translation came from.
+///
+/// This works like [`translate_events_with_config`], but each
+/// translated message starts with an empty, hidden `<span>` with a
+/// `data-i18n-hash` attribute with the [checksum of the
+/// message](state::message_checksum), a `data-i18n-translation-hash`
+/// attribute with the [checksum of the
+/// translation](state::translation_checksum), and a
+/// `data-i18n-source` attribute with the source reference, such as
+/// `src/foo.md:12`. The `path` is used for the source references.
+/// Scripts in the rendered book can use the markers to let readers
+/// give feedback on a specific translation.
+///
+/// Only messages with inline content, such as paragraphs, headings,
+/// and list items, are marked.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::{
+///     extract_events, reconstruct_document, translate_events_with_provenance, MarkdownConfig,
+/// };
+/// use polib::catalog::Catalog;
+/// use polib::message::Message;
+/// use polib::metadata::CatalogMetadata;
+///
+/// let mut catalog = Catalog::new(CatalogMetadata::new());
+/// catalog.append_or_update(
+///     Message::build_singular()
+///         .with_msgid(String::from("Hello"))
+///         .with_msgstr(String::from("Hej"))
+///         .done(),
+/// );
+/// let events = extract_events("Hello", None);
+/// let translated =
+///     translate_events_with_provenance(&events, &catalog, &MarkdownConfig::default(), "src/foo.md");
+/// assert_eq!(
+///     reconstruct_document(&translated),
+///     "<span data-i18n-hash=\"63f0bfacf2c00f6b\" \
+///      data-i18n-translation-hash=\"6e05598299756030\" \
+///      data-i18n-source=\"src/foo.md:1\" hidden></span>Hej"
+/// );
+/// ```
+pub fn translate_events_with_provenance<'a>(
+    events: &'a [(usize, Event<'a>)],
+    catalog: &'a Catalog,
+    config: &MarkdownConfig,
+    path: &str,
+) -> Vec<(usize, Event<'a>)> {
+    translate_events_impl(events, catalog, config, Some(path))
+}
+
+/// Check if `event` can only occur inside a block, such as a
+/// paragraph.
+fn is_inline(event: &Event) -> bool {
+    match event {
+        Event::Text(_)
+        | Event::Code(_)
+        | Event::InlineHtml(_)
+        | Event::InlineMath(_)
+        | Event::FootnoteReference(_) => true,
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Emphasis
+                | Tag::Strong
+                | Tag::Strikethrough
+                | Tag::Superscript
+                | Tag::Subscript
+                | Tag::Link { .. }
+                | Tag::Image { .. }
+        ),
+        _ => false,
+    }
+}
+
+/// Check if `event` starts a block which can contain inline HTML.
+fn is_block_start(event: &Event) -> bool {
+    match event {
+        Event::Start(tag) => !matches!(
+            tag,
+            Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::MetadataBlock(_)
+        ),
+        _ => false,
+    }
+}
+
+/// An invisible HTML element which marks the translation of `msgid`
+/// as `msgstr` from `path` at `lineno`.
+fn provenance_marker(msgid: &str, msgstr: &str, path: &str, lineno: usize) -> String {
+    format!(
+        "<span data-i18n-hash=\"{}\" data-i18n-translation-hash=\"{}\" \
+         data-i18n-source=\"{}:{lineno}\" hidden></span>",
+        state::message_checksum(msgid),
+        state::translation_checksum(msgid, msgstr),
+        html_escape_attribute(path)
+    )
+}
+
+/// Escape `text` for use in a double-quoted HTML attribute.
+fn html_escape_attribute(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+fn translate_events_impl<'a>(
+    events: &'a [(usize, Event<'a>)],
+    catalog: &'a Catalog,
+    config: &MarkdownConfig,
+    provenance_path: Option<&str>,
 ) -> Vec<(usize, Event<'a>)> {
     let mut translated_events = Vec::new();
-    let mut state = None;
+    let mut session = RenderSession::new();
+    let links = if config.keep_reference_links {
+        link_definitions(events)
+    } else {
+        LinkDefinitions::new()
+    };
 
-    for group in group_events(&events) {
+    for group in iter_groups(events) {
         match group {
             Group::Translate(events) => {
+                if let Some((rule, lineno, code)) = split_code_block(events, config) {
+                    translated_events
+                        .extend(translate_code_events(events, lineno, &code, rule, catalog));
+                    session.advance(events);
+                    continue;
+                }
                 // Reconstruct the message.
-                let (msgid, new_state) = reconstruct_markdown(events, state.clone());
-                let translated = catalog
-                    .find_message(None, &msgid, None)
-                    .filter(|msg| !msg.flags().is_fuzzy())
-                    .and_then(|msg| msg.msgstr().ok())
-                    .filter(|msgstr| !msgstr.is_empty());
-                match translated {
-                    Some(msgstr) => {
-                        // Generate new events for `msgstr`, taking
-                        // care to trim away unwanted paragraphs.
-                        translated_events.extend_from_slice(trim_paragraph(
-                            &extract_events(msgstr, state),
-                            events,
-                        ));
+                let (msgid_events, originals) = if config.placeholders {
+                    let (msgid_events, originals) = placeholders::insert_placeholders(events);
+                    (Cow::Owned(msgid_events), originals)
+                } else {
+                    (Cow::Borrowed(events), Vec::new())
+                };
+                let state = session.state().cloned();
+                let msgid = session.render_group(&msgid_events);
+                let translated = if config.skip_math && is_math_only(events) {
+                    None
+                } else {
+                    find_translation(catalog, &msgid)
+                };
+                // Generate new events for `msgstr`, taking care to
+                // trim away unwanted paragraphs. Translations with
+                // broken placeholders are not used, and code blocks
+                // get back the comment markers the translation lost.
+                let repaired =
+                    translated.and_then(|msgstr| repair_code_comments(&msgid, msgstr, config));
+                let new_events = translated.and_then(|msgstr| {
+                    let new_events = match &repaired {
+                        Some(repaired) => {
+                            extract_events_with_links(repaired, state, config, &links)
+                                .into_iter()
+                                .map(|(lineno, event)| (lineno, event.into_static()))
+                                .collect()
+                        }
+                        None => extract_events_with_links(msgstr, state, config, &links),
+                    };
+                    let new_events = trim_paragraph(&new_events, events);
+                    if originals.is_empty() {
+                        Some(new_events.to_vec())
+                    } else {
+                        placeholders::expand_placeholders(new_events, &originals)
+                    }
+                });
+                match new_events {
+                    Some(mut new_events) => {
+                        if let (Some(path), Some(msgstr)) = (provenance_path, translated) {
+                            // Put the marker before the first inline
+                            // event, inside a paragraph or heading.
+                            let idx = new_events.iter().position(|(_, event)| is_inline(event));
+                            if let Some(idx) = idx.filter(|&idx| {
+                                new_events[..idx]
+                                    .iter()
+                                    .all(|(_, event)| is_block_start(event))
+                            }) {
+                                let marker = provenance_marker(&msgid, msgstr, path, events[0].0);
+                                let lineno = new_events[idx].0;
+                                new_events.insert(idx, (lineno, Event::InlineHtml(marker.into())));
+                            }
+                        }
+                        translated_events.extend(new_events);
                     }
                     None => translated_events.extend_from_slice(events),
                 }
-                // Advance the state.
-                state = Some(new_state);
             }
             Group::Skip(events) => {
-                // Copy the events unchanged to the output.
-                translated_events.extend_from_slice(events);
+                // Copy the events to the output, translating the
+                // raw HTML and front matter if requested.
+                let mut skipped = Cow::Borrowed(events);
+                if config.extract_html {
+                    skipped = Cow::Owned(translate_html_events(&skipped, catalog));
+                }
+                if !config.frontmatter_keys.is_empty() {
+                    skipped = Cow::Owned(translate_frontmatter_events(
+                        &skipped,
+                        catalog,
+                        &config.frontmatter_keys,
+                    ));
+                }
+                translated_events.extend_from_slice(&skipped);
                 // Advance the state.
-                let (_, new_state) = reconstruct_markdown(events, state);
-                state = Some(new_state);
+                session.advance(events);
             }
         }
     }
@@ -469,7 +2420,7 @@ mod tests {
             vec![
                 (1, Start(Paragraph)),
                 (1, Text("foo bar".into())),
-                (1, End(Paragraph)),
+                (1, End(TagEnd::Paragraph)),
             ]
         );
     }
@@ -483,7 +2434,7 @@ mod tests {
                 (1, Text("foo".into())),
                 (1, Text(" ".into())),
                 (2, Text("bar".into())),
-                (1, End(Paragraph)),
+                (1, End(TagEnd::Paragraph)),
             ]
         );
     }
@@ -493,9 +2444,17 @@ mod tests {
         assert_eq!(
             extract_events("# Foo Bar", None),
             vec![
-                (1, Start(Heading(H1, None, vec![]))),
+                (
+                    1,
+                    Start(Heading {
+                        level: H1,
+                        id: None,
+                        classes: vec![],
+                        attrs: vec![]
+                    })
+                ),
                 (1, Text("Foo Bar".into())),
-                (1, End(Heading(H1, None, vec![]))),
+                (1, End(TagEnd::Heading(H1))),
             ]
         );
     }
@@ -508,8 +2467,8 @@ mod tests {
                 (1, Start(List(None))),
                 (1, Start(Item)),
                 (1, Text("foo bar".into())),
-                (1, End(Item)),
-                (1, End(List(None))),
+                (1, End(TagEnd::Item)),
+                (1, End(TagEnd::List(false))),
             ]
         );
     }
@@ -537,7 +2496,7 @@ mod tests {
                 (2, Text("bar".into())),
                 (2, Text(" ".into())),
                 (3, Text("baz".into())),
-                (1, End(Paragraph)),
+                (1, End(TagEnd::Paragraph)),
             ]
         );
     }
@@ -685,7 +2644,7 @@ The document[^1] text.
             | `rust_library`    | Produces a Rust library.\n\
         ";
         assert_extract_messages(
-            &input,
+            input,
             vec![
                 (1, "Module Type"),
                 (1, "Description"),
@@ -878,4 +2837,783 @@ BOB
             ],
         );
     }
+
+    /// Extract messages in `document` with `math_delimiters`, assert
+    /// they match `expected`.
+    #[track_caller]
+    fn assert_extract_math_messages(document: &str, expected: Vec<(usize, &str)>) {
+        let config = MarkdownConfig {
+            math_delimiters: vec![
+                (String::from("$$"), String::from("$$")),
+                (String::from("\\("), String::from("\\)")),
+                (String::from("\\["), String::from("\\]")),
+            ],
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            extract_messages_with_config(document, &config)
+                .iter()
+                .map(|(lineno, msg)| (*lineno, &msg[..]))
+                .collect::<Vec<_>>(),
+            expected,
+        )
+    }
+
+    #[test]
+    fn find_math_spans_simple() {
+        let delimiters = vec![(String::from("$"), String::from("$"))];
+        assert_eq!(
+            find_math_spans("a $x$ b $y$", &delimiters),
+            vec![2..5, 8..11]
+        );
+        assert!(find_math_spans("a $x", &delimiters).is_empty());
+        assert!(find_math_spans(r"a \$x$", &delimiters).is_empty());
+    }
+
+    #[test]
+    fn extract_messages_math_escapes() {
+        assert_extract_math_messages(
+            r"The area is \(\pi r^2\) and $$ \{ a_1, a_2 \} $$.",
+            vec![(1, r"The area is \(\pi r^2\) and $$ \{ a_1, a_2 \} $$.")],
+        );
+    }
+
+    #[test]
+    fn extract_messages_math_heading() {
+        assert_extract_math_messages(
+            r"# Euler: \(e^{i\pi} = -1\)",
+            vec![(1, r"Euler: \(e^{i\pi} = -1\)")],
+        );
+    }
+
+    #[test]
+    fn extract_messages_math_block_quote() {
+        assert_extract_math_messages(
+            "> Display:\n> \\[\n> a_1 + a_2\n> \\]",
+            vec![(1, "Display: \\[\na_1 + a_2\n\\]")],
+        );
+    }
+
+    #[test]
+    fn extract_messages_math_escaped_delimiter() {
+        assert_extract_math_messages(r"Not math: \\(x\\).", vec![(1, r"Not math: \\(x\\).")]);
+    }
+
+    #[test]
+    fn extract_messages_math_without_config() {
+        assert_extract_messages(
+            r"The area is \(\pi r^2\).",
+            vec![(1, r"The area is (\pi r^2).")],
+        );
+    }
+
+    #[test]
+    fn extract_messages_html_skipped() {
+        assert_extract_messages("<table>\n<tr><td>Foo</td></tr>\n</table>\n", vec![]);
+    }
+
+    #[test]
+    fn extract_messages_html_text() {
+        let config = MarkdownConfig {
+            extract_html: true,
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            extract_messages_with_config(
+                "Intro\n\
+                 \n\
+                 <table>\n\
+                 \x20 <tr><th>Name</th><td>A <b>bold</b>\n\
+                 \x20   value</td></tr>\n\
+                 \x20 <tr><td>Bar</td></tr>\n\
+                 </table>\n\
+                 \n\
+                 Outro",
+                &config
+            ),
+            vec![
+                (1, "Intro".into()),
+                (4, "Name".into()),
+                (4, "A <b>bold</b> value".into()),
+                (6, "Bar".into()),
+                (9, "Outro".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn translate_events_html_text() {
+        let config = MarkdownConfig {
+            extract_html: true,
+            ..MarkdownConfig::default()
+        };
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        catalog.append_or_update(
+            polib::message::Message::build_singular()
+                .with_msgid(String::from("A <b>bold</b> value"))
+                .with_msgstr(String::from("Une valeur <b>grasse</b>"))
+                .done(),
+        );
+        let events = extract_events_with_config(
+            "<table>\n<tr><th>Name</th><td>A <b>bold</b>\n  value</td></tr>\n</table>\n",
+            None,
+            &config,
+        );
+        let translated = translate_events_with_config(&events, &catalog, &config);
+        assert_eq!(
+            translated,
+            vec![
+                (1, Start(HtmlBlock)),
+                (
+                    1,
+                    Html(
+                        "<table>\n<tr><th>Name</th><td>Une valeur <b>grasse</b></td></tr>\n</table>\n"
+                            .into()
+                    )
+                ),
+                (1, End(TagEnd::HtmlBlock)),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_events_math() {
+        assert_eq!(
+            extract_events("$a_1$ and $$b * c$$", None),
+            vec![
+                (1, Start(Paragraph)),
+                (1, InlineMath("a_1".into())),
+                (1, Text(" and ".into())),
+                (1, DisplayMath("b * c".into())),
+                (1, End(TagEnd::Paragraph)),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_messages_inline_math() {
+        assert_extract_messages(
+            "The area is $\\pi r_1 * r_2$.\n\n$$\n\\sum_{n=1}^{\\infty} a_n\n$$",
+            vec![
+                (1, "The area is $\\pi r_1 * r_2$."),
+                (3, "$$\n\\sum_{n=1}^{\\infty} a_n\n$$"),
+            ],
+        );
+    }
+
+    #[test]
+    fn extract_messages_superscript_subscript() {
+        // Like `mdbook`, we don't parse superscript and subscript by
+        // default, so a single `~` is strikethrough.
+        assert_extract_messages("See ^1^ and ~2~", vec![(1, "See ^1^ and ~~2~~")]);
+
+        let config = MarkdownConfig {
+            superscript_subscript: true,
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            extract_messages_with_config("See ^1^ and ~2~\n\n- ~~Old~~", &config),
+            vec![
+                (1, String::from("See ^1^ and ~2~")),
+                (3, String::from("~~Old~~"))
+            ]
+        );
+    }
+
+    #[test]
+    fn group_events_superscript_subscript() {
+        let text = "See ^1^ and ~2~";
+        let config = MarkdownConfig {
+            superscript_subscript: true,
+            ..MarkdownConfig::default()
+        };
+        let events = extract_events_with_config(text, None, &config);
+        assert!(events
+            .iter()
+            .any(|(_, event)| matches!(event, Event::Start(Tag::Superscript))));
+        let translate_groups = group_events(&events)
+            .into_iter()
+            .filter_map(|group| match group {
+                Group::Translate(events) => Some(events),
+                Group::Skip(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(translate_groups.len(), 1);
+        assert_eq!(reconstruct_markdown(translate_groups[0], None).0, text);
+    }
+
+    #[test]
+    fn translate_superscript_subscript_round_trip() {
+        let config = MarkdownConfig {
+            superscript_subscript: true,
+            ..MarkdownConfig::default()
+        };
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        catalog.append_or_update(
+            polib::message::Message::build_singular()
+                .with_msgid(String::from("Water is ~H2O~."))
+                .with_msgstr(String::from("Vand er ~H2O~ ^a^."))
+                .done(),
+        );
+        let events = extract_events_with_config("Water is ~H2O~.", None, &config);
+        let translated = translate_events_with_config(&events, &catalog, &config);
+        assert!(translated
+            .iter()
+            .any(|(_, event)| matches!(event, Event::Start(Tag::Subscript))));
+        assert_eq!(reconstruct_document(&translated), "Vand er ~H2O~ ^a^.");
+    }
+
+    #[test]
+    fn markdown_config_superscript_subscript() -> anyhow::Result<()> {
+        let config = <mdbook::Config as std::str::FromStr>::from_str(
+            r#"
+            [output.xgettext]
+            superscript-subscript = true
+            "#,
+        )?;
+        let config = MarkdownConfig::from_book_config(&config)?;
+        assert!(config.superscript_subscript);
+        assert!(config
+            .parser_options()
+            .contains(pulldown_cmark::Options::ENABLE_SUBSCRIPT));
+        assert!(!MarkdownConfig::default()
+            .parser_options()
+            .contains(pulldown_cmark::Options::ENABLE_SUPERSCRIPT));
+        Ok(())
+    }
+
+    #[test]
+    fn translate_inline_math_round_trip() {
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        catalog.append_or_update(
+            polib::message::Message::build_singular()
+                .with_msgid(String::from("The area is $\\pi r_1 * r_2$."))
+                .with_msgstr(String::from("Arealet er $\\pi r_1 * r_2$."))
+                .done(),
+        );
+        let events = extract_events("The area is $\\pi r_1 * r_2$.", None);
+        assert_eq!(
+            reconstruct_document(&translate_events(&events, &catalog)),
+            "Arealet er $\\pi r_1 * r_2$."
+        );
+    }
+
+    #[test]
+    fn extract_messages_skip_math() {
+        let config = MarkdownConfig {
+            skip_math: true,
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            extract_messages_with_config(
+                "The area is $\\pi r^2$.\n\n$$\n\\sum_{n=1}^{\\infty} a_n\n$$",
+                &config
+            ),
+            vec![(1, "The area is $\\pi r^2$.".into())]
+        );
+    }
+
+    #[test]
+    fn extract_messages_alert() {
+        // The alert marker is part of the block quote, it is not
+        // offered for translation.
+        assert_extract_messages(
+            "> [!NOTE]\n> Useful *information*.\n>\n> More details.",
+            vec![(2, "Useful _information_."), (4, "More details.")],
+        );
+    }
+
+    #[test]
+    fn extract_messages_definition_list() {
+        assert_extract_messages(
+            "Apple\n\
+             : A red *fruit*.\n\
+             : A company.\n\
+             \n\
+             Orange\n\
+             : Citrus fruit.\n\
+             \n\
+             \x20 With a second paragraph.\n",
+            vec![
+                (1, "Apple"),
+                (2, "A red _fruit_."),
+                (3, "A company."),
+                (5, "Orange"),
+                (6, "Citrus fruit."),
+                (8, "With a second paragraph."),
+            ],
+        );
+    }
+
+    #[test]
+    fn extract_messages_frontmatter() {
+        let config = MarkdownConfig {
+            frontmatter_keys: vec![String::from("title"), String::from("description")],
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            extract_messages_with_config(
+                "---\n\
+                 title: Getting Started\n\
+                 layout: chapter\n\
+                 description: \"How to begin\"\n\
+                 ---\n\
+                 \n\
+                 # Getting Started\n",
+                &config
+            ),
+            vec![
+                (2, "Getting Started".into()),
+                (4, "How to begin".into()),
+                (7, "Getting Started".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_messages_frontmatter_without_config() {
+        // Without keys, the front matter is parsed as Markdown.
+        assert_extract_messages(
+            "---\ntitle: Getting Started\n---\n",
+            vec![(2, "title: Getting Started")],
+        );
+    }
+
+    #[test]
+    fn translate_events_frontmatter() {
+        let config = MarkdownConfig {
+            frontmatter_keys: vec![String::from("title")],
+            ..MarkdownConfig::default()
+        };
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        catalog.append_or_update(
+            polib::message::Message::build_singular()
+                .with_msgid(String::from("Getting Started"))
+                .with_msgstr(String::from("Kom i gang"))
+                .done(),
+        );
+        let events =
+            extract_events_with_config("---\ntitle: Getting Started\n---\n", None, &config);
+        let translated = translate_events_with_config(&events, &catalog, &config);
+        let (markdown, _) = reconstruct_markdown(&translated, None);
+        assert_eq!(markdown, "---\ntitle: Kom i gang\n---");
+    }
+
+    #[test]
+    fn extract_messages_split_code_blocks() {
+        let config = MarkdownConfig {
+            code_rules: Some(code_blocks::default_rules()),
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            extract_messages_with_config(
+                "Intro\n\
+                 \n\
+                 ```rust,editable\n\
+                 // Print a greeting\n\
+                 println!(\"Hello\");\n\
+                 ```\n\
+                 \n\
+                 ```text\n\
+                 // Not split\n\
+                 ```\n",
+                &config
+            ),
+            vec![
+                (1, "Intro".into()),
+                (4, "Print a greeting".into()),
+                (5, "Hello".into()),
+                (8, "```text\n// Not split\n```".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_messages_detect_code_languages() {
+        let mut config = MarkdownConfig {
+            code_rules: Some(code_blocks::default_rules()),
+            ..MarkdownConfig::default()
+        };
+        let document = "```\n\
+                        #!/usr/bin/env python3\n\
+                        print('Hello') # Greet the user\n\
+                        ```\n\
+                        \n\
+                        ```\n\
+                        Some output\n\
+                        ```\n";
+        assert_eq!(
+            extract_messages_with_config(document, &config),
+            vec![
+                (
+                    1,
+                    "```\n#!/usr/bin/env python3\nprint('Hello') # Greet the user\n```".into()
+                ),
+                (6, "```\nSome output\n```".into()),
+            ]
+        );
+        config.detect_code_languages = true;
+        assert_eq!(
+            extract_messages_with_config(document, &config),
+            vec![
+                (3, "Hello".into()),
+                (3, "Greet the user".into()),
+                (6, "```\nSome output\n```".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn translate_events_split_code_blocks() {
+        let config = MarkdownConfig {
+            code_rules: Some(code_blocks::default_rules()),
+            ..MarkdownConfig::default()
+        };
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        for (msgid, msgstr) in [
+            ("Print a greeting", "Udskriv en hilsen"),
+            // This would end the string early.
+            ("Hello", "Sig \"hej\""),
+        ] {
+            catalog.append_or_update(
+                polib::message::Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let events = extract_events_with_config(
+            "```rust\n// Print a greeting\nprintln!(\"Hello\");\n```\n",
+            None,
+            &config,
+        );
+        let translated = translate_events_with_config(&events, &catalog, &config);
+        let (markdown, _) = reconstruct_markdown(&translated, None);
+        assert_eq!(
+            markdown,
+            "```rust\n// Udskriv en hilsen\nprintln!(\"Hello\");\n```"
+        );
+    }
+
+    #[test]
+    fn translate_events_repairs_comment_markers() {
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        catalog.append_or_update(
+            polib::message::Message::build_singular()
+                .with_msgid(String::from("```rust\n// Print a greeting\nhello();\n```"))
+                .with_msgstr(String::from("```rust\nUdskriv en hilsen\nhello();\n```"))
+                .done(),
+        );
+        let events = extract_events(
+            "- Item\n\n  ```rust\n  // Print a greeting\n  hello();\n  ```\n",
+            None,
+        );
+        let translated = translate_events(&events, &catalog);
+        let (markdown, _) = reconstruct_markdown(&translated, None);
+        assert_eq!(
+            markdown,
+            "- Item\n  \n  ```rust\n  // Udskriv en hilsen\n  hello();\n  ```"
+        );
+    }
+
+    #[test]
+    fn extract_messages_keep_reference_links() {
+        let config = MarkdownConfig {
+            keep_reference_links: true,
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            extract_messages_with_config(
+                "See [the guide][guide], [Rust][] and [crates].\n\
+                 \n\
+                 [guide]: https://example.com/guide\n\
+                 [rust]: https://www.rust-lang.org/ \"Rust\"\n\
+                 [crates]: https://crates.io/\n",
+                &config
+            ),
+            vec![(
+                1,
+                "See [the guide][guide], [Rust][Rust] and [crates][crates].".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn translate_events_keep_reference_links() {
+        let config = MarkdownConfig {
+            keep_reference_links: true,
+            ..MarkdownConfig::default()
+        };
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        catalog.append_or_update(
+            polib::message::Message::build_singular()
+                .with_msgid(String::from("See [Rust][Rust]."))
+                .with_msgstr(String::from("Se [sproget][rust]."))
+                .done(),
+        );
+        let events = extract_events_with_config(
+            "See [Rust].\n\n[Rust]: https://www.rust-lang.org/",
+            None,
+            &config,
+        );
+        let translated = translate_events_with_config(&events, &catalog, &config);
+        assert_eq!(
+            reconstruct_document(&translated),
+            "Se [sproget][rust].\n\n[rust]: https://www.rust-lang.org/"
+        );
+    }
+
+    #[test]
+    fn extract_messages_kind_comments() {
+        let config = MarkdownConfig {
+            kind_comments: true,
+            placeholders: true,
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            extract_messages_with_comments("## Run `cargo`\n\n* Item", &config)
+                .into_iter()
+                .map(|message| (message.msgid, message.comment))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    String::from("Run {1}"),
+                    String::from("heading\n{1}: `cargo`")
+                ),
+                (String::from("Item"), String::from("list item")),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_messages_no_wrap() {
+        let messages = extract_messages_with_comments(
+            "Text\n\n```\nfn  main() {}\n```",
+            &MarkdownConfig::default(),
+        );
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| message.no_wrap)
+                .collect::<Vec<_>>(),
+            vec![false, true]
+        );
+    }
+
+    #[test]
+    fn render_session_advance() {
+        let events = extract_events(
+            "# Title\n\n> Quote\n>\n> <div>\n> HTML\n> </div>\n\n\
+             1. Item\n\n   ```rust\n   fn main() {}\n   ```\n\n<!-- comment -->\n\nEnd\n",
+            None,
+        );
+        let mut rendering = RenderSession::new();
+        let mut advancing = RenderSession::new();
+        for group in iter_groups(&events) {
+            rendering.render_group(group.events());
+            advancing.advance(group.events());
+            assert_eq!(advancing.state(), rendering.state());
+        }
+    }
+
+    #[test]
+    fn extract_messages_structure() {
+        let document = "# Intro\n\nSome *text*\n\n## Usage\n\n> Quote\n\n\
+                        ### Details\n\n- Item\n\n## Notes\n\nEnd";
+        let messages = extract_messages_with_comments(document, &MarkdownConfig::default());
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| (
+                    &document[message.span.clone()],
+                    message.kind,
+                    message.headings.join(" > ")
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Intro", kinds::BlockKind::Heading, String::new()),
+                (
+                    "Some *text*",
+                    kinds::BlockKind::Paragraph,
+                    String::from("Intro")
+                ),
+                ("Usage", kinds::BlockKind::Heading, String::from("Intro")),
+                (
+                    "Quote",
+                    kinds::BlockKind::BlockQuote,
+                    String::from("Intro > Usage")
+                ),
+                (
+                    "Details",
+                    kinds::BlockKind::Heading,
+                    String::from("Intro > Usage")
+                ),
+                (
+                    "Item",
+                    kinds::BlockKind::ListItem,
+                    String::from("Intro > Usage > Details")
+                ),
+                ("Notes", kinds::BlockKind::Heading, String::from("Intro")),
+                (
+                    "End",
+                    kinds::BlockKind::Paragraph,
+                    String::from("Intro > Notes")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_messages_code_spans() {
+        let config = MarkdownConfig {
+            code_rules: Some(code_blocks::default_rules()),
+            ..MarkdownConfig::default()
+        };
+        let document = "Text\n\n```rust\nfn main() {\n    // Say hello\n    \
+                        println!(\"Hello\");\n}\n```\n";
+        let messages = extract_messages_with_comments(document, &config);
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| (&document[message.span.clone()], message.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Text", kinds::BlockKind::Paragraph),
+                ("Say hello", kinds::BlockKind::CodeBlock),
+                ("Hello", kinds::BlockKind::CodeBlock),
+            ]
+        );
+    }
+
+    #[test]
+    fn translate_events_with_provenance_markers() {
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        for (msgid, msgstr) in [("Title", "Titel"), ("An _item_.", "Et _punkt_.")] {
+            catalog.append_or_update(
+                polib::message::Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let events = extract_events("# Title\n\n- An *item*.\n- Other.\n", None);
+        let translated = translate_events_with_provenance(
+            &events,
+            &catalog,
+            &MarkdownConfig::default(),
+            "src/\"q\".md",
+        );
+        assert_eq!(
+            reconstruct_document(&translated),
+            format!(
+                "# <span data-i18n-hash=\"{}\" data-i18n-translation-hash=\"{}\" \
+                 data-i18n-source=\"src/&quot;q&quot;.md:1\" hidden></span>Titel\n\n\
+                 - <span data-i18n-hash=\"{}\" data-i18n-translation-hash=\"{}\" \
+                 data-i18n-source=\"src/&quot;q&quot;.md:3\" hidden></span>Et _punkt_.\n\
+                 - Other.",
+                state::message_checksum("Title"),
+                state::translation_checksum("Title", "Titel"),
+                state::message_checksum("An _item_."),
+                state::translation_checksum("An _item_.", "Et _punkt_."),
+            )
+        );
+    }
+
+    #[test]
+    fn reconstruct_markdown_definition_list() {
+        let events = extract_events("Apple\n: A fruit.\n\n  More.\n\nOrange\n: Citrus.", None);
+        let (markdown, _) = reconstruct_markdown(&events, None);
+        let reconstructed_events = extract_events(&markdown, None);
+        assert_eq!(
+            reconstructed_events
+                .iter()
+                .map(|(_, event)| event)
+                .collect::<Vec<_>>(),
+            events.iter().map(|(_, event)| event).collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn reconstruct_document_with_source_options() {
+        let source = "\
+| Name | Size |
+|:-----|-----:|
+| Foo  | 10   |
+
+* Some *emphasis* and __strong__ text.
+* More.
+
+3) Three
+4) Four";
+        let options = ReconstructOptions::from_source(source, &MarkdownConfig::default());
+        assert_eq!(
+            options,
+            ReconstructOptions {
+                emphasis_token: '*',
+                strong_token: "__",
+                list_token: '*',
+                ordered_list_token: ')',
+                increment_ordered_list_bullets: true,
+            }
+        );
+        let events = extract_events(source, None);
+        assert_eq!(
+            reconstruct_document_with_options(&events, &options),
+            "\
+|Name|Size|
+|:---|---:|
+|Foo|10|
+
+* Some *emphasis* and __strong__ text.
+* More.
+
+3) Three
+4) Four"
+        );
+    }
+
+    #[test]
+    fn reconstruct_options_from_source_without_syntax() {
+        assert_eq!(
+            ReconstructOptions::from_source("Just text.", &MarkdownConfig::default()),
+            ReconstructOptions::default()
+        );
+    }
+
+    #[test]
+    fn reconstruct_options_from_source_frontmatter() {
+        let source = "---\ntags:\n- draft\n---\n\n* Item\n";
+        let config = MarkdownConfig {
+            frontmatter_keys: vec![String::from("title")],
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            ReconstructOptions::from_source(source, &config).list_token,
+            '*'
+        );
+        assert_eq!(
+            ReconstructOptions::from_source(source, &MarkdownConfig::default()).list_token,
+            '-'
+        );
+    }
+
+    #[test]
+    fn restore_table_delimiter_rows_in_block_quote() {
+        let source = "> | A | B |\n> |:---:|---|\n> | 1 | 2 |\n\n| C |\n| - |";
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        for (msgid, msgstr) in [("A", "Alfa"), ("C", "Charlie")] {
+            catalog.append_or_update(
+                polib::message::Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let events = extract_events(source, None);
+        let translated = reconstruct_document(&translate_events(&events, &catalog));
+        assert_eq!(
+            restore_table_delimiter_rows(source, &translated, &MarkdownConfig::default()),
+            " > \n > |Alfa|B|\n > |:---:|---|\n > |1|2|\n\n|Charlie|\n| - |"
+        );
+    }
 }
@@ -0,0 +1,211 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Check that translations keep the markup of the source text.
+//!
+//! Translators should translate the text around inline code, links,
+//! and HTML tags, but leave the markup itself alone. A translation
+//! which drops a code span or changes a URL is most likely a mistake.
+//! The functions here find such translations so that they can be
+//! reported or marked fuzzy.
+
+use crate::extract_events;
+use crate::placeholders::parse_placeholder;
+use polib::catalog::Catalog;
+use polib::message::MessageMutView;
+use pulldown_cmark::{Event, Tag};
+
+/// A translation which doesn't have the same markup as its `msgid`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkupMismatch {
+    /// The untranslated message.
+    pub msgid: String,
+    /// Markup in the `msgid` which is missing from the translation.
+    pub missing: Vec<String>,
+    /// Markup in the translation which is not in the `msgid`.
+    pub extra: Vec<String>,
+}
+
+/// Collect the markup in the Markdown `text`, sorted.
+///
+/// This is the inline code spans, the destinations of links and
+/// images, the HTML tags, and any placeholders such as `{1}`.
+fn markup_items(text: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    for (_, event) in extract_events(text, None) {
+        match event {
+            Event::Code(code) => items.push(format!("`{code}`")),
+            Event::Start(Tag::Link { dest_url, .. }) => items.push(format!("link to {dest_url}")),
+            Event::Start(Tag::Image { dest_url, .. }) => items.push(format!("image {dest_url}")),
+            Event::Html(html) | Event::InlineHtml(html) => {
+                items.push(String::from(html.trim()));
+            }
+            Event::Text(text) => {
+                for (idx, _) in text.match_indices('{') {
+                    if let Some((len, _, _)) = parse_placeholder(&text[idx..]) {
+                        items.push(String::from(&text[idx..idx + len]));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    items.sort();
+    items
+}
+
+/// Remove the items in `other` from `items`, counting duplicates.
+pub(crate) fn subtract(items: &[String], other: &[String]) -> Vec<String> {
+    let mut other = other.to_vec();
+    let mut difference = Vec::new();
+    for item in items {
+        match other.iter().position(|o| o == item) {
+            Some(idx) => {
+                other.remove(idx);
+            }
+            None => difference.push(item.clone()),
+        }
+    }
+    difference
+}
+
+/// Compare the markup of `msgid` and its translation `msgstr`.
+///
+/// Returns `None` if the markup is the same, though possibly in a
+/// different order.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::markup::check_markup;
+///
+/// assert_eq!(check_markup("Run `cargo`.", "Kør `cargo`."), None);
+/// let mismatch = check_markup("Run `cargo`.", "Kør cargo.").unwrap();
+/// assert_eq!(mismatch.missing, vec!["`cargo`"]);
+/// ```
+pub fn check_markup(msgid: &str, msgstr: &str) -> Option<MarkupMismatch> {
+    let source_items = markup_items(msgid);
+    let translated_items = markup_items(msgstr);
+    if source_items == translated_items {
+        return None;
+    }
+    Some(MarkupMismatch {
+        msgid: String::from(msgid),
+        missing: subtract(&source_items, &translated_items),
+        extra: subtract(&translated_items, &source_items),
+    })
+}
+
+/// Find the translations in `catalog` with mismatched markup.
+///
+/// Fuzzy and untranslated messages are skipped, as are messages with
+/// plural forms.
+pub fn find_markup_mismatches(catalog: &Catalog) -> Vec<MarkupMismatch> {
+    catalog
+        .messages()
+        .filter(|msg| !msg.is_fuzzy())
+        .filter_map(|msg| {
+            let msgstr = msg.msgstr().ok().filter(|msgstr| !msgstr.is_empty())?;
+            check_markup(msg.msgid(), msgstr)
+        })
+        .collect()
+}
+
+/// Mark the translations in `catalog` with mismatched markup fuzzy.
+///
+/// Fuzzy translations are not used by `mdbook-gettext`, so the
+/// untranslated text is shown instead. Returns the mismatches.
+pub fn mark_markup_mismatches_fuzzy(catalog: &mut Catalog) -> Vec<MarkupMismatch> {
+    let mismatches = find_markup_mismatches(catalog);
+    for mismatch in &mismatches {
+        if let Some(mut message) = catalog.find_message_mut(None, &mismatch.msgid, None) {
+            message.flags_mut().add_flag("fuzzy");
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_check_markup_reordered() {
+        assert_eq!(
+            check_markup(
+                "See [the docs](https://x.org) and `cargo` <b>now</b>.",
+                "<b>Nu</b>: `cargo` og [dokumentationen](https://x.org)."
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check_markup_changed() {
+        assert_eq!(
+            check_markup(
+                "See [the docs](https://x.org) and `cargo`.",
+                "Se [dokumentationen](https://x.dk) og `cargo` og `cargo`."
+            ),
+            Some(MarkupMismatch {
+                msgid: String::from("See [the docs](https://x.org) and `cargo`."),
+                missing: vec![String::from("link to https://x.org")],
+                extra: vec![
+                    String::from("`cargo`"),
+                    String::from("link to https://x.dk")
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_markup_placeholders() {
+        assert_eq!(
+            check_markup("Run {1} from {2}here{/2}.", "Kør {1} {2}herfra."),
+            Some(MarkupMismatch {
+                msgid: String::from("Run {1} from {2}here{/2}."),
+                missing: vec![String::from("{/2}")],
+                extra: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn test_mark_markup_mismatches_fuzzy() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (msgid, msgstr) in [("Use `foo`.", "Brug foo."), ("Use `bar`.", "Brug `bar`.")] {
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+
+        let mismatches = mark_markup_mismatches_fuzzy(&mut catalog);
+        assert_eq!(mismatches.len(), 1);
+        assert!(catalog
+            .find_message(None, "Use `foo`.", None)
+            .unwrap()
+            .is_fuzzy());
+        assert!(!catalog
+            .find_message(None, "Use `bar`.", None)
+            .unwrap()
+            .is_fuzzy());
+        assert_eq!(find_markup_mismatches(&catalog), vec![]);
+    }
+}
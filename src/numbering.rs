@@ -0,0 +1,245 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Write the section numbers of the sidebar in the digits of a
+//! language.
+//!
+//! `mdbook` numbers the chapters in the sidebar as "1.", "1.2.", and
+//! so on. Books in languages such as Arabic or Chinese can show these
+//! numbers as "١.٢." or "一.二." instead. The sidebar is made by the
+//! theme, so the numbers are replaced by a script, see
+//! [`numbering_script`].
+
+use crate::language::same_language;
+use anyhow::{anyhow, bail};
+use std::collections::BTreeMap;
+
+/// A way to write numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberingStyle {
+    /// Western digits: 0123456789.
+    Western,
+    /// Arabic-Indic digits: ٠١٢٣٤٥٦٧٨٩.
+    ArabicIndic,
+    /// Extended Arabic-Indic digits, as used for Persian and Urdu:
+    /// ۰۱۲۳۴۵۶۷۸۹.
+    Persian,
+    /// Devanagari digits: ०१२३४५६७८९.
+    Devanagari,
+    /// CJK numerals: 一, 二, ..., 十, 十一, ..., 九十九.
+    Cjk,
+}
+
+/// The default numbering style of each language.
+const LANGUAGE_STYLES: &[(&str, NumberingStyle)] = &[
+    ("ar", NumberingStyle::ArabicIndic),
+    ("fa", NumberingStyle::Persian),
+    ("ur", NumberingStyle::Persian),
+    ("hi", NumberingStyle::Devanagari),
+    ("mr", NumberingStyle::Devanagari),
+    ("ne", NumberingStyle::Devanagari),
+    ("zh", NumberingStyle::Cjk),
+    ("ja", NumberingStyle::Cjk),
+];
+
+impl NumberingStyle {
+    /// Parse a style name, such as `arabic-indic`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "western" => Some(NumberingStyle::Western),
+            "arabic-indic" => Some(NumberingStyle::ArabicIndic),
+            "persian" => Some(NumberingStyle::Persian),
+            "devanagari" => Some(NumberingStyle::Devanagari),
+            "cjk" => Some(NumberingStyle::Cjk),
+            _ => None,
+        }
+    }
+
+    /// The usual style of `language`, such as Arabic-Indic digits for
+    /// `ar`. Regional variants, such as `zh-TW`, use the style of the
+    /// general language.
+    pub fn for_language(language: &str) -> Self {
+        let general = language.split(['-', '_']).next().unwrap_or(language);
+        LANGUAGE_STYLES
+            .iter()
+            .find(|(key, _)| same_language(key, general))
+            .map_or(NumberingStyle::Western, |(_, style)| *style)
+    }
+
+    /// Read the style for `language` from
+    /// `preprocessor.gettext.numbering`.
+    ///
+    /// The setting is `"auto"` for the usual style of the language, or
+    /// the name of a style. Returns `None` if it is not set.
+    pub fn from_book_config(
+        config: &mdbook::Config,
+        language: &str,
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(value) = config.get("preprocessor.gettext.numbering") else {
+            return Ok(None);
+        };
+        let name = value
+            .as_str()
+            .ok_or_else(|| anyhow!("Expected a string for preprocessor.gettext.numbering"))?;
+        if name == "auto" {
+            return Ok(Some(NumberingStyle::for_language(language)));
+        }
+        match NumberingStyle::from_name(name) {
+            Some(style) => Ok(Some(style)),
+            None => bail!(
+                "Unknown preprocessor.gettext.numbering {name:?}, expected \"auto\", \
+                 \"western\", \"arabic-indic\", \"persian\", \"devanagari\", or \"cjk\""
+            ),
+        }
+    }
+
+    /// The digits from 0 to 9.
+    fn digits(&self) -> [char; 10] {
+        match self {
+            NumberingStyle::Western => ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'],
+            NumberingStyle::ArabicIndic => ['٠', '١', '٢', '٣', '٤', '٥', '٦', '٧', '٨', '٩'],
+            NumberingStyle::Persian => ['۰', '۱', '۲', '۳', '۴', '۵', '۶', '۷', '۸', '۹'],
+            NumberingStyle::Devanagari => ['०', '१', '२', '३', '४', '५', '६', '७', '८', '९'],
+            NumberingStyle::Cjk => ['〇', '一', '二', '三', '四', '五', '六', '七', '八', '九'],
+        }
+    }
+
+    /// Write `number` in this style.
+    ///
+    /// CJK numerals are written with 十 up to 99, larger numbers are
+    /// written digit by digit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::numbering::NumberingStyle;
+    ///
+    /// assert_eq!(NumberingStyle::ArabicIndic.format(12), "١٢");
+    /// assert_eq!(NumberingStyle::Cjk.format(12), "十二");
+    /// assert_eq!(NumberingStyle::Cjk.format(20), "二十");
+    /// ```
+    pub fn format(&self, number: u32) -> String {
+        let digits = self.digits();
+        if *self == NumberingStyle::Cjk && (10..100).contains(&number) {
+            let (tens, ones) = (number / 10, number % 10);
+            let mut result = String::new();
+            if tens > 1 {
+                result.push(digits[tens as usize]);
+            }
+            result.push('十');
+            if ones > 0 {
+                result.push(digits[ones as usize]);
+            }
+            return result;
+        }
+        number
+            .to_string()
+            .chars()
+            .map(|c| c.to_digit(10).map_or(c, |d| digits[d as usize]))
+            .collect()
+    }
+
+    /// Write a section number, such as `1.2.`, in this style.
+    pub fn format_section(&self, section: &str) -> String {
+        section
+            .split('.')
+            .map(|part| match part.parse() {
+                Ok(number) => self.format(number),
+                Err(_) => String::from(part),
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+/// Create a `<script>` element which rewrites the `sections` numbers
+/// in the sidebar in `style`.
+///
+/// The `sections` are the section numbers of the book as written by
+/// `mdbook`, such as `1.2.`. An empty string is returned for the
+/// western style, which `mdbook` uses already.
+pub fn numbering_script<'a>(
+    style: NumberingStyle,
+    sections: impl IntoIterator<Item = &'a str>,
+) -> String {
+    if style == NumberingStyle::Western {
+        return String::new();
+    }
+    let numbers = sections
+        .into_iter()
+        .map(|section| (section, style.format_section(section)))
+        .collect::<BTreeMap<_, _>>();
+    let numbers = serde_json::to_string(&numbers).expect("the numbers can be serialized");
+    format!(
+        r##"<script>
+document.addEventListener("DOMContentLoaded", () => {{
+  const numbers = {numbers};
+  for (const strong of document.querySelectorAll(".sidebar .chapter strong")) {{
+    const number = numbers[strong.textContent];
+    if (number) strong.textContent = number;
+  }}
+}});
+</script>
+"##
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_for_language() {
+        assert_eq!(
+            NumberingStyle::for_language("ar-EG"),
+            NumberingStyle::ArabicIndic
+        );
+        assert_eq!(NumberingStyle::for_language("zh_TW"), NumberingStyle::Cjk);
+        assert_eq!(NumberingStyle::for_language("da"), NumberingStyle::Western);
+    }
+
+    #[test]
+    fn test_format_section() {
+        assert_eq!(NumberingStyle::Persian.format_section("1.10."), "۱.۱۰.");
+        assert_eq!(NumberingStyle::Cjk.format_section("3.11."), "三.十一.");
+        assert_eq!(NumberingStyle::Cjk.format_section("120."), "一二〇.");
+    }
+
+    #[test]
+    fn test_from_book_config() {
+        let config: mdbook::Config = "[preprocessor.gettext]\nnumbering = \"auto\"\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            NumberingStyle::from_book_config(&config, "hi").unwrap(),
+            Some(NumberingStyle::Devanagari)
+        );
+        let config: mdbook::Config = "[preprocessor.gettext]\nnumbering = \"roman\"\n"
+            .parse()
+            .unwrap();
+        assert!(NumberingStyle::from_book_config(&config, "hi").is_err());
+        assert_eq!(
+            NumberingStyle::from_book_config(&mdbook::Config::default(), "hi").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_numbering_script() {
+        assert_eq!(numbering_script(NumberingStyle::Western, ["1."]), "");
+        let script = numbering_script(NumberingStyle::ArabicIndic, ["1.", "1.2."]);
+        assert!(script.contains(r#"const numbers = {"1.":"١.","1.2.":"١.٢."};"#));
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Obsolete entries in PO files.
+//!
+//! When a message is removed from the source, its translation can be
+//! kept at the end of the PO file as an obsolete entry:
+//!
+//! ```po
+//! #~ msgid "Hello"
+//! #~ msgstr "Hej"
+//! ```
+//!
+//! If the message comes back, the translation can be used again. The
+//! `polib` crate skips these entries when it reads a PO file, so they
+//! are handled here.
+
+use crate::catalog_io::{self, WriteOptions};
+use anyhow::Context;
+use polib::catalog::Catalog;
+use std::fs;
+use std::path::Path;
+
+/// A message which is no longer in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObsoleteMessage {
+    /// The untranslated message.
+    pub msgid: String,
+    /// The translation.
+    pub msgstr: String,
+    /// Whether the translation was fuzzy.
+    pub fuzzy: bool,
+}
+
+/// Find the obsolete entries in the PO file `content`.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::obsolete::{parse_obsolete, ObsoleteMessage};
+///
+/// let content = "msgid \"Kept\"\nmsgstr \"Beholdt\"\n\n\
+///                #, fuzzy\n#~ msgid \"Hello\"\n#~ msgstr \"\"\n#~ \"Hej\"\n";
+/// assert_eq!(
+///     parse_obsolete(content),
+///     vec![ObsoleteMessage {
+///         msgid: String::from("Hello"),
+///         msgstr: String::from("Hej"),
+///         fuzzy: true,
+///     }]
+/// );
+/// ```
+pub fn parse_obsolete(content: &str) -> Vec<ObsoleteMessage> {
+    #[derive(PartialEq)]
+    enum Field {
+        None,
+        Msgid,
+        Msgstr,
+    }
+
+    let mut messages = Vec::new();
+    let mut current: Option<ObsoleteMessage> = None;
+    let mut field = Field::None;
+    let mut fuzzy = false;
+    for line in content.lines() {
+        if line.starts_with("#,") {
+            fuzzy = line.contains("fuzzy");
+            continue;
+        }
+        let Some(rest) = line.strip_prefix("#~") else {
+            if line.trim().is_empty() {
+                messages.extend(current.take());
+                field = Field::None;
+                fuzzy = false;
+            }
+            continue;
+        };
+        let rest = rest.trim_start();
+        let (new_field, quoted) = if let Some(quoted) = rest.strip_prefix("msgid ") {
+            messages.extend(current.take());
+            current = Some(ObsoleteMessage {
+                msgid: String::new(),
+                msgstr: String::new(),
+                fuzzy,
+            });
+            (Field::Msgid, quoted)
+        } else if let Some(quoted) = rest.strip_prefix("msgstr ") {
+            (Field::Msgstr, quoted)
+        } else if rest.starts_with('"') {
+            (std::mem::replace(&mut field, Field::None), rest)
+        } else {
+            // Previous strings, `#~|`, and other fields are skipped.
+            field = Field::None;
+            continue;
+        };
+        field = new_field;
+        let text = catalog_io::unescape(quoted.trim().trim_matches('"'));
+        match (&field, current.as_mut()) {
+            (Field::Msgid, Some(message)) => message.msgid.push_str(&text),
+            (Field::Msgstr, Some(message)) => message.msgstr.push_str(&text),
+            _ => {}
+        }
+    }
+    messages.extend(current);
+    messages
+}
+
+/// Format `messages` as obsolete entries.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::obsolete::{format_obsolete, ObsoleteMessage};
+///
+/// let message = ObsoleteMessage {
+///     msgid: String::from("Say \"hi\""),
+///     msgstr: String::from("Sig \"hej\""),
+///     fuzzy: false,
+/// };
+/// assert_eq!(
+///     format_obsolete(&[message]),
+///     "\n#~ msgid \"Say \\\"hi\\\"\"\n#~ msgstr \"Sig \\\"hej\\\"\"\n"
+/// );
+/// ```
+pub fn format_obsolete(messages: &[ObsoleteMessage]) -> String {
+    let mut result = String::new();
+    for message in messages {
+        result.push('\n');
+        if message.fuzzy {
+            result.push_str("#, fuzzy\n");
+        }
+        result.push_str(&format!(
+            "#~ msgid \"{}\"\n",
+            catalog_io::escape(&message.msgid)
+        ));
+        result.push_str(&format!(
+            "#~ msgstr \"{}\"\n",
+            catalog_io::escape(&message.msgstr)
+        ));
+    }
+    result
+}
+
+/// Read the obsolete entries of the PO file at `path`.
+pub fn read_obsolete(path: &Path) -> anyhow::Result<Vec<ObsoleteMessage>> {
+    let content = catalog_io::read_to_string(path)?;
+    Ok(parse_obsolete(&content))
+}
+
+/// Write `catalog` to `path`, followed by the `obsolete` entries.
+///
+/// The file is written as UTF-8 with LF line endings. Use
+/// [`convert_file`](catalog_io::convert_file) afterwards for other
+/// formats.
+pub fn write_with_obsolete(
+    catalog: &Catalog,
+    obsolete: &[ObsoleteMessage],
+    path: &Path,
+) -> anyhow::Result<()> {
+    catalog_io::write_catalog(catalog, path, &WriteOptions::default())?;
+    if !obsolete.is_empty() {
+        let content = catalog_io::read_to_string(path)?;
+        let content = format!("{}\n{}", content.trim_end(), format_obsolete(obsolete));
+        fs::write(path, content).with_context(|| format!("Could not write {:?}", path))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_round_trip() {
+        let messages = vec![
+            ObsoleteMessage {
+                msgid: String::from("Line 1\nLine 2\twith \\ tab"),
+                msgstr: String::from("Linje 1\nLinje 2"),
+                fuzzy: false,
+            },
+            ObsoleteMessage {
+                msgid: String::from("Fuzzy"),
+                msgstr: String::from("Uklar"),
+                fuzzy: true,
+            },
+        ];
+        assert_eq!(parse_obsolete(&format_obsolete(&messages)), messages);
+    }
+
+    #[test]
+    fn test_parse_obsolete_skips_active_messages() {
+        let content = "#, fuzzy\n\
+                       msgid \"Active\"\n\
+                       msgstr \"Aktiv\"\n\
+                       \n\
+                       #~| msgid \"Old\"\n\
+                       #~ msgid \"Gone\"\n\
+                       #~ msgstr \"Væk\"\n";
+        assert_eq!(
+            parse_obsolete(content),
+            vec![ObsoleteMessage {
+                msgid: String::from("Gone"),
+                msgstr: String::from("Væk"),
+                fuzzy: false,
+            }]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Numbered placeholders for inline markup.
+//!
+//! Inline code, links, and inline HTML are replaced by placeholders
+//! such as `{1}` in the messages. Links become a pair of placeholders,
+//! `{1}` and `{/1}`, around the link text, which is still translated.
+//! This keeps URLs and attributes out of reach of the translators,
+//! similar to the inline tags of `po4a`.
+
+use crate::{is_verbatim_math, reconstruct_markdown};
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+/// Replace inline code, links, and inline HTML in `events` with
+/// numbered placeholders.
+///
+/// Returns the new events and the original event of each
+/// placeholder: the event for `{n}` is at index `n - 1`. For links,
+/// this is the `Start` event.
+pub fn insert_placeholders<'a>(
+    events: &[(usize, Event<'a>)],
+) -> (Vec<(usize, Event<'a>)>, Vec<Event<'a>>) {
+    let mut new_events = Vec::with_capacity(events.len());
+    let mut originals = Vec::new();
+    let mut open_links = Vec::new();
+    for (lineno, event) in events {
+        let placeholder = match event {
+            Event::Code(_) | Event::InlineHtml(_) if !is_verbatim_math(event) => {
+                originals.push(event.clone());
+                format!("{{{}}}", originals.len())
+            }
+            Event::Start(Tag::Link { .. }) => {
+                originals.push(event.clone());
+                open_links.push(originals.len());
+                format!("{{{}}}", originals.len())
+            }
+            Event::End(TagEnd::Link) => match open_links.pop() {
+                Some(n) => format!("{{/{n}}}"),
+                None => {
+                    new_events.push((*lineno, event.clone()));
+                    continue;
+                }
+            },
+            _ => {
+                new_events.push((*lineno, event.clone()));
+                continue;
+            }
+        };
+        new_events.push((*lineno, Event::Text(placeholder.into())));
+    }
+    (new_events, originals)
+}
+
+/// Describe the original markup of the placeholders.
+///
+/// This is added as an extracted comment to the messages, so that
+/// translators can see what the placeholders stand for.
+pub fn placeholder_comment(originals: &[Event]) -> String {
+    let mut lines = Vec::new();
+    for (idx, event) in originals.iter().enumerate() {
+        let n = idx + 1;
+        match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                lines.push(format!("{{{n}}}...{{/{n}}}: link to {dest_url}"));
+            }
+            _ => {
+                let (markdown, _) = reconstruct_markdown(&[(1, event.clone())], None);
+                lines.push(format!("{{{n}}}: {markdown}"));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// Parse the placeholder at the start of `text`.
+///
+/// Returns the length of the placeholder, its number, and whether
+/// it is a closing placeholder such as `{/1}`.
+pub fn parse_placeholder(text: &str) -> Option<(usize, usize, bool)> {
+    let rest = text.strip_prefix('{')?;
+    let (is_closing, rest) = match rest.strip_prefix('/') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 || !rest[digits..].starts_with('}') {
+        return None;
+    }
+    let n = rest[..digits].parse().ok()?;
+    Some((text.len() - rest.len() + digits + 1, n, is_closing))
+}
+
+/// Replace the placeholders in `events` with the `originals`.
+///
+/// Text which looks like a placeholder but doesn't match one of the
+/// `originals` is kept as text. Returns `None` if the link
+/// placeholders are not properly paired and nested.
+pub fn expand_placeholders<'a>(
+    events: &[(usize, Event<'a>)],
+    originals: &[Event<'a>],
+) -> Option<Vec<(usize, Event<'a>)>> {
+    let mut expanded = Vec::with_capacity(events.len());
+    let mut open_links = Vec::new();
+    for (lineno, event) in events {
+        let Event::Text(text) = event else {
+            expanded.push((*lineno, event.clone()));
+            continue;
+        };
+        let mut pos = 0;
+        let mut text_start = 0;
+        while let Some(idx) = text[pos..].find('{').map(|idx| pos + idx) {
+            pos = idx + 1;
+            let Some((len, n, is_closing)) = parse_placeholder(&text[idx..]) else {
+                continue;
+            };
+            let Some(original) = n.checked_sub(1).and_then(|idx| originals.get(idx)) else {
+                continue;
+            };
+            let is_link = matches!(original, Event::Start(Tag::Link { .. }));
+            let replacement = match (is_closing, is_link) {
+                (false, true) => {
+                    open_links.push(n);
+                    original.clone()
+                }
+                (false, false) => original.clone(),
+                (true, true) if open_links.last() == Some(&n) => {
+                    open_links.pop();
+                    Event::End(TagEnd::Link)
+                }
+                (true, true) => return None,
+                (true, false) => continue,
+            };
+            if text_start < idx {
+                let prefix = String::from(&text[text_start..idx]);
+                expanded.push((*lineno, Event::Text(prefix.into())));
+            }
+            expanded.push((*lineno, replacement));
+            pos = idx + len;
+            text_start = pos;
+        }
+        if text_start < text.len() {
+            let suffix = String::from(&text[text_start..]);
+            expanded.push((*lineno, Event::Text(suffix.into())));
+        }
+    }
+    open_links.is_empty().then_some(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract_events;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_placeholder() {
+        assert_eq!(parse_placeholder("{12} foo"), Some((4, 12, false)));
+        assert_eq!(parse_placeholder("{/3}"), Some((4, 3, true)));
+        assert_eq!(parse_placeholder("{x}"), None);
+        assert_eq!(parse_placeholder("{/}"), None);
+    }
+
+    #[test]
+    fn test_insert_placeholders() {
+        let events = extract_events("Run `cargo` from [the <b>docs</b>](https://x.org).", None);
+        let (new_events, originals) = insert_placeholders(&events);
+        let (markdown, _) = reconstruct_markdown(&new_events, None);
+        assert_eq!(markdown, "Run {1} from {2}the {3}docs{4}{/2}.");
+        assert_eq!(
+            placeholder_comment(&originals),
+            "{1}: `cargo`\n\
+             {2}...{/2}: link to https://x.org\n\
+             {3}: <b>\n\
+             {4}: </b>"
+        );
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        let events = extract_events("Run `cargo` from [the docs](https://x.org).", None);
+        let (_, originals) = insert_placeholders(&events);
+        let translated = extract_events("Fra {2}dokumentationen{/2}, kør {1} {9}.", None);
+        let expanded = expand_placeholders(&translated, &originals).unwrap();
+        let (markdown, _) = reconstruct_markdown(&expanded, None);
+        assert_eq!(
+            markdown,
+            "Fra [dokumentationen](https://x.org), kør `cargo` {9}."
+        );
+    }
+
+    #[test]
+    fn test_expand_placeholders_unbalanced() {
+        let events = extract_events("See [the docs](https://x.org).", None);
+        let (_, originals) = insert_placeholders(&events);
+        let translated = extract_events("Se {1}dokumentationen.", None);
+        assert_eq!(expand_placeholders(&translated, &originals), None);
+    }
+}
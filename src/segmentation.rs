@@ -0,0 +1,250 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Record how the Markdown was split into messages.
+//!
+//! The messages depend on the version of the extraction code and on
+//! some of the settings in [`MarkdownConfig`]. If a PO file was made
+//! with different rules than the ones used when translating, most
+//! messages won't match and the book appears untranslated. The rules
+//! are therefore recorded in the `X-Segmentation-Rules` header field
+//! of the PO template, so this can be detected.
+
+use crate::catalog_io::{header_field, set_header_field};
+use crate::MarkdownConfig;
+use anyhow::Context;
+use std::path::Path;
+use std::{fmt, fs};
+
+/// The name of the header field with the rules.
+pub const HEADER_FIELD: &str = "X-Segmentation-Rules";
+
+/// The version of the rules for splitting Markdown into messages.
+///
+/// Increase this when a change to the extraction changes existing
+/// messages.
+pub const RULES_VERSION: u32 = 1;
+
+/// The rules used to split Markdown into messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentationRules {
+    /// The version of `mdbook-i18n-helpers`, for information.
+    pub crate_version: String,
+    /// See [`RULES_VERSION`].
+    pub rules_version: u32,
+    /// The settings which change the messages, such as
+    /// `placeholders`.
+    pub settings: Vec<String>,
+}
+
+impl SegmentationRules {
+    /// The rules of this version with `config`.
+    pub fn current(config: &MarkdownConfig) -> Self {
+        let mut settings = Vec::new();
+        for (open, close) in &config.math_delimiters {
+            settings.push(format!("math-delimiters={open}{close}"));
+        }
+        if config.extract_html {
+            settings.push(String::from("extract-html"));
+        }
+        if config.skip_math {
+            settings.push(String::from("skip-math"));
+        }
+        if config.keep_reference_links {
+            settings.push(String::from("keep-reference-links"));
+        }
+        if config.placeholders {
+            settings.push(String::from("placeholders"));
+        }
+        if config.superscript_subscript {
+            settings.push(String::from("superscript-subscript"));
+        }
+        if config.code_rules.is_some() {
+            settings.push(String::from("split-code-blocks"));
+            if config.detect_code_languages {
+                settings.push(String::from("detect-code-languages"));
+            }
+        }
+        SegmentationRules {
+            crate_version: String::from(env!("CARGO_PKG_VERSION")),
+            rules_version: RULES_VERSION,
+            settings,
+        }
+    }
+
+    /// Parse the value of the header field.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::segmentation::SegmentationRules;
+    ///
+    /// let rules = SegmentationRules::parse("0.1.0 rules=1 placeholders").unwrap();
+    /// assert_eq!(rules.rules_version, 1);
+    /// assert_eq!(rules.settings, vec!["placeholders"]);
+    /// assert_eq!(rules.to_string(), "0.1.0 rules=1 placeholders");
+    /// ```
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut words = value.split_whitespace();
+        let crate_version = String::from(words.next()?);
+        let rules_version = words.next()?.strip_prefix("rules=")?.parse().ok()?;
+        Some(SegmentationRules {
+            crate_version,
+            rules_version,
+            settings: words.map(String::from).collect(),
+        })
+    }
+
+    /// Check if messages made with `self` match messages made with
+    /// `other`.
+    pub fn is_compatible(&self, other: &SegmentationRules) -> bool {
+        self.rules_version == other.rules_version && self.settings == other.settings
+    }
+}
+
+impl fmt::Display for SegmentationRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} rules={}", self.crate_version, self.rules_version)?;
+        for setting in &self.settings {
+            write!(f, " {setting}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the `X-Segmentation-Rules` header field in the PO file
+/// `content`.
+///
+/// The header field can be wrapped over several lines, as done by
+/// `msgmerge` for long lines.
+pub fn read_rules(content: &str) -> Option<SegmentationRules> {
+    header_field(content, HEADER_FIELD).and_then(|value| SegmentationRules::parse(&value))
+}
+
+/// Set the `X-Segmentation-Rules` header field in the PO file
+/// `content` to `rules`.
+///
+/// An existing field is replaced, otherwise the field is added at the
+/// end of the header.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::segmentation::{read_rules, write_rules, SegmentationRules};
+/// use mdbook_i18n_helpers::MarkdownConfig;
+///
+/// let content = "msgid \"\"\nmsgstr \"\"\n\"Language: da\\n\"\n\nmsgid \"Foo\"\nmsgstr \"\"\n";
+/// let rules = SegmentationRules::current(&MarkdownConfig::default());
+/// let content = write_rules(content, &rules);
+/// assert_eq!(read_rules(&content), Some(rules));
+/// ```
+pub fn write_rules(content: &str, rules: &SegmentationRules) -> String {
+    set_header_field(content, HEADER_FIELD, &rules.to_string())
+}
+
+/// Set the `X-Segmentation-Rules` header field of the PO file at
+/// `path`, see [`write_rules`].
+pub fn write_rules_to_file(path: &Path, rules: &SegmentationRules) -> anyhow::Result<()> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    fs::write(path, write_rules(&content, rules))
+        .with_context(|| format!("Could not write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_current_rules() {
+        let config = MarkdownConfig {
+            math_delimiters: vec![(String::from("\\("), String::from("\\)"))],
+            placeholders: true,
+            kind_comments: true,
+            ..MarkdownConfig::default()
+        };
+        assert_eq!(
+            SegmentationRules::current(&config).settings,
+            vec!["math-delimiters=\\(\\)", "placeholders"]
+        );
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        let rules = SegmentationRules::parse("0.1.0 rules=1 placeholders").unwrap();
+        assert!(
+            rules.is_compatible(&SegmentationRules::parse("0.2.0 rules=1 placeholders").unwrap())
+        );
+        assert!(
+            !rules.is_compatible(&SegmentationRules::parse("0.2.0 rules=2 placeholders").unwrap())
+        );
+        assert!(!rules.is_compatible(&SegmentationRules::parse("0.1.0 rules=1").unwrap()));
+    }
+
+    #[test]
+    fn test_write_rules_replaces_field() {
+        let content = "msgid \"\"\n\
+                       msgstr \"\"\n\
+                       \"X-Segmentation-Rules: 0.0.1 rules=0\\n\"\n\
+                       \"Language: da\\n\"\n\
+                       \n\
+                       msgid \"Foo\"\n\
+                       msgstr \"\"\n";
+        let rules = SegmentationRules::parse("0.1.0 rules=1").unwrap();
+        assert_eq!(
+            write_rules(content, &rules),
+            "msgid \"\"\n\
+             msgstr \"\"\n\
+             \"X-Segmentation-Rules: 0.1.0 rules=1\\n\"\n\
+             \"Language: da\\n\"\n\
+             \n\
+             msgid \"Foo\"\n\
+             msgstr \"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_rules_with_backslash() {
+        let config = MarkdownConfig {
+            math_delimiters: vec![(String::from("\\("), String::from("\\)"))],
+            ..MarkdownConfig::default()
+        };
+        let rules = SegmentationRules::current(&config);
+        let content = write_rules("msgid \"\"\nmsgstr \"\"\n", &rules);
+        assert!(content.contains("math-delimiters=\\\\(\\\\)"));
+        assert_eq!(read_rules(&content), Some(rules));
+    }
+
+    #[test]
+    fn test_read_wrapped_rules() {
+        let content = "msgid \"\"\n\
+                       msgstr \"\"\n\
+                       \"X-Segmentation-Rules: 0.1.0 rules=1 \"\n\
+                       \"placeholders\\n\"\n\
+                       \"Language: da\\n\"\n";
+        assert_eq!(
+            read_rules(content),
+            SegmentationRules::parse("0.1.0 rules=1 placeholders")
+        );
+        let rules = SegmentationRules::parse("0.2.0 rules=1").unwrap();
+        assert_eq!(
+            write_rules(content, &rules),
+            "msgid \"\"\n\
+             msgstr \"\"\n\
+             \"X-Segmentation-Rules: 0.2.0 rules=1\\n\"\n\
+             \"Language: da\\n\"\n"
+        );
+    }
+}
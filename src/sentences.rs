@@ -0,0 +1,191 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Split text into sentences.
+//!
+//! A sentence ends with `.`, `!`, or `?` followed by a space, or with
+//! the full-width punctuation used in Chinese and Japanese, such as
+//! `。`. Common abbreviations of a few languages, such as "e.g." in
+//! English or "z. B." in German, don't end a sentence.
+
+use crate::language::same_language;
+use std::ops::Range;
+
+/// Abbreviations which don't end a sentence.
+const ABBREVIATIONS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "e.g.", "i.e.", "etc.", "vs.", "cf.", "Mr.", "Mrs.", "Dr.", "No.",
+        ],
+    ),
+    (
+        "da",
+        &[
+            "f.eks.", "bl.a.", "dvs.", "osv.", "ca.", "evt.", "mht.", "nr.",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "z.B.", "z. B.", "d.h.", "d. h.", "usw.", "bzw.", "ca.", "vgl.", "Nr.",
+        ],
+    ),
+    ("fr", &["p. ex.", "etc.", "cf.", "M.", "Mme.", "n°."]),
+    ("es", &["p. ej.", "etc.", "Sr.", "Sra.", "núm."]),
+];
+
+/// Punctuation which ends a sentence without a following space.
+const FULL_WIDTH_ENDS: &[char] = &['。', '！', '？', '．'];
+
+/// Closing quotes and brackets which belong to the sentence before
+/// them.
+const CLOSING: &[char] = &['"', '\'', ')', ']', '”', '’', '」', '』', '）', '】'];
+
+/// The abbreviations of `language`, or of its general language for
+/// regional variants such as `de-AT`.
+fn abbreviations(language: &str) -> &'static [&'static str] {
+    let general = language.split(['-', '_']).next().unwrap_or(language);
+    ABBREVIATIONS
+        .iter()
+        .find(|(key, _)| same_language(key, general))
+        .map_or(&[], |(_, abbreviations)| abbreviations)
+}
+
+/// Check if the period which ends `before` is part of one of the
+/// `abbreviations`, which may continue in `after`.
+fn is_abbreviation(before: &str, after: &str, abbreviations: &[&str]) -> bool {
+    abbreviations.iter().any(|abbreviation| {
+        abbreviation.match_indices('.').any(|(idx, _)| {
+            let (head, tail) = abbreviation.split_at(idx + 1);
+            after.starts_with(tail)
+                && before.strip_suffix(head).is_some_and(|rest| {
+                    rest.is_empty() || rest.ends_with(|c: char| c.is_whitespace() || c == '(')
+                })
+        })
+    })
+}
+
+/// Find the byte ranges of the sentences in `text`, which is written
+/// in `language`.
+///
+/// The ranges don't include the spaces between the sentences.
+pub fn sentence_ranges(text: &str, language: &str) -> Vec<Range<usize>> {
+    let abbreviations = abbreviations(language);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        let full_width = FULL_WIDTH_ENDS.contains(&c);
+        if !full_width && !matches!(c, '.' | '!' | '?') {
+            continue;
+        }
+        let mut end = idx + c.len_utf8();
+        while let Some(&(next_idx, next)) = chars.peek() {
+            if !CLOSING.contains(&next) && !matches!(next, '.' | '!' | '?') {
+                break;
+            }
+            end = next_idx + next.len_utf8();
+            chars.next();
+        }
+        let at_break = match chars.peek() {
+            None => true,
+            Some((_, next)) => full_width || next.is_whitespace(),
+        };
+        if !at_break
+            || (c == '.' && is_abbreviation(&text[start..end], &text[end..], abbreviations))
+        {
+            continue;
+        }
+        push_trimmed(&mut ranges, text, start..end);
+        start = end;
+    }
+    push_trimmed(&mut ranges, text, start..text.len());
+    ranges
+}
+
+/// Add `range` of `text` to `ranges` without surrounding whitespace,
+/// unless it is empty.
+fn push_trimmed(ranges: &mut Vec<Range<usize>>, text: &str, range: Range<usize>) {
+    let part = &text[range.clone()];
+    let trimmed = part.trim_start();
+    let start = range.start + part.len() - trimmed.len();
+    let end = start + trimmed.trim_end().len();
+    if start < end {
+        ranges.push(start..end);
+    }
+}
+
+/// Split `text`, which is written in `language`, into sentences.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::sentences::split_sentences;
+///
+/// assert_eq!(
+///     split_sentences("Use a crate, e.g. serde. It works!", "en"),
+///     vec!["Use a crate, e.g. serde.", "It works!"]
+/// );
+/// assert_eq!(
+///     split_sentences("使用するクレート。動きます！", "ja"),
+///     vec!["使用するクレート。", "動きます！"]
+/// );
+/// ```
+pub fn split_sentences<'a>(text: &'a str, language: &str) -> Vec<&'a str> {
+    sentence_ranges(text, language)
+        .into_iter()
+        .map(|range| &text[range])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(
+            split_sentences("Version 1.0 is out. See \"the notes.\" Done", "en"),
+            vec!["Version 1.0 is out.", "See \"the notes.\"", "Done"]
+        );
+        assert_eq!(
+            split_sentences("Wait... what?! Yes.", "en"),
+            vec!["Wait...", "what?!", "Yes."]
+        );
+        assert_eq!(split_sentences("  ", "en"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_split_sentences_abbreviations() {
+        assert_eq!(
+            split_sentences("Nimm z. B. Serde. Das geht.", "de-AT"),
+            vec!["Nimm z. B. Serde.", "Das geht."]
+        );
+        // German abbreviations don't apply to English.
+        assert_eq!(
+            split_sentences("Take z. B. Serde.", "en"),
+            vec!["Take z.", "B.", "Serde."]
+        );
+    }
+
+    #[test]
+    fn test_split_sentences_cjk() {
+        assert_eq!(
+            split_sentences("这是第一句。「第二句！」第三句", "zh"),
+            vec!["这是第一句。", "「第二句！」", "第三句"]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detect translations whose surroundings have changed.
+//!
+//! A translation stays valid as long as its `msgid` is unchanged, but
+//! the meaning of a paragraph can depend on the paragraphs around it.
+//! When [`MarkdownConfig::source_hashes`](crate::MarkdownConfig) is
+//! set, each message gets an extracted comment such as
+//! `source-hash: 3f2a9c1e` with a hash of the message and its
+//! neighbors. Comparing the hashes in a PO file with the hashes of the
+//! current chapters finds translations which should be reviewed even
+//! though their `msgid` still matches.
+
+use crate::state::fnv1a;
+use crate::ExtractedMessage;
+use polib::catalog::Catalog;
+use std::collections::{HashMap, HashSet};
+
+/// The start of the extracted comment with the source hash.
+pub const SOURCE_HASH_PREFIX: &str = "source-hash: ";
+
+/// Compute the source hash of `msgid` between the `previous` and
+/// `next` messages of the chapter.
+///
+/// The hash is the first 8 hex digits of the 64-bit FNV-1a hash, which
+/// is stable across Rust versions and platforms.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::source_hash::source_hash;
+///
+/// let hash = source_hash(Some("Intro"), "Details", None);
+/// assert_eq!(hash.len(), 8);
+/// assert_ne!(hash, source_hash(Some("New intro"), "Details", None));
+/// ```
+pub fn source_hash(previous: Option<&str>, msgid: &str, next: Option<&str>) -> String {
+    // The NUL separators keep ("ab", "c") and ("a", "bc") apart.
+    let bytes = [previous.unwrap_or(""), msgid, next.unwrap_or("")]
+        .into_iter()
+        .flat_map(|part| part.bytes().chain([0]));
+    let mut hash = fnv1a(bytes);
+    hash.truncate(8);
+    hash
+}
+
+/// Add a source hash comment to each of the `messages` of a chapter.
+pub fn add_source_hashes(messages: &mut [ExtractedMessage]) {
+    let hashes = (0..messages.len())
+        .map(|idx| {
+            let previous = idx.checked_sub(1).map(|idx| messages[idx].msgid.as_str());
+            let next = messages.get(idx + 1).map(|message| message.msgid.as_str());
+            source_hash(previous, &messages[idx].msgid, next)
+        })
+        .collect::<Vec<_>>();
+    for (message, hash) in messages.iter_mut().zip(hashes) {
+        if !message.comment.is_empty() {
+            message.comment.push('\n');
+        }
+        message.comment.push_str(SOURCE_HASH_PREFIX);
+        message.comment.push_str(&hash);
+    }
+}
+
+/// The source hashes in the extracted `comments` of a message.
+pub fn stored_source_hashes(comments: &str) -> impl Iterator<Item = &str> {
+    comments
+        .lines()
+        .filter_map(|line| line.strip_prefix(SOURCE_HASH_PREFIX))
+        .map(str::trim)
+}
+
+/// Collect the source hashes of the `messages` by `msgid`.
+pub fn current_source_hashes<'a>(
+    messages: impl IntoIterator<Item = &'a ExtractedMessage>,
+) -> HashMap<String, HashSet<String>> {
+    let mut hashes = HashMap::<String, HashSet<String>>::new();
+    for message in messages {
+        hashes
+            .entry(message.msgid.clone())
+            .or_default()
+            .extend(stored_source_hashes(&message.comment).map(String::from));
+    }
+    hashes
+}
+
+/// A translation whose source has changed around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleMessage {
+    /// The untranslated message.
+    pub msgid: String,
+    /// The source references of the message.
+    pub source: String,
+}
+
+/// Find the translations in `catalog` with source hashes which are
+/// not in `current`.
+///
+/// Fuzzy and untranslated messages are skipped, as are messages which
+/// are no longer in the book or which have no source hashes.
+pub fn find_stale_messages(
+    catalog: &Catalog,
+    current: &HashMap<String, HashSet<String>>,
+) -> Vec<StaleMessage> {
+    catalog
+        .messages()
+        .filter(|msg| !msg.is_fuzzy() && msg.msgstr().is_ok_and(|msgstr| !msgstr.is_empty()))
+        .filter(|msg| {
+            current.get(msg.msgid()).is_some_and(|hashes| {
+                stored_source_hashes(msg.comments()).any(|hash| !hashes.contains(hash))
+            })
+        })
+        .map(|msg| StaleMessage {
+            msgid: String::from(msg.msgid()),
+            source: String::from(msg.source()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{extract_messages_with_comments, MarkdownConfig};
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    fn extract(document: &str) -> Vec<ExtractedMessage> {
+        let config = MarkdownConfig {
+            source_hashes: true,
+            ..MarkdownConfig::default()
+        };
+        extract_messages_with_comments(document, &config)
+    }
+
+    #[test]
+    fn test_source_hash_stable() {
+        assert_eq!(source_hash(None, "", None), "d94d1218");
+        assert_eq!(
+            source_hash(Some("a"), "b", None),
+            source_hash(Some("a"), "b", None)
+        );
+        assert_ne!(
+            source_hash(Some("ab"), "", None),
+            source_hash(Some("a"), "b", None)
+        );
+    }
+
+    #[test]
+    fn test_add_source_hashes() {
+        let messages = extract("# Setup\n\nRun `cargo`.\n");
+        let comments = messages
+            .iter()
+            .map(|message| message.comment.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            vec![
+                format!(
+                    "source-hash: {}",
+                    source_hash(None, "Setup", Some("Run `cargo`."))
+                ),
+                format!(
+                    "source-hash: {}",
+                    source_hash(Some("Setup"), "Run `cargo`.", None)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_stale_messages() {
+        let old = extract("# Setup\n\nRun `cargo`.\n\nDone.\n");
+        let new = extract("# Installation\n\nRun `cargo`.\n\nDone.\n");
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for message in &old {
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_source(String::from("src/setup.md"))
+                    .with_comments(message.comment.clone())
+                    .with_msgid(message.msgid.clone())
+                    .with_msgstr(format!("Translated: {}", message.msgid))
+                    .done(),
+            );
+        }
+        assert_eq!(
+            find_stale_messages(&catalog, &current_source_hashes(&new)),
+            vec![StaleMessage {
+                msgid: String::from("Run `cargo`."),
+                source: String::from("src/setup.md"),
+            }]
+        );
+        assert_eq!(
+            find_stale_messages(&catalog, &current_source_hashes(&old)),
+            Vec::new()
+        );
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Source references for PO files.
+//!
+//! Every message in a PO file has a `#:` comment with the locations
+//! where it was found. The line numbers in these references change
+//! whenever text is added or removed above a message. To reduce this
+//! churn, the line numbers can be rounded down to a multiple of a
+//! _granularity_:
+//!
+//! - A granularity of 0 removes the line numbers entirely.
+//! - A granularity of 1 keeps the exact line numbers.
+//! - A larger granularity puts the line numbers into buckets.
+//!
+//! The references can also be written as URLs, see [`SourceStyle`].
+
+use std::path::{Component, Path, PathBuf};
+
+/// Round `lineno` down to a multiple of `granularity`.
+///
+/// Returns `None` if the granularity is 0 since no line number should
+/// be used in this case. Line numbers are never rounded down to 0.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::sources::line_bucket;
+///
+/// assert_eq!(line_bucket(17, 0), None);
+/// assert_eq!(line_bucket(17, 1), Some(17));
+/// assert_eq!(line_bucket(17, 10), Some(10));
+/// assert_eq!(line_bucket(7, 10), Some(1));
+/// ```
+pub fn line_bucket(lineno: usize, granularity: usize) -> Option<usize> {
+    match granularity {
+        0 => None,
+        1 => Some(lineno),
+        _ => Some(std::cmp::max(1, lineno - lineno % granularity)),
+    }
+}
+
+/// Build a source reference for `path` and `lineno`.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::sources::build_source;
+///
+/// assert_eq!(build_source("src/foo.md", 17, 0), "src/foo.md");
+/// assert_eq!(build_source("src/foo.md", 17, 1), "src/foo.md:17");
+/// assert_eq!(build_source("src/foo.md", 17, 10), "src/foo.md:10");
+/// ```
+pub fn build_source<P: AsRef<Path>>(path: P, lineno: usize, granularity: usize) -> String {
+    let path = path.as_ref();
+    match line_bucket(lineno, granularity) {
+        Some(lineno) => format!("{}:{}", path.display(), lineno),
+        None => format!("{}", path.display()),
+    }
+}
+
+/// Lexically normalize `path` by removing `.` and `..` components.
+///
+/// A `..` removes the component before it. Leading `..` components
+/// are kept, since there is nothing to remove. The file system is not
+/// used, so symbolic links are not resolved. Use this wherever paths
+/// of chapters are compared or used as keys, so the same chapter gets
+/// the same path everywhere.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::sources::normalize_path;
+/// use std::path::Path;
+///
+/// assert_eq!(normalize_path(Path::new("src/./a/../b.md")), Path::new("src/b.md"));
+/// assert_eq!(normalize_path(Path::new("../shared/b.md")), Path::new("../shared/b.md"));
+/// assert_eq!(normalize_path(Path::new("/book/src/../po")), Path::new("/book/po"));
+/// ```
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// How to write source references.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceStyle {
+    /// Only the path, such as `src/foo.md`.
+    PathOnly,
+    /// The path and line number, such as `src/foo.md:12`.
+    ///
+    /// The line number is rounded down to a multiple of the
+    /// granularity, see [`line_bucket`].
+    Line { granularity: usize },
+    /// A URL pointing to the line, such as
+    /// `https://github.com/org/repo/blob/main/src/foo.md#L12`.
+    ///
+    /// The path is appended to the base URL. The line number is
+    /// rounded like for [`SourceStyle::Line`].
+    Url {
+        base_url: String,
+        granularity: usize,
+    },
+}
+
+impl Default for SourceStyle {
+    fn default() -> Self {
+        SourceStyle::Line { granularity: 1 }
+    }
+}
+
+impl SourceStyle {
+    /// Build a source reference for `path` and `lineno`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mdbook_i18n_helpers::sources::SourceStyle;
+    ///
+    /// let style = SourceStyle::Url {
+    ///     base_url: String::from("https://github.com/org/repo/blob/main/"),
+    ///     granularity: 1,
+    /// };
+    /// assert_eq!(
+    ///     style.build_source("src/foo.md", 12),
+    ///     "https://github.com/org/repo/blob/main/src/foo.md#L12"
+    /// );
+    /// assert_eq!(SourceStyle::PathOnly.build_source("src/foo.md", 12), "src/foo.md");
+    /// ```
+    pub fn build_source<P: AsRef<Path>>(&self, path: P, lineno: usize) -> String {
+        match self {
+            SourceStyle::PathOnly => build_source(path, lineno, 0),
+            SourceStyle::Line { granularity } => build_source(path, lineno, *granularity),
+            SourceStyle::Url {
+                base_url,
+                granularity,
+            } => {
+                let path = path.as_ref().to_string_lossy().replace('\\', "/");
+                let url = format!("{}/{}", base_url.trim_end_matches('/'), path);
+                match line_bucket(lineno, *granularity) {
+                    Some(lineno) => format!("{url}#L{lineno}"),
+                    None => url,
+                }
+            }
+        }
+    }
+}
+
+/// Rebuild the newline-separated `sources` with `granularity`.
+///
+/// This puts source references extracted with a finer granularity
+/// into the buckets of a coarser granularity. Duplicate references
+/// are removed.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::sources::rebucket_sources;
+///
+/// assert_eq!(
+///     rebucket_sources("src/foo.md:12\nsrc/foo.md:17\nsrc/bar.md:3", 10),
+///     "src/foo.md:10\nsrc/bar.md:1"
+/// );
+/// assert_eq!(rebucket_sources("src/foo.md:12", 0), "src/foo.md");
+/// ```
+pub fn rebucket_sources(sources: &str, granularity: usize) -> String {
+    let mut rebucketed: Vec<String> = Vec::new();
+    for source in sources.lines() {
+        let source = match source.rsplit_once(':') {
+            Some((path, lineno)) => match lineno.parse::<usize>() {
+                Ok(lineno) => build_source(path, lineno, granularity),
+                Err(_) => String::from(source),
+            },
+            None => String::from(source),
+        };
+        if !rebucketed.contains(&source) {
+            rebucketed.push(source);
+        }
+    }
+    rebucketed.join("\n")
+}
+
+/// Compare two sets of source references modulo `granularity`.
+///
+/// References which only differ by how their line numbers were
+/// rounded are considered equal.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::sources::sources_equal;
+///
+/// assert!(sources_equal("src/foo.md:12", "src/foo.md:10", 10));
+/// assert!(!sources_equal("src/foo.md:12", "src/foo.md:20", 10));
+/// assert!(!sources_equal("src/foo.md:12", "src/foo.md:10", 1));
+/// ```
+pub fn sources_equal(a: &str, b: &str, granularity: usize) -> bool {
+    rebucket_sources(a, granularity) == rebucket_sources(b, granularity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_bucket_never_zero() {
+        assert_eq!(line_bucket(0, 1), Some(0));
+        assert_eq!(line_bucket(1, 5), Some(1));
+        assert_eq!(line_bucket(5, 5), Some(5));
+        assert_eq!(line_bucket(9, 5), Some(5));
+    }
+
+    #[test]
+    fn test_source_style_url() {
+        let style = SourceStyle::Url {
+            base_url: String::from("https://example.com/blob/main"),
+            granularity: 10,
+        };
+        assert_eq!(
+            style.build_source("src/foo.md", 17),
+            "https://example.com/blob/main/src/foo.md#L10"
+        );
+        let style = SourceStyle::Url {
+            base_url: String::from("https://example.com/blob/main"),
+            granularity: 0,
+        };
+        assert_eq!(
+            style.build_source("src/foo.md", 17),
+            "https://example.com/blob/main/src/foo.md"
+        );
+    }
+
+    #[test]
+    fn test_rebucket_sources_keeps_unknown_format() {
+        assert_eq!(
+            rebucket_sources("src/foo.md\nsrc/bar.md:x", 10),
+            "src/foo.md\nsrc/bar.md:x"
+        );
+    }
+
+    #[test]
+    fn test_rebucket_sources_removes_duplicates() {
+        assert_eq!(
+            rebucket_sources("src/foo.md:1\nsrc/foo.md:2\nsrc/bar.md:2", 0),
+            "src/foo.md\nsrc/bar.md"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Extraction and translation without `mdbook`.
+//!
+//! The functions here work on plain Markdown and PO files. They don't
+//! need a book or a `book.toml` file, which makes them suitable for
+//! build scripts which generate localized documentation at compile
+//! time:
+//!
+//! ```no_run
+//! // build.rs
+//! use mdbook_i18n_helpers::standalone::translate_str;
+//!
+//! fn main() -> anyhow::Result<()> {
+//!     println!("cargo:rerun-if-changed=docs/intro.md");
+//!     println!("cargo:rerun-if-changed=po/da.po");
+//!     let markdown = std::fs::read_to_string("docs/intro.md")?;
+//!     let translated = translate_str(&markdown, "po/da.po")?;
+//!     let out_dir = std::env::var("OUT_DIR")?;
+//!     std::fs::write(format!("{out_dir}/intro.da.md"), translated)?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! The Markdown is handled with the default
+//! [`MarkdownConfig`](crate::MarkdownConfig).
+//!
+//! # Stability
+//!
+//! The functions in this module follow semantic versioning: their
+//! signatures and the format of their output only change in a new
+//! major version. This is unlike the lower-level functions at the
+//! top of the crate, which change whenever the underlying Markdown
+//! parser changes.
+
+use crate::catalog_io::{read_catalog, write_catalog, WriteOptions};
+use crate::sources::build_source;
+use crate::translator::Translator;
+use crate::{extract_messages, MarkdownConfig};
+use anyhow::Context;
+use polib::catalog::Catalog;
+use polib::message::Message;
+use polib::metadata::CatalogMetadata;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Translate `markdown` using the translations in the PO file at
+/// `po_path`.
+///
+/// Messages without a translation and fuzzy translations are left
+/// untranslated.
+pub fn translate_str<P: AsRef<Path>>(markdown: &str, po_path: P) -> anyhow::Result<String> {
+    let catalog = read_catalog(po_path.as_ref())?;
+    Ok(Translator::new(catalog, MarkdownConfig::default()).translate_str(markdown))
+}
+
+/// Find the files matching `globs`.
+///
+/// The files are returned in sorted order without duplicates.
+fn find_files(globs: &[&str]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for pattern in globs {
+        let matches =
+            glob::glob(pattern).with_context(|| format!("Invalid glob pattern {pattern:?}"))?;
+        for path in matches {
+            paths.push(path.with_context(|| format!("Could not read files for {pattern:?}"))?);
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Extract the messages from the Markdown files matching `globs` and
+/// write them to the PO template `out`.
+///
+/// The source references use the paths as matched by the globs, so
+/// relative globs give relative paths.
+pub fn extract_to_pot<P: AsRef<Path>>(globs: &[&str], out: P) -> anyhow::Result<()> {
+    let out = out.as_ref();
+    let mut metadata = CatalogMetadata::new();
+    metadata.mime_version = String::from("1.0");
+    metadata.content_type = String::from("text/plain; charset=UTF-8");
+    metadata.content_transfer_encoding = String::from("8bit");
+    let mut catalog = Catalog::new(metadata);
+
+    for path in find_files(globs)? {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        for (lineno, msgid) in extract_messages(&content) {
+            let source = build_source(&path, lineno, 1);
+            let sources = match catalog.find_message(None, &msgid, None) {
+                Some(msg) => format!("{}\n{}", msg.source(), source),
+                None => source,
+            };
+            let message = Message::build_singular()
+                .with_source(sources)
+                .with_msgid(msgid)
+                .done();
+            catalog.append_or_update(message);
+        }
+    }
+
+    write_catalog(&catalog, out, &WriteOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_translate_str() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let po_path = tmpdir.path().join("da.po");
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_msgid(String::from("Hello _world_"))
+                .with_msgstr(String::from("Hej _verden_"))
+                .done(),
+        );
+        write_catalog(&catalog, &po_path, &WriteOptions::default())?;
+
+        assert_eq!(
+            translate_str("# Hello *world*\n\nUntranslated.", &po_path)?,
+            "# Hej _verden_\n\nUntranslated."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_translate_str_table() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let po_path = tmpdir.path().join("da.po");
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_msgid(String::from("Name"))
+                .with_msgstr(String::from("Navn"))
+                .done(),
+        );
+        write_catalog(&catalog, &po_path, &WriteOptions::default())?;
+
+        // The delimiter row is kept as written, like in mdbook-gettext.
+        assert_eq!(
+            translate_str("| Name |\n|:-----|\n| x    |", &po_path)?,
+            "|Navn|\n|:-----|\n|x|"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_translate_str_missing_po_file() {
+        assert!(translate_str("Hello", "does-not-exist.po").is_err());
+    }
+
+    #[test]
+    fn test_extract_to_pot() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let docs = tmpdir.path().join("docs");
+        fs::create_dir_all(docs.join("nested"))?;
+        fs::write(docs.join("b.md"), "# Title\n\nShared text.")?;
+        fs::write(docs.join("a.md"), "Shared text.")?;
+        fs::write(docs.join("nested/c.md"), "Nested text.")?;
+        fs::write(docs.join("ignored.txt"), "Not Markdown.")?;
+
+        let pot_path = tmpdir.path().join("messages.pot");
+        let pattern = format!("{}/**/*.md", docs.display());
+        extract_to_pot(&[&pattern, &pattern], &pot_path)?;
+
+        let catalog = read_catalog(&pot_path)?;
+        let messages = catalog
+            .messages()
+            .map(|msg| (msg.source(), msg.msgid()))
+            .collect::<Vec<_>>();
+        let a = docs.join("a.md");
+        let b = docs.join("b.md");
+        let c = docs.join("nested/c.md");
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    format!("{}:1\n{}:3", a.display(), b.display()).as_str(),
+                    "Shared text."
+                ),
+                (format!("{}:1", b.display()).as_str(), "Title"),
+                (format!("{}:1", c.display()).as_str(), "Nested text."),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_to_pot_bad_glob() {
+        assert!(extract_to_pot(&["docs/[.md"], "messages.pot").is_err());
+    }
+}
@@ -0,0 +1,537 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sidecar files with metadata about the messages in PO files.
+//!
+//! Some metadata, such as when a translation was last changed or
+//! whether it has been reviewed, has no good place in a PO file. It
+//! is kept in a JSON file next to the PO files instead: the state of
+//! `po/da.po` is in `po/.i18n-state/da.json`. The entries are keyed
+//! by `msgid`.
+//!
+//! Fields which are unknown to this version are kept when the file is
+//! updated, so tools with different versions can share the file.
+//!
+//! The review status and the reviewers of a translation are stored
+//! with a checksum of the translation. When the translation changes,
+//! they no longer apply.
+
+use anyhow::Context;
+use polib::catalog::Catalog;
+use polib::message::{MessageMutView, MessageView};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Name of the directory with the state files, inside the PO
+/// directory.
+pub const STATE_DIR: &str = ".i18n-state";
+
+/// Version of the state file format written by this crate.
+pub const STATE_VERSION: u32 = 1;
+
+/// Metadata about a single message.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EntryState {
+    /// Unix time when the translation was last changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<i64>,
+    /// Review status of the translation, such as `reviewed`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_status: Option<String>,
+    /// Names of the people who reviewed the translation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reviewers: Vec<String>,
+    /// Translations suggested by readers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Suggestion>,
+    /// Checksum of the message when the metadata was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    /// Fields which are not known to this version.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// A translation suggested by a reader.
+///
+/// Suggestions are kept apart from the PO file until a translator
+/// has looked at them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suggestion {
+    /// The suggested translation.
+    pub msgstr: String,
+    /// Who made the suggestion, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reporter: Option<String>,
+    /// The [checksum](translation_checksum) of the translation which
+    /// the reader saw, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation_hash: Option<String>,
+}
+
+/// The contents of a state file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateFile {
+    /// Version of the file format.
+    pub version: u32,
+    /// Metadata by `msgid`.
+    #[serde(default)]
+    pub entries: BTreeMap<String, EntryState>,
+}
+
+impl Default for StateFile {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+/// The path of the state file for `language` in `po_dir`.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::state::state_path;
+/// use std::path::{Path, PathBuf};
+///
+/// assert_eq!(
+///     state_path(Path::new("po"), "da"),
+///     PathBuf::from("po/.i18n-state/da.json")
+/// );
+/// ```
+pub fn state_path(po_dir: &Path, language: &str) -> PathBuf {
+    po_dir.join(STATE_DIR).join(format!("{language}.json"))
+}
+
+impl StateFile {
+    /// Read the state file at `path`.
+    ///
+    /// A missing file is the same as an empty one.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err).with_context(|| format!("Could not read {:?}", path)),
+        };
+        let state: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Could not parse {:?} as a state file", path))?;
+        anyhow::ensure!(
+            state.version <= STATE_VERSION,
+            "{:?} has version {}, but only version {STATE_VERSION} is supported",
+            path,
+            state.version
+        );
+        Ok(state)
+    }
+
+    /// Write the state file to `path`.
+    ///
+    /// The file is written to a temporary file first, which is then
+    /// renamed to `path`. Readers therefore never see a partially
+    /// written file. The directory is created if needed.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        fs::create_dir_all(dir).with_context(|| format!("Could not create {:?}", dir))?;
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let write = || -> std::io::Result<()> {
+            let mut file = fs::File::create(&tmp_path)?;
+            serde_json::to_writer_pretty(&mut file, self)?;
+            file.write_all(b"\n")?;
+            file.sync_all()
+        };
+        write().with_context(|| format!("Could not write {:?}", tmp_path))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Could not rename {:?} to {:?}", tmp_path, path))
+    }
+
+    /// The metadata for `msgid`, if any.
+    pub fn entry(&self, msgid: &str) -> Option<&EntryState> {
+        self.entries.get(msgid)
+    }
+
+    /// The metadata for `msgid`, created if needed.
+    pub fn entry_mut(&mut self, msgid: &str) -> &mut EntryState {
+        self.entries.entry(String::from(msgid)).or_default()
+    }
+
+    /// Remove the entries for which `keep` returns `false`.
+    ///
+    /// Use this to drop the metadata of messages which are gone from
+    /// the PO file.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.entries.retain(|msgid, _| keep(msgid));
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes` as a hex string.
+pub(crate) fn fnv1a(bytes: impl Iterator<Item = u8>) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{hash:016x}")
+}
+
+/// Compute a checksum of a translation.
+///
+/// This is the 64-bit FNV-1a hash of the `msgid` and `msgstr`. It is
+/// stable across platforms and versions, so it can be stored.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::state::translation_checksum;
+///
+/// assert_eq!(translation_checksum("Hello", "Hej"), translation_checksum("Hello", "Hej"));
+/// assert_ne!(translation_checksum("Hello", "Hej"), translation_checksum("Hello", "Hejsa"));
+/// ```
+pub fn translation_checksum(msgid: &str, msgstr: &str) -> String {
+    fnv1a(msgid.bytes().chain([0]).chain(msgstr.bytes()))
+}
+
+/// Compute a checksum of a message.
+///
+/// This identifies the message independently of its translation.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::state::message_checksum;
+///
+/// assert_eq!(message_checksum("Hello"), "63f0bfacf2c00f6b");
+/// ```
+pub fn message_checksum(msgid: &str) -> String {
+    fnv1a(msgid.bytes())
+}
+
+impl StateFile {
+    /// The metadata for the translation of `msgid` as `msgstr`.
+    ///
+    /// The review status and reviewers are cleared if they were
+    /// recorded for a different translation.
+    fn translation_entry_mut(&mut self, msgid: &str, msgstr: &str) -> &mut EntryState {
+        let checksum = translation_checksum(msgid, msgstr);
+        let entry = self.entry_mut(msgid);
+        if entry.checksum.as_deref() != Some(&checksum) {
+            entry.review_status = None;
+            entry.reviewers.clear();
+            entry.checksum = Some(checksum);
+        }
+        entry
+    }
+
+    /// The metadata for `msgid` if it was recorded for the
+    /// translation `msgstr`.
+    fn translation_entry(&self, msgid: &str, msgstr: &str) -> Option<&EntryState> {
+        let entry = self.entry(msgid)?;
+        if entry.checksum.as_deref() != Some(&translation_checksum(msgid, msgstr)) {
+            return None;
+        }
+        Some(entry)
+    }
+
+    /// Record that the translation of `msgid` as `msgstr` has the
+    /// review status `status`.
+    pub fn set_review_status(&mut self, msgid: &str, msgstr: &str, status: &str) {
+        self.translation_entry_mut(msgid, msgstr).review_status = Some(String::from(status));
+    }
+
+    /// The review status of the translation of `msgid` as `msgstr`.
+    ///
+    /// Returns `None` if the translation has not been reviewed or has
+    /// changed since it was reviewed.
+    pub fn review_status(&self, msgid: &str, msgstr: &str) -> Option<&str> {
+        self.translation_entry(msgid, msgstr)?
+            .review_status
+            .as_deref()
+    }
+
+    /// Record that `reviewer` reviewed the translation of `msgid` as
+    /// `msgstr`.
+    ///
+    /// Each reviewer is only recorded once.
+    pub fn add_reviewer(&mut self, msgid: &str, msgstr: &str, reviewer: &str) {
+        let entry = self.translation_entry_mut(msgid, msgstr);
+        if !entry.reviewers.iter().any(|r| r == reviewer) {
+            entry.reviewers.push(String::from(reviewer));
+        }
+    }
+
+    /// The reviewers of the translation of `msgid` as `msgstr`.
+    ///
+    /// This is empty if the translation changed since it was
+    /// reviewed.
+    pub fn reviewers(&self, msgid: &str, msgstr: &str) -> &[String] {
+        self.translation_entry(msgid, msgstr)
+            .map_or(&[], |entry| &entry.reviewers)
+    }
+
+    /// Add a suggested translation of `msgid`.
+    ///
+    /// Returns `false` if the same translation was already suggested.
+    pub fn add_suggestion(&mut self, msgid: &str, suggestion: Suggestion) -> bool {
+        let suggestions = &mut self.entry_mut(msgid).suggestions;
+        if suggestions.iter().any(|s| s.msgstr == suggestion.msgstr) {
+            return false;
+        }
+        suggestions.push(suggestion);
+        true
+    }
+
+    /// The suggested translations of `msgid`.
+    pub fn suggestions(&self, msgid: &str) -> &[Suggestion] {
+        self.entry(msgid).map_or(&[], |entry| &entry.suggestions)
+    }
+}
+
+/// Mark the translations in `catalog` for which `is_accepted` returns
+/// `false` fuzzy.
+///
+/// Fuzzy and untranslated messages are skipped. Returns the `msgid`
+/// of the newly fuzzy messages.
+fn mark_fuzzy_unless(
+    catalog: &mut Catalog,
+    is_accepted: impl Fn(&str, &str) -> bool,
+) -> Vec<String> {
+    let mut rejected = Vec::new();
+    for mut message in catalog.messages_mut() {
+        let is_rejected = match message.msgstr() {
+            Ok(msgstr) if !msgstr.is_empty() && !message.is_fuzzy() => {
+                !is_accepted(message.msgid(), msgstr)
+            }
+            _ => false,
+        };
+        if is_rejected {
+            rejected.push(String::from(message.msgid()));
+            message.flags_mut().add_flag("fuzzy");
+        }
+    }
+    rejected
+}
+
+/// Mark the translations in `catalog` which don't have the review
+/// status `status` in `state` fuzzy.
+///
+/// Fuzzy translations are not used by `mdbook-gettext`. Returns the
+/// `msgid` of the newly fuzzy messages.
+pub fn mark_unreviewed_fuzzy(
+    catalog: &mut Catalog,
+    state: &StateFile,
+    status: &str,
+) -> Vec<String> {
+    mark_fuzzy_unless(catalog, |msgid, msgstr| {
+        state.review_status(msgid, msgstr) == Some(status)
+    })
+}
+
+/// Mark the translations in `catalog` with fewer than `required`
+/// distinct reviewers in `state` fuzzy.
+///
+/// Returns the `msgid` of the newly fuzzy messages.
+pub fn mark_missing_reviewers_fuzzy(
+    catalog: &mut Catalog,
+    state: &StateFile,
+    required: usize,
+) -> Vec<String> {
+    mark_fuzzy_unless(catalog, |msgid, msgstr| {
+        state.reviewers(msgid, msgstr).len() >= required
+    })
+}
+
+/// Load the state file at `path`, change it with `update`, and save
+/// it again.
+///
+/// The file is only written if `update` succeeds.
+pub fn update_state<T>(
+    path: &Path,
+    update: impl FnOnce(&mut StateFile) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut state = StateFile::load(path)?;
+    let result = update(&mut state)?;
+    state.save(path)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_load_missing() -> anyhow::Result<()> {
+        let tmpdir = tempfile::TempDir::new()?;
+        let state = StateFile::load(&state_path(tmpdir.path(), "da"))?;
+        assert_eq!(state, StateFile::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_state() -> anyhow::Result<()> {
+        let tmpdir = tempfile::TempDir::new()?;
+        let path = state_path(tmpdir.path(), "da");
+        update_state(&path, |state| {
+            state.entry_mut("Hello").updated = Some(1000);
+            state.entry_mut("Bye").review_status = Some(String::from("reviewed"));
+            Ok(())
+        })?;
+        update_state(&path, |state| {
+            state.retain(|msgid| msgid != "Bye");
+            Ok(())
+        })?;
+
+        let state = StateFile::load(&path)?;
+        assert_eq!(state.entries.len(), 1);
+        assert_eq!(state.entry("Hello").and_then(|e| e.updated), Some(1000));
+        assert!(!path.with_extension("json.tmp").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_fields() -> anyhow::Result<()> {
+        let tmpdir = tempfile::TempDir::new()?;
+        let path = state_path(tmpdir.path(), "da");
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(
+            &path,
+            r#"{"version": 1, "entries": {"Hello": {"updated": 5, "reviewer": "Someone"}}}"#,
+        )?;
+        update_state(&path, |state| {
+            state.entry_mut("Hello").updated = Some(10);
+            Ok(())
+        })?;
+
+        let state = StateFile::load(&path)?;
+        let entry = state.entry("Hello").unwrap();
+        assert_eq!(entry.updated, Some(10));
+        assert_eq!(
+            entry.extra.get("reviewer"),
+            Some(&serde_json::Value::from("Someone"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_review_status() {
+        let mut state = StateFile::default();
+        state.set_review_status("Hello", "Hej", "approved");
+        assert_eq!(state.review_status("Hello", "Hej"), Some("approved"));
+        assert_eq!(state.review_status("Hello", "Hejsa"), None);
+        assert_eq!(state.review_status("Bye", "Farvel"), None);
+    }
+
+    #[test]
+    fn test_mark_unreviewed_fuzzy() {
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        for (msgid, msgstr) in [("Hello", "Hej"), ("Bye", "Farvel"), ("Missing", "")] {
+            catalog.append_or_update(
+                polib::message::Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let mut state = StateFile::default();
+        state.set_review_status("Hello", "Hej", "approved");
+        state.set_review_status("Bye", "Farvel", "rejected");
+
+        assert_eq!(
+            mark_unreviewed_fuzzy(&mut catalog, &state, "approved"),
+            vec![String::from("Bye")]
+        );
+        assert!(!catalog
+            .find_message(None, "Hello", None)
+            .unwrap()
+            .is_fuzzy());
+        assert!(catalog.find_message(None, "Bye", None).unwrap().is_fuzzy());
+    }
+
+    #[test]
+    fn test_reviewers() {
+        let mut state = StateFile::default();
+        state.set_review_status("Hello", "Hej", "approved");
+        state.add_reviewer("Hello", "Hej", "alice");
+        state.add_reviewer("Hello", "Hej", "alice");
+        state.add_reviewer("Hello", "Hej", "bob");
+        assert_eq!(state.reviewers("Hello", "Hej"), ["alice", "bob"]);
+        assert_eq!(state.reviewers("Hello", "Hejsa"), [] as [String; 0]);
+
+        // A review of a new translation starts over.
+        state.add_reviewer("Hello", "Hejsa", "carol");
+        assert_eq!(state.reviewers("Hello", "Hejsa"), ["carol"]);
+        assert_eq!(state.review_status("Hello", "Hejsa"), None);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let mut state = StateFile::default();
+        let suggestion = Suggestion {
+            msgstr: String::from("Hejsa"),
+            reporter: Some(String::from("reader@example.com")),
+            translation_hash: None,
+        };
+        assert!(state.add_suggestion("Hello", suggestion.clone()));
+        assert!(!state.add_suggestion(
+            "Hello",
+            Suggestion {
+                msgstr: String::from("Hejsa"),
+                reporter: None,
+                translation_hash: None,
+            }
+        ));
+        state.set_review_status("Hello", "Hej", "approved");
+        assert_eq!(state.suggestions("Hello"), [suggestion]);
+        assert_eq!(state.suggestions("Bye"), []);
+    }
+
+    #[test]
+    fn test_mark_missing_reviewers_fuzzy() {
+        let mut catalog = Catalog::new(polib::metadata::CatalogMetadata::new());
+        for (msgid, msgstr) in [("Hello", "Hej"), ("Bye", "Farvel")] {
+            catalog.append_or_update(
+                polib::message::Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let mut state = StateFile::default();
+        state.add_reviewer("Hello", "Hej", "alice");
+        state.add_reviewer("Hello", "Hej", "bob");
+        state.add_reviewer("Bye", "Farvel", "alice");
+
+        assert_eq!(
+            mark_missing_reviewers_fuzzy(&mut catalog, &state, 2),
+            vec![String::from("Bye")]
+        );
+    }
+
+    #[test]
+    fn test_update_state_error() -> anyhow::Result<()> {
+        let tmpdir = tempfile::TempDir::new()?;
+        let path = state_path(tmpdir.path(), "da");
+        let result: anyhow::Result<()> = update_state(&path, |_| anyhow::bail!("failed"));
+        assert!(result.is_err());
+        assert!(!path.exists());
+        Ok(())
+    }
+}
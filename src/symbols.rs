@@ -0,0 +1,237 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Check that translations keep the emoji and symbols of the source.
+//!
+//! Emoji such as 🦀 and symbols such as ⚠️ often carry meaning, for
+//! example to mark warnings or Rust specific notes. A translation
+//! which drops or duplicates one is most likely a mistake, so the
+//! functions here compare the emoji of each `msgid` and `msgstr`.
+
+use crate::markup::subtract;
+use polib::catalog::Catalog;
+
+/// A translation which doesn't have the same emoji as its `msgid`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolMismatch {
+    /// The untranslated message.
+    pub msgid: String,
+    /// Emoji in the `msgid` which are missing from the translation.
+    pub missing: Vec<String>,
+    /// Emoji in the translation which are not in the `msgid`.
+    pub extra: Vec<String>,
+}
+
+/// Check if `c` is an emoji or pictographic symbol.
+fn is_pictographic(c: char) -> bool {
+    matches!(
+        c,
+        '\u{a9}'
+            | '\u{ae}'
+            | '\u{203c}'
+            | '\u{2049}'
+            | '\u{2122}'
+            | '\u{2139}'
+            | '\u{2190}'..='\u{21ff}'
+            | '\u{2300}'..='\u{23ff}'
+            | '\u{24c2}'
+            | '\u{25a0}'..='\u{25ff}'
+            | '\u{2600}'..='\u{27bf}'
+            | '\u{2900}'..='\u{297f}'
+            | '\u{2b00}'..='\u{2bff}'
+            | '\u{3030}'
+            | '\u{303d}'
+            | '\u{3297}'
+            | '\u{3299}'
+            | '\u{1f000}'..='\u{1faff}'
+    )
+}
+
+/// Check if `c` modifies the preceding emoji, such as a skin tone.
+fn is_modifier(c: char) -> bool {
+    matches!(
+        c,
+        '\u{fe0e}' | '\u{fe0f}' | '\u{20e3}' | '\u{1f3fb}'..='\u{1f3ff}' | '\u{e0020}'..='\u{e007f}'
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c, '\u{1f1e6}'..='\u{1f1ff}')
+}
+
+/// Collect the emoji in `text`, sorted.
+///
+/// An emoji together with its modifiers and any emoji joined to it
+/// with a zero width joiner counts as one, as does a flag. Variation
+/// selectors are dropped, so `⚠` and `⚠️` are the same.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::symbols::emoji;
+///
+/// assert_eq!(emoji("⚠️ Warning: 🦀 and 👩‍💻"), vec!["⚠", "👩\u{200d}💻", "🦀"]);
+/// ```
+pub fn emoji(text: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if matches!(c, '#' | '*' | '0'..='9') {
+            // A keycap such as #️⃣ is the character followed by a
+            // variation selector and U+20E3.
+            let mut rest = chars.clone();
+            rest.next_if_eq(&'\u{fe0f}');
+            if rest.next_if_eq(&'\u{20e3}').is_some() {
+                result.push(format!("{c}\u{20e3}"));
+                chars = rest;
+            }
+            continue;
+        }
+        if !is_pictographic(c) {
+            continue;
+        }
+        let mut symbol = String::from(c);
+        if is_regional_indicator(c) {
+            if let Some(next) = chars.next_if(|&next| is_regional_indicator(next)) {
+                symbol.push(next);
+            }
+        }
+        loop {
+            if let Some(modifier) = chars.next_if(|&next| is_modifier(next)) {
+                if !matches!(modifier, '\u{fe0e}' | '\u{fe0f}') {
+                    symbol.push(modifier);
+                }
+            } else if chars.next_if_eq(&'\u{200d}').is_some() {
+                symbol.push('\u{200d}');
+                if let Some(next) = chars.next_if(|&next| is_pictographic(next)) {
+                    symbol.push(next);
+                }
+            } else {
+                break;
+            }
+        }
+        result.push(symbol);
+    }
+    result.sort();
+    result
+}
+
+/// Compare the emoji of `msgid` and its translation `msgstr`.
+///
+/// Emoji in `allowed` are ignored. Returns `None` if the emoji are the
+/// same, though possibly in a different order.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::symbols::check_symbols;
+///
+/// assert_eq!(check_symbols("🦀 Rust", "Rust 🦀", &[]), None);
+/// let mismatch = check_symbols("⚠️ Careful", "Forsigtig", &[]).unwrap();
+/// assert_eq!(mismatch.missing, vec!["⚠"]);
+/// assert_eq!(check_symbols("⚠️ Careful", "Forsigtig", &["⚠️"]), None);
+/// ```
+pub fn check_symbols(msgid: &str, msgstr: &str, allowed: &[&str]) -> Option<SymbolMismatch> {
+    let allowed = allowed
+        .iter()
+        .flat_map(|text| emoji(text))
+        .collect::<Vec<_>>();
+    let source = emoji(msgid)
+        .into_iter()
+        .filter(|symbol| !allowed.contains(symbol))
+        .collect::<Vec<_>>();
+    let translated = emoji(msgstr)
+        .into_iter()
+        .filter(|symbol| !allowed.contains(symbol))
+        .collect::<Vec<_>>();
+    if source == translated {
+        return None;
+    }
+    Some(SymbolMismatch {
+        msgid: String::from(msgid),
+        missing: subtract(&source, &translated),
+        extra: subtract(&translated, &source),
+    })
+}
+
+/// Find the translations in `catalog` with mismatched emoji.
+///
+/// Fuzzy and untranslated messages are skipped, as are messages with
+/// plural forms.
+pub fn find_symbol_mismatches(catalog: &Catalog, allowed: &[&str]) -> Vec<SymbolMismatch> {
+    catalog
+        .messages()
+        .filter(|msg| !msg.is_fuzzy())
+        .filter_map(|msg| {
+            let msgstr = msg.msgstr().ok().filter(|msgstr| !msgstr.is_empty())?;
+            check_symbols(msg.msgid(), msgstr, allowed)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::{Message, MessageMutView};
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_emoji_clusters() {
+        assert_eq!(
+            emoji("Flag 🇩🇰, wave 👋🏽, keycap #️⃣."),
+            vec!["#\u{20e3}", "🇩🇰", "👋🏽"]
+        );
+        assert_eq!(emoji("No emoji here."), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_check_symbols_duplicated() {
+        assert_eq!(
+            check_symbols("🦀 Ferris", "🦀 Ferris 🦀 ✅", &[]),
+            Some(SymbolMismatch {
+                msgid: String::from("🦀 Ferris"),
+                missing: vec![],
+                extra: vec![String::from("✅"), String::from("🦀")],
+            })
+        );
+    }
+
+    #[test]
+    fn test_find_symbol_mismatches() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (msgid, msgstr, fuzzy) in [
+            ("⚠️ Careful", "Forsigtig", false),
+            ("🦀 Rust", "Rust", true),
+            ("💡 Tip", "💡 Tip", false),
+        ] {
+            let mut message = Message::build_singular()
+                .with_msgid(String::from(msgid))
+                .with_msgstr(String::from(msgstr))
+                .done();
+            if fuzzy {
+                message.flags_mut().add_flag("fuzzy");
+            }
+            catalog.append_or_update(message);
+        }
+        let mismatches = find_symbol_mismatches(&catalog, &[]);
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(|m| m.msgid.as_str())
+                .collect::<Vec<_>>(),
+            vec!["⚠️ Careful"]
+        );
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Functions for template-based renderers.
+//!
+//! Renderers which use templates can register these as template
+//! functions or filters, so templates can translate strings and
+//! post-process content the same way as the preprocessor does. Use
+//! them together with
+//! [`template_context`](crate::language_switcher::template_context).
+
+use crate::find_translation;
+use mdbook::utils::{normalize_id, render_markdown};
+use polib::catalog::Catalog;
+
+/// Translate `msgid` with `catalog`.
+///
+/// Returns `msgid` itself if there is no translation, or if the
+/// translation is fuzzy.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::template_functions::gettext;
+/// use polib::catalog::Catalog;
+/// use polib::message::Message;
+/// use polib::metadata::CatalogMetadata;
+///
+/// let mut catalog = Catalog::new(CatalogMetadata::new());
+/// catalog.append_or_update(
+///     Message::build_singular()
+///         .with_msgid(String::from("Next chapter"))
+///         .with_msgstr(String::from("Næste kapitel"))
+///         .done(),
+/// );
+/// assert_eq!(gettext(&catalog, "Next chapter"), "Næste kapitel");
+/// assert_eq!(gettext(&catalog, "Previous chapter"), "Previous chapter");
+/// ```
+pub fn gettext<'a>(catalog: &'a Catalog, msgid: &'a str) -> &'a str {
+    find_translation(catalog, msgid).unwrap_or(msgid)
+}
+
+/// Look up the translation of `msgid` in `catalog`.
+///
+/// Unlike [`gettext`], this returns `None` if there is no translation,
+/// so templates can fall back to something else.
+pub fn po_lookup<'a>(catalog: &'a Catalog, msgid: &str) -> Option<&'a str> {
+    find_translation(catalog, msgid)
+}
+
+/// Turn `text` into an id for a link target, the same way as `mdbook`
+/// does for headings.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::template_functions::slugify;
+///
+/// assert_eq!(slugify("Getting Started"), "getting-started");
+/// ```
+pub fn slugify(text: &str) -> String {
+    normalize_id(text)
+}
+
+/// Render the Markdown `text` to HTML, the same way as `mdbook` does
+/// for chapters.
+pub fn markdown(text: &str) -> String {
+    render_markdown(text, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::{Message, MessageMutView};
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_po_lookup_ignores_fuzzy() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        let mut message = Message::build_singular()
+            .with_msgid(String::from("Hello"))
+            .with_msgstr(String::from("Hej"))
+            .done();
+        message.flags_mut().add_flag("fuzzy");
+        catalog.append_or_update(message);
+        assert_eq!(po_lookup(&catalog, "Hello"), None);
+        assert_eq!(gettext(&catalog, "Hello"), "Hello");
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(markdown("Hello *world*"), "<p>Hello <em>world</em></p>\n");
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fixtures shared by the unit tests.
+//!
+//! This module is also compiled into the `mdbook-i18n-helpers` binary
+//! for the tests of its commands, so not every helper is used by both.
+
+#![allow(dead_code)]
+
+use polib::catalog::Catalog;
+use polib::message::{Message, MessageMutView};
+use polib::metadata::CatalogMetadata;
+
+/// Create a catalog with the `(source, msgid, msgstr)` messages.
+pub fn create_catalog(messages: &[(&str, &str, &str)]) -> Catalog {
+    let mut catalog = Catalog::new(CatalogMetadata::new());
+    for (source, msgid, msgstr) in messages {
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_source(String::from(*source))
+                .with_msgid(String::from(*msgid))
+                .with_msgstr(String::from(*msgstr))
+                .done(),
+        );
+    }
+    catalog
+}
+
+/// Mark the message with `msgid` in `catalog` as fuzzy.
+pub fn mark_fuzzy(catalog: &mut Catalog, msgid: &str) {
+    catalog
+        .find_message_mut(None, msgid, None)
+        .expect("message is in the catalog")
+        .flags_mut()
+        .add_flag("fuzzy");
+}
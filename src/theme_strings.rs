@@ -0,0 +1,298 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Translations of the user interface of the `mdbook` HTML theme.
+//!
+//! The default theme has English labels, such as "Print this book",
+//! in the `title`, `aria-label`, and `placeholder` attributes of its
+//! buttons and links. They are not part of the chapters, so they are
+//! translated by a small script which is added to each chapter, see
+//! [`theme_script`].
+//!
+//! The search results are announced by the search script with texts
+//! such as "3 search results for 'foo':". These are listed in
+//! [`SEARCH_STRINGS`] with `{count}` and `{term}` placeholders. The
+//! search index itself is built by `mdbook` from the translated
+//! chapters, so it needs no translation.
+//!
+//! Translations for a few languages are included here. The PO file of
+//! the book can add or override translations.
+
+use polib::catalog::Catalog;
+use std::collections::BTreeMap;
+
+/// The strings of the default `mdbook` theme.
+pub const THEME_STRINGS: &[&str] = &[
+    "Table of contents",
+    "Toggle Table of Contents",
+    "Change theme",
+    "Themes",
+    "Light",
+    "Rust",
+    "Coal",
+    "Navy",
+    "Ayu",
+    "Search. (Shortkey: s)",
+    "Toggle Searchbar",
+    "Print this book",
+    "Git repository",
+    "Suggest an edit",
+    "Search this book ...",
+    "Page navigation",
+    "Previous chapter",
+    "Next chapter",
+    "Change language",
+    "Languages",
+];
+
+/// The texts shown by the search script of the default `mdbook` theme
+/// above the search results.
+pub const SEARCH_STRINGS: &[&str] = &[
+    "No search results for '{term}'.",
+    "{count} search results for '{term}':",
+];
+
+/// Translations included with `mdbook-i18n-helpers`.
+const BUILTIN_TRANSLATIONS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "da",
+        &[
+            ("Table of contents", "Indholdsfortegnelse"),
+            ("Toggle Table of Contents", "Vis/skjul indholdsfortegnelse"),
+            ("Change theme", "Skift tema"),
+            ("Themes", "Temaer"),
+            ("Light", "Lys"),
+            ("Search. (Shortkey: s)", "Søg. (Genvej: s)"),
+            ("Toggle Searchbar", "Vis/skjul søgefelt"),
+            ("Print this book", "Udskriv denne bog"),
+            ("Git repository", "Git-repository"),
+            ("Suggest an edit", "Foreslå en ændring"),
+            ("Search this book ...", "Søg i denne bog ..."),
+            ("Page navigation", "Sidenavigation"),
+            ("Previous chapter", "Forrige kapitel"),
+            ("Next chapter", "Næste kapitel"),
+            ("Change language", "Skift sprog"),
+            ("Languages", "Sprog"),
+            (
+                "No search results for '{term}'.",
+                "Ingen søgeresultater for '{term}'.",
+            ),
+            (
+                "{count} search results for '{term}':",
+                "{count} søgeresultater for '{term}':",
+            ),
+        ],
+    ),
+    (
+        "de",
+        &[
+            ("Table of contents", "Inhaltsverzeichnis"),
+            (
+                "Toggle Table of Contents",
+                "Inhaltsverzeichnis ein-/ausblenden",
+            ),
+            ("Change theme", "Design ändern"),
+            ("Themes", "Designs"),
+            ("Light", "Hell"),
+            ("Search. (Shortkey: s)", "Suchen. (Tastenkürzel: s)"),
+            ("Toggle Searchbar", "Suchleiste ein-/ausblenden"),
+            ("Print this book", "Dieses Buch drucken"),
+            ("Git repository", "Git-Repository"),
+            ("Suggest an edit", "Änderung vorschlagen"),
+            ("Search this book ...", "Dieses Buch durchsuchen ..."),
+            ("Page navigation", "Seitennavigation"),
+            ("Previous chapter", "Vorheriges Kapitel"),
+            ("Next chapter", "Nächstes Kapitel"),
+            ("Change language", "Sprache ändern"),
+            ("Languages", "Sprachen"),
+            (
+                "No search results for '{term}'.",
+                "Keine Suchergebnisse für '{term}'.",
+            ),
+            (
+                "{count} search results for '{term}':",
+                "{count} Suchergebnisse für '{term}':",
+            ),
+        ],
+    ),
+];
+
+/// Find the translations of the theme strings into `language`.
+///
+/// The included translations are used for `language` or its general
+/// language, so `de-AT` uses the translations for `de`. Translations
+/// in `catalog` take precedence.
+///
+/// # Examples
+///
+/// ```
+/// use mdbook_i18n_helpers::theme_strings::theme_translations;
+/// use polib::catalog::Catalog;
+/// use polib::metadata::CatalogMetadata;
+///
+/// let catalog = Catalog::new(CatalogMetadata::new());
+/// let translations = theme_translations("da-DK", &catalog);
+/// assert_eq!(translations["Print this book"], "Udskriv denne bog");
+/// assert!(theme_translations("xx", &catalog).is_empty());
+/// ```
+pub fn theme_translations(language: &str, catalog: &Catalog) -> BTreeMap<String, String> {
+    let general = language.split(['-', '_']).next().unwrap_or(language);
+    let mut translations = BUILTIN_TRANSLATIONS
+        .iter()
+        .filter(|(lang, _)| *lang == language || *lang == general)
+        .flat_map(|(_, translations)| translations.iter())
+        .map(|(msgid, msgstr)| (String::from(*msgid), String::from(*msgstr)))
+        .collect::<BTreeMap<_, _>>();
+    for msgid in THEME_STRINGS.iter().chain(SEARCH_STRINGS) {
+        let msgstr = catalog
+            .find_message(None, msgid, None)
+            .filter(|message| !message.is_fuzzy())
+            .and_then(|message| message.msgstr().ok())
+            .filter(|msgstr| !msgstr.is_empty());
+        if let Some(msgstr) = msgstr {
+            translations.insert(String::from(*msgid), String::from(msgstr));
+        }
+    }
+    translations
+}
+
+/// Create a `<script>` element which translates the theme strings.
+///
+/// The script runs when the page has loaded. It replaces the
+/// attributes and theme names outside of the chapter which match
+/// `translations`, and translates the header of the search results
+/// whenever the search script changes it. Returns an empty string if
+/// there are no translations.
+pub fn theme_script(translations: &BTreeMap<String, String>) -> String {
+    if translations.is_empty() {
+        return String::new();
+    }
+    // Escape "</" so a translation cannot end the script element.
+    let json = serde_json::to_string(translations)
+        .expect("a map of strings can be serialized")
+        .replace("</", "<\\/");
+    format!(
+        r##"<script>
+document.addEventListener("DOMContentLoaded", () => {{
+  const translations = {json};
+  const translate = (text) =>
+    Object.prototype.hasOwnProperty.call(translations, text) ? translations[text] : text;
+  for (const element of document.querySelectorAll("[title], [aria-label], [placeholder]")) {{
+    if (element.closest("main") && !element.closest("nav")) continue;
+    for (const name of ["title", "aria-label", "placeholder"]) {{
+      if (element.hasAttribute(name)) {{
+        element.setAttribute(name, translate(element.getAttribute(name)));
+      }}
+    }}
+  }}
+  for (const button of document.querySelectorAll("#theme-list button")) {{
+    button.textContent = translate(button.textContent);
+  }}
+  const header = document.getElementById("searchresults-header");
+  if (header) {{
+    const patterns = [
+      [/^No search results for '(.*)'\.$/s, "No search results for '{{term}}'."],
+      [/^(\d+) search results for '(.*)':$/s, "{{count}} search results for '{{term}}':"],
+    ];
+    const localize = () => {{
+      for (const [pattern, msgid] of patterns) {{
+        const match = header.textContent.match(pattern);
+        if (!match) continue;
+        const [term, count] = match.length > 2 ? [match[2], match[1]] : [match[1], ""];
+        const text = translate(msgid)
+          .replace("{{count}}", () => count)
+          .replace("{{term}}", () => term);
+        if (text !== header.textContent) header.textContent = text;
+        return;
+      }}
+    }};
+    new MutationObserver(localize).observe(header, {{ childList: true, characterData: true }});
+  }}
+}});
+</script>
+"##
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_catalog_overrides_builtin() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (msgid, msgstr) in [
+            ("Print this book", "Udskriv bogen"),
+            ("Next chapter", ""),
+            ("Rust", "Rust-tema"),
+        ] {
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_msgid(String::from(msgid))
+                    .with_msgstr(String::from(msgstr))
+                    .done(),
+            );
+        }
+        let translations = theme_translations("da", &catalog);
+        assert_eq!(translations["Print this book"], "Udskriv bogen");
+        assert_eq!(translations["Next chapter"], "Næste kapitel");
+        assert_eq!(translations["Rust"], "Rust-tema");
+    }
+
+    #[test]
+    fn test_builtin_translations_are_theme_strings() {
+        for (_, translations) in BUILTIN_TRANSLATIONS {
+            for (msgid, _) in *translations {
+                assert!(
+                    THEME_STRINGS.contains(msgid) || SEARCH_STRINGS.contains(msgid),
+                    "{msgid:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_theme_script() {
+        assert_eq!(theme_script(&BTreeMap::new()), "");
+        let translations = BTreeMap::from([(String::from("Themes"), String::from("</script><b>"))]);
+        let script = theme_script(&translations);
+        assert!(script.starts_with("<script>\n"));
+        assert!(script.contains(r#"{"Themes":"<\/script><b>"}"#));
+        assert_eq!(script.matches("</script>").count(), 1);
+        assert!(script.contains("\"{count} search results for '{term}':\"]"));
+    }
+
+    #[test]
+    fn test_search_strings_from_catalog() {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        catalog.append_or_update(
+            Message::build_singular()
+                .with_msgid(String::from("No search results for '{term}'."))
+                .with_msgstr(String::from("Ingen resultater for '{term}'."))
+                .done(),
+        );
+        let translations = theme_translations("da", &catalog);
+        assert_eq!(
+            translations["No search results for '{term}'."],
+            "Ingen resultater for '{term}'."
+        );
+        assert_eq!(
+            translations["{count} search results for '{term}':"],
+            "{count} søgeresultater for '{term}':"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Translate Markdown, chapters, and books like `mdbook-gettext`.
+//!
+//! A [`Translator`] holds a catalog and the settings which decide how
+//! the text is translated. Tools outside of `mdbook` can use it to get
+//! the same translations as the `mdbook-gettext` preprocessor:
+//!
+//! ```
+//! use mdbook_i18n_helpers::translator::Translator;
+//! use mdbook_i18n_helpers::MarkdownConfig;
+//! use polib::catalog::Catalog;
+//! use polib::message::Message;
+//! use polib::metadata::CatalogMetadata;
+//!
+//! let mut catalog = Catalog::new(CatalogMetadata::new());
+//! catalog.append_or_update(
+//!     Message::build_singular()
+//!         .with_msgid(String::from("Hello"))
+//!         .with_msgstr(String::from("Hej"))
+//!         .done(),
+//! );
+//! let translator = Translator::new(catalog, MarkdownConfig::default());
+//! assert_eq!(translator.translate_str("# Hello\n\nWorld"), "# Hej\n\nWorld");
+//! ```
+//!
+//! The extra content which the preprocessor adds to chapters, such as
+//! the coverage footer or the language switcher, is not part of the
+//! translation.
+
+use crate::anchors::preserve_heading_anchors;
+use crate::{
+    extract_events_with_config, reconstruct_document_with_options, restore_table_delimiter_rows,
+    translate_events_with_config, translate_events_with_provenance, MarkdownConfig,
+    ReconstructOptions,
+};
+use mdbook::book::{Book, Chapter};
+use mdbook::BookItem;
+use polib::catalog::Catalog;
+use std::path::PathBuf;
+
+/// Translates Markdown with the translations of a catalog.
+pub struct Translator {
+    /// The translations. Fuzzy and untranslated messages are left in
+    /// the source language.
+    pub catalog: Catalog,
+
+    /// How the Markdown is turned into messages. This must match the
+    /// configuration used when the messages were extracted.
+    pub config: MarkdownConfig,
+
+    /// How the translated Markdown is written.
+    pub reconstruct_options: ReconstructOptions,
+
+    /// Give headings without an explicit id the id of the
+    /// untranslated heading, so links to them keep working. This is
+    /// `preprocessor.gettext.preserve-anchors`.
+    pub preserve_anchors: bool,
+
+    /// Mark the translations in chapters with their source, see
+    /// [`translate_events_with_provenance`]. The value is the source
+    /// directory of the book, such as `src`, which is joined with the
+    /// chapter paths to match the source references in the PO files.
+    /// This is `preprocessor.gettext.provenance`.
+    pub provenance_dir: Option<PathBuf>,
+}
+
+impl Translator {
+    /// Create a translator with the default settings.
+    pub fn new(catalog: Catalog, config: MarkdownConfig) -> Self {
+        Self {
+            catalog,
+            config,
+            reconstruct_options: ReconstructOptions::default(),
+            preserve_anchors: false,
+            provenance_dir: None,
+        }
+    }
+
+    /// Translate `text`, marking the translations with `provenance`, a
+    /// source path, if given.
+    fn translate(&self, text: &str, provenance: Option<&str>) -> String {
+        let events = extract_events_with_config(text, None, &self.config);
+        let mut translated_events = match provenance {
+            Some(path) => {
+                translate_events_with_provenance(&events, &self.catalog, &self.config, path)
+            }
+            None => translate_events_with_config(&events, &self.catalog, &self.config),
+        };
+        if self.preserve_anchors {
+            preserve_heading_anchors(&events, &mut translated_events);
+        }
+        let translated =
+            reconstruct_document_with_options(&translated_events, &self.reconstruct_options);
+        restore_table_delimiter_rows(text, &translated, &self.config)
+    }
+
+    /// Translate the Markdown `text`.
+    pub fn translate_str(&self, text: &str) -> String {
+        self.translate(text, None)
+    }
+
+    /// Translate the title and content of `chapter`.
+    ///
+    /// The sub-chapters are not translated, see
+    /// [`Translator::translate_book`].
+    pub fn translate_chapter(&self, chapter: &mut Chapter) {
+        let provenance = match (&self.provenance_dir, &chapter.path) {
+            (Some(dir), Some(path)) => Some(dir.join(path).display().to_string()),
+            _ => None,
+        };
+        chapter.content = self.translate(&chapter.content, provenance.as_deref());
+        chapter.name = self.translate_str(&chapter.name);
+    }
+
+    /// Translate the chapters and part titles of `book`.
+    pub fn translate_book(&self, book: &mut Book) {
+        book.for_each_mut(|item| match item {
+            BookItem::Chapter(chapter) => self.translate_chapter(chapter),
+            BookItem::Separator => {}
+            BookItem::PartTitle(title) => *title = self.translate_str(title),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polib::message::Message;
+    use polib::metadata::CatalogMetadata;
+    use pretty_assertions::assert_eq;
+
+    fn translator(translations: &[(&str, &str)]) -> Translator {
+        let mut catalog = Catalog::new(CatalogMetadata::new());
+        for (msgid, msgstr) in translations {
+            catalog.append_or_update(
+                Message::build_singular()
+                    .with_msgid(String::from(*msgid))
+                    .with_msgstr(String::from(*msgstr))
+                    .done(),
+            );
+        }
+        Translator::new(catalog, MarkdownConfig::default())
+    }
+
+    #[test]
+    fn test_translate_str_options() {
+        let mut translator = translator(&[("Some _emphasis_", "Noget _fremhævet_")]);
+        assert_eq!(
+            translator.translate_str("* Some *emphasis*\n"),
+            "- Noget _fremhævet_"
+        );
+        translator.reconstruct_options =
+            ReconstructOptions::from_source("* Some *emphasis*\n", &translator.config);
+        assert_eq!(
+            translator.translate_str("* Some *emphasis*\n"),
+            "* Noget *fremhævet*"
+        );
+    }
+
+    #[test]
+    fn test_translate_chapter() {
+        let mut translator = translator(&[("Getting Started", "Kom i gang"), ("Hello", "Hej")]);
+        translator.preserve_anchors = true;
+        let mut chapter = Chapter::new(
+            "Getting Started",
+            String::from("# Getting Started\n\nHello"),
+            "start.md",
+            Vec::new(),
+        );
+        translator.translate_chapter(&mut chapter);
+        assert_eq!(chapter.name, "Kom i gang");
+        assert_eq!(chapter.content, "# Kom i gang { #getting-started }\n\nHej");
+
+        translator.preserve_anchors = false;
+        translator.provenance_dir = Some(PathBuf::from("src"));
+        let mut chapter = Chapter::new("", String::from("Hello"), "start.md", Vec::new());
+        translator.translate_chapter(&mut chapter);
+        assert!(chapter
+            .content
+            .contains("data-i18n-source=\"src/start.md:1\""));
+        assert!(chapter.content.ends_with("Hej"));
+    }
+
+    #[test]
+    fn test_translate_book() {
+        let translator = translator(&[("Part", "Del"), ("Intro", "Introduktion")]);
+        let mut book = Book::new();
+        book.push_item(BookItem::PartTitle(String::from("Part")));
+        let mut chapter = Chapter::new("Intro", String::from("Intro"), "intro.md", Vec::new());
+        chapter.sub_items.push(BookItem::Chapter(Chapter::new(
+            "Intro",
+            String::from("Untranslated"),
+            "intro/more.md",
+            Vec::new(),
+        )));
+        book.push_item(BookItem::Chapter(chapter));
+        translator.translate_book(&mut book);
+
+        let items = book
+            .iter()
+            .map(|item| match item {
+                BookItem::Chapter(chapter) => format!("{}: {}", chapter.name, chapter.content),
+                BookItem::PartTitle(title) => title.clone(),
+                BookItem::Separator => String::from("---"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![
+                "Del",
+                "Introduktion: Introduktion",
+                "Introduktion: Untranslated"
+            ]
+        );
+    }
+}